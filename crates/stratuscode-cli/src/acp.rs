@@ -0,0 +1,780 @@
+//! `stratuscode acp`: the Agent Client Protocol (JSON-RPC over stdio) toward
+//! an editor such as Zed. Each `session/new` starts a backend in the session's
+//! `cwd`; timeline events come back as `session/update` notifications and
+//! agent questions as `session/request_permission` requests.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::agents::{AgentDefinition, BUILT_IN_AGENTS};
+use crate::app::PendingQuestion;
+use crate::backend::{BackendClient, BackendNotification, ChatState, TimelineEvent};
+use crate::redact::Redactor;
+use crate::render::{format_tool_args, tool_label};
+use crate::sandbox::Sandbox;
+
+const PROTOCOL_VERSION: u64 = 1;
+/// The option every question gets for skipping it.
+const SKIP_OPTION: &str = "skip";
+
+/// A backend initialized for one project, as started by `main`.
+pub struct Backend {
+    pub client: BackendClient,
+    pub notifications: Receiver<BackendNotification>,
+    pub state: ChatState,
+    pub agents: Vec<AgentDefinition>,
+    pub sandbox: Option<Sandbox>,
+    pub redactor: Option<Redactor>,
+}
+
+enum Incoming {
+    Editor(Value),
+    Malformed(String),
+    EditorClosed,
+    Backend(u64, BackendNotification),
+    BackendExited(u64),
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: -32602,
+            message: message.into(),
+        }
+    }
+
+    fn internal(message: impl ToString) -> Self {
+        Self {
+            code: -32603,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The `session/prompt` waiting for its turn to finish.
+struct Turn {
+    request_id: Value,
+    started: bool,
+    cancelled: bool,
+    error: Option<String>,
+}
+
+struct Session {
+    id: String,
+    client: BackendClient,
+    agent: String,
+    agents: Vec<AgentDefinition>,
+    sandbox: Option<Sandbox>,
+    redactor: Option<Redactor>,
+    translator: Translator,
+    turn: Option<Turn>,
+    /// The question last sent to the editor, so a repeated
+    /// `question_pending` doesn't ask twice.
+    asked: Option<String>,
+}
+
+impl Session {
+    fn close(mut self) {
+        self.client.shutdown();
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.stop();
+        }
+    }
+
+    fn modes(&self) -> Value {
+        let built_in = BUILT_IN_AGENTS
+            .iter()
+            .map(|(name, description)| (name.to_string(), Some(description.to_string())));
+        let custom = self
+            .agents
+            .iter()
+            .map(|agent| (agent.name.clone(), agent.description.clone()));
+        let available: Vec<Value> = built_in
+            .chain(custom)
+            .map(|(name, description)| {
+                json!({ "id": name, "name": name, "description": description })
+            })
+            .collect();
+        json!({ "currentModeId": self.agent, "availableModes": available })
+    }
+
+    /// The update for an event, with tool output redacted first.
+    fn translate(&mut self, event: &TimelineEvent) -> Option<Value> {
+        match &self.redactor {
+            Some(redactor) if event.kind == "tool_result" => {
+                let mut event = event.clone();
+                redactor.redact_event(&mut event);
+                self.translator.update(&event)
+            }
+            _ => self.translator.update(event),
+        }
+    }
+}
+
+struct Server<F> {
+    start: F,
+    tx: Sender<Incoming>,
+    session: Option<Session>,
+    /// Bumped for each backend, so notifications from one that was replaced
+    /// are dropped.
+    generation: u64,
+    next_request_id: u64,
+    /// Our `session/request_permission` requests, by id.
+    questions: HashMap<u64, PendingQuestion>,
+}
+
+/// Serves one editor connection until stdin closes. `start` initializes a
+/// backend for a project directory.
+pub fn serve(start: impl FnMut(&std::path::Path) -> Result<Backend>) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    spawn_editor_reader(tx.clone());
+    let mut server = Server {
+        start,
+        tx,
+        session: None,
+        generation: 0,
+        next_request_id: 0,
+        questions: HashMap::new(),
+    };
+    for incoming in rx {
+        match incoming {
+            Incoming::Editor(msg) => server.handle_editor(msg)?,
+            Incoming::Malformed(e) => send(&json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": e },
+            }))?,
+            Incoming::EditorClosed => break,
+            Incoming::Backend(generation, notif) if generation == server.generation => {
+                server.handle_backend(notif)?
+            }
+            Incoming::BackendExited(generation) if generation == server.generation => {
+                server.finish_turn(Some("The backend exited".to_string()))?
+            }
+            Incoming::Backend(..) | Incoming::BackendExited(_) => {}
+        }
+    }
+    if let Some(session) = server.session.take() {
+        session.close();
+    }
+    Ok(())
+}
+
+fn spawn_editor_reader(tx: Sender<Incoming>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let incoming = match serde_json::from_str(&line) {
+                Ok(msg) => Incoming::Editor(msg),
+                Err(e) => Incoming::Malformed(e.to_string()),
+            };
+            if tx.send(incoming).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(Incoming::EditorClosed);
+    });
+}
+
+fn send(msg: &Value) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", msg)?;
+    stdout.flush()
+}
+
+fn respond(id: Value, result: Result<Value, RpcError>) -> io::Result<()> {
+    match result {
+        Ok(result) => send(&json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+        Err(e) => send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        })),
+    }
+}
+
+fn session_update(session_id: &str, update: Value) -> io::Result<()> {
+    send(&json!({
+        "jsonrpc": "2.0",
+        "method": "session/update",
+        "params": { "sessionId": session_id, "update": update },
+    }))
+}
+
+impl<F: FnMut(&std::path::Path) -> Result<Backend>> Server<F> {
+    fn handle_editor(&mut self, msg: Value) -> Result<()> {
+        let id = msg.get("id").cloned();
+        let Some(method) = msg.get("method").and_then(Value::as_str) else {
+            // The editor's answer to one of our requests.
+            if let Some(id) = id.as_ref().and_then(Value::as_u64) {
+                self.answer_question(id, &msg)?;
+            }
+            return Ok(());
+        };
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "agentCapabilities": {
+                    "loadSession": false,
+                    "promptCapabilities": {
+                        "image": true,
+                        "audio": false,
+                        "embeddedContext": true,
+                    },
+                },
+                "authMethods": [],
+            })),
+            // API keys come from `stratuscode auth`; there's nothing to do here.
+            "authenticate" => Ok(json!({})),
+            "session/new" => self.new_session(&params),
+            "session/set_mode" => self.set_mode(&params),
+            "session/prompt" => match self.prompt(id.clone().unwrap_or(Value::Null), &params) {
+                // Answered when the turn ends.
+                Ok(()) => return Ok(()),
+                Err(e) => Err(e),
+            },
+            "session/cancel" => {
+                self.cancel();
+                return Ok(());
+            }
+            _ => Err(RpcError {
+                code: -32601,
+                message: format!("Method not found: {}", method),
+            }),
+        };
+        if let Some(id) = id {
+            respond(id, result)?;
+        }
+        Ok(())
+    }
+
+    fn new_session(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let cwd = params
+            .get("cwd")
+            .and_then(Value::as_str)
+            .map(PathBuf::from)
+            .ok_or_else(|| RpcError::invalid_params("cwd is required"))?;
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+        self.questions.clear();
+        let backend = (self.start)(&cwd).map_err(RpcError::internal)?;
+        self.generation += 1;
+        let (generation, tx) = (self.generation, self.tx.clone());
+        let notifications = backend.notifications;
+        thread::spawn(move || {
+            for notif in notifications {
+                if tx.send(Incoming::Backend(generation, notif)).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(Incoming::BackendExited(generation));
+        });
+
+        let mut translator = Translator::default();
+        for event in &backend.state.timeline_events {
+            translator.update(event);
+        }
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let session = Session {
+            id: format!("session-{}-{}", millis, generation),
+            client: backend.client,
+            agent: backend.state.agent,
+            agents: backend.agents,
+            sandbox: backend.sandbox,
+            redactor: backend.redactor,
+            translator,
+            turn: None,
+            asked: None,
+        };
+        let result = json!({ "sessionId": session.id, "modes": session.modes() });
+        self.session = Some(session);
+        Ok(result)
+    }
+
+    fn session_for(&mut self, params: &Value) -> Result<&mut Session, RpcError> {
+        let id = params.get("sessionId").and_then(Value::as_str);
+        match &mut self.session {
+            Some(session) if Some(session.id.as_str()) == id => Ok(session),
+            _ => Err(RpcError::invalid_params(format!(
+                "Unknown session: {}",
+                id.unwrap_or("")
+            ))),
+        }
+    }
+
+    fn set_mode(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let session = self.session_for(params)?;
+        let mode = params
+            .get("modeId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::invalid_params("modeId is required"))?;
+        let payload = if BUILT_IN_AGENTS.iter().any(|(name, _)| *name == mode) {
+            json!({ "agent": mode })
+        } else {
+            let agent = session
+                .agents
+                .iter()
+                .find(|a| a.name == mode)
+                .ok_or_else(|| RpcError::invalid_params(format!("Unknown mode: {}", mode)))?;
+            json!({ "agent": agent.name, "definition": agent.payload() })
+        };
+        session
+            .client
+            .call("set_agent", payload)
+            .map_err(RpcError::internal)?;
+        session.agent = mode.to_string();
+        Ok(json!({}))
+    }
+
+    fn prompt(&mut self, request_id: Value, params: &Value) -> Result<(), RpcError> {
+        let session = self.session_for(params)?;
+        if session.turn.is_some() {
+            return Err(RpcError::invalid_params("A prompt is already running"));
+        }
+        let blocks = params
+            .get("prompt")
+            .and_then(Value::as_array)
+            .ok_or_else(|| RpcError::invalid_params("prompt is required"))?;
+        let payload = message_payload(blocks);
+        session
+            .client
+            .call("send_message", payload)
+            .map_err(RpcError::internal)?;
+        session.turn = Some(Turn {
+            request_id,
+            started: false,
+            cancelled: false,
+            error: None,
+        });
+        Ok(())
+    }
+
+    fn cancel(&mut self) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if let Some(turn) = &mut session.turn {
+            turn.cancelled = true;
+            let _ = session.client.call("abort", json!({}));
+        }
+    }
+
+    /// Answers the `session/prompt` in flight, with `error` unless it was
+    /// cancelled.
+    fn finish_turn(&mut self, error: Option<String>) -> io::Result<()> {
+        let Some(turn) = self.session.as_mut().and_then(|s| s.turn.take()) else {
+            return Ok(());
+        };
+        let result = match error.or(turn.error) {
+            Some(message) if !turn.cancelled => Err(RpcError::internal(message)),
+            _ => Ok(json!({
+                "stopReason": if turn.cancelled { "cancelled" } else { "end_turn" },
+            })),
+        };
+        respond(turn.request_id, result)
+    }
+
+    fn handle_backend(&mut self, notif: BackendNotification) -> Result<()> {
+        let Some(session) = &mut self.session else {
+            return Ok(());
+        };
+        match notif.method.as_str() {
+            "timeline_event" => {
+                if let Ok(event) = serde_json::from_value::<TimelineEvent>(notif.params) {
+                    if let Some(update) = session.translate(&event) {
+                        session_update(&session.id, update)?;
+                    }
+                }
+            }
+            "state" => {
+                let Ok(state) = serde_json::from_value::<ChatState>(notif.params) else {
+                    return Ok(());
+                };
+                // Tool statuses change in place and only arrive with the state.
+                for event in &state.timeline_events {
+                    if let Some(update) = session.translate(event) {
+                        session_update(&session.id, update)?;
+                    }
+                }
+                if state.agent != session.agent {
+                    session.agent = state.agent.clone();
+                    session_update(
+                        &session.id,
+                        json!({ "sessionUpdate": "current_mode_update", "currentModeId": state.agent }),
+                    )?;
+                }
+                match &mut session.turn {
+                    Some(turn) if state.is_loading => turn.started = true,
+                    Some(turn) if turn.started => self.finish_turn(None)?,
+                    _ => {}
+                }
+            }
+            "error" => {
+                let message = notif
+                    .params
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| notif.params.to_string());
+                match &mut session.turn {
+                    // The message never started a turn, so no state will end it.
+                    Some(turn) if !turn.started => self.finish_turn(Some(message))?,
+                    Some(turn) => turn.error = Some(message),
+                    None => {}
+                }
+            }
+            "todos_changed" => {
+                let entries: Vec<Value> = notif
+                    .params
+                    .get("list")
+                    .and_then(Value::as_array)
+                    .map(|list| list.iter().filter_map(plan_entry).collect())
+                    .unwrap_or_default();
+                session_update(
+                    &session.id,
+                    json!({ "sessionUpdate": "plan", "entries": entries }),
+                )?;
+            }
+            "question_pending" => {
+                let question = notif
+                    .params
+                    .get("question")
+                    .and_then(|q| serde_json::from_value::<PendingQuestion>(q.clone()).ok());
+                if let Some(question) = question {
+                    if session.asked.as_deref() != Some(question.id.as_str()) {
+                        session.asked = Some(question.id.clone());
+                        self.ask(question)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Shows a question as a permission prompt: one option per answer, plus
+    /// one to skip it.
+    fn ask(&mut self, question: PendingQuestion) -> io::Result<()> {
+        let (Some(session), Some(item)) = (&self.session, question.questions.first()) else {
+            return Ok(());
+        };
+        let mut options: Vec<Value> = item
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, opt)| json!({ "optionId": i.to_string(), "name": opt.label, "kind": "allow_once" }))
+            .collect();
+        options.push(json!({ "optionId": SKIP_OPTION, "name": "Skip", "kind": "reject_once" }));
+        self.next_request_id += 1;
+        send(&json!({
+            "jsonrpc": "2.0",
+            "id": self.next_request_id,
+            "method": "session/request_permission",
+            "params": {
+                "sessionId": session.id,
+                "toolCall": {
+                    "toolCallId": question.id,
+                    "title": item.question,
+                    "kind": "think",
+                    "status": "pending",
+                },
+                "options": options,
+            },
+        }))?;
+        self.questions.insert(self.next_request_id, question);
+        Ok(())
+    }
+
+    fn answer_question(&mut self, request_id: u64, msg: &Value) -> Result<()> {
+        let (Some(question), Some(session)) =
+            (self.questions.remove(&request_id), &mut self.session)
+        else {
+            return Ok(());
+        };
+        let outcome = &msg["result"]["outcome"];
+        let label = match outcome["outcome"].as_str() {
+            Some("selected") => outcome["optionId"]
+                .as_str()
+                .and_then(|id| id.parse::<usize>().ok())
+                .and_then(|i| Some(question.questions.first()?.options.get(i)?.label.clone())),
+            _ => None,
+        };
+        match label {
+            Some(answer) => session.client.call(
+                "answer_question",
+                json!({ "id": question.id, "answers": vec![answer] }),
+            )?,
+            None => session
+                .client
+                .call("skip_question", json!({ "id": question.id }))?,
+        };
+        Ok(())
+    }
+}
+
+/// The `send_message` params for an ACP prompt. Text and resource links go
+/// into the message; embedded resources and images become attachments.
+fn message_payload(blocks: &[Value]) -> Value {
+    let mut content = Vec::new();
+    let mut attachments = Vec::new();
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => content.push(block["text"].as_str().unwrap_or("").to_string()),
+            Some("resource_link") => {
+                let uri = block["uri"].as_str().unwrap_or("");
+                content.push(uri.strip_prefix("file://").unwrap_or(uri).to_string());
+            }
+            Some("resource") => {
+                let resource = &block["resource"];
+                let uri = resource["uri"].as_str().unwrap_or("");
+                match resource["text"].as_str() {
+                    Some(text) => attachments.push(json!({
+                        "type": "text",
+                        "name": uri.strip_prefix("file://").unwrap_or(uri),
+                        "mime": resource["mimeType"].as_str().unwrap_or("text/plain"),
+                        "text": text,
+                    })),
+                    None => content.push(uri.to_string()),
+                }
+            }
+            Some("image") => attachments.push(json!({
+                "type": "image",
+                "name": block["uri"].as_str().unwrap_or("image"),
+                "mime": block["mimeType"],
+                "data": block["data"],
+            })),
+            _ => {}
+        }
+    }
+    json!({ "content": content.join("\n"), "attachments": attachments })
+}
+
+fn plan_entry(todo: &Value) -> Option<Value> {
+    let status = match todo["status"].as_str()? {
+        status @ ("pending" | "in_progress" | "completed") => status,
+        _ => return None,
+    };
+    let priority = match todo["priority"].as_str() {
+        Some("high") => "high",
+        Some("low") => "low",
+        _ => "medium",
+    };
+    Some(json!({ "content": todo["content"], "priority": priority, "status": status }))
+}
+
+fn tool_kind(name: &str) -> &'static str {
+    match name {
+        "read" => "read",
+        "write" | "edit" | "multi_edit" | "apply_patch" => "edit",
+        "bash" => "execute",
+        "grep" | "glob" | "ls" | "codesearch" | "lsp" => "search",
+        "websearch" | "webfetch" => "fetch",
+        "question" | "todoread" | "todowrite" => "think",
+        _ => "other",
+    }
+}
+
+fn tool_status(status: Option<&str>) -> &'static str {
+    match status {
+        Some("running") => "in_progress",
+        Some("completed") => "completed",
+        Some("failed") => "failed",
+        _ => "pending",
+    }
+}
+
+/// Whether a tool result reports an error, the same test the backend uses to
+/// mark the call failed.
+fn result_failed(content: &str) -> bool {
+    serde_json::from_str::<Value>(content)
+        .is_ok_and(|value| !value["error"].is_null() || value["success"].as_bool() == Some(false))
+}
+
+/// Turns timeline events, which the backend re-sends whole as they grow or
+/// change status, into the incremental updates ACP expects. Sub-agent events
+/// stay inside their `task` call.
+#[derive(Default)]
+struct Translator {
+    /// Bytes of each assistant and reasoning event already sent.
+    sent_text: HashMap<String, usize>,
+    /// The status last sent for each tool call.
+    tool_status: HashMap<String, &'static str>,
+    results: HashSet<String>,
+}
+
+impl Translator {
+    fn update(&mut self, event: &TimelineEvent) -> Option<Value> {
+        if event.parent_tool_call_id.is_some() {
+            return None;
+        }
+        match event.kind.as_str() {
+            kind @ ("assistant" | "reasoning") => {
+                let sent = self.sent_text.entry(event.id.clone()).or_default();
+                let chunk = event.content.get(*sent..).filter(|c| !c.is_empty())?;
+                *sent = event.content.len();
+                let update = if kind == "assistant" {
+                    "agent_message_chunk"
+                } else {
+                    "agent_thought_chunk"
+                };
+                Some(json!({
+                    "sessionUpdate": update,
+                    "content": { "type": "text", "text": chunk },
+                }))
+            }
+            "tool_call" => {
+                let id = event.tool_call_id.as_deref()?;
+                let status = tool_status(event.status.as_deref());
+                match self.tool_status.insert(id.to_string(), status) {
+                    None => {
+                        let name = event.tool_name.as_deref().unwrap_or("tool");
+                        let args = format_tool_args(&event.content);
+                        let title = if args.is_empty() {
+                            tool_label(name)
+                        } else {
+                            format!("{} {}", tool_label(name), args)
+                        };
+                        let raw_input: Value =
+                            serde_json::from_str(&event.content).unwrap_or(Value::Null);
+                        let locations: Vec<Value> = raw_input["file_path"]
+                            .as_str()
+                            .map(|path| json!({ "path": path }))
+                            .into_iter()
+                            .collect();
+                        Some(json!({
+                            "sessionUpdate": "tool_call",
+                            "toolCallId": id,
+                            "title": title,
+                            "kind": tool_kind(name),
+                            "status": status,
+                            "rawInput": raw_input,
+                            "locations": locations,
+                        }))
+                    }
+                    Some(prev) if prev != status => Some(json!({
+                        "sessionUpdate": "tool_call_update",
+                        "toolCallId": id,
+                        "status": status,
+                    })),
+                    Some(_) => None,
+                }
+            }
+            "tool_result" => {
+                let id = event.tool_call_id.as_deref()?;
+                if !self.results.insert(id.to_string()) {
+                    return None;
+                }
+                let status = if result_failed(&event.content) {
+                    "failed"
+                } else {
+                    "completed"
+                };
+                self.tool_status.insert(id.to_string(), status);
+                Some(json!({
+                    "sessionUpdate": "tool_call_update",
+                    "toolCallId": id,
+                    "status": status,
+                    "content": [{
+                        "type": "content",
+                        "content": { "type": "text", "text": event.content },
+                    }],
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, kind: &str, content: &str, extra: Value) -> TimelineEvent {
+        let mut event = json!({
+            "id": id,
+            "sessionId": "s1",
+            "createdAt": 0,
+            "kind": kind,
+            "content": content,
+        });
+        event
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().cloned().unwrap_or_default());
+        serde_json::from_value(event).unwrap()
+    }
+
+    #[test]
+    fn streams_only_new_text() {
+        let mut translator = Translator::default();
+        let first = translator.update(&event("a1", "assistant", "Hel", json!({})));
+        assert_eq!(first.unwrap()["content"]["text"], "Hel");
+        let next = translator.update(&event("a1", "assistant", "Hello", json!({})));
+        assert_eq!(next.unwrap()["content"]["text"], "lo");
+        assert!(translator
+            .update(&event("a1", "assistant", "Hello", json!({})))
+            .is_none());
+    }
+
+    #[test]
+    fn follows_a_tool_call_to_its_result() {
+        let mut translator = Translator::default();
+        let call = |status: &str| {
+            event(
+                "t1-call",
+                "tool_call",
+                r#"{"file_path":"src/main.rs"}"#,
+                json!({ "toolCallId": "t1", "toolName": "read", "status": status }),
+            )
+        };
+        let started = translator.update(&call("running")).unwrap();
+        assert_eq!(started["sessionUpdate"], "tool_call");
+        assert_eq!(started["kind"], "read");
+        assert_eq!(started["status"], "in_progress");
+        assert_eq!(started["locations"][0]["path"], "src/main.rs");
+        assert!(translator.update(&call("running")).is_none());
+
+        let result = event(
+            "t1-result",
+            "tool_result",
+            r#"{"error":"not found"}"#,
+            json!({ "toolCallId": "t1", "toolName": "read" }),
+        );
+        let finished = translator.update(&result).unwrap();
+        assert_eq!(finished["status"], "failed");
+        assert!(translator.update(&result).is_none());
+        assert!(translator.update(&call("failed")).is_none());
+    }
+
+    #[test]
+    fn prompt_blocks_become_text_and_attachments() {
+        let payload = message_payload(&[
+            json!({ "type": "text", "text": "Explain this" }),
+            json!({ "type": "resource_link", "uri": "file:///repo/src/lib.rs", "name": "lib.rs" }),
+            json!({
+                "type": "resource",
+                "resource": { "uri": "file:///repo/notes.md", "text": "# Notes" },
+            }),
+        ]);
+        assert_eq!(payload["content"], "Explain this\n/repo/src/lib.rs");
+        assert_eq!(payload["attachments"][0]["name"], "/repo/notes.md");
+        assert_eq!(payload["attachments"][0]["text"], "# Notes");
+    }
+}
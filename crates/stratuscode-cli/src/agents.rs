@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Agents every project has; custom agents are cycled after these.
+pub const BUILT_IN_AGENTS: [(&str, &str); 2] = [
+    (
+        "build",
+        "Default agent for development work with full access",
+    ),
+    ("plan", "Read-only agent for analysis and exploration"),
+];
+
+/// On-disk shape of `.stratuscode/agents/<name>.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AgentFile {
+    name: Option<String>,
+    description: Option<String>,
+    /// Path to the system prompt, relative to the agent file.
+    prompt_file: Option<String>,
+    /// Tool names the agent may call; all tools when omitted.
+    tools: Option<Vec<String>>,
+    model: Option<String>,
+}
+
+/// A user-defined agent, sent to the backend with `set_agent`.
+#[derive(Debug, Clone)]
+pub struct AgentDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub prompt: Option<String>,
+    pub tools: Option<Vec<String>>,
+    pub model: Option<String>,
+}
+
+impl AgentDefinition {
+    pub fn payload(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "prompt": self.prompt,
+            "tools": self.tools,
+            "model": self.model,
+        })
+    }
+}
+
+/// Loads every agent in `.stratuscode/agents`, sorted by name. Files that fail
+/// to parse are reported in the second list instead of aborting the load.
+pub fn load_agents(project_dir: &Path) -> (Vec<AgentDefinition>, Vec<String>) {
+    let dir = project_dir.join(".stratuscode").join("agents");
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut agents: Vec<AgentDefinition> = Vec::new();
+    let mut errors = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+            continue;
+        }
+        match load_agent(&path) {
+            Ok(agent) if BUILT_IN_AGENTS.iter().any(|(name, _)| *name == agent.name) => {
+                errors.push(format!(
+                    "{}: '{}' is a built-in agent",
+                    path.display(),
+                    agent.name
+                ));
+            }
+            Ok(agent) if agents.iter().any(|a| a.name == agent.name) => {
+                errors.push(format!(
+                    "{}: duplicate agent '{}'",
+                    path.display(),
+                    agent.name
+                ));
+            }
+            Ok(agent) => agents.push(agent),
+            Err(e) => errors.push(e),
+        }
+    }
+    agents.sort_by(|a, b| a.name.cmp(&b.name));
+    (agents, errors)
+}
+
+fn load_agent(path: &Path) -> Result<AgentDefinition, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file: AgentFile =
+        toml::from_str(&raw).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    let name = file
+        .name
+        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .ok_or_else(|| format!("{}: agent has no name", path.display()))?;
+    let prompt = match file.prompt_file {
+        Some(prompt_file) => {
+            let prompt_path = path.parent().unwrap_or(Path::new(".")).join(prompt_file);
+            Some(std::fs::read_to_string(&prompt_path).map_err(|e| {
+                format!(
+                    "{}: failed to read {}: {}",
+                    path.display(),
+                    prompt_path.display(),
+                    e
+                )
+            })?)
+        }
+        None => None,
+    };
+    Ok(AgentDefinition {
+        name,
+        description: file.description,
+        prompt,
+        tools: file.tools,
+        model: file.model,
+    })
+}
@@ -0,0 +1,204 @@
+use crate::app::QuestionInfo;
+use clap::ValueEnum;
+
+/// What `--auto-answer` does with questions no `--answer` covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AutoAnswer {
+    /// Pick the first option
+    First,
+    /// Skip the question
+    Skip,
+}
+
+/// How an unattended run settles a question.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Answer(Vec<String>),
+    Skip,
+}
+
+/// Answers given up front with `--answer key=value` and `--auto-answer`, so
+/// questions asked during a `--prompt` run don't wait for a terminal.
+#[derive(Debug, Clone, Default)]
+pub struct PresetAnswers {
+    pub answers: Vec<(String, String)>,
+    pub auto: Option<AutoAnswer>,
+}
+
+/// Clap parser for `--answer`: `key=value`, split at the first `=`.
+pub fn parse_answer(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected key=value, got '{}'", arg)),
+    }
+}
+
+impl PresetAnswers {
+    /// The first `--answer` whose key matches the question settles it;
+    /// otherwise `--auto-answer` does. `Ok(None)` leaves the question to the
+    /// user, and `Err` names an answer that fits none of the options.
+    pub fn resolve(&self, item: &QuestionInfo) -> Result<Option<Resolution>, String> {
+        if let Some((key, value)) = self.answers.iter().find(|(key, _)| key_matches(key, item)) {
+            return match_value(value, item)
+                .map(|answers| Some(Resolution::Answer(answers)))
+                .ok_or_else(|| {
+                    let labels: Vec<&str> = item.options.iter().map(|o| o.label.as_str()).collect();
+                    format!(
+                        "--answer {}={}: no option matches (choose from: {})",
+                        key,
+                        value,
+                        labels.join(", ")
+                    )
+                });
+        }
+        Ok(match self.auto {
+            Some(AutoAnswer::First) => Some(
+                item.options
+                    .first()
+                    .map(|o| Resolution::Answer(vec![o.label.clone()]))
+                    .unwrap_or(Resolution::Skip),
+            ),
+            Some(AutoAnswer::Skip) => Some(Resolution::Skip),
+            None => None,
+        })
+    }
+}
+
+/// Keys match the question's id (`q-0`), its header, or any part of its
+/// text, ignoring case.
+fn key_matches(key: &str, item: &QuestionInfo) -> bool {
+    let key = key.to_lowercase();
+    item.id.to_lowercase() == key
+        || item
+            .header
+            .as_deref()
+            .is_some_and(|h| h.to_lowercase() == key)
+        || item.question.to_lowercase().contains(&key)
+}
+
+/// Values name an option by label (ignoring case and a "(Recommended)"
+/// suffix) or by 1-based number. Questions that take several answers accept
+/// a comma-separated list, and ones that allow a custom answer take any text.
+fn match_value(value: &str, item: &QuestionInfo) -> Option<Vec<String>> {
+    let parts: Vec<&str> = if item.allow_multiple.unwrap_or(false) {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect()
+    } else {
+        vec![value]
+    };
+    let matched: Option<Vec<String>> = parts.iter().map(|part| match_option(part, item)).collect();
+    match matched {
+        Some(answers) if !answers.is_empty() => Some(answers),
+        _ if item.allow_custom.unwrap_or(false) => Some(vec![value.to_string()]),
+        _ => None,
+    }
+}
+
+fn match_option(part: &str, item: &QuestionInfo) -> Option<String> {
+    if let Ok(n) = part.parse::<usize>() {
+        return item.options.get(n.checked_sub(1)?).map(|o| o.label.clone());
+    }
+    let part = part.to_lowercase();
+    item.options
+        .iter()
+        .find(|o| {
+            let label = o.label.to_lowercase();
+            label == part || label.trim_end_matches("(recommended)").trim_end() == part
+        })
+        .map(|o| o.label.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::QuestionOption;
+
+    fn question(allow_multiple: bool, allow_custom: bool) -> QuestionInfo {
+        QuestionInfo {
+            id: "q-0".to_string(),
+            question: "Which database should we use?".to_string(),
+            header: Some("Database".to_string()),
+            options: ["Postgres (Recommended)", "SQLite", "MySQL"]
+                .iter()
+                .map(|label| QuestionOption {
+                    label: label.to_string(),
+                    description: None,
+                })
+                .collect(),
+            allow_multiple: Some(allow_multiple),
+            allow_custom: Some(allow_custom),
+            default_option: None,
+        }
+    }
+
+    fn answer(label: &str) -> Result<Option<Resolution>, String> {
+        Ok(Some(Resolution::Answer(vec![label.to_string()])))
+    }
+
+    #[test]
+    fn parses_key_value() {
+        assert_eq!(
+            parse_answer("database = sqlite"),
+            Ok(("database".to_string(), "sqlite".to_string()))
+        );
+        assert_eq!(
+            parse_answer("a=b=c"),
+            Ok(("a".to_string(), "b=c".to_string()))
+        );
+        assert!(parse_answer("database").is_err());
+        assert!(parse_answer("=sqlite").is_err());
+    }
+
+    #[test]
+    fn answers_by_key_then_auto() {
+        let q = question(false, false);
+        let presets = |key: &str, value: &str, auto| PresetAnswers {
+            answers: vec![(key.to_string(), value.to_string())],
+            auto,
+        };
+        assert_eq!(
+            presets("database", "sqlite", None).resolve(&q),
+            answer("SQLite")
+        );
+        assert_eq!(presets("q-0", "3", None).resolve(&q), answer("MySQL"));
+        assert_eq!(
+            presets("which DATABASE", "postgres", None).resolve(&q),
+            answer("Postgres (Recommended)")
+        );
+        assert!(presets("database", "oracle", None).resolve(&q).is_err());
+        assert!(presets("database", "0", None).resolve(&q).is_err());
+        assert_eq!(presets("editor", "vim", None).resolve(&q), Ok(None));
+        assert_eq!(
+            presets("editor", "vim", Some(AutoAnswer::First)).resolve(&q),
+            answer("Postgres (Recommended)")
+        );
+        assert_eq!(
+            presets("editor", "vim", Some(AutoAnswer::Skip)).resolve(&q),
+            Ok(Some(Resolution::Skip))
+        );
+    }
+
+    #[test]
+    fn multiple_and_custom_answers() {
+        let presets = |value: &str| PresetAnswers {
+            answers: vec![("database".to_string(), value.to_string())],
+            auto: None,
+        };
+        assert_eq!(
+            presets("sqlite, 3").resolve(&question(true, false)),
+            Ok(Some(Resolution::Answer(vec![
+                "SQLite".to_string(),
+                "MySQL".to_string()
+            ])))
+        );
+        assert_eq!(
+            presets("DuckDB").resolve(&question(false, true)),
+            answer("DuckDB")
+        );
+    }
+}
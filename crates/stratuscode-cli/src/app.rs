@@ -1,13 +1,53 @@
-use serde::Deserialize;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ignore::{WalkBuilder, WalkState};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use walkdir::WalkDir;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use crossterm::event::KeyEvent;
+use ratatui::style::{Modifier, Style};
 use ratatui::text::Line;
 
-use crate::backend::{BackendClient, BackendNotification, ChatState, TimelineEvent};
+use crate::agents::{AgentDefinition, BUILT_IN_AGENTS};
+use crate::commands::{arrange_models, filter_models};
+use crate::config::{
+    BellMode, ClipboardMode, IndexConfig, ModelSource, ScreenReaderMode, SpinnerConfig,
+};
+use crate::i18n::{t, tf};
+use crate::macros::{MacroAction, Macros};
+use crate::perf::PerfStats;
+use crate::symbols::SymbolEntry;
+use crate::templates::{expand_template, PromptTemplate, TemplateContext};
+use crate::usage::TurnUsage;
+
+use crate::backend::{
+    order_events, BackendClient, BackendNotification, ChatState, EventPage, SessionInfo,
+    TimelineEvent,
+};
+use crate::changes::{file_diffs, revert_hunk, touched_files, DiffHunk, TouchedFile};
+use crate::env::EnvVar;
+use crate::redact::Redactor;
+use crate::render::{
+    extract_diff_summary, format_tool_args, render_diff, EventLineCache, TimelineFilter,
+};
+use crate::text::{format_number, truncate_text};
+
+const MAX_TOOL_OUTPUT_BYTES: usize = 64 * 1024;
+/// Id prefix of user events shown before the backend echoes them.
+const PENDING_SEND_PREFIX: &str = "pending-";
+static NEXT_PENDING_SEND: AtomicUsize = AtomicUsize::new(0);
+/// Seconds each configured loading verb stays up.
+const LOADING_VERB_SECS: u64 = 3;
+const BOOKMARK_LABEL_WIDTH: usize = 60;
+/// How much of a file the @ mention preview reads.
+const MENTION_PREVIEW_BYTES: u64 = 256 * 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UiMode {
@@ -19,8 +59,158 @@ pub enum UiMode {
     QuestionPrompt,
     PlanActions,
     HelpAbout,
+    PasteConfirm,
+    AgentPicker,
+    TodoFocus,
+    Notifications,
+    Telemetry,
+    Recovery,
+    TemplatePicker,
+    Pager,
+    FileSidebar,
+    ReasoningPicker,
+    TrustPrompt,
+    Bookmarks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ToastLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl ToastLevel {
+    /// Errors stay up longest so they aren't missed.
+    pub fn ttl(self) -> Duration {
+        match self {
+            ToastLevel::Info => Duration::from_secs(4),
+            ToastLevel::Warn => Duration::from_secs(7),
+            ToastLevel::Error => Duration::from_secs(12),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ToastLevel::Info => "Info",
+            ToastLevel::Warn => "Warning",
+            ToastLevel::Error => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub level: ToastLevel,
+    pub shown_at: Instant,
+    /// Wall clock time for the notifications list, in epoch milliseconds.
+    pub created_at_ms: i64,
+}
+
+/// Toasts shown at once; older ones are dropped from the stack (but stay in
+/// the notification history).
+pub const MAX_TOASTS: usize = 3;
+pub const NOTIFICATION_HISTORY: usize = 50;
+/// The status bar without a `[status]` config: mode, model and usage on top,
+/// the context gauge below.
+pub fn default_status_layout() -> Vec<Vec<String>> {
+    vec![
+        vec!["agent", "model", "thinking", "tokens", "timer"],
+        vec!["context"],
+    ]
+    .into_iter()
+    .map(|line| line.into_iter().map(String::from).collect())
+    .collect()
+}
+
+/// Built-in spinner label for a running tool.
+fn tool_verb(name: &str) -> String {
+    match name {
+        "read" => t("ui.verb_read").to_string(),
+        "write" | "edit" | "multi_edit" | "apply_patch" => t("ui.verb_edit").to_string(),
+        "bash" => t("ui.verb_bash").to_string(),
+        "grep" | "glob" | "ls" | "codesearch" => t("ui.verb_search").to_string(),
+        "webfetch" | "websearch" => t("ui.verb_web").to_string(),
+        "task" => t("ui.verb_task").to_string(),
+        _ => tf("ui.verb_tool", &[("tool", &name)]),
+    }
+}
+
+/// Whether `accessibility.screen_reader` asks for screen reader output; `auto`
+/// checks VoiceOver's setting on macOS. Desktop accessibility flags like
+/// `GTK_MODULES=atk-bridge` are set on plenty of machines without a screen
+/// reader, so Linux needs `on`.
+pub fn screen_reader_detected(mode: ScreenReaderMode) -> bool {
+    match mode {
+        ScreenReaderMode::On => return true,
+        ScreenReaderMode::Off => return false,
+        ScreenReaderMode::Auto => {}
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let voice_over = std::process::Command::new("defaults")
+            .args(["read", "com.apple.universalaccess", "voiceOverOnOffKey"])
+            .output();
+        if let Ok(out) = voice_over {
+            return String::from_utf8_lossy(&out.stdout).trim() == "1";
+        }
+    }
+    false
+}
+
+/// `NO_COLOR` set to anything non-empty (https://no-color.org), or a dumb
+/// terminal.
+pub fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) || dumb_terminal()
+}
+
+/// `TERM=dumb`: no cursor addressing, so the full-screen UI can't draw.
+pub fn dumb_terminal() -> bool {
+    std::env::var("TERM").is_ok_and(|term| term == "dumb")
+}
+
+/// The checked-out branch, or the short commit hash on a detached HEAD.
+/// Follows the `gitdir:` file used by worktrees and submodules.
+pub fn read_git_branch(project_dir: &Path) -> Option<String> {
+    let dot_git = project_dir.join(".git");
+    let git_dir = if dot_git.is_file() {
+        let link = std::fs::read_to_string(&dot_git).ok()?;
+        let target = link.trim().strip_prefix("gitdir:")?.trim();
+        project_dir.join(target)
+    } else {
+        dot_git
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: refs/heads/") {
+        Some(branch) => Some(branch.to_string()),
+        None => Some(head.chars().take(7).collect()),
+    }
 }
 
+/// Turns averaged for `App::turns_remaining`.
+const CONTEXT_SAMPLES: usize = 5;
+/// Rows shown at once in `/notifications`.
+pub const NOTIFICATIONS_PAGE: usize = 10;
+/// Rows shown at once in the output pager.
+pub const PAGER_PAGE: usize = 20;
+/// Seconds a turn runs before `bell = "long-tasks"` rings.
+pub const DEFAULT_BELL_AFTER_SECS: u64 = 30;
+/// Seconds a turn runs before the `timer` segment turns red.
+pub const DEFAULT_TURN_WARN_SECS: u64 = 120;
+/// How long the todo strip shows "all done" after the last todo finishes.
+pub const TODOS_DONE_FLASH: Duration = Duration::from_secs(5);
+/// Diff lines shown inline when `diff.max_lines` is unset.
+pub const DIFF_MAX_LINES: usize = 120;
+/// Default size budget for the `/withdiff` attachment.
+pub const WITH_DIFF_MAX_BYTES: usize = 20_000;
+/// Newest timeline events the backend sends with each state; older ones are
+/// paged in when the view reaches the top.
+pub const TIMELINE_WINDOW: usize = 400;
+/// Events fetched per `get_events` page.
+pub const EARLIER_PAGE: usize = 200;
+
 #[derive(Debug, Clone)]
 pub struct CommandItem {
     pub name: &'static str,
@@ -36,7 +226,7 @@ pub struct FileResult {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModelEntry {
     pub id: String,
@@ -47,14 +237,221 @@ pub struct ModelEntry {
     pub reasoning: Option<bool>,
 }
 
+const RECENT_MODELS_LIMIT: usize = 5;
+
+/// Recently used and starred models, kept in `~/.stratuscode/model-prefs.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPrefs {
+    /// Most recent first.
+    pub recent: Vec<String>,
+    pub favorites: Vec<String>,
+    /// Reasoning effort last chosen for each model.
+    pub reasoning: HashMap<String, String>,
+}
+
+fn model_prefs_path() -> Option<std::path::PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        Path::new(&home)
+            .join(".stratuscode")
+            .join("model-prefs.json"),
+    )
+}
+
+impl ModelPrefs {
+    pub fn load() -> Self {
+        model_prefs_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(path) = model_prefs_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(raw) = serde_json::to_string_pretty(self) {
+            let _ = std::fs::write(path, raw);
+        }
+    }
+
+    pub fn record_use(&mut self, id: &str) {
+        self.recent.retain(|r| r != id);
+        self.recent.insert(0, id.to_string());
+        self.recent.truncate(RECENT_MODELS_LIMIT);
+        self.save();
+    }
+
+    /// Returns whether the model is now a favorite.
+    pub fn toggle_favorite(&mut self, id: &str) -> bool {
+        let starred = if self.is_favorite(id) {
+            self.favorites.retain(|f| f != id);
+            false
+        } else {
+            self.favorites.push(id.to_string());
+            true
+        };
+        self.save();
+        starred
+    }
+
+    pub fn is_favorite(&self, id: &str) -> bool {
+        self.favorites.iter().any(|f| f == id)
+    }
+
+    pub fn record_reasoning(&mut self, id: &str, effort: &str) {
+        self.reasoning.insert(id.to_string(), effort.to_string());
+        self.save();
+    }
+}
+
+/// Reasoning efforts in Ctrl+R order.
+pub const REASONING_LEVELS: [&str; 4] = ["off", "low", "medium", "high"];
+
+/// The model list is slow to fetch, so the picker opens from this on-disk copy
+/// and only refetches once it is older than `MODEL_CACHE_TTL`.
+const MODEL_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelCache {
+    /// Seconds since the Unix epoch.
+    fetched_at: u64,
+    entries: Vec<ModelEntry>,
+}
+
+fn model_cache_path() -> Option<std::path::PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        Path::new(&home)
+            .join(".stratuscode")
+            .join("cache")
+            .join("models.json"),
+    )
+}
+
+/// Cached models and whether they are still within the TTL.
+pub fn load_model_cache() -> Option<(Vec<ModelEntry>, bool)> {
+    let raw = std::fs::read_to_string(model_cache_path()?).ok()?;
+    let cache: ModelCache = serde_json::from_str(&raw).ok()?;
+    let fetched = UNIX_EPOCH + Duration::from_secs(cache.fetched_at);
+    let fresh = SystemTime::now()
+        .duration_since(fetched)
+        .is_ok_and(|age| age < MODEL_CACHE_TTL);
+    Some((cache.entries, fresh))
+}
+
+pub fn save_model_cache(entries: &[ModelEntry]) -> std::io::Result<()> {
+    let path = model_cache_path().ok_or_else(|| std::io::Error::other("HOME is not set"))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let fetched_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cache = ModelCache {
+        fetched_at,
+        entries: entries.to_vec(),
+    };
+    let raw = serde_json::to_string(&cache).map_err(std::io::Error::other)?;
+    std::fs::write(path, raw)
+}
+
+/// What the @ mention preview shows of a file.
+#[derive(Debug)]
+pub enum FilePreview {
+    /// Up to `MENTION_PREVIEW_BYTES`, cut at a line end when the file is longer.
+    Text(String),
+    Binary,
+    Unreadable,
+}
+
+fn read_file_preview(path: &Path) -> FilePreview {
+    use std::io::Read;
+    let mut bytes = Vec::new();
+    let read = std::fs::File::open(path)
+        .and_then(|file| file.take(MENTION_PREVIEW_BYTES).read_to_end(&mut bytes));
+    if read.is_err() {
+        return FilePreview::Unreadable;
+    }
+    if bytes.iter().take(8192).any(|b| *b == 0) {
+        return FilePreview::Binary;
+    }
+    if bytes.len() as u64 == MENTION_PREVIEW_BYTES {
+        if let Some(end) = bytes.iter().rposition(|b| *b == b'\n') {
+            bytes.truncate(end);
+        }
+    }
+    FilePreview::Text(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// A marked timeline event, saved with its session.
 #[allow(dead_code)]
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SessionInfo {
+pub struct Bookmark {
+    pub id: String,
+    pub event_id: String,
+    pub label: String,
+    pub created_at: i64,
+}
+
+/// A diff pager's hunks, each with the line its `@@` header is on.
+pub type PagerHunks = Vec<(usize, DiffHunk)>;
+
+#[derive(Debug, Clone)]
+pub struct Pager {
+    pub title: String,
+    pub lines: Vec<Line<'static>>,
+    pub scroll: usize,
+    pub hunks: PagerHunks,
+    /// Index into `hunks` once one is selected with n/N.
+    pub hunk: Option<usize>,
+}
+
+impl Pager {
+    pub fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(PAGER_PAGE)
+    }
+
+    pub fn selected_hunk(&self) -> Option<&DiffHunk> {
+        self.hunks.get(self.hunk?).map(|(_, hunk)| hunk)
+    }
+
+    /// Moves the selection by `step` hunks, wrapping, and scrolls it to the top.
+    pub fn select_hunk(&mut self, step: isize) {
+        if self.hunks.is_empty() {
+            return;
+        }
+        let len = self.hunks.len() as isize;
+        let next = match self.hunk {
+            Some(i) => (i as isize + step).rem_euclid(len),
+            None if step < 0 => len - 1,
+            None => 0,
+        } as usize;
+        self.hunk = Some(next);
+        self.scroll = self.hunks[next].0.min(self.max_scroll());
+    }
+}
+
+/// A session whose last turn never finished because the TUI or backend died,
+/// from `find_interrupted_session`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptedSession {
     pub id: String,
     pub title: String,
-    pub message_count: Option<u64>,
-    pub first_message: Option<String>,
+    pub updated_at: i64,
+    pub last_message: Option<String>,
+    /// Tool calls that finished and can be replayed.
+    pub completed_tools: u64,
+    /// Tool calls that were still running and will be marked failed.
+    pub pending_tools: u64,
 }
 
 #[allow(dead_code)]
@@ -67,6 +464,13 @@ pub struct TodoItem {
     pub priority: Option<String>,
 }
 
+/// A todo being edited from the focused strip; `id` is `None` when adding.
+#[derive(Debug, Clone)]
+pub struct TodoEdit {
+    pub id: Option<String>,
+    pub input: String,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -95,6 +499,59 @@ pub struct QuestionInfo {
     pub options: Vec<QuestionOption>,
     pub allow_multiple: Option<bool>,
     pub allow_custom: Option<bool>,
+    pub default_option: Option<usize>,
+}
+
+impl QuestionInfo {
+    /// The backend's recommended option, falling back to one labelled
+    /// "(Recommended)" as the question tool asks models to do.
+    pub fn default_index(&self) -> Option<usize> {
+        self.default_option
+            .filter(|&i| i < self.options.len())
+            .or_else(|| {
+                self.options
+                    .iter()
+                    .position(|o| o.label.contains("(Recommended)"))
+            })
+    }
+}
+
+/// A provider call the backend is about to retry, from `retry_status`.
+#[derive(Debug, Clone)]
+pub struct RetryStatus {
+    pub rate_limited: bool,
+    /// The attempt that will run next.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub retry_at: Instant,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RetryStatusPayload {
+    reason: String,
+    attempt: u32,
+    max_attempts: u32,
+    delay_ms: u64,
+}
+
+impl RetryStatus {
+    /// "Rate limited — retrying in 12s (attempt 2/5)"
+    pub fn label(&self) -> String {
+        let remaining = self.retry_at.saturating_duration_since(Instant::now());
+        let secs = (remaining.as_millis() as u64).div_ceil(1000);
+        format!(
+            "{} — retrying in {}s (attempt {}/{})",
+            if self.rate_limited {
+                "Rate limited"
+            } else {
+                "Provider error"
+            },
+            secs,
+            self.attempt,
+            self.max_attempts
+        )
+    }
 }
 
 #[allow(dead_code)]
@@ -104,6 +561,49 @@ pub struct PendingQuestion {
     pub id: String,
     pub session_id: String,
     pub questions: Vec<QuestionInfo>,
+    /// Milliseconds since the epoch.
+    pub created_at: Option<i64>,
+}
+
+impl PendingQuestion {
+    /// The prompt state for the first question, with the default option (or
+    /// the first one) preselected.
+    pub fn to_state(&self) -> Option<QuestionState> {
+        let item = self.questions.first()?;
+        let options = item.options.clone();
+        let default_index = item.default_index();
+        let preselect = default_index.unwrap_or(0);
+        let mut selected = vec![false; options.len()];
+        if preselect < selected.len() {
+            selected[preselect] = true;
+        }
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let pending_for = self
+            .created_at
+            .map(|at| Duration::from_millis(now_ms.saturating_sub(at).max(0) as u64))
+            .unwrap_or_default();
+        Some(QuestionState {
+            id: self.id.clone(),
+            question: item.question.clone(),
+            header: item.header.clone(),
+            options,
+            allow_multiple: item.allow_multiple.unwrap_or(false),
+            allow_custom: item.allow_custom.unwrap_or(false),
+            selected,
+            focused_index: preselect,
+            custom_input: String::new(),
+            custom_active: false,
+            filter: String::new(),
+            offset: preselect.saturating_sub(QUESTION_VIEWPORT - 1),
+            default_index,
+            asked_at: Instant::now()
+                .checked_sub(pending_for)
+                .unwrap_or_else(Instant::now),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -118,19 +618,261 @@ pub struct QuestionState {
     pub focused_index: usize,
     pub custom_input: String,
     pub custom_active: bool,
+    /// Type-to-filter query over option labels and descriptions.
+    pub filter: String,
+    /// First visible option, as an index into `visible_options()`.
+    pub offset: usize,
+    pub default_index: Option<usize>,
+    pub asked_at: Instant,
+}
+
+/// Option rows shown at once in the question prompt.
+pub const QUESTION_VIEWPORT: usize = 8;
+
+impl QuestionState {
+    /// Indices of the options matching `filter`.
+    pub fn visible_options(&self) -> Vec<usize> {
+        let query = self.filter.trim().to_lowercase();
+        self.options
+            .iter()
+            .enumerate()
+            .filter(|(_, opt)| {
+                query.is_empty()
+                    || opt.label.to_lowercase().contains(&query)
+                    || opt
+                        .description
+                        .as_ref()
+                        .is_some_and(|d| d.to_lowercase().contains(&query))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Focusable rows in order: the visible options, then the custom row
+    /// (`options.len()`) when custom answers are allowed.
+    fn rows(&self) -> Vec<usize> {
+        let mut rows = self.visible_options();
+        if self.allow_custom {
+            rows.push(self.options.len());
+        }
+        rows
+    }
+
+    pub fn move_focus(&mut self, delta: isize) {
+        let rows = self.rows();
+        if rows.is_empty() {
+            return;
+        }
+        let current = rows
+            .iter()
+            .position(|&r| r == self.focused_index)
+            .unwrap_or(0);
+        let next = (current as isize + delta).clamp(0, rows.len() as isize - 1) as usize;
+        self.focused_index = rows[next];
+        self.scroll_to_focus();
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.offset = 0;
+        // With nothing left to focus, park on the (absent) custom row so
+        // Enter can't answer a hidden option.
+        self.focused_index = self.rows().first().copied().unwrap_or(self.options.len());
+    }
+
+    fn scroll_to_focus(&mut self) {
+        let Some(pos) = self
+            .visible_options()
+            .iter()
+            .position(|&i| i == self.focused_index)
+        else {
+            return;
+        };
+        if pos < self.offset {
+            self.offset = pos;
+        } else if pos >= self.offset + QUESTION_VIEWPORT {
+            self.offset = pos + 1 - QUESTION_VIEWPORT;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    Image,
+    /// Binary document such as a PDF, sent base64 encoded.
+    File,
+    /// UTF-8 content inlined into the prompt by the backend.
+    Text,
+}
+
+impl AttachmentKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AttachmentKind::Image => "image",
+            AttachmentKind::File => "file",
+            AttachmentKind::Text => "text",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AttachmentUpload {
+    /// Assigned by `add_attachment`; stable while the attachment is pending.
+    pub id: u32,
+    pub kind: AttachmentKind,
+    pub name: String,
+    /// Size of the original content in bytes.
+    pub size: usize,
+    /// Base64 for images and files, raw content for text.
     pub data: String,
     pub mime: String,
 }
 
+impl AttachmentUpload {
+    /// Label shown in place of the attachment's marker in the input.
+    pub fn chip_label(&self) -> String {
+        format!(
+            "[{}, {}]",
+            self.name,
+            crate::attachments::format_size(self.size)
+        )
+    }
+}
+
+/// When new timeline content moves the view along with it. Scrolling up
+/// stops following in every mode; End resumes it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowMode {
+    #[default]
+    Always,
+    /// Follow replies and your own messages, but hold still for tool calls
+    /// and their output.
+    Assistant,
+    /// Never move the view; End jumps to the latest.
+    Manual,
+}
+
+impl FollowMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "always" | "all" => Some(Self::Always),
+            "assistant" | "replies" => Some(Self::Assistant),
+            "manual" | "off" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Always => Self::Assistant,
+            Self::Assistant => Self::Manual,
+            Self::Manual => Self::Always,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Self::Always => t("ui.follow_always"),
+            Self::Assistant => t("ui.follow_assistant"),
+            Self::Manual => t("ui.follow_manual"),
+        }
+        .to_string()
+    }
+
+    /// Whether a change to an event of this kind should pull the view along.
+    pub fn follows(self, kind: &str) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Assistant => matches!(kind, "assistant" | "user"),
+            Self::Manual => false,
+        }
+    }
+}
+
+/// View preferences persisted per project in `.stratuscode/ui-state.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UiPrefs {
+    pub compact_view: bool,
+    pub todos_expanded: bool,
+    pub reasoning_effort: String,
+    pub show_timestamps: bool,
+    pub follow_mode: FollowMode,
+}
+
+impl Default for UiPrefs {
+    fn default() -> Self {
+        Self {
+            compact_view: false,
+            todos_expanded: false,
+            reasoning_effort: "off".to_string(),
+            show_timestamps: false,
+            follow_mode: FollowMode::Always,
+        }
+    }
+}
+
+fn ui_prefs_path(project_dir: &str) -> std::path::PathBuf {
+    Path::new(project_dir)
+        .join(".stratuscode")
+        .join("ui-state.json")
+}
+
+impl UiPrefs {
+    /// Missing or unreadable state falls back to the defaults.
+    pub fn load(project_dir: &str) -> Self {
+        std::fs::read_to_string(ui_prefs_path(project_dir))
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, project_dir: &str) -> std::io::Result<()> {
+        let path = ui_prefs_path(project_dir);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let raw = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, raw)
+    }
+}
+
 pub struct App {
     pub state: ChatState,
     pub input: String,
     pub cursor: usize,
     pub should_quit: bool,
+    /// Set by Ctrl+Z or SIGTSTP; the main loop owns the terminal and suspends.
+    pub suspend_requested: bool,
+    /// `--inline`: render in a viewport at the bottom of the normal screen.
+    pub inline_mode: bool,
+    /// Timeline lines already written to scrollback in inline mode.
+    pub inline_flushed: usize,
+    /// Print the transcript to stdout once the alternate screen is closed.
+    pub print_on_exit: bool,
+    /// Params of the `send_message` for the turn in flight.
+    pub last_send: Option<serde_json::Value>,
+    /// Messages sent while another was in flight, with their pending event ids.
+    pub queued_sends: VecDeque<(String, serde_json::Value)>,
+    /// Settings held back until the project folder is trusted: `hooks`,
+    /// `verify`, `readOnly` and whether `[env]` was skipped.
+    pub untrusted: Option<serde_json::Value>,
+    /// `--sandbox` container, e.g. `docker:ubuntu:24.04`.
+    pub sandbox: Option<String>,
+    /// `[env]` from the config, as set on the backend process.
+    pub env_vars: Vec<EnvVar>,
+    /// Masks secrets wherever events are shown or exported; the timeline
+    /// itself stays raw. `None` with `[redact] enabled = false`.
+    pub redactor: Option<Arc<Redactor>>,
+    /// When Ctrl+C last aborted a turn; a second press soon after quits.
+    pub abort_requested_at: Option<Instant>,
+    /// The last message submitted with Enter, against key repeat.
+    pub last_submit: Option<(serde_json::Value, Instant)>,
+    /// A failed `send_message` that `/retry` resends.
+    pub retry_payload: Option<serde_json::Value>,
+    pub retry_status: Option<RetryStatus>,
+    pub model_source: ModelSource,
     pub reasoning_effort: String,
     pub mode: UiMode,
     pub command_query: String,
@@ -141,10 +883,25 @@ pub struct App {
     pub model_selected: usize,
     pub model_offset: usize,
     pub model_entries: Vec<ModelEntry>,
+    pub model_prefs: ModelPrefs,
+    /// A `list_models` call is wanted; the main loop runs it off-thread.
+    pub models_refresh_requested: bool,
+    pub models_loading: bool,
+    /// A picker entry to ping (Ctrl+T); the main loop runs it off-thread.
+    pub model_test_requested: Option<ModelEntry>,
+    /// The last pinged model's id and, once it's back, its latency or error.
+    pub model_test: Option<(String, Option<Result<u64, String>>)>,
+    pub reasoning_selected: usize,
     pub custom_model_mode: bool,
     pub custom_model_input: String,
     pub session_list: Vec<SessionInfo>,
     pub session_selected: usize,
+    /// Agents from `.stratuscode/agents`, after the built-in ones.
+    pub custom_agents: Vec<AgentDefinition>,
+    pub agent_selected: usize,
+    /// Loaded from the templates directory whenever the picker opens.
+    pub templates: Vec<PromptTemplate>,
+    pub template_selected: usize,
     pub session_offset: usize,
     pub session_rename_active: bool,
     pub session_rename_input: String,
@@ -152,45 +909,220 @@ pub struct App {
     pub question: Option<QuestionState>,
     pub todos: Vec<TodoItem>,
     pub todo_counts: TodoCounts,
+    /// When the last open todo was completed, for the strip's "all done".
+    pub todos_done_at: Option<Instant>,
     pub compact_view: bool,
+    pub show_timestamps: bool,
+    pub timeline_filter: TimelineFilter,
+    pub follow_mode: FollowMode,
+    /// Timeline content changed since the last frame; whether `follow_mode`
+    /// lets the view go with it. Otherwise the view is held in place.
+    pub follow_growth: Option<bool>,
+    /// Timeline line count at the last frame, to hold the view steady.
+    pub timeline_lines_seen: usize,
     pub scroll_from_bottom: usize,
     pub dirty: bool,
-    pub toast: Option<(String, Instant)>,
+    /// Toasts on screen, oldest first.
+    pub toasts: Vec<Toast>,
+    /// Recent toasts for `/notifications`, oldest first.
+    pub notifications: std::collections::VecDeque<Toast>,
+    pub notifications_offset: usize,
     pub last_todos_refresh: Instant,
     pub last_question_poll: Instant,
     pub project_dir: String,
     pub pending_gg: bool,
+    /// Pending attachments, in the order of their markers in `input`.
     pub attachments: Vec<AttachmentUpload>,
+    pub next_attachment_id: u32,
+    /// A paste above `paste_confirm_lines` waiting on the PasteConfirm modal.
+    pub pending_paste: Option<String>,
+    /// Offered by the Recovery banner at startup.
+    pub interrupted: Option<InterruptedSession>,
+    /// The interrupted session picked in the banner, to recover off the UI
+    /// thread.
+    pub resume_requested: Option<InterruptedSession>,
+    /// Plan file contents shown by the PlanActions viewer.
+    pub plan_content: Option<String>,
+    pub plan_scroll: usize,
+    pub plan_action_selected: usize,
+    /// Set by "Edit plan"; the main loop owns the terminal and runs $EDITOR.
+    pub edit_plan_requested: bool,
+    pub paste_confirm_selected: usize,
+    pub paste_confirm_lines: usize,
+    pub read_only: bool,
+    /// When read-only mode was last turned on (Unix ms); tool calls from
+    /// before then aren't violations.
+    read_only_since: i64,
+    /// Tools refused in read-only mode, as listed by the backend's
+    /// `initialize` response.
+    pub mutating_tools: Vec<String>,
+    /// Screen reader friendly output: no animation or box drawing, and state
+    /// changes announced as toasts.
+    pub accessible: bool,
+    /// `NO_COLOR`: frames are drawn without colors.
+    pub no_color: bool,
+    /// A mutating tool call seen in read-only mode; the loop aborts the turn.
+    pub read_only_violation: Option<String>,
     pub file_index: Vec<FileResult>,
+    pub index_config: IndexConfig,
+    /// Diff lines shown inline; longer diffs open in the pager on Enter.
+    pub diff_max_lines: usize,
+    /// The last timeline event when `/revert` ran; `/patch` leaves out the
+    /// changes up to it.
+    pub changes_since: Option<String>,
+    pub file_index_loading: bool,
+    pub recent_mentions: Vec<String>,
+    pub symbol_index: Vec<SymbolEntry>,
+    pub file_index_refresh: Option<Sender<IndexSignal>>,
     pub show_splash: bool,
-    pub show_telemetry_details: bool,
+    /// Per-turn token usage for the telemetry panel.
+    pub usage_turns: Vec<TurnUsage>,
+    pub usage_refresh_requested: bool,
+    /// Status segment names per status line.
+    pub status_layout: Vec<Vec<String>>,
+    pub git_branch: Option<String>,
+    /// Context size at the end of each recent turn, for the turns-left estimate.
+    pub context_samples: Vec<u64>,
     pub needs_clear: bool,
     pub timeline_revision: u64,
     pub timeline_cache_rev: u64,
     pub timeline_cache_width: usize,
     pub timeline_cache_compact: bool,
-    pub timeline_cache: Vec<Line<'static>>,
+    pub timeline_cache_timestamps: bool,
+    pub timeline_cache_filter: TimelineFilter,
+    pub timeline_cache: Arc<Vec<Line<'static>>>,
+    pub event_line_cache: EventLineCache,
+    /// The performance HUD (`--perf`, Ctrl+Shift+P).
+    pub perf_hud: bool,
+    pub perf: PerfStats,
     pub base_model: String,
     pub spinner_index: usize,
+    pub spinner_config: SpinnerConfig,
+    /// When the current turn started, for rotating the loading verbs and the
+    /// `timer` status segment.
+    pub busy_since: Option<Instant>,
+    /// Time spent in finished turns this session.
+    pub session_active: Duration,
+    /// A turn running longer than this shows its timer in red.
+    pub turn_warn_after: Duration,
+    pub bell: BellMode,
+    pub bell_after: Duration,
+    /// Set when a turn ends and the bell is due; the main loop rings it.
+    pub ring_bell: bool,
+    /// The terminal title last set, so it's only rewritten when it changes.
+    pub window_title: Option<String>,
+    pub clipboard: ClipboardMode,
+    /// The touched-files sidebar (Ctrl+B); focused in `UiMode::FileSidebar`.
+    pub sidebar_open: bool,
+    pub sidebar_selected: usize,
+    /// `touched_files` as of a timeline revision.
+    touched_files_cache: Option<(u64, Arc<Vec<TouchedFile>>)>,
+    /// Tool calls and results from before the resident window, so
+    /// `touched_files` covers the whole session: the id of the resident
+    /// event they lead up to, and the events.
+    earlier_tools: Option<(String, Vec<TimelineEvent>)>,
+    /// `touched_files` needs the tool events before this resident event.
+    pub earlier_tools_requested: Option<String>,
+    pub earlier_tools_loading: bool,
+    /// `mention_preview` of the last path previewed, with its mtime then.
+    mention_preview_cache: Option<(String, Option<SystemTime>, Arc<FilePreview>)>,
+    /// Where each message starts in the laid-out timeline: header line and
+    /// first event id.
+    pub timeline_anchors: Vec<(usize, String)>,
+    /// The message at the top of the timeline view, or the latest one while
+    /// following; what Ctrl+K bookmarks.
+    pub focused_event: Option<String>,
+    /// Scrolls this message to the top of the view on the next draw.
+    pub jump_to_event: Option<String>,
+    pub bookmarks: Vec<Bookmark>,
+    pub bookmark_selected: usize,
+    /// Files included with every message this session, relative to the
+    /// project (`/pin`, Ctrl+P in the @ overlay).
+    pub pinned: Vec<String>,
+    /// `/withdiff`: attach the working tree's `git diff` to every message.
+    pub with_diff: bool,
+    pub with_diff_status: bool,
+    pub with_diff_max_bytes: usize,
+    /// Ctrl+G: a URL from the input to fetch and attach as page text.
+    pub url_fetch_requested: Option<String>,
+    pub url_fetching: Option<String>,
+    /// `/retrytool` is waiting on the backend's `tool_retried` notification.
+    pub tool_retrying: bool,
+    /// The view reached the top with events left out of the window: the id
+    /// of the first resident event, to page in the ones before it.
+    pub earlier_requested: Option<String>,
+    pub earlier_loading: bool,
+    /// Compaction markers opened with Alt+E to show their summary.
+    pub expanded_summaries: Vec<String>,
+    /// `task` calls, by tool call id, whose sub-agent timeline Ctrl+O folded.
+    pub collapsed_subagents: Vec<String>,
+    /// `codesearch` hits for `related_query`, shown under the @ matches.
+    pub related_code: Vec<RelatedCode>,
+    pub related_query: Option<String>,
+    pub related_inflight: bool,
+    /// Set once `codesearch` fails (no Ollama/Qdrant); not retried after.
+    pub related_error: Option<String>,
+    /// Last edit of the @ query, for the `codesearch` debounce.
+    pub mention_typed_at: Instant,
     pub todos_expanded: bool,
+    pub todo_selected: usize,
+    pub todo_edit: Option<TodoEdit>,
+    /// The session was just titled from its first message; Alt+T renames it.
+    pub title_offer: bool,
+    /// The title being typed after `t`, shown in the timeline border.
+    pub title_rename: Option<String>,
+    pub macros: Macros,
+    /// Alt+q or Alt+@ was pressed; the next key names the register.
+    pub macro_pending: Option<MacroAction>,
+    /// The register being recorded and its keys so far.
+    pub macro_recording: Option<(char, Vec<KeyEvent>)>,
+    /// Keys of a replaying macro, fed to `handle_key` one per loop tick.
+    pub macro_queue: VecDeque<KeyEvent>,
     pub todos_request_inflight: bool,
+    /// The backend pushes `todos_changed` and `question_pending`, so the
+    /// polls are only needed against older backends.
+    pub push_updates: bool,
     pub question_request_inflight: bool,
     pub auto_scroll: bool,
     pub reindex_inflight: bool,
+    /// Incremental output streamed for running tool calls, keyed by tool call id.
+    pub tool_outputs: HashMap<String, String>,
+    /// Bytes cut from the front of each `tool_outputs` entry to stay under
+    /// `MAX_TOOL_OUTPUT_BYTES`.
+    pub tool_output_dropped: HashMap<String, usize>,
+    /// Text shown by the Pager modal.
+    pub pager: Option<Pager>,
 }
 
 impl App {
     pub fn new(state: ChatState, project_dir: String, base_model: String) -> Self {
+        let git_branch = read_git_branch(Path::new(&project_dir));
         let show_splash = state.timeline_events.is_empty();
+        let prefs = UiPrefs::load(&project_dir);
         let reasoning_effort = state
             .reasoning_effort_override
             .clone()
-            .unwrap_or_else(|| "off".to_string());
+            .unwrap_or_else(|| prefs.reasoning_effort.clone());
         Self {
             state,
             input: String::new(),
             cursor: 0,
             should_quit: false,
+            suspend_requested: false,
+            inline_mode: false,
+            inline_flushed: 0,
+            print_on_exit: false,
+            last_send: None,
+            queued_sends: VecDeque::new(),
+            last_submit: None,
+            abort_requested_at: None,
+            env_vars: Vec::new(),
+            redactor: None,
+            sandbox: None,
+            untrusted: None,
+            retry_payload: None,
+            retry_status: None,
+            model_source: ModelSource::Default,
             reasoning_effort,
             mode: UiMode::Normal,
             command_query: String::new(),
@@ -201,10 +1133,20 @@ impl App {
             model_selected: 0,
             model_offset: 0,
             model_entries: Vec::new(),
+            model_prefs: ModelPrefs::load(),
+            models_refresh_requested: false,
+            model_test_requested: None,
+            model_test: None,
+            reasoning_selected: 0,
+            models_loading: false,
             custom_model_mode: false,
             custom_model_input: String::new(),
             session_list: Vec::new(),
             session_selected: 0,
+            custom_agents: Vec::new(),
+            agent_selected: 0,
+            templates: Vec::new(),
+            template_selected: 0,
             session_offset: 0,
             session_rename_active: false,
             session_rename_input: String::new(),
@@ -217,107 +1159,1018 @@ impl App {
                 completed: 0,
                 total: 0,
             },
-            compact_view: false,
+            todos_done_at: None,
+            compact_view: prefs.compact_view,
+            show_timestamps: prefs.show_timestamps,
+            timeline_filter: TimelineFilter::All,
+            follow_mode: prefs.follow_mode,
+            follow_growth: None,
+            timeline_lines_seen: 0,
             scroll_from_bottom: 0,
             dirty: true,
-            toast: None,
+            toasts: Vec::new(),
+            notifications: std::collections::VecDeque::new(),
+            notifications_offset: 0,
             last_todos_refresh: Instant::now(),
             last_question_poll: Instant::now(),
             project_dir,
             pending_gg: false,
             attachments: Vec::new(),
+            next_attachment_id: 1,
+            pending_paste: None,
+            interrupted: None,
+            resume_requested: None,
+            plan_content: None,
+            plan_scroll: 0,
+            plan_action_selected: 0,
+            edit_plan_requested: false,
+            paste_confirm_selected: 0,
+            paste_confirm_lines: DEFAULT_PASTE_CONFIRM_LINES,
+            read_only: false,
+            accessible: false,
+            no_color: false,
+            read_only_violation: None,
+            read_only_since: 0,
+            mutating_tools: Vec::new(),
             file_index: Vec::new(),
+            index_config: IndexConfig::default(),
+            diff_max_lines: DIFF_MAX_LINES,
+            changes_since: None,
+            file_index_loading: false,
+            recent_mentions: Vec::new(),
+            symbol_index: Vec::new(),
+            file_index_refresh: None,
             show_splash,
-            show_telemetry_details: false,
+            usage_turns: Vec::new(),
+            usage_refresh_requested: false,
+            context_samples: Vec::new(),
+            status_layout: default_status_layout(),
+            git_branch,
             needs_clear: false,
             timeline_revision: 0,
             timeline_cache_rev: 0,
             timeline_cache_width: 0,
             timeline_cache_compact: false,
-            timeline_cache: Vec::new(),
+            timeline_cache_timestamps: false,
+            timeline_cache_filter: TimelineFilter::All,
+            timeline_cache: Arc::new(Vec::new()),
+            event_line_cache: EventLineCache::default(),
+            perf_hud: false,
+            perf: PerfStats::default(),
             base_model,
             spinner_index: 0,
-            todos_expanded: false,
+            spinner_config: SpinnerConfig::default(),
+            busy_since: None,
+            bell: BellMode::default(),
+            bell_after: Duration::from_secs(DEFAULT_BELL_AFTER_SECS),
+            session_active: Duration::ZERO,
+            turn_warn_after: Duration::from_secs(DEFAULT_TURN_WARN_SECS),
+            ring_bell: false,
+            window_title: None,
+            clipboard: ClipboardMode::default(),
+            sidebar_open: false,
+            sidebar_selected: 0,
+            touched_files_cache: None,
+            earlier_tools: None,
+            earlier_tools_requested: None,
+            earlier_tools_loading: false,
+            mention_preview_cache: None,
+            timeline_anchors: Vec::new(),
+            focused_event: None,
+            jump_to_event: None,
+            bookmarks: Vec::new(),
+            bookmark_selected: 0,
+            pinned: Vec::new(),
+            with_diff: false,
+            with_diff_status: false,
+            with_diff_max_bytes: WITH_DIFF_MAX_BYTES,
+            url_fetch_requested: None,
+            url_fetching: None,
+            earlier_requested: None,
+            earlier_loading: false,
+            expanded_summaries: Vec::new(),
+            collapsed_subagents: Vec::new(),
+            tool_retrying: false,
+            related_code: Vec::new(),
+            related_query: None,
+            related_inflight: false,
+            related_error: None,
+            mention_typed_at: Instant::now(),
+            todos_expanded: prefs.todos_expanded,
+            todo_selected: 0,
+            todo_edit: None,
+            title_offer: false,
+            title_rename: None,
+            macros: Macros::load(),
+            macro_pending: None,
+            macro_recording: None,
+            macro_queue: VecDeque::new(),
             todos_request_inflight: false,
+            push_updates: false,
             question_request_inflight: false,
             auto_scroll: true,
             reindex_inflight: false,
+            tool_outputs: HashMap::new(),
+            tool_output_dropped: HashMap::new(),
+            pager: None,
+        }
+    }
+
+    pub fn ui_prefs(&self) -> UiPrefs {
+        UiPrefs {
+            compact_view: self.compact_view,
+            todos_expanded: self.todos_expanded,
+            reasoning_effort: self.reasoning_effort.clone(),
+            show_timestamps: self.show_timestamps,
+            follow_mode: self.follow_mode,
         }
     }
 
+    /// The picker rows in display order, matching `model_selected`.
+    pub fn visible_models(&self) -> Vec<ModelEntry> {
+        arrange_models(
+            &filter_models(&self.model_entries, &self.model_query),
+            &self.model_prefs,
+        )
+    }
+
+    /// Swaps in a fresh model list, keeping the highlighted model selected if
+    /// it is still listed.
+    pub fn apply_model_list(&mut self, entries: Vec<ModelEntry>) {
+        let selected_id = self
+            .visible_models()
+            .get(self.model_selected)
+            .map(|e| e.id.clone());
+        self.model_entries = entries;
+        let filtered = self.visible_models();
+        self.model_selected = selected_id
+            .and_then(|id| filtered.iter().position(|e| e.id == id))
+            .unwrap_or(self.model_selected.min(filtered.len().saturating_sub(1)));
+        self.mark_dirty();
+    }
+
     pub fn mark_dirty(&mut self) {
         self.dirty = true;
     }
 
-    pub fn set_toast(&mut self, msg: impl Into<String>) {
-        self.toast = Some((msg.into(), Instant::now()));
-        self.mark_dirty();
+    pub fn status_shows(&self, segment: &str) -> bool {
+        self.status_layout.iter().flatten().any(|s| s == segment)
     }
 
-    pub fn update_state(&mut self, next: ChatState) {
-        let was_loading = self.state.is_loading;
-        self.state = next;
-        if let Some(re) = &self.state.reasoning_effort_override {
-            self.reasoning_effort = re.clone();
-        }
-        if !self.state.timeline_events.is_empty() {
-            self.show_splash = false;
-        }
-        if !was_loading && self.state.is_loading {
-            self.auto_scroll = true;
-            self.scroll_from_bottom = 0;
+    /// Turns left before the context fills, at the average growth of the
+    /// last few turns. `None` until there is growth to extrapolate from.
+    pub fn turns_remaining(&self) -> Option<u64> {
+        let first = *self.context_samples.first()?;
+        let last = *self.context_samples.last()?;
+        let steps = self.context_samples.len() as u64 - 1;
+        if steps == 0 || last <= first {
+            return None;
         }
-        if self.auto_scroll {
-            self.scroll_from_bottom = 0;
-        }
-        if matches!(self.mode, UiMode::SessionHistory) {
-            self.history_needs_refresh = true;
-        }
-        self.timeline_revision = self.timeline_revision.saturating_add(1);
-        self.mark_dirty();
+        let growth = (last - first).div_ceil(steps);
+        Some(self.state.context_usage.limit.saturating_sub(last) / growth)
     }
 
-    pub fn upsert_timeline(&mut self, event: TimelineEvent) {
-        if let Some(idx) = self
+    /// Frame of the busy spinner; `None` in accessible mode, which doesn't animate.
+    pub fn spinner(&self) -> Option<&'static str> {
+        let frames = self.spinner_config.style.frames();
+        (!self.accessible).then(|| frames[self.spinner_index % frames.len()])
+    }
+
+    /// The label beside the spinner: what the running tool is doing, or one
+    /// of the configured verbs while the model thinks.
+    pub fn loading_verb(&self) -> String {
+        let latest = self
             .state
             .timeline_events
             .iter()
-            .position(|e| e.id == event.id)
-        {
-            self.state.timeline_events[idx] = event;
-        } else {
-            self.state.timeline_events.push(event);
+            .rev()
+            .find(|e| matches!(e.kind.as_str(), "tool_call" | "reasoning" | "assistant"));
+        if let Some(event) = latest {
+            if event.kind == "tool_call" && event.status.as_deref() == Some("running") {
+                let name = event.tool_name.as_deref().unwrap_or("tool");
+                return match self.spinner_config.tools.get(name) {
+                    Some(verb) => verb.clone(),
+                    None => tool_verb(name),
+                };
+            }
+            if event.kind == "reasoning" && event.streaming == Some(true) {
+                return t("ui.verb_reasoning").to_string();
+            }
         }
-        self.show_splash = false;
-        self.timeline_revision = self.timeline_revision.saturating_add(1);
-        if self.auto_scroll {
-            self.scroll_from_bottom = 0;
+        let verbs = &self.spinner_config.verbs;
+        if verbs.is_empty() {
+            return t("ui.thinking").to_string();
         }
-        if matches!(self.mode, UiMode::SessionHistory) {
-            self.history_needs_refresh = true;
+        let elapsed = self.busy_since.map_or(0, |since| since.elapsed().as_secs());
+        verbs[(elapsed / LOADING_VERB_SECS) as usize % verbs.len()].clone()
+    }
+
+    pub fn current_model(&self) -> String {
+        self.state
+            .model_override
+            .clone()
+            .unwrap_or_else(|| self.base_model.clone())
+    }
+
+    /// The efforts the current model accepts: just "off" for models the list
+    /// marks as not reasoning, every level when the list doesn't say.
+    pub fn reasoning_levels(&self) -> &'static [&'static str] {
+        let model = self.current_model();
+        let reasoning = self
+            .model_entries
+            .iter()
+            .find(|e| e.id == model)
+            .and_then(|e| e.reasoning);
+        match reasoning {
+            Some(false) => &REASONING_LEVELS[..1],
+            _ => &REASONING_LEVELS,
         }
-        self.mark_dirty();
     }
 
-    pub fn handle_notification(&mut self, notif: BackendNotification) {
-        match notif.method.as_str() {
-            "state" => {
-                if let Ok(next) = serde_json::from_value::<ChatState>(notif.params) {
-                    self.update_state(next);
+    /// The start of a project file for the @ mention preview, read again
+    /// only when another path is selected or the file changes.
+    pub fn mention_preview(&mut self, path: &str) -> Arc<FilePreview> {
+        let full_path = Path::new(&self.project_dir).join(path);
+        let modified = std::fs::metadata(&full_path)
+            .and_then(|m| m.modified())
+            .ok();
+        if let Some((cached, at, preview)) = &self.mention_preview_cache {
+            if cached == path && *at == modified {
+                return Arc::clone(preview);
+            }
+        }
+        let preview = Arc::new(read_file_preview(&full_path));
+        self.mention_preview_cache = Some((path.to_string(), modified, Arc::clone(&preview)));
+        preview
+    }
+
+    /// Files read or changed in the session, worked out again only when the
+    /// timeline changes. Events before the resident window are fetched once
+    /// in the background; until they arrive only the window counts.
+    pub fn touched_files(&mut self) -> Arc<Vec<TouchedFile>> {
+        if let Some((rev, files)) = &self.touched_files_cache {
+            if *rev == self.timeline_revision {
+                return Arc::clone(files);
+            }
+        }
+        let resident = &self.state.timeline_events;
+        let first = resident.first().map(|e| e.id.clone());
+        let earlier = match &self.earlier_tools {
+            Some((before, events)) if self.state.earlier_events > 0 => {
+                if first.as_ref() != Some(before) && !self.earlier_tools_loading {
+                    self.earlier_tools_requested = first;
                 }
+                events.as_slice()
             }
-            "timeline_event" => {
-                if let Ok(event) = serde_json::from_value::<TimelineEvent>(notif.params) {
-                    if self.reindex_inflight
-                        && event.kind == "tool_result"
-                        && event.tool_name.as_deref() == Some("codesearch")
-                    {
-                        self.reindex_inflight = false;
-                        self.set_toast("Reindex complete".to_string());
+            _ => {
+                if self.state.earlier_events > 0 && !self.earlier_tools_loading {
+                    self.earlier_tools_requested = first;
+                }
+                &[]
+            }
+        };
+        let files = if earlier.is_empty() {
+            touched_files(resident, Path::new(&self.project_dir))
+        } else {
+            let ids: HashSet<&str> = resident.iter().map(|e| e.id.as_str()).collect();
+            let events: Vec<TimelineEvent> = earlier
+                .iter()
+                .filter(|e| !ids.contains(e.id.as_str()))
+                .chain(resident)
+                .cloned()
+                .collect();
+            touched_files(&events, Path::new(&self.project_dir))
+        };
+        let files = Arc::new(files);
+        self.touched_files_cache = Some((self.timeline_revision, Arc::clone(&files)));
+        files
+    }
+
+    /// The session's title, unless it's still the placeholder.
+    pub fn session_title(&self) -> Option<String> {
+        self.state
+            .title
+            .clone()
+            .or_else(|| {
+                let id = self.state.session_id.as_deref()?;
+                let session = self.session_list.iter().find(|s| s.id == id)?;
+                Some(session.title.clone())
+            })
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty() && !is_placeholder_title(title))
+    }
+
+    /// The terminal window title: what the agent is doing and the session,
+    /// so the state shows in the window switcher and tmux.
+    pub fn title_text(&self) -> String {
+        let status = if self.state.is_loading {
+            format!("⏳ {}", self.loading_verb())
+        } else if self.state.error.is_some() {
+            format!("❌ {}", t("ui.title_failed"))
+        } else {
+            format!("✅ {}", t("ui.title_waiting"))
+        };
+        let session = self.session_title().or_else(|| {
+            self.state
+                .timeline_events
+                .iter()
+                .find(|e| e.kind == "user")
+                .and_then(|e| e.content.lines().next())
+                .map(|line| truncate_text(line.trim(), 40))
+        });
+        match session {
+            Some(session) => format!("StratusCode {} · {}", status, session),
+            None => format!("StratusCode {}", status),
+        }
+    }
+
+    /// The command and output of a `verify` run that failed after the last
+    /// turn, while nothing has been sent since.
+    pub fn failed_verify(&self) -> Option<(String, &str)> {
+        let events = &self.state.timeline_events;
+        let result = events
+            .iter()
+            .rev()
+            .take_while(|e| e.kind != "user")
+            .find(|e| e.kind == "tool_result" && e.tool_name.as_deref() == Some("verify"))
+            .filter(|e| e.status.as_deref() == Some("failed"))?;
+        let command = events
+            .iter()
+            .find(|e| e.kind == "tool_call" && e.tool_call_id == result.tool_call_id)
+            .and_then(|call| serde_json::from_str::<serde_json::Value>(&call.content).ok())
+            .and_then(|args| Some(args.get("command")?.as_str()?.to_string()))
+            .unwrap_or_default();
+        Some((command, result.content.as_str()))
+    }
+
+    /// The failed tool call the last turn ended on, which `/retrytool` can
+    /// run again. A call the turn went on past is left alone, since the
+    /// model already acted on its failure.
+    pub fn retryable_tool(&self) -> Option<&TimelineEvent> {
+        let events = &self.state.timeline_events;
+        let last = events
+            .iter()
+            .rev()
+            .take_while(|e| e.kind != "user")
+            .filter(|e| e.parent_tool_call_id.is_none())
+            .find(|e| matches!(e.kind.as_str(), "assistant" | "tool_call" | "tool_result"))?;
+        let id = last.tool_call_id.as_deref()?;
+        events
+            .iter()
+            .rev()
+            .find(|e| e.kind == "tool_call" && e.tool_call_id.as_deref() == Some(id))
+            .filter(|e| e.status.as_deref() == Some("failed"))
+    }
+
+    /// Takes new todo counts, noting when they go from open work to all done.
+    pub fn set_todo_counts(&mut self, counts: TodoCounts) {
+        let was_done = self.todo_counts.completed == self.todo_counts.total;
+        if self.todo_counts.total > 0
+            && !was_done
+            && counts.total > 0
+            && counts.completed == counts.total
+        {
+            self.todos_done_at = Some(Instant::now());
+        }
+        self.todo_counts = counts;
+    }
+
+    /// Drops the strip's "all done" once it has been up long enough.
+    pub fn expire_todos_done(&mut self) {
+        if self.todos_done_at.is_some() && self.todos_done_flash().is_none() {
+            self.todos_done_at = None;
+            self.mark_dirty();
+        }
+    }
+
+    /// Time left on the strip's "all done", if it's showing.
+    pub fn todos_done_flash(&self) -> Option<Duration> {
+        TODOS_DONE_FLASH
+            .checked_sub(self.todos_done_at?.elapsed())
+            .filter(|left| !left.is_zero())
+    }
+
+    /// The focused event when it's a message Ctrl+Q can quote.
+    pub fn focused_message(&self) -> Option<&TimelineEvent> {
+        let id = self.focused_event.as_ref()?;
+        self.state
+            .timeline_events
+            .iter()
+            .find(|e| e.id == *id)
+            .filter(|e| matches!(e.kind.as_str(), "user" | "assistant"))
+            .filter(|e| !e.content.trim().is_empty())
+    }
+
+    /// The focused event when it's a compaction marker with a summary Alt+E
+    /// can show.
+    pub fn focused_compaction(&self) -> Option<&TimelineEvent> {
+        let id = self.focused_event.as_ref()?;
+        self.state
+            .timeline_events
+            .iter()
+            .find(|e| e.id == *id)
+            .filter(|e| e.kind == "compaction" && !e.content.trim().is_empty())
+    }
+
+    /// The `task` call Ctrl+O folds: the first one with sub-agent events in
+    /// the focused message, else the latest.
+    pub fn subagent_call_to_toggle(&self) -> Option<String> {
+        let events = &self.state.timeline_events;
+        let calls: Vec<(usize, &str)> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.kind == "tool_call" && e.parent_tool_call_id.is_none())
+            .filter_map(|(i, e)| Some((i, e.tool_call_id.as_deref()?)))
+            .filter(|(_, id)| {
+                events
+                    .iter()
+                    .any(|e| e.parent_tool_call_id.as_deref() == Some(*id))
+            })
+            .collect();
+        if let Some(range) = self.focused_message_range() {
+            if let Some((_, id)) = calls.iter().find(|(i, _)| range.contains(i)) {
+                return Some(id.to_string());
+            }
+        }
+        calls.last().map(|(_, id)| id.to_string())
+    }
+
+    /// Indexes of the focused message's events: from the focused one up to
+    /// the next user message.
+    pub fn focused_message_range(&self) -> Option<std::ops::Range<usize>> {
+        let events = &self.state.timeline_events;
+        let id = self.focused_event.as_ref()?;
+        let start = events.iter().position(|e| e.id == *id)?;
+        let end = events[start + 1..]
+            .iter()
+            .position(|e| e.kind == "user")
+            .map_or(events.len(), |offset| start + 1 + offset);
+        Some(start..end)
+    }
+
+    /// Folds or unfolds one `task` call's sub-agent timeline.
+    pub fn toggle_subagent(&mut self, id: &str) {
+        match self.collapsed_subagents.iter().position(|c| c == id) {
+            Some(index) => {
+                self.collapsed_subagents.remove(index);
+            }
+            None => self.collapsed_subagents.push(id.to_string()),
+        }
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.mark_dirty();
+    }
+
+    /// Shows or hides a compaction marker's summary.
+    pub fn toggle_summary(&mut self, id: &str) {
+        match self.expanded_summaries.iter().position(|e| e == id) {
+            Some(index) => {
+                self.expanded_summaries.remove(index);
+            }
+            None => self.expanded_summaries.push(id.to_string()),
+        }
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.mark_dirty();
+    }
+
+    /// Tells accessible mode about a change that's otherwise only visible
+    /// (a spinner starting, a tool's status icon flipping).
+    pub fn announce(&mut self, msg: impl Into<String>) {
+        if self.accessible {
+            self.set_toast(msg);
+        }
+    }
+
+    pub fn set_toast(&mut self, msg: impl Into<String>) {
+        self.push_toast(ToastLevel::Info, msg);
+    }
+
+    pub fn warn_toast(&mut self, msg: impl Into<String>) {
+        self.push_toast(ToastLevel::Warn, msg);
+    }
+
+    pub fn error_toast(&mut self, msg: impl Into<String>) {
+        self.push_toast(ToastLevel::Error, msg);
+    }
+
+    /// Shows a toast and records it in the notification history. Repeating
+    /// the message on screen restarts its timer instead of stacking a copy.
+    pub fn push_toast(&mut self, level: ToastLevel, msg: impl Into<String>) {
+        let toast = Toast {
+            message: msg.into(),
+            level,
+            shown_at: Instant::now(),
+            created_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0),
+        };
+        self.toasts
+            .retain(|t| t.message != toast.message || t.level != toast.level);
+        self.toasts.push(toast.clone());
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.notifications.push_back(toast);
+        if self.notifications.len() > NOTIFICATION_HISTORY {
+            self.notifications.pop_front();
+        }
+        self.mark_dirty();
+    }
+
+    pub fn expire_toasts(&mut self) {
+        let before = self.toasts.len();
+        self.toasts
+            .retain(|t| t.shown_at.elapsed() <= t.level.ttl());
+        if self.toasts.len() != before {
+            self.mark_dirty();
+        }
+    }
+
+    /// Time until the next toast expires.
+    pub fn next_toast_expiry(&self) -> Option<Duration> {
+        self.toasts
+            .iter()
+            .map(|t| t.level.ttl().saturating_sub(t.shown_at.elapsed()))
+            .min()
+    }
+
+    pub fn update_state(&mut self, mut next: ChatState) {
+        let was_loading = self.state.is_loading;
+        if next.session_id == self.state.session_id
+            && self.state.title.as_deref().is_none_or(is_placeholder_title)
+            && next
+                .title
+                .as_deref()
+                .is_some_and(|t| !is_placeholder_title(t))
+        {
+            self.title_offer = true;
+        }
+        // Pins belong to the session; a new or resumed one starts without.
+        if self.state.session_id.is_some() && next.session_id != self.state.session_id {
+            self.pinned.clear();
+            self.session_active = Duration::ZERO;
+        }
+        if next.session_id != self.state.session_id {
+            order_events(&mut next.timeline_events);
+        }
+        // Only the newest events come with each state. While scrolled back,
+        // keep the older ones already paged in; at the bottom they go.
+        if next.earlier_events > 0
+            && self.scroll_from_bottom > 0
+            && next.session_id == self.state.session_id
+        {
+            if let Some(first) = next.timeline_events.first().map(|e| e.id.clone()) {
+                let kept: Vec<TimelineEvent> = self
+                    .state
+                    .timeline_events
+                    .iter()
+                    .take_while(|e| e.id != first && !e.id.starts_with(PENDING_SEND_PREFIX))
+                    .cloned()
+                    .collect();
+                next.earlier_events = next.earlier_events.saturating_sub(kept.len() as u64);
+                next.timeline_events.splice(0..0, kept);
+            }
+        }
+        // A send the backend dropped never gets echoed; let it go once the
+        // turn it raced with is over. Queued ones wait their turn.
+        let turn_ended = was_loading && !next.is_loading;
+        let echoed: Vec<&str> = next
+            .timeline_events
+            .iter()
+            .filter_map(|e| e.client_id.as_deref())
+            .collect();
+        let pending: Vec<TimelineEvent> = self
+            .state
+            .timeline_events
+            .iter()
+            .filter(|e| {
+                e.id.starts_with(PENDING_SEND_PREFIX)
+                    && !echoed.contains(&e.id.as_str())
+                    && !(turn_ended && e.status.as_deref() == Some("sending"))
+            })
+            .cloned()
+            .collect();
+        next.timeline_events.extend(pending);
+        let previous: HashMap<&str, &TimelineEvent> = self
+            .state
+            .timeline_events
+            .iter()
+            .map(|e| (e.id.as_str(), e))
+            .collect();
+        if next.timeline_events.is_empty() {
+            self.event_line_cache.clear();
+        }
+        for event in &next.timeline_events {
+            let unchanged = previous.get(event.id.as_str()).is_some_and(|prev| {
+                prev.content == event.content
+                    && prev.status == event.status
+                    && prev.streaming == event.streaming
+            });
+            if !unchanged {
+                self.event_line_cache.invalidate(&event.id);
+            }
+        }
+        let resident: HashSet<&str> = next.timeline_events.iter().map(|e| e.id.as_str()).collect();
+        for id in previous.keys().filter(|id| !resident.contains(*id)) {
+            self.event_line_cache.invalidate(id);
+        }
+        // Tool events leaving the front of the window join the earlier ones
+        // already fetched, so `touched_files` needn't fetch them again.
+        if next.session_id != self.state.session_id {
+            self.earlier_tools = None;
+        } else if let (Some((before, earlier)), Some(first)) =
+            (&mut self.earlier_tools, next.timeline_events.first())
+        {
+            let old = &self.state.timeline_events;
+            if old.first().map(|e| &e.id) == Some(before) && old.iter().any(|e| e.id == first.id) {
+                earlier.extend(
+                    old.iter()
+                        .take_while(|e| e.id != first.id)
+                        .filter(|e| is_tool_event(e) && !resident.contains(e.id.as_str()))
+                        .cloned(),
+                );
+                *before = first.id.clone();
+            }
+        }
+        self.state = next;
+        // Streamed output is only shown while its call runs; the result
+        // replaces it afterwards.
+        let running: HashSet<&str> = self
+            .state
+            .timeline_events
+            .iter()
+            .filter(|e| e.kind == "tool_call" && e.status.as_deref() == Some("running"))
+            .filter_map(|e| e.tool_call_id.as_deref())
+            .collect();
+        self.tool_outputs
+            .retain(|id, _| running.contains(id.as_str()));
+        self.tool_output_dropped
+            .retain(|id, _| running.contains(id.as_str()));
+        if let Some(re) = &self.state.reasoning_effort_override {
+            self.reasoning_effort = re.clone();
+        }
+        if !self.state.timeline_events.is_empty() {
+            self.show_splash = false;
+        }
+        if was_loading && !self.state.is_loading && self.state.error.is_none() {
+            self.last_send = None;
+        }
+        if !was_loading && self.state.is_loading {
+            self.busy_since = Some(Instant::now());
+            self.announce("Working");
+        } else if was_loading && !self.state.is_loading {
+            match self.state.error.clone() {
+                Some(error) => self.announce(format!("Failed: {}", error)),
+                None => self.announce("Done"),
+            }
+            let elapsed = self
+                .busy_since
+                .map_or(Duration::ZERO, |since| since.elapsed());
+            self.session_active += elapsed;
+            self.ring_bell = match self.bell {
+                BellMode::Always => true,
+                BellMode::LongTasks => elapsed >= self.bell_after,
+                BellMode::Never => false,
+            };
+        }
+        if !self.state.is_loading {
+            self.retry_status = None;
+        }
+        if was_loading && !self.state.is_loading {
+            if self.mode == UiMode::Telemetry || self.status_shows("cost") {
+                self.usage_refresh_requested = true;
+            }
+            self.git_branch = read_git_branch(Path::new(&self.project_dir));
+            self.context_samples.push(self.state.context_usage.used);
+            if self.context_samples.len() > CONTEXT_SAMPLES {
+                self.context_samples.remove(0);
+            }
+        }
+        if !was_loading && self.state.is_loading && self.follow_mode != FollowMode::Manual {
+            self.auto_scroll = true;
+            self.scroll_from_bottom = 0;
+        }
+        let kind = self.state.timeline_events.last().map(|e| e.kind.clone());
+        self.note_growth(kind.as_deref().unwrap_or(""));
+        if matches!(self.mode, UiMode::SessionHistory) {
+            self.history_needs_refresh = true;
+        }
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.mark_dirty();
+    }
+
+    /// Takes the events before resident event `before` for `touched_files`.
+    pub fn set_earlier_tools(&mut self, before: String, events: Vec<TimelineEvent>) {
+        let events = events.into_iter().filter(is_tool_event).collect();
+        self.earlier_tools = Some((before, events));
+        self.touched_files_cache = None;
+        self.mark_dirty();
+    }
+
+    /// Puts a page from `get_events` in front of the timeline, unless the
+    /// timeline has moved on since it was asked for.
+    pub fn prepend_earlier(&mut self, before: &str, page: EventPage) {
+        if self.state.timeline_events.first().map(|e| e.id.as_str()) != Some(before) {
+            return;
+        }
+        let first = page.events.first().map(|e| e.id.clone());
+        self.state.timeline_events.splice(0..0, page.events);
+        self.state.earlier_events = page.earlier;
+        if let (Some((key, _)), Some(first)) = (&mut self.earlier_tools, first) {
+            if key == before {
+                *key = first;
+            }
+        }
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.mark_dirty();
+    }
+
+    /// Adds a pattern; what's already on screen is laid out again with it.
+    pub fn add_redaction(&mut self, re: regex::Regex) {
+        let Some(redactor) = &mut self.redactor else {
+            return;
+        };
+        Arc::make_mut(redactor).add(re);
+        self.event_line_cache.clear();
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.mark_dirty();
+    }
+
+    /// Records that timeline content changed; the next frame follows it or
+    /// holds the view, per `follow_mode`.
+    fn note_growth(&mut self, kind: &str) {
+        let follows = self.follow_mode.follows(kind);
+        self.follow_growth = Some(self.follow_growth.unwrap_or(false) || follows);
+    }
+
+    /// `text` as it may be shown: masked by the redactor, if there is one.
+    pub fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match &self.redactor {
+            Some(redactor) => redactor.redact(text),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    pub fn set_read_only(&mut self, on: bool) {
+        if on && !self.read_only {
+            self.read_only_since = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as i64);
+        }
+        self.read_only = on;
+    }
+
+    pub fn upsert_timeline(&mut self, event: TimelineEvent) {
+        self.event_line_cache.invalidate(&event.id);
+        if let Some(client_id) = &event.client_id {
+            self.state.timeline_events.retain(|e| e.id != *client_id);
+            self.event_line_cache.invalidate(client_id);
+        }
+        let kind = event.kind.clone();
+        if kind == "tool_result"
+            || (kind == "tool_call" && event.status.as_deref() != Some("running"))
+        {
+            if let Some(id) = &event.tool_call_id {
+                self.tool_outputs.remove(id);
+                self.tool_output_dropped.remove(id);
+            }
+        }
+        if let Some(idx) = self
+            .state
+            .timeline_events
+            .iter()
+            .position(|e| e.id == event.id)
+        {
+            self.state.timeline_events[idx] = event;
+        } else {
+            self.state.timeline_events.push(event);
+        }
+        self.show_splash = false;
+        self.timeline_revision = self.timeline_revision.saturating_add(1);
+        self.note_growth(&kind);
+        if matches!(self.mode, UiMode::SessionHistory) {
+            self.history_needs_refresh = true;
+        }
+        self.mark_dirty();
+    }
+
+    /// Shows a message as a "sending…" (or "queued") user event until the
+    /// backend's own event for it, carrying the returned id as `clientId`,
+    /// replaces it.
+    pub fn push_pending_send(&mut self, content: &str, queued: bool) -> String {
+        let id = format!(
+            "{}{}",
+            PENDING_SEND_PREFIX,
+            NEXT_PENDING_SEND.fetch_add(1, Ordering::Relaxed)
+        );
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        self.upsert_timeline(TimelineEvent {
+            id: id.clone(),
+            session_id: self.state.session_id.clone().unwrap_or_default(),
+            created_at,
+            kind: "user".to_string(),
+            content: content.to_string(),
+            tokens: None,
+            streaming: None,
+            tool_call_id: None,
+            tool_name: None,
+            status: Some(if queued { "queued" } else { "sending" }.to_string()),
+            attachments: None,
+            parent_tool_call_id: None,
+            client_id: None,
+            compaction: None,
+        });
+        id
+    }
+
+    pub fn has_pending_send(&self) -> bool {
+        self.state
+            .timeline_events
+            .iter()
+            .any(|e| e.id.starts_with(PENDING_SEND_PREFIX))
+    }
+
+    /// A turn is running or a sent message hasn't been picked up yet.
+    pub fn send_in_flight(&self) -> bool {
+        self.state.is_loading
+            || self.state.timeline_events.iter().any(|e| {
+                e.id.starts_with(PENDING_SEND_PREFIX) && e.status.as_deref() == Some("sending")
+            })
+    }
+
+    /// Flips a queued pending event to "sending…".
+    pub fn mark_sending(&mut self, id: &str) {
+        if let Some(event) = self.state.timeline_events.iter_mut().find(|e| e.id == id) {
+            event.status = Some("sending".to_string());
+            self.event_line_cache.invalidate(id);
+            self.timeline_revision = self.timeline_revision.saturating_add(1);
+            self.mark_dirty();
+        }
+    }
+
+    /// Opens the question prompt unless this question is already showing.
+    pub fn show_question(&mut self, question: QuestionState) {
+        if self.question.as_ref().map(|q| q.id.as_str()) != Some(question.id.as_str()) {
+            self.announce("Question: waiting for your answer");
+            self.question = Some(question);
+            self.mode = UiMode::QuestionPrompt;
+            self.mark_dirty();
+        }
+    }
+
+    /// Closes the question prompt when it is showing `id`, or whatever it is
+    /// showing when `id` is `None`. Returns whether a question was closed.
+    pub fn dismiss_question(&mut self, id: Option<&str>) -> bool {
+        let showing = self.question.as_ref().map(|q| q.id.as_str());
+        if showing.is_none() || (id.is_some() && showing != id) {
+            return false;
+        }
+        self.question = None;
+        if self.mode == UiMode::QuestionPrompt {
+            self.mode = UiMode::Normal;
+        }
+        self.mark_dirty();
+        true
+    }
+
+    /// Announces a tool call starting or failing, once per status change.
+    fn announce_tool_status(&mut self, event: &TimelineEvent) {
+        let previous = self
+            .state
+            .timeline_events
+            .iter()
+            .find(|e| e.id == event.id)
+            .and_then(|e| e.status.clone());
+        if previous == event.status {
+            return;
+        }
+        let name = event.tool_name.as_deref().unwrap_or("tool");
+        match event.status.as_deref() {
+            Some("running") => self.announce(format!("Running {}", name)),
+            Some("failed") => self.announce(format!("{} failed", name)),
+            _ => {}
+        }
+    }
+
+    fn is_current_session(&self, params: &serde_json::Value) -> bool {
+        params.get("sessionId").and_then(|v| v.as_str()) == self.state.session_id.as_deref()
+    }
+
+    pub fn handle_notification(&mut self, notif: BackendNotification) {
+        match notif.method.as_str() {
+            "todos_changed" if self.is_current_session(&notif.params) => {
+                if let Some(list) = notif
+                    .params
+                    .get("list")
+                    .and_then(|v| serde_json::from_value::<Vec<TodoItem>>(v.clone()).ok())
+                {
+                    self.todos = list;
+                    self.todo_selected = self.todo_selected.min(self.todos.len().saturating_sub(1));
+                }
+                if let Some(counts) = notif
+                    .params
+                    .get("counts")
+                    .and_then(|v| serde_json::from_value::<TodoCounts>(v.clone()).ok())
+                {
+                    self.set_todo_counts(counts);
+                }
+                self.mark_dirty();
+            }
+            "question_pending" if self.is_current_session(&notif.params) => {
+                let pending = notif
+                    .params
+                    .get("question")
+                    .and_then(|v| serde_json::from_value::<PendingQuestion>(v.clone()).ok());
+                match pending.and_then(|p| p.to_state()) {
+                    Some(question) => self.show_question(question),
+                    // Answered or skipped elsewhere.
+                    None => {
+                        self.dismiss_question(None);
+                    }
+                }
+            }
+            "question_resolved" if self.is_current_session(&notif.params) => {
+                let id = notif.params.get("id").and_then(|v| v.as_str());
+                // Answers given here have already closed the prompt.
+                if id.is_some() && self.dismiss_question(id) {
+                    match notif.params.get("status").and_then(|v| v.as_str()) {
+                        Some("answered") => self.set_toast(t("toast.question_answered_elsewhere")),
+                        Some("skipped") => self.set_toast(t("toast.question_skipped_elsewhere")),
+                        _ => self.set_toast(t("toast.question_cancelled")),
+                    }
+                }
+            }
+            "state" => {
+                if let Ok(next) = serde_json::from_value::<ChatState>(notif.params) {
+                    self.update_state(next);
+                }
+            }
+            "timeline_event" => {
+                if let Ok(event) = serde_json::from_value::<TimelineEvent>(notif.params) {
+                    if self.read_only
+                        && event.kind == "tool_call"
+                        && event.created_at >= self.read_only_since
+                    {
+                        if let Some(name) = event.tool_name.as_deref() {
+                            if self.mutating_tools.iter().any(|t| t == name) {
+                                self.read_only_violation = Some(name.to_string());
+                            }
+                        }
+                    }
+                    if self.accessible && event.kind == "tool_call" {
+                        self.announce_tool_status(&event);
+                    }
+                    if self.reindex_inflight
+                        && event.kind == "tool_result"
+                        && event.tool_name.as_deref() == Some("codesearch")
+                    {
+                        self.reindex_inflight = false;
+                        self.set_toast(t("toast.reindex_complete"));
                     }
                     self.upsert_timeline(event);
                 }
             }
+            "tool_retried" => {
+                self.tool_retrying = false;
+                let id = notif.params.get("toolCallId").and_then(|v| v.as_str());
+                let tool = self
+                    .state
+                    .timeline_events
+                    .iter()
+                    .find(|e| e.kind == "tool_call" && e.tool_call_id.as_deref() == id)
+                    .and_then(|e| e.tool_name.clone())
+                    .unwrap_or_else(|| "tool".to_string());
+                if let Some(error) = notif.params.get("error").and_then(|v| v.as_str()) {
+                    self.error_toast(tf(
+                        "toast.tool_retry_error",
+                        &[("tool", &tool), ("error", &error)],
+                    ));
+                } else if notif.params.get("status").and_then(|v| v.as_str()) == Some("completed") {
+                    self.set_toast(tf("toast.tool_retried", &[("tool", &tool)]));
+                } else {
+                    self.warn_toast(tf("toast.tool_retry_failed", &[("tool", &tool)]));
+                }
+            }
+            "tool_output" => {
+                let id = notif.params.get("toolCallId").and_then(|v| v.as_str());
+                let chunk = notif.params.get("chunk").and_then(|v| v.as_str());
+                if let (Some(id), Some(chunk)) = (id, chunk) {
+                    let buf = self.tool_outputs.entry(id.to_string()).or_default();
+                    buf.push_str(chunk);
+                    if buf.len() > MAX_TOOL_OUTPUT_BYTES {
+                        let mut cut = buf.len() - MAX_TOOL_OUTPUT_BYTES;
+                        while !buf.is_char_boundary(cut) {
+                            cut += 1;
+                        }
+                        buf.drain(..cut);
+                        *self.tool_output_dropped.entry(id.to_string()).or_default() += cut;
+                    }
+                    self.note_growth("tool_output");
+                    self.mark_dirty();
+                }
+            }
             "tokens_update" => {
                 if let Ok(update) = serde_json::from_value::<serde_json::Value>(notif.params) {
                     if let Some(tokens) = update.get("tokens") {
@@ -338,6 +2191,17 @@ impl App {
                     self.mark_dirty();
                 }
             }
+            "retry_status" => {
+                self.retry_status = serde_json::from_value::<RetryStatusPayload>(notif.params)
+                    .ok()
+                    .map(|p| RetryStatus {
+                        rate_limited: p.reason == "rate_limit",
+                        attempt: p.attempt,
+                        max_attempts: p.max_attempts,
+                        retry_at: Instant::now() + Duration::from_millis(p.delay_ms),
+                    });
+                self.mark_dirty();
+            }
             "context_status" => {
                 if let Ok(status) = serde_json::from_value::<String>(notif.params) {
                     self.state.context_status = Some(status);
@@ -347,6 +2211,9 @@ impl App {
             "plan_exit_proposed" => {
                 if let Some(flag) = notif.params.as_bool() {
                     if flag && self.state.agent == "plan" {
+                        self.load_plan();
+                        self.plan_scroll = 0;
+                        self.plan_action_selected = 0;
                         self.mode = UiMode::PlanActions;
                     }
                 }
@@ -356,6 +2223,8 @@ impl App {
                 if let Some(id) = notif.params.as_str() {
                     self.state.session_id = Some(id.to_string());
                 }
+                self.retry_payload = None;
+                self.context_samples.clear();
                 if matches!(self.mode, UiMode::SessionHistory) {
                     self.history_needs_refresh = true;
                 }
@@ -363,14 +2232,16 @@ impl App {
             }
             "error" => {
                 if let Some(s) = notif.params.as_str() {
-                    self.set_toast(s.to_string());
+                    self.error_toast(s.to_string());
                 }
+                self.retry_payload = self.last_send.take();
                 // Clear loading state on error
                 self.state.is_loading = false;
                 // Mark any running tool calls as failed
                 for event in &mut self.state.timeline_events {
                     if event.kind == "tool_call" && event.status.as_deref() == Some("running") {
                         event.status = Some("failed".to_string());
+                        self.event_line_cache.invalidate(&event.id);
                     }
                 }
                 self.timeline_revision = self.timeline_revision.saturating_add(1);
@@ -381,52 +2252,53 @@ impl App {
     }
 }
 
-pub fn build_file_index(project_dir: &Path) -> Vec<FileResult> {
-    let mut index = Vec::new();
-    let excludes = [
-        "node_modules",
-        ".git",
-        "dist",
-        "build",
-        ".next",
-        ".cache",
-        ".turbo",
-        ".output",
-        ".nuxt",
-        "coverage",
-        "__pycache__",
-        ".stratuscode",
-        ".vscode",
-        ".idea",
-    ];
-
-    for entry in WalkDir::new(project_dir)
-        .follow_links(false)
-        .max_depth(6)
-        .into_iter()
-        .filter_entry(|entry| {
-            let name = entry.file_name().to_string_lossy();
-            if name.starts_with('.') {
-                return false;
-            }
-            if excludes.iter().any(|e| name == *e) {
-                return false;
-            }
-            true
-        })
-        .filter_map(Result::ok)
-    {
-        let path = entry.path();
-        let rel = match path.strip_prefix(project_dir) {
-            Ok(r) => r,
-            Err(_) => continue,
-        };
-        let rel_str = rel.to_string_lossy().to_string();
-        index.push(FileResult {
-            relative_path: rel_str,
-            is_dir: entry.file_type().is_dir(),
+/// Asks the background indexer to rebuild the file index.
+pub enum IndexSignal {
+    /// Explicit `/reindex`; the result is announced with a toast.
+    Reindex,
+    /// The watcher saw files appear, disappear or move.
+    Changed,
+}
+
+/// Walks the project in parallel, honoring `.gitignore`, `.ignore` and git
+/// excludes plus the `[index]` config. Hidden entries are skipped.
+pub fn build_file_index(project_dir: &Path, config: &IndexConfig) -> Vec<FileResult> {
+    let (tx, rx) = std::sync::mpsc::channel::<FileResult>();
+    let excluded: Vec<std::path::PathBuf> =
+        config.exclude.iter().map(|d| project_dir.join(d)).collect();
+    let max_files = config.max_files.unwrap_or(usize::MAX);
+    let found = AtomicUsize::new(0);
+    WalkBuilder::new(project_dir)
+        .follow_links(config.follow_symlinks)
+        .max_depth(config.max_depth)
+        .require_git(false)
+        .filter_entry(move |entry| !excluded.iter().any(|dir| entry.path() == dir))
+        .build_parallel()
+        .run(|| {
+            let tx = tx.clone();
+            let found = &found;
+            Box::new(move |entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if entry.depth() == 0 {
+                    return WalkState::Continue;
+                }
+                let Ok(rel) = entry.path().strip_prefix(project_dir) else {
+                    return WalkState::Continue;
+                };
+                if found.fetch_add(1, Ordering::Relaxed) >= max_files {
+                    return WalkState::Quit;
+                }
+                let _ = tx.send(FileResult {
+                    relative_path: rel.to_string_lossy().to_string(),
+                    is_dir: entry.file_type().is_some_and(|t| t.is_dir()),
+                });
+                WalkState::Continue
+            })
         });
-    }
+    drop(tx);
+    let mut index: Vec<FileResult> = rx.into_iter().collect();
 
     index.sort_by(|a, b| {
         let a_depth = a.relative_path.matches('/').count();
@@ -441,28 +2313,262 @@ pub fn build_file_index(project_dir: &Path) -> Vec<FileResult> {
     index
 }
 
-pub fn filter_files(index: &[FileResult], query: &str, max_results: usize) -> Vec<FileResult> {
-    let lower = query.to_lowercase();
-    let mut results = Vec::new();
-    for item in index.iter() {
-        if !lower.is_empty() && !item.relative_path.to_lowercase().contains(&lower) {
-            continue;
+/// Something an @ mention can point at.
+#[derive(Debug, Clone)]
+pub enum MentionTarget {
+    File(FileResult),
+    Symbol(SymbolEntry),
+    /// A `codesearch` hit for the query as a whole.
+    Related(RelatedCode),
+}
+
+/// A chunk of code `codesearch` ranked as relevant to the @ query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelatedCode {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl MentionTarget {
+    /// The text matched against the query: the relative path for files, the
+    /// bare name for symbols.
+    pub fn label(&self) -> &str {
+        match self {
+            MentionTarget::File(file) => &file.relative_path,
+            MentionTarget::Symbol(symbol) => &symbol.name,
+            MentionTarget::Related(related) => &related.path,
+        }
+    }
+
+    /// The text inserted after the `@`.
+    pub fn insert_text(&self) -> String {
+        match self {
+            MentionTarget::File(file) => file.relative_path.clone(),
+            MentionTarget::Symbol(symbol) => symbol.reference(),
+            MentionTarget::Related(related) => related.path.clone(),
+        }
+    }
+}
+
+/// A mention that matched the @ query, with the char positions of its label
+/// that matched so the overlay can highlight them.
+#[derive(Debug, Clone)]
+pub struct MentionMatch {
+    pub target: MentionTarget,
+    pub indices: Vec<usize>,
+}
+
+/// Pastes with at least this many lines ask before landing in the prompt.
+pub const DEFAULT_PASTE_CONFIRM_LINES: usize = 2000;
+
+/// How many recently mentioned paths get a ranking boost.
+const RECENT_MENTIONS_LIMIT: usize = 20;
+/// Plain queries shorter than this only search files; symbols would drown them.
+const MIN_SYMBOL_QUERY_CHARS: usize = 3;
+
+fn recent_bonus(recent: &[String], text: &str) -> i64 {
+    recent
+        .iter()
+        .position(|r| r == text)
+        .map(|pos| ((RECENT_MENTIONS_LIMIT - pos.min(RECENT_MENTIONS_LIMIT)) * 8) as i64)
+        .unwrap_or(0)
+}
+
+/// Fuzzy-ranks files and symbols against `query`. Matches on the file name
+/// score higher than matches spread across directories, shorter paths win
+/// ties, and recently mentioned entries are boosted. A query starting with `#`
+/// searches symbols only. An empty query lists recent mentions first, then the
+/// file index in its depth-first order.
+pub fn filter_mentions(
+    files: &[FileResult],
+    symbols: &[SymbolEntry],
+    query: &str,
+    recent: &[String],
+    max_results: usize,
+) -> Vec<MentionMatch> {
+    let matcher = SkimMatcherV2::default().smart_case();
+    let score_symbols = |query: &str| -> Vec<(i64, MentionMatch)> {
+        symbols
+            .iter()
+            .filter_map(|symbol| {
+                let (score, indices) = if query.is_empty() {
+                    (0, Vec::new())
+                } else {
+                    matcher.fuzzy_indices(&symbol.name, query)?
+                };
+                let total = score * 2 - symbol.name.chars().count() as i64 / 4
+                    + recent_bonus(recent, &symbol.reference());
+                Some((
+                    total,
+                    MentionMatch {
+                        target: MentionTarget::Symbol(symbol.clone()),
+                        indices,
+                    },
+                ))
+            })
+            .collect()
+    };
+
+    let mut scored = if let Some(symbol_query) = query.strip_prefix('#') {
+        score_symbols(symbol_query)
+    } else if query.is_empty() {
+        return recent
+            .iter()
+            .filter_map(|path| files.iter().find(|f| &f.relative_path == path))
+            .chain(files.iter().filter(|f| !recent.contains(&f.relative_path)))
+            .take(max_results)
+            .map(|file| MentionMatch {
+                target: MentionTarget::File(file.clone()),
+                indices: Vec::new(),
+            })
+            .collect();
+    } else {
+        let mut scored: Vec<(i64, MentionMatch)> = files
+            .iter()
+            .filter_map(|file| {
+                let path = &file.relative_path;
+                let (score, indices) = matcher.fuzzy_indices(path, query)?;
+                let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+                let name_bonus = matcher.fuzzy_match(&path[name_start..], query).unwrap_or(0);
+                let total = score + name_bonus - path.chars().count() as i64 / 4
+                    + recent_bonus(recent, path);
+                Some((
+                    total,
+                    MentionMatch {
+                        target: MentionTarget::File(file.clone()),
+                        indices,
+                    },
+                ))
+            })
+            .collect();
+        if query.chars().count() >= MIN_SYMBOL_QUERY_CHARS {
+            scored.extend(score_symbols(query));
+        }
+        scored
+    };
+    scored.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.target.label().cmp(b.1.target.label()))
+    });
+    scored
+        .into_iter()
+        .take(max_results)
+        .map(|(_, m)| m)
+        .collect()
+}
+
+/// Related code from `codesearch` for this many query chars or more.
+pub const RELATED_MIN_QUERY_CHARS: usize = 4;
+/// Typing pause before the @ query goes to `codesearch`.
+pub const RELATED_DEBOUNCE: Duration = Duration::from_millis(400);
+/// Related-code rows under the fuzzy matches.
+const RELATED_RESULTS: usize = 5;
+
+impl App {
+    /// The @ overlay's rows: fuzzy file and symbol matches, then `codesearch`
+    /// hits for the same query in files not already listed.
+    pub fn mention_matches(&self, query: &str) -> Vec<MentionMatch> {
+        let mut matches = filter_mentions(
+            &self.file_index,
+            &self.symbol_index,
+            query,
+            &self.recent_mentions,
+            10,
+        );
+        if self.related_query.as_deref() == Some(query.trim()) {
+            let related: Vec<MentionMatch> = self
+                .related_code
+                .iter()
+                .filter(|r| !matches.iter().any(|m| m.target.label() == r.path))
+                .map(|r| MentionMatch {
+                    target: MentionTarget::Related(r.clone()),
+                    indices: Vec::new(),
+                })
+                .collect();
+            matches.extend(related);
         }
-        results.push(item.clone());
-        if results.len() >= max_results {
-            break;
+        matches
+    }
+
+    /// The @ query once typing has paused long enough to search for it, when
+    /// it hasn't been searched already.
+    pub fn related_query_due(&self) -> Option<String> {
+        if self.mode != UiMode::FileMention || self.related_inflight || self.related_error.is_some()
+        {
+            return None;
+        }
+        let query = file_query_from_input(&self.input, self.cursor);
+        let query = query.trim();
+        (query.chars().count() >= RELATED_MIN_QUERY_CHARS
+            && self.related_query.as_deref() != Some(query))
+        .then(|| query.to_string())
+    }
+}
+
+/// Related code from a `codesearch` tool result, paths relative to the project.
+pub fn parse_related_code(
+    result: &serde_json::Value,
+    project_dir: &str,
+) -> Result<Vec<RelatedCode>, String> {
+    let parsed: serde_json::Value = result
+        .as_str()
+        .and_then(|raw| serde_json::from_str(raw).ok())
+        .ok_or_else(|| "No response".to_string())?;
+    if let Some(error) = parsed.get("error") {
+        let message = parsed.get("message").and_then(|m| m.as_str());
+        return Err(message
+            .or(error.as_str())
+            .unwrap_or("codesearch failed")
+            .to_string());
+    }
+    let prefix = format!("{}/", project_dir.trim_end_matches('/'));
+    let mut related: Vec<RelatedCode> = Vec::new();
+    for hit in parsed["results"].as_array().into_iter().flatten() {
+        let Some(path) = hit["filePath"].as_str() else {
+            continue;
+        };
+        let code = RelatedCode {
+            path: path.strip_prefix(&prefix).unwrap_or(path).to_string(),
+            start_line: hit["startLine"].as_u64().unwrap_or(1) as usize,
+            end_line: hit["endLine"].as_u64().unwrap_or(1) as usize,
+        };
+        // One row per file, its best chunk.
+        if !related.iter().any(|r| r.path == code.path) {
+            related.push(code);
         }
     }
-    results
+    related.truncate(RELATED_RESULTS);
+    Ok(related)
 }
 
+/// Builds the index synchronously when no background indexer is running.
 pub fn ensure_file_index(app: &mut App) {
-    if app.file_index.is_empty() {
-        let index = build_file_index(Path::new(&app.project_dir));
+    if app.file_index.is_empty() && app.file_index_refresh.is_none() {
+        let started = Instant::now();
+        let index = build_file_index(Path::new(&app.project_dir), &app.index_config);
+        app.set_toast(index_summary(
+            index.len(),
+            started.elapsed(),
+            &app.index_config,
+        ));
         app.file_index = index;
     }
 }
 
+/// "Indexed 12,431 files in 80ms", noting when `max_files` cut the walk short.
+pub fn index_summary(count: usize, elapsed: Duration, config: &IndexConfig) -> String {
+    let mut summary = format!(
+        "Indexed {} files in {}ms",
+        format_number(count as u64),
+        elapsed.as_millis()
+    );
+    if config.max_files.is_some_and(|max| count >= max) {
+        summary.push_str(" (stopped at index.max_files)");
+    }
+    summary
+}
+
 pub fn file_query_from_input(input: &str, cursor: usize) -> String {
     let upto = &input[..cursor.min(input.len())];
     if let Some(idx) = upto.rfind('@') {
@@ -479,6 +2585,28 @@ pub fn insert_file_mention(app: &mut App, path: &str) {
         let after = app.input[app.cursor..].to_string();
         app.input = format!("{}{} {}", before, path, after);
         app.cursor = before.len() + path.len() + 1;
+        app.recent_mentions.retain(|p| p != path);
+        app.recent_mentions.insert(0, path.to_string());
+        app.recent_mentions.truncate(RECENT_MENTIONS_LIMIT);
+    }
+}
+
+pub fn pin_file(app: &mut App, path: &str) {
+    if app.pinned.iter().any(|p| p == path) {
+        app.warn_toast(tf("toast.already_pinned", &[("file", &path)]));
+        return;
+    }
+    app.pinned.push(path.to_string());
+    app.set_toast(tf("toast.pinned", &[("file", &path)]));
+}
+
+/// Drops the `@query` being typed, for when the @ overlay pins instead of
+/// inserting a mention.
+pub fn remove_mention_query(app: &mut App) {
+    app.cursor = crate::input::clamp_cursor(&app.input, app.cursor);
+    if let Some(idx) = app.input[..app.cursor].rfind('@') {
+        app.input.replace_range(idx..app.cursor, "");
+        app.cursor = idx;
     }
 }
 
@@ -513,6 +2641,542 @@ pub fn collect_answers(q: &QuestionState) -> Vec<String> {
     answers
 }
 
+impl App {
+    /// Where the backend keeps the plan for the current session.
+    pub fn plan_file_path(&self) -> Option<std::path::PathBuf> {
+        let session_id = self.state.session_id.as_ref()?;
+        Some(
+            Path::new(&self.project_dir)
+                .join(".stratuscode")
+                .join("plans")
+                .join(format!("{}.md", session_id)),
+        )
+    }
+
+    pub fn load_plan(&mut self) {
+        self.plan_content = self
+            .plan_file_path()
+            .and_then(|path| std::fs::read_to_string(path).ok());
+        self.mark_dirty();
+    }
+
+    /// Built-in agents first, then custom ones in name order.
+    pub fn agent_names(&self) -> Vec<String> {
+        BUILT_IN_AGENTS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain(self.custom_agents.iter().map(|a| a.name.clone()))
+            .collect()
+    }
+}
+
+/// Switches the active agent, sending the definition along for custom agents
+/// since the backend only knows the built-in ones.
+pub fn switch_agent(app: &mut App, client: &Arc<Mutex<BackendClient>>, name: &str) {
+    let definition = app
+        .custom_agents
+        .iter()
+        .find(|a| a.name == name)
+        .map(|a| a.payload());
+    app.state.agent = name.to_string();
+    let _ = client.lock().unwrap().call(
+        "set_agent",
+        json!({ "agent": name, "definition": definition }),
+    );
+    app.mark_dirty();
+}
+
+/// Opens the pager on the full output of the most recent tool call that has
+/// any: the streamed log when there is one, else the recorded result.
+pub fn open_output_pager(app: &mut App) {
+    let found = app
+        .state
+        .timeline_events
+        .iter()
+        .rev()
+        .filter(|e| e.kind == "tool_call")
+        .find_map(|call| {
+            let id = call.tool_call_id.as_deref()?;
+            // Streamed output only exists while the call runs, capped from
+            // the front; a finished call shows its full result.
+            let (text, dropped) = match app.tool_outputs.get(id) {
+                Some(text) => (
+                    text.clone(),
+                    app.tool_output_dropped.get(id).copied().unwrap_or(0),
+                ),
+                None => (
+                    app.state
+                        .timeline_events
+                        .iter()
+                        .find(|e| e.kind == "tool_result" && e.tool_call_id.as_deref() == Some(id))
+                        .map(|e| e.content.clone())?,
+                    0,
+                ),
+            };
+            let name = call.tool_name.as_deref().unwrap_or("tool");
+            Some((
+                format!("{} {}", name, format_tool_args(&call.content)),
+                text,
+                dropped,
+            ))
+        });
+    match found {
+        Some((title, text, dropped)) => {
+            let mut lines: Vec<Line<'static>> = Vec::new();
+            if dropped > 0 {
+                let size = crate::attachments::format_size(dropped);
+                lines.push(Line::styled(
+                    tf("ui.pager_output_dropped", &[("size", &size)]),
+                    Style::default().add_modifier(Modifier::DIM),
+                ));
+            }
+            lines.extend(app.redact(&text).lines().map(|l| Line::from(l.to_string())));
+            let mut pager = Pager {
+                title: app.redact(&title).into_owned(),
+                lines,
+                scroll: 0,
+                hunks: Vec::new(),
+                hunk: None,
+            };
+            // Logs are read from the end.
+            pager.scroll = pager.max_scroll();
+            app.pager = Some(pager);
+            app.mode = UiMode::Pager;
+        }
+        None => app.warn_toast(t("toast.no_tool_output")),
+    }
+    app.mark_dirty();
+}
+
+/// Opens the pager on a diff too long to show inline, from the focused
+/// message when it has one; does nothing when every diff fits.
+pub fn open_diff_pager(app: &mut App) {
+    let width = crossterm::terminal::size()
+        .map(|(cols, _)| cols as usize)
+        .unwrap_or(100)
+        .saturating_sub(10);
+    let events = &app.state.timeline_events;
+    let folded_diff = |e: &TimelineEvent| {
+        if e.kind != "tool_result" {
+            return None;
+        }
+        let (summary, lines) = extract_diff_summary(&app.redact(&e.content), width)?;
+        let diff = serde_json::from_str::<serde_json::Value>(&e.content)
+            .ok()?
+            .get("diff")?
+            .as_str()?
+            .to_string();
+        (lines.len() > app.diff_max_lines).then(|| (e.tool_call_id.clone(), summary, lines, diff))
+    };
+    // The first folded diff in the focused message, else the latest.
+    let found = app
+        .focused_message_range()
+        .and_then(|range| events[range].iter().find_map(folded_diff))
+        .or_else(|| events.iter().rev().find_map(folded_diff));
+    let Some((call_id, summary, lines, diff)) = found else {
+        return;
+    };
+    let (lines, hunks) = diff_hunk_lines(app, &diff, width).unwrap_or((lines, Vec::new()));
+    let args = app
+        .state
+        .timeline_events
+        .iter()
+        .find(|e| e.kind == "tool_call" && e.tool_call_id.is_some() && e.tool_call_id == call_id)
+        .map(|call| {
+            format!(
+                "{} {}",
+                call.tool_name.as_deref().unwrap_or("tool"),
+                app.redact(&format_tool_args(&call.content))
+            )
+        })
+        .unwrap_or_else(|| "Diff".to_string());
+    app.pager = Some(Pager {
+        title: format!("{} {}", args, summary),
+        lines,
+        scroll: 0,
+        hunks,
+        hunk: None,
+    });
+    app.mode = UiMode::Pager;
+    app.mark_dirty();
+}
+
+/// A diff drawn file by file so each `@@` line can be tied to its hunk.
+/// `None` when no file in it resolves inside the project. The lines are
+/// masked; the hunks keep the raw text to copy or revert.
+fn diff_hunk_lines(
+    app: &App,
+    diff: &str,
+    width: usize,
+) -> Option<(Vec<Line<'static>>, PagerHunks)> {
+    let project_dir = Path::new(&app.project_dir);
+    let files = file_diffs(diff, project_dir, project_dir);
+    if files.is_empty() {
+        return None;
+    }
+    let mut lines = Vec::new();
+    let mut hunks = Vec::new();
+    for file in &files {
+        let (_, file_lines) = render_diff(&app.redact(&file.to_unified()), width);
+        let mut file_hunks = file.split_hunks().into_iter();
+        for line in file_lines {
+            let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+            if text.starts_with("@@") {
+                if let Some(hunk) = file_hunks.next() {
+                    hunks.push((lines.len(), hunk));
+                }
+            }
+            lines.push(line);
+        }
+    }
+    Some((lines, hunks))
+}
+
+/// `c` on a diff hunk: closes the pager and starts a message quoting it.
+pub fn comment_on_hunk(app: &mut App, hunk: &DiffHunk) {
+    const QUOTED_LINES: usize = 30;
+    let mut quote = format!("> `{}`\n", hunk.path);
+    for line in hunk.text.lines().take(QUOTED_LINES) {
+        quote.push_str(&format!("> {}\n", line));
+    }
+    if hunk.text.lines().count() > QUOTED_LINES {
+        quote.push_str("> …\n");
+    }
+    quote.push('\n');
+    app.pager = None;
+    app.mode = UiMode::Normal;
+    app.input = quote;
+    app.cursor = app.input.len();
+    app.mark_dirty();
+}
+
+/// `r` on a diff hunk: undoes it in the working tree.
+pub fn revert_selected_hunk(app: &mut App) {
+    let Some(hunk) = app.pager.as_ref().and_then(Pager::selected_hunk).cloned() else {
+        return;
+    };
+    match revert_hunk(Path::new(&app.project_dir), &hunk) {
+        Ok(()) => app.set_toast(tf("toast.hunk_reverted", &[("file", &hunk.path)])),
+        Err(e) => app.error_toast(tf("toast.hunk_revert_failed", &[("error", &e)])),
+    }
+}
+
+/// The backend names new sessions "New session - <time>" until their first
+/// message titles them.
+fn is_placeholder_title(title: &str) -> bool {
+    title.starts_with("New session - ")
+}
+
+/// Up to `limit` events before the one with id `before` (all of them without
+/// a limit).
+pub fn fetch_earlier(
+    client: &Arc<Mutex<BackendClient>>,
+    before: &str,
+    limit: Option<usize>,
+) -> Result<EventPage, String> {
+    let resp = client
+        .lock()
+        .unwrap()
+        .call("get_events", json!({ "before": before, "limit": limit }))
+        .map_err(|e| e.to_string())?;
+    serde_json::from_value(resp).map_err(|e| e.to_string())
+}
+
+/// Pages in everything before the resident events, e.g. to jump to an old
+/// bookmark.
+pub fn load_all_earlier(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    let Some(first) = app.state.timeline_events.first().map(|e| e.id.clone()) else {
+        return;
+    };
+    match fetch_earlier(client, &first, None) {
+        Ok(page) => app.prepend_earlier(&first, page),
+        Err(e) => app.error_toast(tf("toast.earlier_failed", &[("error", &e)])),
+    }
+}
+
+fn is_tool_event(event: &TimelineEvent) -> bool {
+    matches!(event.kind.as_str(), "tool_call" | "tool_result")
+}
+
+/// The whole timeline for exports: the resident events plus the ones left
+/// out of the window, without keeping the latter around. Unredacted, so
+/// patches apply; transcripts mask it themselves.
+pub fn whole_timeline(app: &App, client: &Arc<Mutex<BackendClient>>) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+    if let Some(first) = app.state.timeline_events.first() {
+        if app.state.earlier_events > 0 {
+            if let Ok(page) = fetch_earlier(client, &first.id, None) {
+                events = page.events;
+            }
+        }
+    }
+    events.extend(app.state.timeline_events.iter().cloned());
+    order_events(&mut events);
+    events
+}
+
+/// Ctrl+K: bookmarks the focused message in the current session.
+pub fn bookmark_focused(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    let Some(session_id) = app.state.session_id.clone() else {
+        app.warn_toast(t("toast.bookmark_nothing"));
+        return;
+    };
+    let Some(event) = app
+        .focused_event
+        .as_ref()
+        .filter(|id| !id.starts_with(PENDING_SEND_PREFIX))
+        .and_then(|id| app.state.timeline_events.iter().find(|e| e.id == *id))
+    else {
+        app.warn_toast(t("toast.bookmark_nothing"));
+        return;
+    };
+    let label = app.redact(&bookmark_label(event)).into_owned();
+    let resp = client.lock().unwrap().call(
+        "add_bookmark",
+        json!({ "sessionId": session_id, "eventId": event.id, "label": label }),
+    );
+    match resp {
+        Ok(_) => app.set_toast(tf("toast.bookmarked", &[("label", &label)])),
+        Err(e) => app.error_toast(tf("toast.bookmark_failed", &[("error", &e)])),
+    }
+}
+
+/// The first line of a message, or the tool it starts with.
+fn bookmark_label(event: &TimelineEvent) -> String {
+    let text = match event.kind.as_str() {
+        "tool_call" | "tool_result" => event.tool_name.clone().unwrap_or_default(),
+        _ => event
+            .content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    truncate_text(&text, BOOKMARK_LABEL_WIDTH)
+}
+
+/// `/bookmarks`: lists the session's bookmarks to jump back to.
+pub fn open_bookmarks(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    let Some(session_id) = app.state.session_id.clone() else {
+        app.warn_toast(t("toast.bookmarks_empty"));
+        return;
+    };
+    let resp = client
+        .lock()
+        .unwrap()
+        .call("list_bookmarks", json!({ "sessionId": session_id }));
+    match resp.map(serde_json::from_value::<Vec<Bookmark>>) {
+        Ok(Ok(list)) if list.is_empty() => app.warn_toast(t("toast.bookmarks_empty")),
+        Ok(Ok(list)) => {
+            app.bookmarks = list;
+            app.bookmark_selected = 0;
+            app.mode = UiMode::Bookmarks;
+        }
+        Ok(Err(e)) => app.error_toast(tf("toast.bookmark_failed", &[("error", &e)])),
+        Err(e) => app.error_toast(tf("toast.bookmark_failed", &[("error", &e)])),
+    }
+}
+
+/// Ctrl+B: opens the sidebar focused, focuses it when it's open but not, and
+/// closes it from focus.
+pub fn toggle_sidebar(app: &mut App) {
+    if !app.sidebar_open {
+        app.sidebar_open = true;
+        app.mode = UiMode::FileSidebar;
+    } else if app.mode == UiMode::FileSidebar {
+        app.sidebar_open = false;
+        app.mode = UiMode::Normal;
+    } else {
+        app.mode = UiMode::FileSidebar;
+    }
+    app.mark_dirty();
+}
+
+/// Opens the pager on the sidebar's selected file: its diffs when it was
+/// changed, else the file itself.
+pub fn open_file_preview(app: &mut App) {
+    let files = app.touched_files();
+    let Some(file) = files.get(app.sidebar_selected) else {
+        return;
+    };
+    let width = crossterm::terminal::size()
+        .map(|(cols, _)| cols as usize)
+        .unwrap_or(100)
+        .saturating_sub(10);
+    let (title, lines) = if file.diffs.is_empty() {
+        let path = Path::new(&app.project_dir).join(&file.path);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let lines = text
+                    .lines()
+                    .enumerate()
+                    .map(|(idx, line)| Line::raw(format!("{:>5}  {}", idx + 1, line)))
+                    .collect();
+                (file.path.clone(), lines)
+            }
+            Err(e) => {
+                app.error_toast(format!("{}: {}", file.path, e));
+                return;
+            }
+        }
+    } else {
+        let diff: String = file.diffs.iter().map(|d| d.to_unified()).collect();
+        let (summary, lines) = render_diff(&diff, width);
+        (format!("{} {}", file.path, summary), lines)
+    };
+    app.pager = Some(Pager {
+        title,
+        lines,
+        scroll: 0,
+        hunks: Vec::new(),
+        hunk: None,
+    });
+    app.mode = UiMode::Pager;
+    app.mark_dirty();
+}
+
+/// Replaces the input with the expanded template so it can be reviewed and
+/// edited before sending.
+pub fn apply_template(app: &mut App, template: &PromptTemplate, arg: &str) {
+    let selection = if template.body.contains("{{selection}}") {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let context = TemplateContext {
+        selection,
+        file: app
+            .recent_mentions
+            .first()
+            .map(|path| format!("@{}", path))
+            .unwrap_or_default(),
+        arg: arg.to_string(),
+    };
+    app.input = expand_template(&template.body, &context);
+    app.cursor = app.input.len();
+    app.mode = UiMode::Normal;
+    app.mark_dirty();
+}
+
+/// Ctrl+Q or `/quote`: quotes the focused message into the input with `> `
+/// prefixes. Text copied from inside the message quotes just that part.
+pub fn quote_focused(app: &mut App) {
+    let Some(content) = app.focused_message().map(|e| e.content.trim().to_string()) else {
+        app.warn_toast(t("toast.quote_nothing"));
+        return;
+    };
+    let selection = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .unwrap_or_default();
+    let selection = selection.trim();
+    let text = if !selection.is_empty() && content.contains(selection) {
+        selection
+    } else {
+        content.as_str()
+    };
+    let quote: String = text
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                ">\n".to_string()
+            } else {
+                format!("> {}\n", line)
+            }
+        })
+        .collect();
+    if !app.input.is_empty() && !app.input.ends_with("\n\n") {
+        app.input.push_str(if app.input.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        });
+    }
+    app.input.push_str(&quote);
+    app.input.push('\n');
+    app.cursor = app.input.len();
+    app.mode = UiMode::Normal;
+    app.mark_dirty();
+}
+
+/// Shift+Esc or `/skip`: ends the running tool call and lets the model carry
+/// on with a cancelled result, where Esc stops the whole turn.
+pub fn cancel_tool(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    let tool = app
+        .state
+        .timeline_events
+        .iter()
+        .rev()
+        .find(|e| e.kind == "tool_call" && e.status.as_deref() == Some("running"))
+        .and_then(|e| e.tool_name.clone())
+        .unwrap_or_else(|| "tool".to_string());
+    let resp = client.lock().unwrap().call("cancel_tool", json!({}));
+    match resp.map(|resp| resp["cancelled"].as_u64().unwrap_or(0)) {
+        Ok(0) => app.warn_toast(t("toast.no_tool_running")),
+        Ok(_) => app.set_toast(tf("toast.tool_cancelled", &[("tool", &tool)])),
+        Err(e) => app.error_toast(tf("toast.tool_cancel_failed", &[("error", &e)])),
+    }
+}
+
+/// `/retrytool`: runs the failed call the last turn ended on again. The
+/// backend answers at once and reports the outcome as `tool_retried`.
+pub fn retry_failed_tool(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    if app.state.is_loading || app.tool_retrying {
+        app.warn_toast(t("toast.tool_retry_busy"));
+        return;
+    }
+    let Some(call) = app.retryable_tool() else {
+        app.warn_toast(t("toast.tool_retry_nothing"));
+        return;
+    };
+    let tool_call_id = call.tool_call_id.clone().unwrap_or_default();
+    let tool = call.tool_name.clone().unwrap_or_else(|| "tool".to_string());
+    let resp = client
+        .lock()
+        .unwrap()
+        .call("retry_tool_call", json!({ "toolCallId": tool_call_id }));
+    match resp {
+        Ok(_) => {
+            app.tool_retrying = true;
+            app.auto_scroll = true;
+            app.scroll_from_bottom = 0;
+            app.mark_dirty();
+        }
+        Err(e) => app.error_toast(tf(
+            "toast.tool_retry_error",
+            &[("tool", &tool), ("error", &e)],
+        )),
+    }
+}
+
+/// Sets the reasoning effort and remembers it for the current model.
+pub fn set_reasoning_effort(app: &mut App, client: &Arc<Mutex<BackendClient>>, effort: &str) {
+    app.reasoning_effort = effort.to_string();
+    let _ = client
+        .lock()
+        .unwrap()
+        .call("set_reasoning_effort", json!({ "reasoningEffort": effort }));
+    let model = app.current_model();
+    app.model_prefs.record_reasoning(&model, effort);
+    app.set_toast(tf("toast.reasoning", &[("effort", &effort)]));
+}
+
+/// Expands the todo strip and focuses it for editing, or collapses it.
+pub fn toggle_todos(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    app.todos_expanded = !app.todos_expanded;
+    app.todo_edit = None;
+    if app.todos_expanded {
+        app.mode = UiMode::TodoFocus;
+    } else if app.mode == UiMode::TodoFocus {
+        app.mode = UiMode::Normal;
+    }
+    refresh_todos(app, client);
+    app.todo_selected = app.todo_selected.min(app.todos.len().saturating_sub(1));
+    app.mark_dirty();
+}
+
 pub fn refresh_todos(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
     if let Some(session_id) = &app.state.session_id {
         if let Ok(resp) = client
@@ -527,7 +3191,7 @@ pub fn refresh_todos(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
             }
             if let Some(counts_val) = resp.get("counts") {
                 if let Ok(counts) = serde_json::from_value::<TodoCounts>(counts_val.clone()) {
-                    app.todo_counts = counts;
+                    app.set_todo_counts(counts);
                 }
             }
             app.mark_dirty();
@@ -0,0 +1,347 @@
+use base64::Engine;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::app::{App, AttachmentKind, AttachmentUpload};
+use crate::constants::{IMAGE_MARKER, PASTE_CHAR_THRESHOLD, PASTE_LINE_THRESHOLD};
+
+const MAX_IMAGE_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+const MAX_FILE_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+const MAX_TEXT_ATTACHMENT_BYTES: u64 = 1024 * 1024;
+
+fn mime_for_extension(ext: &str) -> Option<&'static str> {
+    let mime = match ext.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "md" | "markdown" => "text/markdown",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "txt" | "log" => "text/plain",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Reads a file from disk into an attachment. Images and PDFs are sent as
+/// base64, anything that decodes as UTF-8 is sent as text, other binaries are
+/// rejected.
+pub fn load_attachment(path: &Path) -> Result<AttachmentUpload, String> {
+    let meta =
+        std::fs::metadata(path).map_err(|_| format!("File not found: {}", path.display()))?;
+    if !meta.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+    let mime = path
+        .extension()
+        .and_then(|ext| mime_for_extension(&ext.to_string_lossy()));
+    let size = meta.len();
+
+    let (kind, limit) = match mime {
+        Some(m) if m.starts_with("image/") => (AttachmentKind::Image, MAX_IMAGE_ATTACHMENT_BYTES),
+        Some("application/pdf") => (AttachmentKind::File, MAX_FILE_ATTACHMENT_BYTES),
+        _ => (AttachmentKind::Text, MAX_TEXT_ATTACHMENT_BYTES),
+    };
+    if size > limit {
+        return Err(format!(
+            "{} is too large ({}, max {})",
+            name,
+            format_size(size as usize),
+            format_size(limit as usize)
+        ));
+    }
+
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    if kind == AttachmentKind::Image {
+        // Normalise to PNG like clipboard images so the backend sees one format.
+        let png = if mime == Some("image/png") {
+            bytes
+        } else {
+            image::load_from_memory(&bytes)
+                .ok()
+                .and_then(|img| encode_png(&img))
+                .ok_or_else(|| format!("Failed to process image: {}", name))?
+        };
+        return Ok(AttachmentUpload {
+            id: 0,
+            kind,
+            name,
+            size: png.len(),
+            data: base64::engine::general_purpose::STANDARD.encode(&png),
+            mime: "image/png".to_string(),
+        });
+    }
+    let data = match kind {
+        AttachmentKind::Text => {
+            if bytes.contains(&0) {
+                return Err(format!("Unsupported binary file: {}", name));
+            }
+            String::from_utf8(bytes).map_err(|_| format!("Unsupported binary file: {}", name))?
+        }
+        _ => base64::engine::general_purpose::STANDARD.encode(&bytes),
+    };
+
+    Ok(AttachmentUpload {
+        id: 0,
+        kind,
+        name,
+        size: size as usize,
+        data,
+        mime: mime.unwrap_or("text/plain").to_string(),
+    })
+}
+
+/// Resolves `/attach` arguments and pasted paths: surrounding quotes are
+/// stripped, `~` expands to the home directory and relative paths are taken
+/// from the project root.
+pub fn resolve_attachment_path(raw: &str, project_dir: &str) -> PathBuf {
+    let trimmed = raw.trim().trim_matches(|c| c == '"' || c == '\'');
+    let path = match trimmed.strip_prefix("~/") {
+        Some(rest) => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => PathBuf::from(trimmed),
+        },
+        None => PathBuf::from(trimmed),
+    };
+    if path.is_absolute() {
+        path
+    } else {
+        Path::new(project_dir).join(path)
+    }
+}
+
+/// Files in a paste that consists only of absolute paths to existing files,
+/// as produced by dragging files onto the terminal. Handles shell-escaped
+/// spaces (`Screenshot\ 2024-...png`), quoting and `file://` URLs. Returns
+/// `None` if any part of the paste is not such a path.
+pub fn pasted_file_paths(text: &str, project_dir: &str) -> Option<Vec<PathBuf>> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || trimmed.contains('\n') {
+        return None;
+    }
+    let resolve = |token: &str| -> Option<PathBuf> {
+        let token = match token.strip_prefix("file://") {
+            Some(rest) => percent_decode(rest),
+            None => token.to_string(),
+        };
+        if !(token.starts_with('/') || token.starts_with("~/")) {
+            return None;
+        }
+        let path = resolve_attachment_path(&token, project_dir);
+        path.is_file().then_some(path)
+    };
+    // Some terminals paste a dragged path verbatim, spaces and all.
+    if let Some(path) = resolve(trimmed.trim_matches(|c| c == '"' || c == '\'')) {
+        return Some(vec![path]);
+    }
+    split_shell_words(trimmed)
+        .iter()
+        .map(|token| resolve(token))
+        .collect()
+}
+
+/// Splits on unescaped whitespace, honoring backslash escapes and quotes.
+fn split_shell_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+pub fn encode_png(img: &image::DynamicImage) -> Option<Vec<u8>> {
+    let mut buf = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut buf, image::ImageFormat::Png).ok()?;
+    Some(buf.into_inner())
+}
+
+pub fn is_large_paste(text: &str) -> bool {
+    text.lines().count() >= PASTE_LINE_THRESHOLD || text.len() >= PASTE_CHAR_THRESHOLD
+}
+
+/// Large pastes travel as text attachments rather than living in the input
+/// between paste sentinels, which made every edit walk the whole paste.
+pub fn paste_attachment(text: String) -> AttachmentUpload {
+    let line_count = text.lines().count().max(1);
+    AttachmentUpload {
+        id: 0,
+        kind: AttachmentKind::Text,
+        name: format!(
+            "Pasted {} line{}",
+            line_count,
+            if line_count == 1 { "" } else { "s" }
+        ),
+        size: text.len(),
+        data: text,
+        mime: "text/plain".to_string(),
+    }
+}
+
+/// The first URL in the input (bare, `@https://…` or `@web <url>`) that isn't
+/// attached or being fetched yet, offered for Ctrl+G.
+pub fn unfetched_url(app: &App) -> Option<String> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let re = URL.get_or_init(|| Regex::new(r#"https?://[^\s<>"'`]+"#).expect("valid URL pattern"));
+    re.find_iter(&app.input)
+        .map(|m| {
+            m.as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']'])
+                .to_string()
+        })
+        .find(|url| {
+            app.url_fetching.as_deref() != Some(url.as_str())
+                && !app.attachments.iter().any(|a| a.name == *url)
+        })
+}
+
+/// The page text from a `webfetch` tool result.
+pub fn webfetch_text(result: &Value) -> Result<String, String> {
+    let parsed: Value = result
+        .as_str()
+        .and_then(|raw| serde_json::from_str(raw).ok())
+        .ok_or_else(|| "No response".to_string())?;
+    match parsed.get("content").and_then(|c| c.as_str()) {
+        Some(content) if parsed.get("error").is_none() => Ok(content.to_string()),
+        _ => Err(parsed
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("Fetch failed")
+            .to_string()),
+    }
+}
+
+pub fn url_attachment(url: String, text: String) -> AttachmentUpload {
+    AttachmentUpload {
+        id: 0,
+        kind: AttachmentKind::Text,
+        name: url,
+        size: text.len(),
+        data: text,
+        mime: "text/plain".to_string(),
+    }
+}
+
+/// Inserts a marker at the cursor and keeps `app.attachments` in the same
+/// order as the markers in the input.
+pub fn add_attachment(app: &mut App, mut upload: AttachmentUpload) {
+    upload.id = app.next_attachment_id;
+    app.next_attachment_id += 1;
+    let index = app.input[..app.cursor].matches(IMAGE_MARKER).count();
+    app.input.insert(app.cursor, IMAGE_MARKER);
+    app.cursor += IMAGE_MARKER.len_utf8();
+    app.attachments
+        .insert(index.min(app.attachments.len()), upload);
+    app.mark_dirty();
+}
+
+/// Removes an attachment together with its marker.
+pub fn remove_attachment(app: &mut App, id: u32) -> Option<AttachmentUpload> {
+    let index = app.attachments.iter().position(|a| a.id == id)?;
+    if let Some((pos, _)) = app.input.match_indices(IMAGE_MARKER).nth(index) {
+        app.input.remove(pos);
+        if app.cursor > pos {
+            app.cursor -= IMAGE_MARKER.len_utf8();
+        }
+    }
+    app.mark_dirty();
+    Some(app.attachments.remove(index))
+}
+
+/// Drops the attachments whose markers lie in `input[start..end]`. Call before
+/// removing that range from the input.
+pub fn drop_attachments_in_range(app: &mut App, start: usize, end: usize) {
+    let first = app.input[..start].matches(IMAGE_MARKER).count();
+    let count = app.input[start..end].matches(IMAGE_MARKER).count();
+    let last = (first + count).min(app.attachments.len());
+    if first < last {
+        app.attachments.drain(first..last);
+    }
+}
+
+/// Glyph standing in for a thumbnail in the attachments strip.
+pub fn attachment_glyph(kind: AttachmentKind) -> &'static str {
+    match kind {
+        AttachmentKind::Image => "▣",
+        AttachmentKind::File => "▤",
+        AttachmentKind::Text => "≡",
+    }
+}
+
+/// The `attachments` entry sent with `send_message`.
+pub fn attachment_payload(attachment: &AttachmentUpload) -> Value {
+    match attachment.kind {
+        AttachmentKind::Text => json!({
+            "type": "text",
+            "name": attachment.name,
+            "mime": attachment.mime,
+            "text": attachment.data,
+        }),
+        AttachmentKind::Image | AttachmentKind::File => json!({
+            "type": attachment.kind.as_str(),
+            "name": attachment.name,
+            "mime": attachment.mime,
+            "data": attachment.data,
+        }),
+    }
+}
+
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{}B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1}KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1}MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
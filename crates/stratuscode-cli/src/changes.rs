@@ -0,0 +1,535 @@
+use crate::backend::TimelineEvent;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// One file's part of a unified diff, with paths relative to the project.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    /// None for a file the diff creates.
+    pub old: Option<String>,
+    /// None for a file the diff deletes.
+    pub new: Option<String>,
+    /// The `@@` hunks, each line newline-terminated.
+    pub hunks: String,
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl FileDiff {
+    pub fn path(&self) -> &str {
+        self.new.as_deref().or(self.old.as_deref()).unwrap_or("")
+    }
+
+    /// The section as `git apply` expects it, `diff --git` header included.
+    pub fn to_git(&self) -> String {
+        let name = self.path();
+        let mut out = format!("diff --git a/{} b/{}\n", name, name);
+        match (&self.old, &self.new) {
+            (None, _) => out.push_str("new file mode 100644\n"),
+            (_, None) => out.push_str("deleted file mode 100644\n"),
+            _ => {}
+        }
+        out.push_str(&self.to_unified());
+        out
+    }
+
+    /// `---`/`+++` headers and hunks.
+    pub fn to_unified(&self) -> String {
+        let side = |path: &Option<String>, prefix: &str| match path {
+            Some(path) => format!("{}/{}", prefix, path),
+            None => "/dev/null".to_string(),
+        };
+        format!(
+            "--- {}\n+++ {}\n{}",
+            side(&self.old, "a"),
+            side(&self.new, "b"),
+            self.hunks
+        )
+    }
+}
+
+/// One `@@` hunk of a file diff, to copy, revert or discuss on its own.
+#[derive(Debug, Clone)]
+pub struct DiffHunk {
+    pub path: String,
+    /// The `@@` line and the hunk's lines, newline-terminated.
+    pub text: String,
+    /// The hunk alone as a patch `git apply` accepts.
+    pub patch: String,
+}
+
+impl FileDiff {
+    /// The diff split at each `@@` line.
+    pub fn split_hunks(&self) -> Vec<DiffHunk> {
+        let mut texts: Vec<String> = Vec::new();
+        for line in self.hunks.lines() {
+            match texts.last_mut() {
+                Some(text) if !line.starts_with("@@") => text.push_str(line),
+                _ => texts.push(line.to_string()),
+            }
+            if let Some(text) = texts.last_mut() {
+                text.push('\n');
+            }
+        }
+        texts
+            .into_iter()
+            .map(|text| {
+                let single = FileDiff {
+                    hunks: text.clone(),
+                    ..self.clone()
+                };
+                DiffHunk {
+                    path: self.path().to_string(),
+                    text,
+                    patch: single.to_git(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Undoes one hunk in the working tree with `git apply -R`; the error is
+/// git's own message.
+pub fn revert_hunk(project_dir: &Path, hunk: &DiffHunk) -> Result<(), String> {
+    use std::io::Write;
+    let mut child = std::process::Command::new("git")
+        .args(["apply", "-R", "--recount", "-"])
+        .current_dir(project_dir)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(hunk.patch.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let out = child.wait_with_output().map_err(|e| e.to_string())?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    Err(stderr
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("git apply failed")
+        .trim_start_matches("error: ")
+        .to_string())
+}
+
+/// What the agent did to a file over the session; later changes win, except
+/// that a file created in the session stays created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Read,
+    Modified,
+    Created,
+    Deleted,
+}
+
+impl FileChange {
+    pub fn marker(self) -> &'static str {
+        match self {
+            FileChange::Read => "·",
+            FileChange::Modified => "M",
+            FileChange::Created => "A",
+            FileChange::Deleted => "D",
+        }
+    }
+
+    fn then(self, next: FileChange) -> FileChange {
+        match (self, next) {
+            (_, FileChange::Read) => self,
+            (FileChange::Created, FileChange::Modified) => FileChange::Created,
+            _ => next,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TouchedFile {
+    pub path: String,
+    pub change: FileChange,
+    pub additions: usize,
+    pub deletions: usize,
+    /// Every diff made to the file, oldest first.
+    pub diffs: Vec<FileDiff>,
+}
+
+/// The file diffs of each successful edit, write or apply_patch call, in
+/// order, with when the call finished. Edits carry their diff in the result;
+/// apply_patch only reports the files it touched, so its input patch is used
+/// instead.
+pub fn tool_diffs(events: &[TimelineEvent], project_dir: &Path) -> Vec<(i64, Vec<FileDiff>)> {
+    let calls: HashMap<&str, &TimelineEvent> = events
+        .iter()
+        .filter(|e| e.kind == "tool_call")
+        .filter_map(|e| Some((e.tool_call_id.as_deref()?, e)))
+        .collect();
+    let mut out = Vec::new();
+    for result in events.iter().filter(|e| e.kind == "tool_result") {
+        if result.status.as_deref() == Some("failed") {
+            continue;
+        }
+        let Ok(value) = serde_json::from_str::<Value>(&result.content) else {
+            continue;
+        };
+        if value.get("success").and_then(Value::as_bool) == Some(false)
+            || value.get("error").and_then(Value::as_bool) == Some(true)
+        {
+            continue;
+        }
+        let (diff, base) = match value.get("diff").and_then(Value::as_str) {
+            Some(diff) => (diff.to_string(), project_dir.to_path_buf()),
+            None => {
+                let Some(call) = result
+                    .tool_call_id
+                    .as_deref()
+                    .and_then(|id| calls.get(id))
+                    .filter(|call| call.tool_name.as_deref() == Some("apply_patch"))
+                else {
+                    continue;
+                };
+                let Ok(args) = serde_json::from_str::<Value>(&call.content) else {
+                    continue;
+                };
+                let Some(input) = args.get("patch").and_then(Value::as_str) else {
+                    continue;
+                };
+                let base = args
+                    .get("cwd")
+                    .and_then(Value::as_str)
+                    .map(|cwd| project_dir.join(cwd))
+                    .unwrap_or_else(|| project_dir.to_path_buf());
+                (input.to_string(), base)
+            }
+        };
+        let files = file_diffs(&diff, &base, project_dir);
+        if !files.is_empty() {
+            out.push((result.created_at, files));
+        }
+    }
+    out
+}
+
+/// Files the agent read or changed, sorted by path so directories group.
+pub fn touched_files(events: &[TimelineEvent], project_dir: &Path) -> Vec<TouchedFile> {
+    let mut files: HashMap<String, TouchedFile> = HashMap::new();
+    let mut touch = |path: String, change: FileChange, diff: Option<FileDiff>| {
+        let entry = files.entry(path.clone()).or_insert(TouchedFile {
+            path,
+            change,
+            additions: 0,
+            deletions: 0,
+            diffs: Vec::new(),
+        });
+        entry.change = entry.change.then(change);
+        if let Some(diff) = diff {
+            entry.additions += diff.additions;
+            entry.deletions += diff.deletions;
+            entry.diffs.push(diff);
+        }
+    };
+    for read in events.iter().filter(|e| {
+        e.kind == "tool_call"
+            && e.tool_name.as_deref() == Some("read")
+            && e.status.as_deref() == Some("completed")
+    }) {
+        let path = serde_json::from_str::<Value>(&read.content)
+            .ok()
+            .and_then(|args| Some(args.get("file_path")?.as_str()?.to_string()));
+        if let Some(path) = path {
+            touch(relative_path(&path, project_dir), FileChange::Read, None);
+        }
+    }
+    for diff in tool_diffs(events, project_dir)
+        .into_iter()
+        .flat_map(|(_, files)| files)
+    {
+        let change = match (&diff.old, &diff.new) {
+            (None, _) => FileChange::Created,
+            (_, None) => FileChange::Deleted,
+            _ => FileChange::Modified,
+        };
+        touch(diff.path().to_string(), change, Some(diff));
+    }
+    let mut files: Vec<TouchedFile> = files.into_values().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Splits a unified diff into per-file sections. Hunk bodies are read by
+/// their line counts, so a `---` inside one is a removed line rather than a
+/// file header; `diff --git`, `index` and mode lines are dropped.
+pub fn file_diffs(diff: &str, base: &Path, project_dir: &Path) -> Vec<FileDiff> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut files: Vec<FileDiff> = Vec::new();
+    let (mut old_left, mut new_left) = (0usize, 0usize);
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        idx += 1;
+        if old_left > 0 || new_left > 0 {
+            let Some(file) = files.last_mut() else {
+                continue;
+            };
+            match line.chars().next() {
+                Some('+') => {
+                    new_left = new_left.saturating_sub(1);
+                    file.additions += 1;
+                }
+                Some('-') => {
+                    old_left = old_left.saturating_sub(1);
+                    file.deletions += 1;
+                }
+                Some('\\') => {}
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            file.hunks.push_str(line);
+            file.hunks.push('\n');
+            continue;
+        }
+        if line.starts_with('\\') || line.starts_with("@@") {
+            let Some(file) = files.last_mut() else {
+                continue;
+            };
+            if line.starts_with("@@") {
+                (old_left, new_left) = hunk_counts(line);
+            }
+            // "\ No newline at end of file" can follow a hunk's last line.
+            file.hunks.push_str(line);
+            file.hunks.push('\n');
+        } else if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(idx).and_then(|next| next.strip_prefix("+++ ")),
+        ) {
+            idx += 1;
+            let old = patch_path(old, base, project_dir);
+            let new = patch_path(new, base, project_dir);
+            if old.is_some() || new.is_some() {
+                files.push(FileDiff {
+                    old,
+                    new,
+                    hunks: String::new(),
+                    additions: 0,
+                    deletions: 0,
+                });
+            }
+        }
+    }
+    files.retain(|file| !file.hunks.is_empty());
+    files
+}
+
+/// The old and new line counts from `@@ -12,5 +12,7 @@`; a missing count is 1.
+fn hunk_counts(header: &str) -> (usize, usize) {
+    let count = |range: Option<&str>| {
+        range
+            .and_then(|r| r.split_once(',').map(|(_, n)| n).or(Some("1")))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0)
+    };
+    let mut parts = header.split_whitespace().skip(1);
+    let old = count(parts.next().and_then(|p| p.strip_prefix('-')));
+    let new = count(parts.next().and_then(|p| p.strip_prefix('+')));
+    (old, new)
+}
+
+/// A `---`/`+++` path relative to the project, or None for `/dev/null`.
+fn patch_path(raw: &str, base: &Path, project_dir: &Path) -> Option<String> {
+    // `diff -u` puts a timestamp after a tab.
+    let raw = raw.split('\t').next().unwrap_or(raw).trim();
+    if raw == "/dev/null" {
+        return None;
+    }
+    let raw = raw
+        .strip_prefix("a/")
+        .or_else(|| raw.strip_prefix("b/"))
+        .unwrap_or(raw);
+    Some(relative_path(
+        &base.join(raw).to_string_lossy(),
+        project_dir,
+    ))
+}
+
+fn relative_path(path: &str, project_dir: &Path) -> String {
+    let path = Path::new(path);
+    path.strip_prefix(project_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// `git diff HEAD` in the project (plain `git diff` before the first commit),
+/// optionally preceded by `git status --short`, cut to `max_bytes`. `None`
+/// outside a repository or with nothing changed.
+pub fn working_tree_diff(
+    project_dir: &Path,
+    with_status: bool,
+    max_bytes: usize,
+) -> Option<String> {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .current_dir(project_dir)
+            .output()
+            .ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+    };
+    let diff = git(&["diff", "HEAD"]).or_else(|| git(&["diff"]))?;
+    let status = if with_status {
+        git(&["status", "--short"]).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    if diff.trim().is_empty() && status.trim().is_empty() {
+        return None;
+    }
+    let mut text = if status.trim().is_empty() {
+        diff
+    } else {
+        format!(
+            "$ git status --short\n{}\n$ git diff HEAD\n{}",
+            status, diff
+        )
+    };
+    if text.len() > max_bytes {
+        let mut cut = max_bytes;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let cut = text[..cut].rfind('\n').map_or(cut, |nl| nl + 1);
+        let dropped = text.len() - cut;
+        text.truncate(cut);
+        text.push_str(&format!("... (truncated, {} more bytes)\n", dropped));
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1,2 @@
+--- old comment
++// new comment
++pub mod app;
+@@ -10,3 +11,3 @@ fn main() {
+ let a = 1;
+-let b = 2;
++let b = 3;
+ let c = 4;
+--- /dev/null
++++ b/README.md
+@@ -0,0 +1 @@
++# Readme
+";
+
+    fn event(id: &str, kind: &str, tool: &str, status: &str, content: Value) -> TimelineEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "sessionId": "s1",
+            "createdAt": 0,
+            "kind": kind,
+            "content": content.to_string(),
+            "toolCallId": id.trim_end_matches("-result"),
+            "toolName": tool,
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn missing_hunk_count_is_one() {
+        assert_eq!(hunk_counts("@@ -12,5 +12,7 @@ fn main()"), (5, 7));
+        assert_eq!(hunk_counts("@@ -1 +1,2 @@"), (1, 2));
+        assert_eq!(hunk_counts("@@ -0,0 +1 @@"), (0, 1));
+    }
+
+    #[test]
+    fn file_diffs_count_lines_by_hunk_header() {
+        let project = Path::new("/repo");
+        let files = file_diffs(DIFF, project, project);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path(), "src/lib.rs");
+        assert_eq!((files[0].additions, files[0].deletions), (3, 2));
+        assert_eq!(files[1].old, None);
+        assert_eq!(files[1].path(), "README.md");
+        assert_eq!((files[1].additions, files[1].deletions), (1, 0));
+    }
+
+    #[test]
+    fn split_hunks_makes_one_patch_per_hunk() {
+        let project = Path::new("/repo");
+        let hunks = file_diffs(DIFF, project, project)[0].split_hunks();
+        assert_eq!(hunks.len(), 2);
+        assert!(hunks[0]
+            .text
+            .starts_with("@@ -1 +1,2 @@\n--- old comment\n"));
+        assert!(hunks[1].text.starts_with("@@ -10,3 +11,3 @@"));
+        assert!(hunks[1].patch.starts_with(
+            "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -10,3"
+        ));
+        assert!(!hunks[1].patch.contains("old comment"));
+    }
+
+    #[test]
+    fn touched_files_merge_reads_and_edits() {
+        let events = vec![
+            event(
+                "t1",
+                "tool_call",
+                "read",
+                "completed",
+                json!({ "file_path": "/repo/src/lib.rs" }),
+            ),
+            event(
+                "t2",
+                "tool_call",
+                "read",
+                "completed",
+                json!({ "file_path": "/repo/Cargo.toml" }),
+            ),
+            event(
+                "t3-result",
+                "tool_result",
+                "edit",
+                "completed",
+                json!({ "diff": DIFF }),
+            ),
+            event(
+                "t4-result",
+                "tool_result",
+                "edit",
+                "failed",
+                json!({ "diff": DIFF }),
+            ),
+        ];
+        let files = touched_files(&events, Path::new("/repo"));
+        let summary: Vec<(&str, FileChange, usize, usize)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.change, f.additions, f.deletions))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Cargo.toml", FileChange::Read, 0, 0),
+                ("README.md", FileChange::Created, 1, 0),
+                ("src/lib.rs", FileChange::Modified, 3, 2),
+            ]
+        );
+    }
+}
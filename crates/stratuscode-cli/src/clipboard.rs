@@ -0,0 +1,52 @@
+use crate::config::ClipboardMode;
+use base64::Engine;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Kept open for the whole run: on X11 and Wayland the copied text is served
+/// by this handle and disappears when it is dropped.
+static SYSTEM: OnceLock<Mutex<Option<arboard::Clipboard>>> = OnceLock::new();
+
+/// Puts `text` on the clipboard. `Auto` uses the system clipboard locally and
+/// OSC 52 over SSH or when there is no system clipboard (headless machines),
+/// which the terminal, or tmux with `set-clipboard on`, applies on the
+/// user's side.
+pub fn copy_text(text: &str, mode: ClipboardMode) -> Result<(), String> {
+    match mode {
+        ClipboardMode::System => copy_system(text),
+        ClipboardMode::Osc52 => copy_osc52(text),
+        ClipboardMode::Auto if over_ssh() => copy_osc52(text),
+        ClipboardMode::Auto => copy_system(text).or_else(|_| copy_osc52(text)),
+    }
+}
+
+fn over_ssh() -> bool {
+    std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some()
+}
+
+fn copy_system(text: &str) -> Result<(), String> {
+    let mut slot = SYSTEM
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .map_err(|e| e.to_string())?;
+    let clipboard = match slot.as_mut() {
+        Some(clipboard) => clipboard,
+        None => slot.insert(arboard::Clipboard::new().map_err(|e| e.to_string())?),
+    };
+    clipboard.set_text(text).map_err(|e| e.to_string())
+}
+
+fn copy_osc52(text: &str) -> Result<(), String> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+    let mut out = std::io::stdout();
+    if std::env::var_os("TMUX").is_some() {
+        // Passed straight to the outer terminal when tmux allows passthrough;
+        // the plain sequence below covers `set-clipboard on`.
+        let escaped = sequence.replace('\x1b', "\x1b\x1b");
+        write!(out, "\x1bPtmux;{}\x1b\\", escaped).map_err(|e| e.to_string())?;
+    }
+    out.write_all(sequence.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| e.to_string())
+}
@@ -1,70 +1,262 @@
+use ratatui::text::Line;
 use serde_json::json;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use crate::app::refresh_todos;
-use crate::app::{App, CommandItem, ModelEntry, SessionInfo, UiMode};
-use crate::backend::BackendClient;
+use crate::app::{
+    apply_template, cancel_tool, load_model_cache, open_bookmarks, open_output_pager, pin_file,
+    quote_focused, retry_failed_tool, toggle_todos, whole_timeline, ModelPrefs,
+};
+use crate::app::{App, CommandItem, FollowMode, IndexSignal, ModelEntry, Pager, UiMode};
+use crate::attachments::{add_attachment, load_attachment, resolve_attachment_path};
+use crate::backend::{BackendClient, SessionInfo};
+use crate::changes::{FileChange, TouchedFile};
+use crate::clipboard::copy_text;
+use crate::export::{session_patch, transcript_html};
+use crate::i18n::{t, tf};
+use crate::input::{fix_failed_verify, retry_failed_send};
+use crate::macros::key_name;
+use crate::perf::profile_json;
+use crate::redact::save_pattern;
+use crate::render::TimelineFilter;
+use crate::store::SessionStore;
+use crate::templates::{load_templates, templates_dir};
+use crate::ui::build_changes_lines;
 
 pub fn commands_list() -> Vec<CommandItem> {
     vec![
         CommandItem {
             name: "new",
             shortcut: Some("n"),
-            description: "Start a new session",
+            description: t("commands.new"),
             action: "session:new",
         },
         CommandItem {
             name: "clear",
             shortcut: Some("c"),
-            description: "Clear current conversation",
+            description: t("commands.clear"),
             action: "session:clear",
         },
         CommandItem {
             name: "history",
             shortcut: Some("h"),
-            description: "View session history",
+            description: t("commands.history"),
             action: "session:history",
         },
         CommandItem {
             name: "plan",
             shortcut: Some("p"),
-            description: "Enter plan mode",
+            description: t("commands.plan"),
             action: "mode:plan",
         },
         CommandItem {
             name: "build",
             shortcut: Some("b"),
-            description: "Exit plan mode and start building",
+            description: t("commands.build"),
             action: "mode:build",
         },
+        CommandItem {
+            name: "readonly",
+            shortcut: None,
+            description: t("commands.readonly"),
+            action: "mode:readonly",
+        },
         CommandItem {
             name: "reindex",
             shortcut: None,
-            description: "Reindex codebase for search",
+            description: t("commands.reindex"),
             action: "tool:reindex",
         },
         CommandItem {
             name: "todos",
             shortcut: Some("t"),
-            description: "Show todo list",
+            description: t("commands.todos"),
             action: "tool:todos",
         },
         CommandItem {
             name: "revert",
             shortcut: Some("r"),
-            description: "Revert files to previous state",
+            description: t("commands.revert"),
             action: "tool:revert",
         },
         CommandItem {
             name: "models",
             shortcut: Some("m"),
-            description: "Change AI model",
+            description: t("commands.models"),
             action: "settings:model",
         },
+        CommandItem {
+            name: "template",
+            shortcut: Some("tpl"),
+            description: t("commands.template"),
+            action: "input:template",
+        },
+        CommandItem {
+            name: "reasoning",
+            shortcut: None,
+            description: t("commands.reasoning"),
+            action: "settings:reasoning",
+        },
+        CommandItem {
+            name: "agents",
+            shortcut: None,
+            description: t("commands.agents"),
+            action: "settings:agents",
+        },
+        CommandItem {
+            name: "attach",
+            shortcut: Some("a"),
+            description: t("commands.attach"),
+            action: "input:attach",
+        },
+        CommandItem {
+            name: "filter",
+            shortcut: Some("f"),
+            description: t("commands.filter"),
+            action: "view:filter",
+        },
+        CommandItem {
+            name: "follow",
+            shortcut: None,
+            description: t("commands.follow"),
+            action: "view:follow",
+        },
+        CommandItem {
+            name: "compact",
+            shortcut: None,
+            description: t("commands.compact"),
+            action: "view:compact",
+        },
+        CommandItem {
+            name: "timestamps",
+            shortcut: None,
+            description: t("commands.timestamps"),
+            action: "view:timestamps",
+        },
+        CommandItem {
+            name: "usage",
+            shortcut: None,
+            description: t("commands.usage"),
+            action: "view:usage",
+        },
+        CommandItem {
+            name: "output",
+            shortcut: None,
+            description: t("commands.output"),
+            action: "view:output",
+        },
+        CommandItem {
+            name: "notifications",
+            shortcut: None,
+            description: t("commands.notifications"),
+            action: "view:notifications",
+        },
+        CommandItem {
+            name: "export",
+            shortcut: Some("e"),
+            description: t("commands.export"),
+            action: "session:export",
+        },
+        CommandItem {
+            name: "quote",
+            shortcut: None,
+            description: t("commands.quote"),
+            action: "input:quote",
+        },
+        CommandItem {
+            name: "copy",
+            shortcut: None,
+            description: t("commands.copy"),
+            action: "session:copy",
+        },
+        CommandItem {
+            name: "patch",
+            shortcut: None,
+            description: t("commands.patch"),
+            action: "session:patch",
+        },
+        CommandItem {
+            name: "env",
+            shortcut: None,
+            description: t("commands.env"),
+            action: "view:env",
+        },
+        CommandItem {
+            name: "pin",
+            shortcut: None,
+            description: t("commands.pin"),
+            action: "context:pin",
+        },
+        CommandItem {
+            name: "unpin",
+            shortcut: None,
+            description: t("commands.unpin"),
+            action: "context:unpin",
+        },
+        CommandItem {
+            name: "withdiff",
+            shortcut: None,
+            description: t("commands.withdiff"),
+            action: "context:withdiff",
+        },
+        CommandItem {
+            name: "bookmarks",
+            shortcut: None,
+            description: t("commands.bookmarks"),
+            action: "view:bookmarks",
+        },
+        CommandItem {
+            name: "redact",
+            shortcut: None,
+            description: t("commands.redact"),
+            action: "settings:redact",
+        },
+        CommandItem {
+            name: "macros",
+            shortcut: None,
+            description: t("commands.macros"),
+            action: "view:macros",
+        },
+        CommandItem {
+            name: "retry",
+            shortcut: None,
+            description: t("commands.retry"),
+            action: "session:retry",
+        },
+        CommandItem {
+            name: "fix",
+            shortcut: None,
+            description: t("commands.fix"),
+            action: "session:fix",
+        },
+        CommandItem {
+            name: "skip",
+            shortcut: None,
+            description: t("commands.skip"),
+            action: "tool:skip",
+        },
+        CommandItem {
+            name: "retrytool",
+            shortcut: None,
+            description: t("commands.retrytool"),
+            action: "tool:retry",
+        },
+        CommandItem {
+            name: "perf",
+            shortcut: None,
+            description: t("commands.perf"),
+            action: "view:perf",
+        },
+        CommandItem {
+            name: "changes",
+            shortcut: None,
+            description: t("commands.changes"),
+            action: "view:changes",
+        },
         CommandItem {
             name: "about",
             shortcut: None,
-            description: "About StratusCode",
+            description: t("commands.about"),
             action: "help:about",
         },
     ]
@@ -105,7 +297,7 @@ pub fn execute_command(
     app: &mut App,
     client: &Arc<Mutex<BackendClient>>,
     cmd: &CommandItem,
-    _arg: Option<String>,
+    arg: Option<String>,
 ) {
     match cmd.action {
         "session:new" | "session:clear" => {
@@ -123,12 +315,32 @@ pub fn execute_command(
                     app.session_selected = 0;
                     app.mode = UiMode::SessionHistory;
                 } else {
-                    app.set_toast("Failed to parse sessions".to_string());
+                    app.error_toast(t("toast.sessions_parse_failed"));
                 }
             } else {
-                app.set_toast("Failed to load sessions".to_string());
+                match SessionStore::open().and_then(|store| store.list_sessions(&app.project_dir, 20)) {
+                    Ok(list) => {
+                        app.session_list = list;
+                        app.session_selected = 0;
+                        app.mode = UiMode::SessionHistory;
+                        app.warn_toast(t("toast.sessions_from_store"));
+                    }
+                    Err(e) => app.error_toast(tf("toast.sessions_load_failed", &[("error", &e)])),
+                }
             }
         }
+        "mode:readonly" => {
+            app.set_read_only(!app.read_only);
+            let _ = client
+                .lock()
+                .unwrap()
+                .call("set_read_only", json!({ "readOnly": app.read_only }));
+            app.set_toast(if app.read_only {
+                t("toast.read_only_on")
+            } else {
+                t("toast.read_only_off")
+            });
+        }
         "mode:plan" => {
             let _ = client.lock().unwrap().call("set_agent", json!({ "agent": "plan" }));
             app.state.agent = "plan".to_string();
@@ -138,40 +350,298 @@ pub fn execute_command(
             app.state.agent = "build".to_string();
         }
         "tool:reindex" => {
-            app.file_index.clear();
+            match &app.file_index_refresh {
+                Some(tx) => {
+                    let _ = tx.send(IndexSignal::Reindex);
+                    app.file_index_loading = true;
+                }
+                None => app.file_index.clear(),
+            }
             app.reindex_inflight = true;
-            app.set_toast("Reindexing...".to_string());
+            app.set_toast(t("toast.reindexing"));
             let _ = client.lock().unwrap().call("execute_tool", json!({ "name": "codesearch", "args": { "query": "__reindex__", "reindex": true } }));
         }
-        "tool:todos" => {
-            app.todos_expanded = !app.todos_expanded;
-            refresh_todos(app, client);
-        }
+        "tool:todos" => toggle_todos(app, client),
         "tool:revert" => {
             let _ = client.lock().unwrap().call("execute_tool", json!({ "name": "revert", "args": {} }));
+            app.changes_since = app.state.timeline_events.last().map(|e| e.id.clone());
         }
         "settings:model" => {
-            match client.lock().unwrap().call("list_models", json!({})) {
-                Ok(resp) => {
-                    if let Some(entries_val) = resp.get("entries") {
-                        if let Ok(entries) = serde_json::from_value::<Vec<ModelEntry>>(entries_val.clone()) {
-                            app.model_entries = entries;
-                            app.model_query.clear();
-                            app.model_selected = 0;
-                            app.model_offset = 0;
-                            app.mode = UiMode::ModelPicker;
-                        } else {
-                            app.set_toast("Failed to parse model list".to_string());
-                        }
-                    } else {
-                        app.set_toast("Model list unavailable".to_string());
+            match load_model_cache() {
+                Some((entries, fresh)) => {
+                    app.model_entries = entries;
+                    app.models_refresh_requested = !fresh;
+                }
+                None => {
+                    app.model_entries.clear();
+                    app.models_refresh_requested = true;
+                }
+            }
+            app.models_loading = app.models_refresh_requested;
+            app.model_query.clear();
+            app.model_selected = 0;
+            app.model_offset = 0;
+            app.mode = UiMode::ModelPicker;
+        }
+        "input:template" => {
+            let templates = load_templates();
+            let arg = arg.unwrap_or_default();
+            let (name, rest) = arg.trim().split_once(' ').unwrap_or((arg.trim(), ""));
+            if name.is_empty() {
+                if templates.is_empty() {
+                    let dir = templates_dir()
+                        .map(|d| d.display().to_string())
+                        .unwrap_or_else(|| "~/.config/stratuscode/templates".to_string());
+                    app.warn_toast(tf("toast.no_templates", &[("dir", &dir)]));
+                } else {
+                    app.templates = templates;
+                    app.template_selected = 0;
+                    app.mode = UiMode::TemplatePicker;
+                }
+            } else {
+                match templates.iter().find(|t| t.name == name) {
+                    Some(template) => apply_template(app, template, rest.trim()),
+                    None => app.warn_toast(tf("toast.unknown_template", &[("name", &name)])),
+                }
+            }
+        }
+        "settings:reasoning" => {
+            app.reasoning_selected = app
+                .reasoning_levels()
+                .iter()
+                .position(|l| *l == app.reasoning_effort)
+                .unwrap_or(0);
+            app.mode = UiMode::ReasoningPicker;
+        }
+        "settings:agents" => {
+            app.agent_selected = app
+                .agent_names()
+                .iter()
+                .position(|name| *name == app.state.agent)
+                .unwrap_or(0);
+            app.mode = UiMode::AgentPicker;
+        }
+        "view:filter" => {
+            let next = match arg.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+                Some(value) => match TimelineFilter::parse(value) {
+                    Some(filter) => filter,
+                    None => {
+                        app.warn_toast(tf("toast.unknown_filter", &[("filter", &value)]));
+                        return;
+                    }
+                },
+                None => app.timeline_filter.next(),
+            };
+            app.timeline_filter = next;
+            app.scroll_from_bottom = 0;
+            app.set_toast(tf("toast.filter", &[("filter", &next.label())]));
+        }
+        "view:follow" => {
+            let next = match arg.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+                Some(value) => match FollowMode::parse(value) {
+                    Some(mode) => mode,
+                    None => {
+                        app.warn_toast(tf("toast.unknown_follow", &[("mode", &value)]));
+                        return;
+                    }
+                },
+                None => app.follow_mode.next(),
+            };
+            app.follow_mode = next;
+            app.set_toast(tf("toast.follow", &[("mode", &next.label())]));
+        }
+        "input:attach" => match arg.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+            Some(raw) => {
+                let path = resolve_attachment_path(raw, &app.project_dir);
+                match load_attachment(&path) {
+                    Ok(upload) => {
+                        app.set_toast(tf("toast.attached", &[("file", &upload.chip_label())]));
+                        add_attachment(app, upload);
                     }
+                    Err(err) => app.error_toast(err),
                 }
-                Err(_) => {
-                    app.set_toast("Failed to load models".to_string());
+            }
+            None => app.warn_toast(t("toast.attach_usage")),
+        },
+        "session:export" => match arg.as_deref().map(str::trim) {
+            Some("stdout") => {
+                app.print_on_exit = !app.print_on_exit;
+                app.set_toast(if app.print_on_exit {
+                    t("toast.print_on_exit_on")
+                } else {
+                    t("toast.print_on_exit_off")
+                });
+            }
+            Some(rest) if rest == "html" || rest.starts_with("html ") => {
+                export_html(app, client, rest["html".len()..].trim());
+            }
+            _ => app.warn_toast(t("toast.export_usage")),
+        },
+        "session:patch" => export_patch(app, client, arg.as_deref().unwrap_or("").trim()),
+        "input:quote" => quote_focused(app),
+        "session:copy" => copy_response(app, arg.as_deref().unwrap_or("").trim()),
+        "view:usage" => {
+            app.mode = UiMode::Telemetry;
+            app.usage_refresh_requested = true;
+        }
+        "view:output" => open_output_pager(app),
+        "view:changes" => open_changes_pager(app),
+        "tool:skip" => cancel_tool(app, client),
+        "tool:retry" => retry_failed_tool(app, client),
+        "session:retry" => retry_failed_send(app, client),
+        "session:fix" => fix_failed_verify(app, client),
+        "view:perf" => match arg.as_deref().map(str::trim) {
+            None | Some("") => {
+                app.perf_hud = !app.perf_hud;
+                app.mark_dirty();
+            }
+            Some(rest) if rest == "dump" || rest.starts_with("dump ") => {
+                dump_perf(app, rest["dump".len()..].trim());
+            }
+            _ => app.warn_toast(t("toast.perf_usage")),
+        },
+        "settings:redact" => {
+            let arg = arg.unwrap_or_default();
+            let Some(pattern) = arg
+                .trim()
+                .strip_prefix("add ")
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+            else {
+                app.warn_toast(t("toast.redact_usage"));
+                return;
+            };
+            let re = match regex::Regex::new(pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    app.error_toast(tf("toast.redact_invalid", &[("error", &e)]));
+                    return;
+                }
+            };
+            if let Err(e) = save_pattern(pattern) {
+                app.error_toast(tf("toast.redact_save_failed", &[("error", &e)]));
+            }
+            if app.redactor.is_some() {
+                app.add_redaction(re);
+                app.set_toast(tf("toast.redact_added", &[("pattern", &pattern)]));
+            } else {
+                app.warn_toast(t("toast.redact_disabled"));
+            }
+        }
+        "view:bookmarks" => open_bookmarks(app, client),
+        "context:pin" => {
+            let arg = arg.unwrap_or_default();
+            let path = arg.trim().trim_start_matches('@');
+            if path.is_empty() {
+                app.warn_toast(t("toast.pin_usage"));
+                return;
+            }
+            let project = Path::new(&app.project_dir);
+            let full = project.join(path);
+            if !full.is_file() {
+                app.warn_toast(t("toast.pin_not_file"));
+                return;
+            }
+            let relative = full
+                .strip_prefix(project)
+                .map(|p| p.to_string_lossy().trim_start_matches("./").to_string())
+                .unwrap_or_else(|_| path.to_string());
+            pin_file(app, &relative);
+        }
+        "context:withdiff" => {
+            app.with_diff = !app.with_diff;
+            app.set_toast(if app.with_diff {
+                t("toast.with_diff_on")
+            } else {
+                t("toast.with_diff_off")
+            });
+        }
+        "context:unpin" => {
+            let arg = arg.unwrap_or_default();
+            let path = arg.trim().trim_start_matches('@');
+            if path.is_empty() {
+                if app.pinned.is_empty() {
+                    app.warn_toast(t("toast.pinned_none"));
+                } else {
+                    app.pinned.clear();
+                    app.set_toast(t("toast.unpinned_all"));
                 }
+            } else if let Some(idx) = app.pinned.iter().position(|p| p == path) {
+                app.pinned.remove(idx);
+                app.set_toast(tf("toast.unpinned", &[("file", &path)]));
+            } else {
+                app.warn_toast(tf("toast.not_pinned", &[("file", &path)]));
+            }
+        }
+        "view:macros" => {
+            if app.macros.registers.is_empty() {
+                app.warn_toast(t("toast.macros_empty"));
+            } else {
+                let lines = app
+                    .macros
+                    .registers
+                    .iter()
+                    .map(|(register, keys)| {
+                        let names: Vec<String> = keys.iter().filter_map(key_name).collect();
+                        Line::from(format!("@{}  {}", register, names.join(" ")))
+                    })
+                    .collect();
+                app.pager = Some(Pager {
+                    title: tf("ui.macros_title", &[("count", &app.macros.registers.len())]),
+                    lines,
+                    scroll: 0,
+                    hunks: Vec::new(),
+                    hunk: None,
+                });
+                app.mode = UiMode::Pager;
+            }
+        }
+        "view:env" => {
+            if app.env_vars.is_empty() {
+                app.warn_toast(t("toast.env_empty"));
+            } else {
+                let lines = app
+                    .env_vars
+                    .iter()
+                    .map(|var| {
+                        let value = if var.secret {
+                            t("ui.env_secret").to_string()
+                        } else {
+                            var.value.clone()
+                        };
+                        Line::from(format!("{} = {}", var.name, value))
+                    })
+                    .collect();
+                app.pager = Some(Pager {
+                    title: tf("ui.env_title", &[("count", &app.env_vars.len())]),
+                    lines,
+                    scroll: 0,
+                    hunks: Vec::new(),
+                    hunk: None,
+                });
+                app.mode = UiMode::Pager;
             }
         }
+        "view:notifications" => {
+            app.notifications_offset = 0;
+            app.mode = UiMode::Notifications;
+        }
+        "view:compact" => {
+            app.compact_view = !app.compact_view;
+            app.set_toast(if app.compact_view {
+                t("toast.compact_on")
+            } else {
+                t("toast.compact_off")
+            });
+        }
+        "view:timestamps" => {
+            app.show_timestamps = !app.show_timestamps;
+            app.set_toast(if app.show_timestamps {
+                t("toast.timestamps_on")
+            } else {
+                t("toast.timestamps_off")
+            });
+        }
         "help:about" => {
             app.mode = UiMode::HelpAbout;
         }
@@ -230,3 +700,255 @@ pub fn sort_models_by_provider(entries: &[ModelEntry]) -> Vec<ModelEntry> {
     }
     sorted
 }
+
+/// Provider-sorted models headed by "Favorites" and "Recent" groups. Those
+/// groups repeat entries from the provider groups below them.
+pub fn arrange_models(entries: &[ModelEntry], prefs: &ModelPrefs) -> Vec<ModelEntry> {
+    let pinned = |ids: &[String], group: &str| -> Vec<ModelEntry> {
+        ids.iter()
+            .filter_map(|id| entries.iter().find(|e| &e.id == id))
+            .map(|e| ModelEntry {
+                group: group.to_string(),
+                ..e.clone()
+            })
+            .collect()
+    };
+    let mut arranged = pinned(&prefs.favorites, "Favorites");
+    let recent: Vec<String> = prefs
+        .recent
+        .iter()
+        .filter(|id| !prefs.is_favorite(id))
+        .cloned()
+        .collect();
+    arranged.extend(pinned(&recent, "Recent"));
+    arranged.extend(sort_models_by_provider(entries));
+    arranged
+}
+
+/// `path` resolved against the project, or `stratuscode-<session>.<extension>`
+/// in the project directory when empty.
+fn export_path(app: &App, path: &str, extension: &str) -> std::path::PathBuf {
+    let session = app
+        .state
+        .session_id
+        .as_deref()
+        .map(|id| {
+            id.chars()
+                .rev()
+                .take(8)
+                .collect::<String>()
+                .chars()
+                .rev()
+                .collect()
+        })
+        .unwrap_or_else(|| chrono::Local::now().format("%Y%m%d-%H%M%S").to_string());
+    if path.is_empty() {
+        std::path::Path::new(&app.project_dir)
+            .join(format!("stratuscode-{}.{}", session, extension))
+    } else {
+        resolve_attachment_path(path, &app.project_dir)
+    }
+}
+
+/// Writes the conversation as a standalone HTML page, by default to
+/// `stratuscode-<session>.html` in the project directory.
+fn export_html(app: &mut App, client: &Arc<Mutex<BackendClient>>, path: &str) {
+    let path = export_path(app, path, "html");
+    let project = std::path::Path::new(&app.project_dir)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let title = format!(
+        "{} · {}",
+        project,
+        chrono::Local::now().format("%Y-%m-%d %H:%M")
+    );
+    let mut events = whole_timeline(app, client);
+    if let Some(redactor) = &app.redactor {
+        redactor.redact_events(&mut events);
+    }
+    match std::fs::write(&path, transcript_html(&title, &events)) {
+        Ok(()) => app.set_toast(tf("toast.exported", &[("path", &path.display())])),
+        Err(e) => app.error_toast(tf(
+            "toast.write_failed",
+            &[("path", &path.display()), ("error", &e)],
+        )),
+    }
+}
+
+/// Writes a `/perf dump` profile, by default to
+/// `stratuscode-perf-<time>.json` in the project.
+fn dump_perf(app: &mut App, path: &str) {
+    let path = if path.is_empty() {
+        std::path::Path::new(&app.project_dir).join(format!(
+            "stratuscode-perf-{}.json",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ))
+    } else {
+        resolve_attachment_path(path, &app.project_dir)
+    };
+    let profile = profile_json(
+        &app.perf,
+        app.state.timeline_events.len(),
+        app.event_line_cache.hits_and_misses(),
+    );
+    let written = serde_json::to_string_pretty(&profile)
+        .map_err(std::io::Error::from)
+        .and_then(|raw| std::fs::write(&path, raw));
+    match written {
+        Ok(()) => app.set_toast(tf("toast.perf_dumped", &[("path", &path.display())])),
+        Err(e) => app.error_toast(tf(
+            "toast.write_failed",
+            &[("path", &path.display()), ("error", &e)],
+        )),
+    }
+}
+
+/// Writes the session's file changes as a patch for `git apply`, by default to
+/// `stratuscode-<session>.patch`, and copies its path to the clipboard.
+fn export_patch(app: &mut App, client: &Arc<Mutex<BackendClient>>, path: &str) {
+    let (patch, count) = session_patch(
+        &whole_timeline(app, client),
+        std::path::Path::new(&app.project_dir),
+        app.changes_since.as_deref(),
+    );
+    if count == 0 {
+        app.warn_toast(t("toast.patch_empty"));
+        return;
+    }
+    let path = export_path(app, path, "patch");
+    if let Err(e) = std::fs::write(&path, patch) {
+        app.error_toast(tf(
+            "toast.write_failed",
+            &[("path", &path.display()), ("error", &e)],
+        ));
+        return;
+    }
+    let copied = copy_text(&path.display().to_string(), app.clipboard).is_ok();
+    app.set_toast(if copied {
+        tf(
+            "toast.patch_exported",
+            &[("count", &count), ("path", &path.display())],
+        )
+    } else {
+        tf("toast.exported", &[("path", &path.display())])
+    });
+}
+
+/// Opens the pager on a per-directory summary of the files changed this
+/// session.
+fn open_changes_pager(app: &mut App) {
+    let files = app.touched_files();
+    let changed: Vec<TouchedFile> = files
+        .iter()
+        .filter(|f| f.change != FileChange::Read)
+        .cloned()
+        .collect();
+    if changed.is_empty() {
+        app.warn_toast(t("toast.changes_empty"));
+        return;
+    }
+    let (additions, deletions) = changed
+        .iter()
+        .fold((0, 0), |(a, d), f| (a + f.additions, d + f.deletions));
+    app.pager = Some(Pager {
+        title: tf(
+            "ui.changes_title",
+            &[
+                ("count", &changed.len()),
+                ("additions", &additions),
+                ("deletions", &deletions),
+            ],
+        ),
+        lines: build_changes_lines(&changed),
+        scroll: 0,
+        hunks: Vec::new(),
+        hunk: None,
+    });
+    app.mode = UiMode::Pager;
+}
+
+/// Copies the last response, or with `code` the last code block in it.
+fn copy_response(app: &mut App, arg: &str) {
+    let response = last_response(&app.state.timeline_events);
+    if response.is_empty() {
+        app.warn_toast(t("toast.copy_nothing"));
+        return;
+    }
+    let (text, done) = match arg {
+        "" => (response, t("toast.copied_response")),
+        "code" => match last_code_block(&response) {
+            Some(code) => (code, t("toast.copied_code")),
+            None => {
+                app.warn_toast(t("toast.copy_no_code"));
+                return;
+            }
+        },
+        _ => {
+            app.warn_toast(t("toast.copy_usage"));
+            return;
+        }
+    };
+    match copy_text(&text, app.clipboard) {
+        Ok(()) => app.set_toast(done),
+        Err(e) => app.error_toast(tf("toast.copy_failed", &[("error", &e)])),
+    }
+}
+
+/// The assistant text since the last user message, sub-agents left out.
+fn last_response(events: &[crate::backend::TimelineEvent]) -> String {
+    let start = events
+        .iter()
+        .rposition(|e| e.kind == "user")
+        .map_or(0, |idx| idx + 1);
+    events[start..]
+        .iter()
+        .filter(|e| e.kind == "assistant" && e.parent_tool_call_id.is_none())
+        .map(|e| e.content.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn last_code_block(markdown: &str) -> Option<String> {
+    use pulldown_cmark::{Event, Parser, Tag};
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => current = Some(String::new()),
+            Event::Text(text) => {
+                if let Some(code) = current.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => blocks.extend(current.take()),
+            _ => {}
+        }
+    }
+    blocks.pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_shortcut_parses_to_its_command() {
+        let commands = commands_list();
+        for cmd in &commands {
+            let Some(shortcut) = cmd.shortcut else {
+                continue;
+            };
+            assert!(
+                commands.iter().all(|other| other.name != shortcut
+                    && (other.name == cmd.name || other.shortcut != Some(shortcut))),
+                "/{} is claimed by more than one command",
+                shortcut
+            );
+            let (parsed, arg) = parse_command(&format!("/{} x", shortcut)).unwrap();
+            assert_eq!(parsed.name, cmd.name);
+            assert_eq!(arg.as_deref(), Some("x"));
+        }
+    }
+}
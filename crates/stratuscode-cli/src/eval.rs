@@ -0,0 +1,367 @@
+//! `stratuscode eval suite.toml`: runs each case's prompt against a copy of
+//! its fixture repo, checks the assertions, and reports the results as JUnit
+//! XML or JSON so prompt and agent changes can be regression-tested in CI.
+//!
+//! ```toml
+//! name = "smoke"
+//! timeout_secs = 600
+//!
+//! [[cases]]
+//! name = "adds a helper"
+//! fixture = "fixtures/calculator"   # relative to the suite file
+//! prompt = "Add a `square` function to src/lib.rs with a test"
+//! assert = [
+//!   { file_exists = "src/lib.rs" },
+//!   { file = "src/lib.rs", matches = "fn square" },
+//!   { command = "cargo test" },
+//!   { output_matches = "(?i)square" },
+//! ]
+//! ```
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::export::escape_html;
+use crate::text::truncate_text;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+/// How much of a failing command's output goes into the report.
+const COMMAND_OUTPUT_CHARS: usize = 400;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    pub name: Option<String>,
+    /// Per-case limit unless a case sets its own.
+    pub timeout_secs: Option<u64>,
+    pub cases: Vec<Case>,
+    /// The suite file's directory; fixtures are relative to it.
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    pub name: String,
+    pub fixture: PathBuf,
+    pub prompt: String,
+    pub agent: Option<String>,
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub assert: Vec<Assertion>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Assertion {
+    FileExists {
+        file_exists: String,
+    },
+    FileMatches {
+        file: String,
+        matches: String,
+    },
+    /// Passes when the command exits 0, e.g. the fixture's test suite.
+    Command {
+        command: String,
+    },
+    /// Matched against the agent's answers in the turn.
+    OutputMatches {
+        output_matches: String,
+    },
+}
+
+impl Assertion {
+    fn label(&self) -> String {
+        match self {
+            Assertion::FileExists { file_exists } => format!("file exists: {}", file_exists),
+            Assertion::FileMatches { file, matches } => format!("{} matches /{}/", file, matches),
+            Assertion::Command { command } => format!("command passes: {}", command),
+            Assertion::OutputMatches { output_matches } => {
+                format!("output matches /{}/", output_matches)
+            }
+        }
+    }
+
+    /// `Err` carries why it failed.
+    fn check(&self, dir: &Path, output: &str) -> Result<(), String> {
+        match self {
+            Assertion::FileExists { file_exists } => {
+                if dir.join(file_exists).exists() {
+                    Ok(())
+                } else {
+                    Err(format!("{} does not exist", file_exists))
+                }
+            }
+            Assertion::FileMatches { file, matches } => {
+                let re = Regex::new(matches).map_err(|e| e.to_string())?;
+                let content = std::fs::read_to_string(dir.join(file))
+                    .map_err(|e| format!("Failed to read {}: {}", file, e))?;
+                if re.is_match(&content) {
+                    Ok(())
+                } else {
+                    Err(format!("no match in {}", file))
+                }
+            }
+            Assertion::Command { command } => {
+                let out = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .current_dir(dir)
+                    .output()
+                    .map_err(|e| format!("Failed to run: {}", e))?;
+                if out.status.success() {
+                    return Ok(());
+                }
+                let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&out.stderr));
+                let tail: String = text
+                    .chars()
+                    .rev()
+                    .take(COMMAND_OUTPUT_CHARS)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .collect();
+                Err(format!("{}\n{}", out.status, tail.trim()))
+            }
+            Assertion::OutputMatches { output_matches } => {
+                let re = Regex::new(output_matches).map_err(|e| e.to_string())?;
+                if re.is_match(output) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "no match in: {}",
+                        truncate_text(output.trim(), 120)
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl Suite {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut suite: Suite =
+            toml::from_str(&raw).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        suite.dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        if suite.name.is_none() {
+            suite.name = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        }
+        Ok(suite)
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("eval")
+    }
+
+    pub fn timeout(&self, case: &Case) -> Duration {
+        Duration::from_secs(
+            case.timeout_secs
+                .or(self.timeout_secs)
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        )
+    }
+}
+
+/// Copies a fixture into a fresh directory under the system temp dir.
+pub fn copy_fixture(fixture: &Path, case_index: usize) -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!(
+        "stratuscode-eval-{}-{}",
+        std::process::id(),
+        case_index
+    ));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    if let Err(e) = copy_dir(fixture, &dir) {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+    Ok(dir)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionResult {
+    pub name: String,
+    pub passed: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaseResult {
+    pub name: String,
+    pub passed: bool,
+    pub duration_secs: f64,
+    /// Set when the case couldn't run at all (missing fixture, backend
+    /// error, timeout); its assertions are then skipped.
+    pub error: Option<String>,
+    pub assertions: Vec<AssertionResult>,
+}
+
+impl CaseResult {
+    pub fn errored(name: &str, duration: Duration, error: String) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: false,
+            duration_secs: duration.as_secs_f64(),
+            error: Some(error),
+            assertions: Vec::new(),
+        }
+    }
+
+    pub fn checked(case: &Case, duration: Duration, dir: &Path, output: &str) -> Self {
+        let assertions: Vec<AssertionResult> = case
+            .assert
+            .iter()
+            .map(|assertion| {
+                let result = assertion.check(dir, output);
+                AssertionResult {
+                    name: assertion.label(),
+                    passed: result.is_ok(),
+                    message: result.err(),
+                }
+            })
+            .collect();
+        Self {
+            name: case.name.clone(),
+            passed: assertions.iter().all(|a| a.passed),
+            duration_secs: duration.as_secs_f64(),
+            error: None,
+            assertions,
+        }
+    }
+
+    fn failure_message(&self) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(error.clone());
+        }
+        let failed: Vec<String> = self
+            .assertions
+            .iter()
+            .filter(|a| !a.passed)
+            .map(|a| format!("{}: {}", a.name, a.message.as_deref().unwrap_or("failed")))
+            .collect();
+        (!failed.is_empty()).then(|| failed.join("\n"))
+    }
+}
+
+pub fn json_report(suite: &str, results: &[CaseResult]) -> String {
+    let passed = results.iter().filter(|r| r.passed).count();
+    serde_json::to_string_pretty(&serde_json::json!({
+        "suite": suite,
+        "passed": passed,
+        "failed": results.len() - passed,
+        "cases": results,
+    }))
+    .unwrap_or_default()
+}
+
+pub fn junit_report(suite: &str, results: &[CaseResult]) -> String {
+    let failures = results.iter().filter(|r| !r.passed).count();
+    let time: f64 = results.iter().map(|r| r.duration_secs).sum();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        escape_html(suite),
+        results.len(),
+        failures,
+        time
+    ));
+    for result in results {
+        out.push_str(&format!(
+            "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape_html(&result.name),
+            escape_html(suite),
+            result.duration_secs
+        ));
+        match result.failure_message() {
+            Some(message) => {
+                let first = message.lines().next().unwrap_or("");
+                out.push_str(&format!(
+                    ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                    escape_html(first),
+                    escape_html(&message)
+                ));
+            }
+            None => out.push_str("/>\n"),
+        }
+    }
+    out.push_str("</testsuite>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_assertion_kind() {
+        let suite: Suite = toml::from_str(
+            r#"
+            [[cases]]
+            name = "c"
+            fixture = "fx"
+            prompt = "p"
+            assert = [
+              { file_exists = "a" },
+              { file = "b", matches = "x" },
+              { command = "true" },
+              { output_matches = "ok" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let kinds: Vec<String> = suite.cases[0].assert.iter().map(|a| a.label()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "file exists: a",
+                "b matches /x/",
+                "command passes: true",
+                "output matches /ok/"
+            ]
+        );
+    }
+
+    #[test]
+    fn junit_escapes_and_reports_failures() {
+        let results = [
+            CaseResult {
+                name: "ok".to_string(),
+                passed: true,
+                duration_secs: 1.0,
+                error: None,
+                assertions: Vec::new(),
+            },
+            CaseResult::errored("<bad>", Duration::from_secs(2), "timed out".to_string()),
+        ];
+        let xml = junit_report("s", &results);
+        assert!(xml.contains("tests=\"2\" failures=\"1\" time=\"3.000\""));
+        assert!(xml.contains("<testcase name=\"ok\" classname=\"s\" time=\"1.000\"/>"));
+        assert!(xml.contains("name=\"&lt;bad&gt;\""));
+        assert!(xml.contains("<failure message=\"timed out\">timed out</failure>"));
+    }
+}
@@ -0,0 +1,103 @@
+//! `--event-socket <path>`: mirrors the backend's notifications onto a local
+//! Unix socket, one `{"event": …, "data": …}` object per line, so an editor
+//! extension can show diffs and todo state without speaking the backend
+//! protocol. A client that connects mid-session first gets the latest
+//! `state`, `todos_changed` and `question_pending`.
+
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::backend::BackendNotification;
+
+/// Notifications whose latest value is replayed to new clients.
+const REPLAYED: [&str; 3] = ["state", "todos_changed", "question_pending"];
+
+type Clients = Arc<Mutex<Vec<Box<dyn Write + Send>>>>;
+
+/// The listening socket; the file is removed when this is dropped.
+pub struct EventSocket {
+    path: PathBuf,
+    clients: Clients,
+    latest: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl EventSocket {
+    #[cfg(unix)]
+    pub fn bind(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::UnixListener;
+        use std::time::Duration;
+
+        // A socket left behind by a run that crashed; anything else is kept.
+        if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))?;
+        let socket = Self {
+            path: path.to_path_buf(),
+            clients: Arc::default(),
+            latest: Arc::default(),
+        };
+        let (clients, latest) = (socket.clients.clone(), socket.latest.clone());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                // A client that stops reading is dropped rather than stalling the UI.
+                let _ = stream.set_write_timeout(Some(Duration::from_millis(500)));
+                let replay = latest.lock().unwrap();
+                let caught_up = REPLAYED
+                    .iter()
+                    .filter_map(|method| replay.get(*method))
+                    .all(|line| stream.write_all(line.as_bytes()).is_ok());
+                if caught_up {
+                    clients.lock().unwrap().push(Box::new(stream));
+                }
+            }
+        });
+        Ok(socket)
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(_path: &Path) -> Result<Self> {
+        anyhow::bail!("--event-socket needs Unix domain sockets")
+    }
+
+    /// Passes notifications through, copying each one to every client.
+    pub fn mirror(&self, rx: Receiver<BackendNotification>) -> Receiver<BackendNotification> {
+        let (tx, mirrored) = mpsc::channel();
+        let (clients, latest) = (self.clients.clone(), self.latest.clone());
+        thread::spawn(move || {
+            for notif in rx {
+                let mut line = json!({ "event": notif.method, "data": notif.params }).to_string();
+                line.push('\n');
+                // Held while writing so a client connecting now gets either
+                // this line or the replay that includes it.
+                let mut latest = latest.lock().unwrap();
+                clients
+                    .lock()
+                    .unwrap()
+                    .retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+                if REPLAYED.contains(&notif.method.as_str()) {
+                    latest.insert(notif.method.clone(), line);
+                }
+                drop(latest);
+                if tx.send(notif).is_err() {
+                    return;
+                }
+            }
+        });
+        mirrored
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
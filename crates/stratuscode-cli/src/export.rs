@@ -0,0 +1,406 @@
+use crate::backend::TimelineEvent;
+use crate::changes::{tool_diffs, FileDiff};
+use crate::render::{extract_diff_summary, format_iso, format_tool_args, tool_icon};
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Renders the conversation as markdown: user and assistant messages in full,
+/// tool calls as one-line summaries, each turn headed with when it started.
+/// Reasoning and sub-agent events are left out.
+pub fn transcript_markdown(events: &[TimelineEvent]) -> String {
+    let mut out = String::new();
+    let mut last_heading: Option<&str> = None;
+    for event in events.iter().filter(|e| e.parent_tool_call_id.is_none()) {
+        match event.kind.as_str() {
+            "user" => {
+                out.push_str(&format!(
+                    "## You · {}\n\n{}\n\n",
+                    format_iso(event.created_at),
+                    event.content.trim()
+                ));
+                last_heading = Some("user");
+            }
+            "assistant" | "tool_call" | "tool_result" | "status" | "error" => {
+                if last_heading != Some("assistant") {
+                    out.push_str(&format!(
+                        "## Assistant · {}\n\n",
+                        format_iso(event.created_at)
+                    ));
+                    last_heading = Some("assistant");
+                }
+                match event.kind.as_str() {
+                    "assistant" if !event.content.trim().is_empty() => {
+                        out.push_str(&format!("{}\n\n", event.content.trim()));
+                    }
+                    "tool_call" => {
+                        let name = event.tool_name.as_deref().unwrap_or("tool");
+                        let args = format_tool_args(&event.content);
+                        let status = event.status.as_deref().unwrap_or("completed");
+                        out.push_str(&format!("- {} {}", tool_icon(name), name));
+                        if !args.is_empty() {
+                            out.push_str(&format!(" `{}`", args));
+                        }
+                        out.push_str(&format!(" — {}\n\n", status));
+                    }
+                    "tool_result" => {
+                        if let Some((summary, _)) = extract_diff_summary(&event.content, 80) {
+                            out.push_str(&format!("  diff {}\n\n", summary));
+                        }
+                    }
+                    "status" if !event.content.trim().is_empty() => {
+                        out.push_str(&format!("> {}\n\n", event.content.trim()));
+                    }
+                    "error" => {
+                        out.push_str(&format!("> Error: {}\n\n", event.content.trim()));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// The file changes made in the session as one patch `git apply` accepts:
+/// each edit's diff, or the input of an `apply_patch` call, with paths made
+/// relative to the project, each call's diffs preceded by a `# <timestamp>`
+/// line that `git apply` skips. Starts after the `since` event or the last
+/// revert, whichever is later, as changes before either were undone. Returns
+/// the patch and how many tool calls went into it.
+pub fn session_patch(
+    events: &[TimelineEvent],
+    project_dir: &Path,
+    since: Option<&str>,
+) -> (String, usize) {
+    let start = events
+        .iter()
+        .rposition(|e| {
+            Some(e.id.as_str()) == since
+                || (e.kind == "tool_call" && e.tool_name.as_deref() == Some("revert"))
+        })
+        .map_or(0, |idx| idx + 1);
+    let diffs = tool_diffs(&events[start..], project_dir);
+    let patch = diffs
+        .iter()
+        .map(|(at, files)| {
+            let body: String = files.iter().map(FileDiff::to_git).collect();
+            format!("# {}\n{}", format_iso(*at), body)
+        })
+        .collect();
+    (patch, diffs.len())
+}
+
+const HTML_STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', sans-serif; max-width: 860px; margin: 2rem auto; padding: 0 1rem; background: #0f1117; color: #d6d9e0; line-height: 1.5; }
+h1 { font-size: 1.3rem; color: #fff; }
+.turn { border-left: 3px solid #3a3f4b; padding: 0.2rem 1rem; margin: 1.2rem 0; }
+.turn.user { border-color: #a78bfa; }
+.turn.assistant { border-color: #22d3ee; }
+.role { font-size: 0.75rem; text-transform: uppercase; letter-spacing: 0.08em; color: #8b90a0; }
+.role time { margin-left: 0.6rem; text-transform: none; letter-spacing: normal; color: #5c6170; }
+pre { background: #161922; padding: 0.7rem; border-radius: 6px; overflow-x: auto; font-size: 0.85rem; }
+code { font-family: 'JetBrains Mono', Menlo, monospace; }
+details.tool { margin: 0.4rem 0; background: #161922; border-radius: 6px; padding: 0.3rem 0.7rem; }
+details.tool summary { cursor: pointer; font-family: Menlo, monospace; font-size: 0.85rem; }
+details.tool.failed summary { color: #f87171; }
+.status { color: #8b90a0; font-style: italic; }
+.error { color: #f87171; }
+.diff .add { color: #4ade80; } .diff .del { color: #f87171; } .diff .hunk { color: #22d3ee; } .diff .file { color: #a78bfa; }
+.kw { color: #c084fc; } .str { color: #a3e635; } .com { color: #6b7280; font-style: italic; } .num { color: #fb923c; }
+";
+
+/// Renders the conversation as a standalone HTML page: markdown with
+/// highlighted code blocks, each tool call as a collapsible block holding its
+/// result, and diffs colored by line. Like the markdown transcript it stamps
+/// each turn with its start time and leaves out reasoning and sub-agent
+/// events.
+pub fn transcript_html(title: &str, events: &[TimelineEvent]) -> String {
+    let results: HashMap<&str, &TimelineEvent> = events
+        .iter()
+        .filter(|e| e.kind == "tool_result")
+        .filter_map(|e| Some((e.tool_call_id.as_deref()?, e)))
+        .collect();
+    let mut body = String::new();
+    let mut open_turn: Option<&str> = None;
+    for event in events.iter().filter(|e| e.parent_tool_call_id.is_none()) {
+        let turn = match event.kind.as_str() {
+            "user" => "user",
+            "assistant" | "tool_call" | "status" | "error" => "assistant",
+            _ => continue,
+        };
+        if open_turn != Some(turn) || turn == "user" {
+            if open_turn.is_some() {
+                body.push_str("</section>\n");
+            }
+            let role = if turn == "user" { "You" } else { "Assistant" };
+            let at = format_iso(event.created_at);
+            body.push_str(&format!(
+                "<section class=\"turn {}\"><div class=\"role\">{}<time datetime=\"{}\">{}</time></div>\n",
+                turn, role, at, at
+            ));
+            open_turn = Some(turn);
+        }
+        match event.kind.as_str() {
+            "user" | "assistant" if !event.content.trim().is_empty() => {
+                body.push_str(&markdown_html(event.content.trim()));
+            }
+            "tool_call" => {
+                let name = event.tool_name.as_deref().unwrap_or("tool");
+                let args = format_tool_args(&event.content);
+                let status = event.status.as_deref().unwrap_or("completed");
+                let result = event.tool_call_id.as_deref().and_then(|id| results.get(id));
+                let diff = result.and_then(|r| extract_diff_summary(&r.content, 80));
+                body.push_str(&format!(
+                    "<details class=\"tool {}\"><summary>{} {} <code>{}</code> — {}{}</summary>\n",
+                    escape_html(status),
+                    escape_html(tool_icon(name)),
+                    escape_html(name),
+                    escape_html(&args),
+                    escape_html(status),
+                    diff.as_ref()
+                        .map(|(summary, _)| format!(" {}", escape_html(summary)))
+                        .unwrap_or_default()
+                ));
+                match result {
+                    Some(result) => body.push_str(&tool_result_html(&result.content)),
+                    None => body.push_str("<p class=\"status\">No result recorded.</p>\n"),
+                }
+                body.push_str("</details>\n");
+            }
+            "status" if !event.content.trim().is_empty() => {
+                body.push_str(&format!(
+                    "<p class=\"status\">{}</p>\n",
+                    escape_html(event.content.trim())
+                ));
+            }
+            "error" => {
+                body.push_str(&format!(
+                    "<p class=\"error\">Error: {}</p>\n",
+                    escape_html(event.content.trim())
+                ));
+            }
+            _ => {}
+        }
+    }
+    if open_turn.is_some() {
+        body.push_str("</section>\n");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape_html(title),
+    )
+}
+
+/// A diff when the result carries one, otherwise the raw result text.
+fn tool_result_html(content: &str) -> String {
+    let diff = serde_json::from_str::<serde_json::Value>(content)
+        .ok()
+        .and_then(|v| v.get("diff")?.as_str().map(str::to_string));
+    let Some(diff) = diff else {
+        return format!("<pre><code>{}</code></pre>\n", escape_html(content));
+    };
+    let mut out = String::from("<pre class=\"diff\"><code>");
+    for line in diff.lines() {
+        let class = if line.starts_with("+++") || line.starts_with("---") {
+            "file"
+        } else if line.starts_with("@@") {
+            "hunk"
+        } else if line.starts_with('+') {
+            "add"
+        } else if line.starts_with('-') {
+            "del"
+        } else {
+            ""
+        };
+        if class.is_empty() {
+            out.push_str(&escape_html(line));
+        } else {
+            out.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                class,
+                escape_html(line)
+            ));
+        }
+        out.push('\n');
+    }
+    out.push_str("</code></pre>\n");
+    out
+}
+
+/// Markdown to HTML, with fenced code run through `highlight_code`. Raw HTML
+/// in messages is escaped rather than passed through.
+fn markdown_html(content: &str) -> String {
+    let mut events = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    for event in Parser::new_ext(
+        content,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    ) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((lang, String::new()));
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((lang, text)) = code.take() {
+                    events.push(Event::Html(
+                        format!(
+                            "<pre><code class=\"language-{}\">{}</code></pre>\n",
+                            escape_html(&lang),
+                            highlight_code(&text)
+                        )
+                        .into(),
+                    ));
+                }
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, buf)) = code.as_mut() {
+                    buf.push_str(&text);
+                }
+            }
+            Event::Html(raw) => events.push(Event::Text(raw)),
+            other => events.push(other),
+        }
+    }
+    let mut out = String::new();
+    html::push_html(&mut out, events.into_iter());
+    out
+}
+
+/// Keywords common to the languages the agent usually writes.
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "class",
+    "const",
+    "continue",
+    "def",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "fn",
+    "for",
+    "from",
+    "func",
+    "function",
+    "if",
+    "impl",
+    "import",
+    "in",
+    "interface",
+    "let",
+    "match",
+    "mod",
+    "mut",
+    "new",
+    "None",
+    "null",
+    "pub",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "switch",
+    "this",
+    "trait",
+    "true",
+    "try",
+    "type",
+    "use",
+    "var",
+    "while",
+    "yield",
+];
+
+/// A small language-agnostic highlighter: strings, `//` and `#` comments,
+/// numbers and common keywords. Enough to make shared code readable without
+/// bundling a real grammar.
+fn highlight_code(code: &str) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let span = |out: &mut String, class: &str, text: &str| {
+        out.push_str(&format!(
+            "<span class=\"{}\">{}</span>",
+            class,
+            escape_html(text)
+        ));
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let rest_starts = |s: &str| chars[i..].iter().take(s.len()).copied().eq(s.chars());
+        if rest_starts("//")
+            || (c == '#' && (i == 0 || chars[i - 1] == '\n' || chars[i - 1] == ' '))
+        {
+            let end = chars[i..]
+                .iter()
+                .position(|&ch| ch == '\n')
+                .map_or(chars.len(), |p| i + p);
+            span(&mut out, "com", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if c == '"' || c == '\'' || c == '`' {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != c && chars[end] != '\n' {
+                if chars[end] == '\\' {
+                    end += 1;
+                }
+                end += 1;
+            }
+            let end = (end + 1).min(chars.len());
+            span(&mut out, "str", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if c.is_ascii_digit() && (i == 0 || !is_ident(chars[i - 1])) {
+            let end = chars[i..]
+                .iter()
+                .position(|&ch| !(ch.is_ascii_alphanumeric() || ch == '.' || ch == '_'))
+                .map_or(chars.len(), |p| i + p);
+            span(&mut out, "num", &chars[i..end].iter().collect::<String>());
+            i = end;
+        } else if is_ident(c) {
+            let end = chars[i..]
+                .iter()
+                .position(|&ch| !is_ident(ch))
+                .map_or(chars.len(), |p| i + p);
+            let word: String = chars[i..end].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                span(&mut out, "kw", &word);
+            } else {
+                out.push_str(&escape_html(&word));
+            }
+            i = end;
+        } else {
+            out.push_str(&escape_html(&c.to_string()));
+            i += 1;
+        }
+    }
+    out
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
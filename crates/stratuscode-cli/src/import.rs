@@ -0,0 +1,332 @@
+use crate::text::truncate_text;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Agent CLIs whose sessions `stratuscode import` understands.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportSource {
+    /// A Claude Code transcript, `~/.claude/projects/<project>/<session>.jsonl`
+    ClaudeCode,
+    /// An opencode session: `opencode export` output or a file under
+    /// `storage/session/`
+    Opencode,
+}
+
+impl ImportSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ImportSource::ClaudeCode => "Claude Code",
+            ImportSource::Opencode => "opencode",
+        }
+    }
+}
+
+/// The payload of `import_session`, shared by every source.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedSession {
+    pub title: String,
+    pub source: String,
+    pub messages: Vec<ImportedMessage>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedMessage {
+    pub role: String,
+    pub content: String,
+    pub created_at: Option<i64>,
+    pub tool_calls: Vec<ImportedToolCall>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+    pub result: Option<String>,
+    pub status: String,
+}
+
+pub fn load_session(source: ImportSource, path: &Path) -> Result<ImportedSession> {
+    let mut session = match source {
+        ImportSource::ClaudeCode => parse_claude_code(path)?,
+        ImportSource::Opencode => parse_opencode(path)?,
+    };
+    if session.messages.is_empty() {
+        return Err(anyhow!("No messages found in {}", path.display()));
+    }
+    if session.title.trim().is_empty() {
+        session.title = session
+            .messages
+            .iter()
+            .find(|m| m.role == "user")
+            .map(|m| truncate_text(m.content.lines().next().unwrap_or(""), 60))
+            .unwrap_or_else(|| "Imported session".to_string());
+    }
+    Ok(session)
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&raw).map_err(|e| anyhow!("Invalid {}: {}", path.display(), e))
+}
+
+fn timestamp_ms(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_millis()),
+        _ => None,
+    }
+}
+
+/// Text of a string or of the `text` blocks in a content array.
+fn block_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Claude Code writes one JSON line per content block; consecutive assistant
+/// lines are merged back into one message, and tool results (sent as user
+/// lines) are attached to the call they answer.
+fn parse_claude_code(path: &Path) -> Result<ImportedSession> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let mut title = String::new();
+    let mut messages: Vec<ImportedMessage> = Vec::new();
+    for line in raw.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let kind = entry.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        if kind == "summary" {
+            if let Some(summary) = entry.get("summary").and_then(|s| s.as_str()) {
+                title = summary.to_string();
+            }
+            continue;
+        }
+        let skip = |key: &str| entry.get(key).and_then(|v| v.as_bool()) == Some(true);
+        if !matches!(kind, "user" | "assistant") || skip("isMeta") || skip("isSidechain") {
+            continue;
+        }
+        let Some(content) = entry.get("message").and_then(|m| m.get("content")) else {
+            continue;
+        };
+        let created_at = timestamp_ms(entry.get("timestamp"));
+        let blocks = content.as_array().cloned().unwrap_or_default();
+
+        for block in blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+        {
+            let id = block
+                .get("tool_use_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let failed = block.get("is_error").and_then(|v| v.as_bool()) == Some(true);
+            let result = block.get("content").map(block_text).unwrap_or_default();
+            if let Some(call) = messages
+                .iter_mut()
+                .rev()
+                .flat_map(|m| m.tool_calls.iter_mut())
+                .find(|c| c.id == id)
+            {
+                call.result = Some(result);
+                call.status = if failed { "failed" } else { "completed" }.to_string();
+            }
+        }
+
+        let text = block_text(content);
+        let tool_calls: Vec<ImportedToolCall> = blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+            .map(|b| ImportedToolCall {
+                id: b
+                    .get("id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                name: b
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("tool")
+                    .to_string(),
+                arguments: b.get("input").map(|v| v.to_string()).unwrap_or_default(),
+                result: None,
+                status: "completed".to_string(),
+            })
+            .collect();
+        if text.trim().is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        match messages.last_mut() {
+            Some(last) if kind == "assistant" && last.role == "assistant" => {
+                if !text.trim().is_empty() {
+                    if !last.content.is_empty() {
+                        last.content.push_str("\n\n");
+                    }
+                    last.content.push_str(&text);
+                }
+                last.tool_calls.extend(tool_calls);
+            }
+            _ => messages.push(ImportedMessage {
+                role: kind.to_string(),
+                content: text,
+                created_at,
+                tool_calls,
+            }),
+        }
+    }
+    Ok(ImportedSession {
+        title,
+        source: ImportSource::ClaudeCode.label().to_string(),
+        messages,
+    })
+}
+
+/// Accepts `opencode export` output (`{info, messages: [{info, parts}]}`) or
+/// a session file from opencode's storage directory, whose messages and parts
+/// live in sibling `message/` and `part/` directories.
+fn parse_opencode(path: &Path) -> Result<ImportedSession> {
+    let root = read_json(path)?;
+    let (info, entries) = match root.get("messages").and_then(|m| m.as_array()) {
+        Some(messages) => (
+            root.get("info").cloned().unwrap_or_default(),
+            messages
+                .iter()
+                .map(|m| {
+                    let parts = m
+                        .get("parts")
+                        .and_then(|p| p.as_array())
+                        .cloned()
+                        .unwrap_or_default();
+                    (m.get("info").cloned().unwrap_or_default(), parts)
+                })
+                .collect(),
+        ),
+        None => (root.clone(), opencode_storage_messages(path, &root)?),
+    };
+    let mut messages = Vec::new();
+    for (message, parts) in entries {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("");
+        if !matches!(role, "user" | "assistant") {
+            continue;
+        }
+        let mut text = Vec::new();
+        let mut tool_calls = Vec::new();
+        for part in &parts {
+            match part.get("type").and_then(|t| t.as_str()) {
+                Some("text") if part.get("synthetic").and_then(|s| s.as_bool()) != Some(true) => {
+                    if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                        text.push(t.to_string());
+                    }
+                }
+                Some("tool") => {
+                    let state = part.get("state").cloned().unwrap_or_default();
+                    let status = state.get("status").and_then(|s| s.as_str()).unwrap_or("");
+                    let result = state.get("output").or_else(|| state.get("error")).map(|v| {
+                        v.as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| v.to_string())
+                    });
+                    tool_calls.push(ImportedToolCall {
+                        id: part
+                            .get("callID")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                        name: part
+                            .get("tool")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("tool")
+                            .to_string(),
+                        arguments: state
+                            .get("input")
+                            .map(|v| v.to_string())
+                            .unwrap_or_default(),
+                        result,
+                        status: if status == "error" {
+                            "failed"
+                        } else {
+                            "completed"
+                        }
+                        .to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        let content = text.join("\n\n");
+        if content.trim().is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+        messages.push(ImportedMessage {
+            role: role.to_string(),
+            content,
+            created_at: timestamp_ms(message.get("time").and_then(|t| t.get("created"))),
+            tool_calls,
+        });
+    }
+    Ok(ImportedSession {
+        title: info
+            .get("title")
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string(),
+        source: ImportSource::Opencode.label().to_string(),
+        messages,
+    })
+}
+
+/// `storage/session/<project>/<id>.json` -> messages from
+/// `storage/message/<id>/` with parts from `storage/part/<message id>/`.
+fn opencode_storage_messages(path: &Path, session: &Value) -> Result<Vec<(Value, Vec<Value>)>> {
+    let id = session
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("{} is not an opencode session", path.display()))?;
+    let storage = path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::parent)
+        .ok_or_else(|| anyhow!("Can't find opencode storage for {}", path.display()))?;
+    let mut messages: Vec<Value> = read_json_dir(&storage.join("message").join(id));
+    messages.sort_by_key(|m| timestamp_ms(m.get("time").and_then(|t| t.get("created"))));
+    Ok(messages
+        .into_iter()
+        .map(|message| {
+            let message_id = message.get("id").and_then(|v| v.as_str()).unwrap_or("");
+            let parts = read_json_dir(&storage.join("part").join(message_id));
+            (message, parts)
+        })
+        .collect())
+}
+
+/// Every parseable `.json` file in a directory, ordered by file name (opencode
+/// ids sort by creation time).
+fn read_json_dir(dir: &Path) -> Vec<Value> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    paths.sort();
+    paths.iter().filter_map(|p| read_json(p).ok()).collect()
+}
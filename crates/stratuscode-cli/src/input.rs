@@ -1,19 +1,33 @@
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use base64::Engine;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde_json::json;
 
 use crate::app::{
-    collect_answers, ensure_file_index, file_query_from_input, insert_file_mention, select_option,
-    App, UiMode,
+    apply_template, bookmark_focused, cancel_tool, collect_answers, comment_on_hunk,
+    ensure_file_index, file_query_from_input, insert_file_mention, load_all_earlier,
+    open_diff_pager, open_file_preview, pin_file, quote_focused, refresh_todos,
+    remove_mention_query, revert_selected_hunk, select_option, set_reasoning_effort, switch_agent,
+    toggle_sidebar, toggle_todos, App, AttachmentKind, AttachmentUpload, FileResult, MentionTarget,
+    RelatedCode, TodoEdit, UiMode, NOTIFICATIONS_PAGE, PAGER_PAGE, QUESTION_VIEWPORT,
 };
-use crate::backend::BackendClient;
-use crate::commands::{
-    commands_list, execute_command, filter_commands, filter_models, parse_command,
-    sort_models_by_provider,
+use crate::attachments::{
+    add_attachment, attachment_payload, drop_attachments_in_range, encode_png, is_large_paste,
+    load_attachment, paste_attachment, pasted_file_paths, remove_attachment, unfetched_url,
 };
+use crate::backend::BackendClient;
+use crate::changes::working_tree_diff;
+use crate::clipboard::copy_text;
+use crate::commands::{commands_list, execute_command, filter_commands, parse_command};
+use crate::config::ModelSource;
 use crate::constants::{IMAGE_MARKER, PASTE_END, PASTE_START};
+use crate::i18n::{t, tf};
+use crate::macros::{is_register, MacroAction};
+use crate::store::SessionStore;
+use crate::trust::trust_folder;
 
 pub fn clamp_cursor(value: &str, cursor: usize) -> usize {
     let mut idx = cursor.min(value.len());
@@ -95,26 +109,473 @@ pub fn handle_paste(app: &mut App, text: String) {
         if text.is_empty() {
             return;
         }
-        let cursor = clamp_cursor(&app.input, app.cursor);
-        let insertion = format!("{}{}{}", PASTE_START, text, PASTE_END);
-        let prev = prev_char_start(&app.input, cursor).and_then(|i| app.input[i..].chars().next());
-        let next = char_at(&app.input, cursor);
-
-        if prev == Some(PASTE_END) {
-            let before_end = cursor.saturating_sub(PASTE_END.len_utf8());
-            app.input.insert_str(before_end, &text);
-            app.cursor = before_end + text.len() + PASTE_END.len_utf8();
-        } else if next == Some(PASTE_START) {
-            let start_len = PASTE_START.len_utf8();
-            let insert_at = cursor + start_len;
-            app.input.insert_str(insert_at, &text);
-            app.cursor = insert_at + text.len();
-        } else {
-            app.input.insert_str(cursor, &insertion);
-            app.cursor = cursor + insertion.len();
+        if let Some(paths) = pasted_file_paths(&text, &app.project_dir) {
+            for path in paths {
+                match load_attachment(&path) {
+                    Ok(upload) => {
+                        app.set_toast(tf("toast.attached", &[("file", &upload.chip_label())]));
+                        add_attachment(app, upload);
+                    }
+                    Err(err) => app.error_toast(err),
+                }
+            }
+            return;
+        }
+        if text.lines().count() >= app.paste_confirm_lines {
+            app.pending_paste = Some(text);
+            app.paste_confirm_selected = 0;
+            app.mode = UiMode::PasteConfirm;
+            app.mark_dirty();
+            return;
+        }
+        if is_large_paste(&text) {
+            add_attachment(app, paste_attachment(text));
+            return;
+        }
+        insert_paste_inline(app, &text);
+    }
+}
+
+fn handle_todo_keys(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient>>) {
+    if let Some(edit) = &mut app.todo_edit {
+        match key.code {
+            KeyCode::Enter => {
+                let edit = app.todo_edit.take().unwrap();
+                let content = edit.input.trim();
+                if content.is_empty() {
+                    return;
+                }
+                let result = match (&edit.id, &app.state.session_id) {
+                    (Some(id), _) => client
+                        .lock()
+                        .unwrap()
+                        .call("update_todo", json!({ "id": id, "content": content })),
+                    (None, Some(session_id)) => client.lock().unwrap().call(
+                        "add_todo",
+                        json!({ "sessionId": session_id, "content": content }),
+                    ),
+                    (None, None) => {
+                        app.warn_toast(t("toast.todos_need_session"));
+                        return;
+                    }
+                };
+                if result.is_err() {
+                    app.error_toast(t("toast.todo_save_failed"));
+                }
+                refresh_todos(app, client);
+                if edit.id.is_none() {
+                    app.todo_selected = app.todos.len().saturating_sub(1);
+                }
+            }
+            KeyCode::Backspace => {
+                edit.input.pop();
+            }
+            KeyCode::Char(ch) if is_text_input(key.modifiers) => {
+                edit.input.push(ch);
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match key.code {
+        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            toggle_todos(app, client);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.todo_selected = app.todo_selected.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            if app.todo_selected + 1 < app.todos.len() {
+                app.todo_selected += 1;
+            }
+        }
+        KeyCode::Char(' ') => {
+            if let Some(todo) = app.todos.get(app.todo_selected) {
+                let next = match todo.status.as_str() {
+                    "pending" => "in_progress",
+                    "in_progress" => "completed",
+                    _ => "pending",
+                };
+                let result = client
+                    .lock()
+                    .unwrap()
+                    .call("update_todo", json!({ "id": todo.id, "status": next }));
+                if result.is_err() {
+                    app.error_toast(t("toast.todo_update_failed"));
+                }
+                refresh_todos(app, client);
+            }
+        }
+        KeyCode::Char('e') => {
+            if let Some(todo) = app.todos.get(app.todo_selected) {
+                app.todo_edit = Some(TodoEdit {
+                    id: Some(todo.id.clone()),
+                    input: todo.content.clone(),
+                });
+            }
+        }
+        KeyCode::Char('a') => {
+            app.todo_edit = Some(TodoEdit {
+                id: None,
+                input: String::new(),
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Sends the message, or queues it behind the one in flight so a quick second
+/// Enter can't race it.
+fn send_message(app: &mut App, client: &Arc<Mutex<BackendClient>>, payload: serde_json::Value) {
+    app.title_offer = false;
+    let content = payload["content"].as_str().unwrap_or_default().to_string();
+    let queued = app.send_in_flight();
+    let id = app.push_pending_send(&content, queued);
+    if queued {
+        app.queued_sends.push_back((id, payload));
+    } else {
+        dispatch_send(app, client, id, payload);
+    }
+}
+
+/// Sends the oldest queued message once nothing is in flight.
+pub fn send_next_queued(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    if app.send_in_flight() {
+        return;
+    }
+    if let Some((id, payload)) = app.queued_sends.pop_front() {
+        app.mark_sending(&id);
+        dispatch_send(app, client, id, payload);
+    }
+}
+
+/// `/retry`: sends the message whose turn failed again.
+pub fn retry_failed_send(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    let Some(payload) = app.retry_payload.take() else {
+        app.warn_toast(t("toast.retry_nothing"));
+        return;
+    };
+    app.auto_scroll = true;
+    app.scroll_from_bottom = 0;
+    app.mark_dirty();
+    send_message(app, client, payload);
+}
+
+/// `/fix`: hands the output of the failed `verify` run back to the agent.
+pub fn fix_failed_verify(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    if app.state.is_loading {
+        app.warn_toast(t("toast.fix_busy"));
+        return;
+    }
+    let Some((command, output)) = app.failed_verify() else {
+        app.warn_toast(t("toast.fix_nothing"));
+        return;
+    };
+    let content = format!(
+        "`{}` failed after your changes:\n\n```\n{}\n```\n\nFix the failures.",
+        command, output
+    );
+    app.auto_scroll = true;
+    app.scroll_from_bottom = 0;
+    app.mark_dirty();
+    send_message(
+        app,
+        client,
+        json!({ "content": content, "attachments": null }),
+    );
+}
+
+/// Sends on a worker thread so a slow backend doesn't block input. The
+/// payload is kept so the message can be retried if the turn fails.
+fn dispatch_send(
+    app: &mut App,
+    client: &Arc<Mutex<BackendClient>>,
+    client_id: String,
+    mut payload: serde_json::Value,
+) {
+    app.retry_payload = None;
+    app.last_send = Some(payload.clone());
+    payload["clientId"] = json!(client_id);
+    add_send_context(app, &mut payload);
+    let client = client.clone();
+    std::thread::spawn(move || {
+        let _ = client.lock().unwrap().call("send_message", payload);
+    });
+}
+
+/// Adds what goes with every message: pinned files and, with /withdiff, the
+/// working tree's diff.
+pub fn add_send_context(app: &App, payload: &mut serde_json::Value) {
+    if !app.pinned.is_empty() {
+        payload["pinned"] = json!(app.pinned);
+    }
+    if app.with_diff {
+        let diff = working_tree_diff(
+            Path::new(&app.project_dir),
+            app.with_diff_status,
+            app.with_diff_max_bytes,
+        );
+        if let Some(diff) = diff {
+            let attachment =
+                json!({ "type": "text", "name": "git diff", "mime": "text/x-diff", "text": diff });
+            match payload["attachments"].as_array_mut() {
+                Some(list) => list.push(attachment),
+                None => payload["attachments"] = json!([attachment]),
+            }
+        }
+    }
+}
+
+/// An identical message, or an empty Enter, this soon after a send is taken
+/// as key repeat.
+const SEND_DEBOUNCE: Duration = Duration::from_millis(400);
+
+/// A second Ctrl+C this soon after one that aborted a turn quits.
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(2);
+
+/// Actions under the plan viewer, in display order.
+pub const PLAN_ACTION_OPTIONS: [&str; 3] = ["Accept and build", "Edit plan", "Keep planning"];
+
+fn resolve_plan_action(app: &mut App, client: &Arc<Mutex<BackendClient>>, choice: usize) {
+    match choice {
+        0 => {
+            send_message(
+                app,
+                client,
+                json!({ "content": "The plan is approved. Read the plan file and start implementing.", "agentOverride": "build", "options": { "buildSwitch": true } }),
+            );
+            app.mode = UiMode::Normal;
+        }
+        1 => {
+            if app.plan_file_path().is_some() {
+                app.edit_plan_requested = true;
+            } else {
+                app.warn_toast(t("toast.no_plan_file"));
+            }
+        }
+        _ => {
+            let _ = client.lock().unwrap().call("reset_plan_exit", json!({}));
+            app.mode = UiMode::Normal;
+        }
+    }
+}
+
+/// Locale keys for the choices offered by the PasteConfirm modal, in display
+/// order.
+pub const PASTE_CONFIRM_OPTIONS: [&str; 3] =
+    ["ui.paste_attach", "ui.paste_inline", "ui.paste_cancel"];
+
+fn resolve_pending_paste(app: &mut App, choice: usize) {
+    app.mode = UiMode::Normal;
+    let Some(text) = app.pending_paste.take() else {
+        return;
+    };
+    match choice {
+        0 => add_attachment(app, paste_attachment(text)),
+        1 => insert_paste_inline(app, &text),
+        _ => app.set_toast(t("toast.paste_discarded")),
+    }
+    app.mark_dirty();
+}
+
+/// Shows a session read from the store when the backend can't load it.
+fn load_saved_session(app: &mut App, session_id: &str) {
+    match SessionStore::open().and_then(|store| store.timeline_events(session_id)) {
+        Ok(events) => {
+            app.state.timeline_events = events;
+            app.state.session_id = Some(session_id.to_string());
+            app.warn_toast(t("toast.session_from_store"));
+        }
+        Err(e) => app.error_toast(tf("toast.session_load_failed", &[("error", &e)])),
+    }
+}
+
+/// Loads the interrupted session, replaying tool results that finished after
+/// the crash.
+fn resume_interrupted(app: &mut App) {
+    app.mode = UiMode::Normal;
+    app.resume_requested = app.interrupted.take();
+}
+
+/// Records the project folder as trusted and hands the backend the settings
+/// held back until now.
+fn trust_project(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    app.mode = after_trust_prompt(app);
+    let Some(withheld) = app.untrusted.take() else {
+        return;
+    };
+    if let Err(e) = trust_folder(Path::new(&app.project_dir)) {
+        app.error_toast(tf("toast.trust_save_failed", &[("error", &e)]));
+    }
+    let read_only = withheld["readOnly"].as_bool().unwrap_or(false);
+    app.set_read_only(read_only);
+    let mut client = client.lock().unwrap();
+    let _ = client.call("set_read_only", json!({ "readOnly": read_only }));
+    let _ = client.call(
+        "set_project_commands",
+        json!({ "hooks": withheld["hooks"], "verify": withheld["verify"] }),
+    );
+    drop(client);
+    app.set_toast(if withheld["env"].as_bool().unwrap_or(false) {
+        t("toast.trusted_env")
+    } else {
+        t("toast.trusted")
+    });
+}
+
+/// The interrupted-session prompt waits behind the trust prompt.
+fn after_trust_prompt(app: &App) -> UiMode {
+    if app.interrupted.is_some() {
+        UiMode::Recovery
+    } else {
+        UiMode::Normal
+    }
+}
+
+/// Clears the in-flight marker so the banner doesn't come back next launch.
+fn dismiss_interrupted(app: &mut App, client: &Arc<Mutex<BackendClient>>) {
+    app.mode = UiMode::Normal;
+    let Some(session) = app.interrupted.take() else {
+        return;
+    };
+    if let Err(e) = client
+        .lock()
+        .unwrap()
+        .call("dismiss_interrupted", json!({ "sessionId": session.id }))
+    {
+        app.error_toast(tf("toast.session_dismiss_failed", &[("error", &e)]));
+    }
+}
+
+/// Inserts pasted text at the cursor between paste sentinels, merging with a
+/// paste the cursor is touching.
+fn insert_paste_inline(app: &mut App, text: &str) {
+    let cursor = clamp_cursor(&app.input, app.cursor);
+    let insertion = format!("{}{}{}", PASTE_START, text, PASTE_END);
+    let prev = prev_char_start(&app.input, cursor).and_then(|i| app.input[i..].chars().next());
+    let next = char_at(&app.input, cursor);
+
+    if prev == Some(PASTE_END) {
+        let before_end = cursor.saturating_sub(PASTE_END.len_utf8());
+        app.input.insert_str(before_end, text);
+        app.cursor = before_end + text.len() + PASTE_END.len_utf8();
+    } else if next == Some(PASTE_START) {
+        let start_len = PASTE_START.len_utf8();
+        let insert_at = cursor + start_len;
+        app.input.insert_str(insert_at, text);
+        app.cursor = insert_at + text.len();
+    } else {
+        app.input.insert_str(cursor, &insertion);
+        app.cursor = cursor + insertion.len();
+    }
+    app.mark_dirty();
+}
+
+/// Alt+q starts and stops recording, Alt+@ replays, and the key after either
+/// names the register. Returns true when the key was one of these; every
+/// other key is appended to the recording.
+fn handle_macro_keys(app: &mut App, key: KeyEvent) -> bool {
+    if let Some(action) = app.macro_pending.take() {
+        match key.code {
+            KeyCode::Esc => {}
+            KeyCode::Char(c) if is_register(c) => match action {
+                MacroAction::Record => {
+                    app.macro_recording = Some((c, Vec::new()));
+                    app.set_toast(tf("toast.macro_recording", &[("register", &c)]));
+                }
+                MacroAction::Replay => match app.macros.registers.get(&c) {
+                    Some(keys) if !keys.is_empty() => {
+                        app.macro_queue.extend(keys.iter().copied());
+                        app.set_toast(tf("toast.macro_replaying", &[("register", &c)]));
+                    }
+                    _ => app.warn_toast(tf("toast.macro_empty", &[("register", &c)])),
+                },
+            },
+            _ => app.warn_toast(t("toast.macro_bad_register")),
         }
         app.mark_dirty();
+        return true;
     }
+    let alt =
+        key.modifiers.contains(KeyModifiers::ALT) && !key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Char('q') if alt => {
+            match app.macro_recording.take() {
+                Some((register, keys)) => {
+                    let count = keys.len();
+                    app.macros.registers.insert(register, keys);
+                    match app.macros.save() {
+                        Ok(()) => app.set_toast(tf(
+                            "toast.macro_saved",
+                            &[("count", &count), ("register", &register)],
+                        )),
+                        Err(e) => app.error_toast(tf("toast.macro_save_failed", &[("error", &e)])),
+                    }
+                }
+                None => {
+                    app.macro_pending = Some(MacroAction::Record);
+                    app.set_toast(t("toast.macro_register"));
+                }
+            }
+            app.mark_dirty();
+            true
+        }
+        KeyCode::Char('@') if alt => {
+            app.macro_pending = Some(MacroAction::Replay);
+            app.set_toast(t("toast.macro_register"));
+            app.mark_dirty();
+            true
+        }
+        _ => {
+            if let Some((_, keys)) = &mut app.macro_recording {
+                keys.push(key);
+            }
+            false
+        }
+    }
+}
+
+/// Editing the session title in the timeline border after `t`.
+fn handle_title_rename(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient>>) {
+    let Some(text) = app.title_rename.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Esc => app.title_rename = None,
+        KeyCode::Backspace => {
+            text.pop();
+        }
+        KeyCode::Enter => {
+            let title = text.trim().to_string();
+            app.title_rename = None;
+            if let (false, Some(session_id)) = (title.is_empty(), app.state.session_id.clone()) {
+                let resp = client.lock().unwrap().call(
+                    "rename_session",
+                    json!({ "sessionId": session_id, "title": title }),
+                );
+                match resp {
+                    Ok(_) => {
+                        app.set_toast(tf("toast.session_renamed", &[("title", &title)]));
+                        app.state.title = Some(title);
+                    }
+                    Err(e) => app.error_toast(tf("toast.session_rename_failed", &[("error", &e)])),
+                }
+            }
+        }
+        KeyCode::Char(ch) if is_text_input(key.modifiers) => text.push(ch),
+        _ => {}
+    }
+    app.mark_dirty();
+}
+
+/// Whether a character key should be typed rather than treated as a
+/// shortcut. Ctrl+Alt is how Windows reports AltGr, which IMEs and many
+/// keyboard layouts use for ordinary characters.
+fn is_text_input(modifiers: KeyModifiers) -> bool {
+    let control = modifiers.contains(KeyModifiers::CONTROL);
+    let alt = modifiers.contains(KeyModifiers::ALT);
+    control == alt
 }
 
 pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient>>) {
@@ -122,6 +583,23 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
     // This guards against corruption from paste events or other edge cases.
     app.cursor = clamp_cursor(&app.input, app.cursor);
 
+    if handle_macro_keys(app, key) {
+        return;
+    }
+    if matches!(key.code, KeyCode::Esc) && app.todo_edit.is_some() {
+        app.todo_edit = None;
+        app.mark_dirty();
+        return;
+    }
+    if app.title_rename.is_some() {
+        handle_title_rename(app, key, client);
+        return;
+    }
+    if key.code == KeyCode::Esc && key.modifiers.contains(KeyModifiers::SHIFT) {
+        cancel_tool(app, client);
+        app.mark_dirty();
+        return;
+    }
     if matches!(key.code, KeyCode::Esc) {
         if app.state.is_loading {
             let client = client.clone();
@@ -132,6 +610,7 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
         if !matches!(app.mode, UiMode::Normal) {
             app.mode = UiMode::Normal;
         }
+        app.pending_paste = None;
         app.mark_dirty();
         return;
     }
@@ -142,15 +621,36 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
     app.pending_gg = false;
 
     match key.code {
+        KeyCode::Char('p' | 'P')
+            if key
+                .modifiers
+                .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) =>
+        {
+            app.perf_hud = !app.perf_hud;
+            app.mark_dirty();
+        }
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            if app.state.is_loading {
+            let again = app
+                .abort_requested_at
+                .is_some_and(|at| at.elapsed() < QUIT_CONFIRM_WINDOW);
+            if app.state.is_loading && !again {
                 let _ = client.lock().unwrap().call("abort", json!({}));
+                app.abort_requested_at = Some(Instant::now());
+                app.set_toast(t("toast.ctrl_c_again"));
             } else {
                 app.should_quit = true;
             }
         }
+        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            if cfg!(unix) {
+                app.suspend_requested = true;
+            } else {
+                app.warn_toast(t("toast.suspend_unsupported"));
+            }
+        }
         KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.show_telemetry_details = !app.show_telemetry_details;
+            app.mode = UiMode::Telemetry;
+            app.usage_refresh_requested = true;
             app.mark_dirty();
         }
         KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -181,8 +681,10 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
             if app.cursor > 0 {
                 let before = app.input[..app.cursor].trim_end();
                 let last_space = before.rfind(' ').map(|i| i + 1).unwrap_or(0);
-                let after = app.input[app.cursor..].to_string();
-                app.input = format!("{}{}", &before[..last_space], after);
+                drop_attachments_in_range(app, last_space, app.cursor);
+                let before = &app.input[..last_space];
+                let after = &app.input[app.cursor..];
+                app.input = format!("{}{}", before, after);
                 app.cursor = last_space;
                 app.mark_dirty();
             }
@@ -196,36 +698,75 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
             app.mark_dirty();
         }
         KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            let next = match app.reasoning_effort.as_str() {
-                "off" => "low",
-                "low" => "medium",
-                "medium" => "high",
-                _ => "off",
-            };
-            app.reasoning_effort = next.to_string();
-            let _ = client
-                .lock()
-                .unwrap()
-                .call("set_reasoning_effort", json!({ "reasoningEffort": next }));
-            app.set_toast(format!("Reasoning: {}", next));
+            let levels = app.reasoning_levels();
+            if levels.len() == 1 {
+                let model = app.current_model();
+                app.warn_toast(tf("toast.reasoning_unsupported", &[("model", &model)]));
+                return;
+            }
+            let next = levels
+                .iter()
+                .position(|l| *l == app.reasoning_effort)
+                .map_or(levels[0], |i| levels[(i + 1) % levels.len()]);
+            set_reasoning_effort(app, client, next);
+        }
+        KeyCode::Char('f' | 'F') if key.modifiers == KeyModifiers::ALT => {
+            app.follow_mode = app.follow_mode.next();
+            app.set_toast(tf("toast.follow", &[("mode", &app.follow_mode.label())]));
+        }
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.timeline_filter = app.timeline_filter.next();
+            app.scroll_from_bottom = 0;
+            app.set_toast(tf(
+                "toast.filter",
+                &[("filter", &app.timeline_filter.label())],
+            ));
+        }
+        KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => quote_focused(app),
+        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match app.subagent_call_to_toggle() {
+                Some(id) => app.toggle_subagent(&id),
+                None => app.set_toast(t("toast.no_subagent")),
+            }
         }
         KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.todos_expanded = !app.todos_expanded;
+            toggle_todos(app, client);
+        }
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            toggle_sidebar(app);
+        }
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            bookmark_focused(app, client);
+        }
+        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match unfetched_url(app) {
+                Some(_) if app.url_fetching.is_some() => app.warn_toast(t("toast.url_busy")),
+                Some(url) => {
+                    app.url_fetching = Some(url.clone());
+                    app.url_fetch_requested = Some(url);
+                }
+                None => app.warn_toast(t("toast.url_none")),
+            }
             app.mark_dirty();
-            crate::app::refresh_todos(app, client);
+        }
+        KeyCode::Char(c @ '1'..='9')
+            if key.modifiers.contains(KeyModifiers::ALT) && !app.attachments.is_empty() =>
+        {
+            let index = c as usize - '1' as usize;
+            if let Some(id) = app.attachments.get(index).map(|a| a.id) {
+                if let Some(removed) = remove_attachment(app, id) {
+                    app.set_toast(tf("toast.removed", &[("file", &removed.chip_label())]));
+                }
+            }
         }
         KeyCode::Tab => {
-            let next = if app.state.agent == "build" {
-                "plan"
-            } else {
-                "build"
-            };
-            app.state.agent = next.to_string();
-            let _ = client
-                .lock()
-                .unwrap()
-                .call("set_agent", json!({ "agent": next }));
-            app.mark_dirty();
+            let names = app.agent_names();
+            let next = names
+                .iter()
+                .position(|name| *name == app.state.agent)
+                .map(|i| names[(i + 1) % names.len()].clone())
+                .unwrap_or_else(|| names[0].clone());
+            switch_agent(app, client, &next);
         }
         KeyCode::Char('/') if app.input.is_empty() => {
             app.mode = UiMode::CommandPalette;
@@ -268,17 +809,36 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
             app.auto_scroll = true;
             app.mark_dirty();
         }
+        KeyCode::Char('t' | 'T')
+            if app.mode == UiMode::Normal
+                && key.modifiers == KeyModifiers::ALT
+                && app.title_offer =>
+        {
+            app.title_offer = false;
+            app.title_rename = Some(app.session_title().unwrap_or_default());
+            app.mark_dirty();
+        }
+        KeyCode::Char('e' | 'E')
+            if app.mode == UiMode::Normal
+                && key.modifiers == KeyModifiers::ALT
+                && app.focused_compaction().is_some() =>
+        {
+            if let Some(id) = app.focused_compaction().map(|e| e.id.clone()) {
+                app.toggle_summary(&id);
+            }
+        }
         KeyCode::Enter => {
             let content = app.input.trim().to_string();
             if content.starts_with('/') {
+                // Cleared first so commands like /attach can populate the input.
+                app.input.clear();
+                app.cursor = 0;
+                app.attachments.clear();
                 if let Some((cmd, arg)) = parse_command(&content) {
                     execute_command(app, client, &cmd, arg);
                 } else {
-                    app.set_toast("Unknown command".to_string());
+                    app.warn_toast(t("toast.unknown_command"));
                 }
-                app.input.clear();
-                app.cursor = 0;
-                app.attachments.clear();
                 app.mark_dirty();
                 return;
             }
@@ -292,14 +852,15 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
                     json!(app
                         .attachments
                         .iter()
-                        .map(|a| json!({
-                            "type": "image",
-                            "data": a.data,
-                            "mime": a.mime
-                        }))
+                        .map(attachment_payload)
                         .collect::<Vec<_>>())
                 };
                 let payload = json!({ "content": text_content, "attachments": attachments });
+                let repeated = app
+                    .last_submit
+                    .as_ref()
+                    .is_some_and(|(last, at)| *last == payload && at.elapsed() < SEND_DEBOUNCE);
+                app.last_submit = Some((payload.clone(), Instant::now()));
                 app.input.clear();
                 app.cursor = 0;
                 app.attachments.clear();
@@ -307,29 +868,22 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
                 app.auto_scroll = true;
                 app.scroll_from_bottom = 0;
                 app.mark_dirty();
-                let client = client.clone();
-                std::thread::spawn(move || {
-                    let _ = client.lock().unwrap().call("send_message", payload);
-                });
+                if !repeated {
+                    send_message(app, client, payload);
+                }
+            } else if app
+                .last_submit
+                .as_ref()
+                .is_none_or(|(_, at)| at.elapsed() >= SEND_DEBOUNCE)
+            {
+                open_diff_pager(app);
             }
         }
         KeyCode::Backspace => {
             if let Some((new_value, new_cursor)) = handle_backspace(&app.input, app.cursor) {
-                let removed_images = app
-                    .input
-                    .chars()
-                    .filter(|&c| c == IMAGE_MARKER)
-                    .count()
-                    .saturating_sub(new_value.chars().filter(|&c| c == IMAGE_MARKER).count());
+                drop_attachments_in_range(app, new_cursor, app.cursor);
                 app.input = new_value;
                 app.cursor = new_cursor;
-                if removed_images > 0 && !app.attachments.is_empty() {
-                    for _ in 0..removed_images {
-                        if !app.attachments.is_empty() {
-                            app.attachments.pop();
-                        }
-                    }
-                }
                 app.mark_dirty();
             }
         }
@@ -347,31 +901,33 @@ pub fn handle_key(app: &mut App, key: KeyEvent, client: &Arc<Mutex<BackendClient
         }
         KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             match read_clipboard_image() {
-                ClipboardImageResult::Image(data) => {
-                    app.input.insert(app.cursor, IMAGE_MARKER);
-                    app.cursor += IMAGE_MARKER.len_utf8();
-                    app.attachments.push(crate::app::AttachmentUpload {
-                        data,
-                        mime: "image/png".to_string(),
-                    });
-                    app.set_toast("Image attached".to_string());
-                    app.mark_dirty();
+                ClipboardImageResult::Image { data, size } => {
+                    add_attachment(
+                        app,
+                        AttachmentUpload {
+                            id: 0,
+                            kind: AttachmentKind::Image,
+                            name: "clipboard.png".to_string(),
+                            size,
+                            data,
+                            mime: "image/png".to_string(),
+                        },
+                    );
+                    app.set_toast(t("toast.image_attached"));
                 }
                 ClipboardImageResult::TooLarge => {
-                    app.set_toast("Image too large (max 50MB)".to_string());
+                    app.error_toast(t("toast.image_too_large"));
                     app.mark_dirty();
                 }
                 ClipboardImageResult::ConversionError => {
-                    app.set_toast("Failed to process clipboard image".to_string());
+                    app.error_toast(t("toast.image_failed"));
                     app.mark_dirty();
                 }
                 ClipboardImageResult::NotAvailable => {}
             }
         }
         KeyCode::Char(ch) => {
-            if !key.modifiers.contains(KeyModifiers::CONTROL)
-                && !key.modifiers.contains(KeyModifiers::ALT)
-            {
+            if is_text_input(key.modifiers) {
                 app.input.insert(app.cursor, ch);
                 app.cursor += ch.len_utf8();
                 app.mark_dirty();
@@ -438,9 +994,7 @@ pub fn handle_overlay_keys(
                     app.command_offset = 0;
                 }
                 KeyCode::Char(ch) => {
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT)
-                    {
+                    if is_text_input(key.modifiers) {
                         app.command_query.push(ch);
                         app.command_selected = 0;
                         app.command_offset = 0;
@@ -467,7 +1021,7 @@ pub fn handle_overlay_keys(
         UiMode::FileMention => {
             let query = file_query_from_input(&app.input, app.cursor);
             ensure_file_index(app);
-            let results = crate::app::filter_files(&app.file_index, &query, 10);
+            let results = app.mention_matches(&query);
             match key.code {
                 KeyCode::Esc => app.mode = UiMode::Normal,
                 KeyCode::Up => app.file_selected = app.file_selected.saturating_sub(1),
@@ -477,11 +1031,26 @@ pub fn handle_overlay_keys(
                     }
                 }
                 KeyCode::Tab | KeyCode::Enter => {
-                    if let Some(file) = results.get(app.file_selected) {
-                        insert_file_mention(app, &file.relative_path);
+                    if let Some(found) = results.get(app.file_selected) {
+                        insert_file_mention(app, &found.target.insert_text());
                     }
                     app.mode = UiMode::Normal;
                 }
+                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    match results.get(app.file_selected).map(|found| &found.target) {
+                        Some(MentionTarget::File(FileResult {
+                            relative_path: path,
+                            is_dir: false,
+                        }))
+                        | Some(MentionTarget::Related(RelatedCode { path, .. })) => {
+                            let path = path.clone();
+                            remove_mention_query(app);
+                            pin_file(app, &path);
+                            app.mode = UiMode::Normal;
+                        }
+                        _ => app.warn_toast(t("toast.pin_not_file")),
+                    }
+                }
                 KeyCode::Backspace => {
                     if app.cursor > 0 {
                         let before = &app.input[..app.cursor];
@@ -491,16 +1060,16 @@ pub fn handle_overlay_keys(
                             app.cursor -= byte_len;
                         }
                     }
+                    app.mention_typed_at = Instant::now();
                     if !app.input.contains('@') {
                         app.mode = UiMode::Normal;
                     }
                 }
                 KeyCode::Char(ch) => {
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT)
-                    {
+                    if is_text_input(key.modifiers) {
                         app.input.insert(app.cursor, ch);
                         app.cursor += ch.len_utf8();
+                        app.mention_typed_at = Instant::now();
                     }
                 }
                 _ => {}
@@ -509,8 +1078,7 @@ pub fn handle_overlay_keys(
             return true;
         }
         UiMode::ModelPicker => {
-            let filtered = filter_models(&app.model_entries, &app.model_query);
-            let filtered = sort_models_by_provider(&filtered);
+            let filtered = app.visible_models();
             let total = filtered.len() + 1; // custom row
             match key.code {
                 KeyCode::Esc => {
@@ -524,6 +1092,28 @@ pub fn handle_overlay_keys(
                         app.model_selected += 1;
                     }
                 }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some(entry) = filtered.get(app.model_selected) {
+                        let starred = app.model_prefs.toggle_favorite(&entry.id);
+                        app.set_toast(format!(
+                            "{} {}",
+                            if starred { "Starred" } else { "Unstarred" },
+                            entry.name
+                        ));
+                        // Keep the cursor on the same model as the rows move.
+                        app.model_selected = app
+                            .visible_models()
+                            .iter()
+                            .position(|e| e.id == entry.id)
+                            .unwrap_or(0);
+                    }
+                }
+                KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some(entry) = filtered.get(app.model_selected) {
+                        app.model_test = Some((entry.id.clone(), None));
+                        app.model_test_requested = Some(entry.clone());
+                    }
+                }
                 KeyCode::PageUp => {
                     app.model_selected = app.model_selected.saturating_sub(10);
                 }
@@ -538,6 +1128,8 @@ pub fn handle_overlay_keys(
                             .lock()
                             .unwrap()
                             .call("set_model", json!({ "model": entry.id }));
+                        app.model_source = ModelSource::Picker;
+                        app.model_prefs.record_use(&entry.id);
                         if let Some(provider) = &entry.provider_key {
                             let _ = client
                                 .lock()
@@ -549,12 +1141,23 @@ pub fn handle_overlay_keys(
                                 .unwrap()
                                 .call("set_provider", json!({ "provider": null }));
                         }
-                        let next_reasoning = if entry.reasoning.unwrap_or(false) {
-                            "medium"
-                        } else {
-                            "off"
+                        let next_reasoning = match entry.reasoning {
+                            Some(false) => "off".to_string(),
+                            reasoning => app
+                                .model_prefs
+                                .reasoning
+                                .get(&entry.id)
+                                .cloned()
+                                .unwrap_or_else(|| {
+                                    if reasoning == Some(true) {
+                                        "medium"
+                                    } else {
+                                        "off"
+                                    }
+                                    .to_string()
+                                }),
                         };
-                        app.reasoning_effort = next_reasoning.to_string();
+                        app.reasoning_effort = next_reasoning.clone();
                         let _ = client.lock().unwrap().call(
                             "set_reasoning_effort",
                             json!({ "reasoningEffort": next_reasoning }),
@@ -570,9 +1173,7 @@ pub fn handle_overlay_keys(
                     }
                 }
                 KeyCode::Char(ch) => {
-                    if !key.modifiers.contains(KeyModifiers::CONTROL)
-                        && !key.modifiers.contains(KeyModifiers::ALT)
-                    {
+                    if is_text_input(key.modifiers) {
                         if app.custom_model_mode {
                             app.custom_model_input.push(ch);
                         } else {
@@ -598,15 +1199,23 @@ pub fn handle_overlay_keys(
                     .lock()
                     .unwrap()
                     .call("set_model", json!({ "model": model }));
+                app.model_source = ModelSource::Picker;
+                app.model_prefs.record_use(model);
                 let _ = client
                     .lock()
                     .unwrap()
                     .call("set_provider", json!({ "provider": null }));
-                app.reasoning_effort = "off".to_string();
+                let effort = app
+                    .model_prefs
+                    .reasoning
+                    .get(model)
+                    .cloned()
+                    .unwrap_or_else(|| "off".to_string());
+                app.reasoning_effort = effort.clone();
                 let _ = client
                     .lock()
                     .unwrap()
-                    .call("set_reasoning_effort", json!({ "reasoningEffort": "off" }));
+                    .call("set_reasoning_effort", json!({ "reasoningEffort": effort }));
                 app.mode = UiMode::Normal;
                 app.custom_model_mode = false;
                 app.custom_model_input.clear();
@@ -614,6 +1223,174 @@ pub fn handle_overlay_keys(
             app.mark_dirty();
             return true;
         }
+        UiMode::Pager => {
+            if let Some(pager) = app.pager.as_mut() {
+                let max = pager.max_scroll();
+                match key.code {
+                    KeyCode::Char('n' | ']') if !pager.hunks.is_empty() => pager.select_hunk(1),
+                    KeyCode::Char('N' | '[') if !pager.hunks.is_empty() => pager.select_hunk(-1),
+                    KeyCode::Char('y') if pager.hunk.is_some() => {
+                        let patch = pager.selected_hunk().map(|h| h.patch.clone());
+                        if let Some(patch) = patch {
+                            match copy_text(&patch, app.clipboard) {
+                                Ok(()) => app.set_toast(t("toast.hunk_copied")),
+                                Err(e) => {
+                                    app.error_toast(tf("toast.copy_failed", &[("error", &e)]))
+                                }
+                            }
+                        }
+                    }
+                    KeyCode::Char('r') if pager.hunk.is_some() => revert_selected_hunk(app),
+                    KeyCode::Char('c') if pager.hunk.is_some() => {
+                        if let Some(hunk) = pager.selected_hunk().cloned() {
+                            comment_on_hunk(app, &hunk);
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        pager.scroll = pager.scroll.saturating_sub(1)
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        pager.scroll = (pager.scroll + 1).min(max)
+                    }
+                    KeyCode::PageUp => pager.scroll = pager.scroll.saturating_sub(PAGER_PAGE),
+                    KeyCode::PageDown | KeyCode::Char(' ') => {
+                        pager.scroll = (pager.scroll + PAGER_PAGE).min(max)
+                    }
+                    KeyCode::Char('g') | KeyCode::Home => pager.scroll = 0,
+                    KeyCode::Char('G') | KeyCode::End => pager.scroll = max,
+                    KeyCode::Char('q') | KeyCode::Enter => app.mode = UiMode::Normal,
+                    _ => {}
+                }
+            } else {
+                app.mode = UiMode::Normal;
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::TemplatePicker => {
+            match key.code {
+                KeyCode::Up => app.template_selected = app.template_selected.saturating_sub(1),
+                KeyCode::Down => {
+                    if app.template_selected + 1 < app.templates.len() {
+                        app.template_selected += 1;
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some(template) = app.templates.get(app.template_selected).cloned() {
+                        apply_template(app, &template, "");
+                    }
+                }
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::Bookmarks => {
+            match key.code {
+                KeyCode::Up => app.bookmark_selected = app.bookmark_selected.saturating_sub(1),
+                KeyCode::Down => {
+                    if app.bookmark_selected + 1 < app.bookmarks.len() {
+                        app.bookmark_selected += 1;
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some(bookmark) = app.bookmarks.get(app.bookmark_selected) {
+                        let event_id = bookmark.event_id.clone();
+                        let mut shown = app.timeline_anchors.iter().any(|(_, id)| *id == event_id);
+                        // Older than the resident window: the anchors catch
+                        // up on the next frame.
+                        if !shown && app.state.earlier_events > 0 {
+                            load_all_earlier(app, client);
+                            shown = app.state.timeline_events.iter().any(|e| e.id == event_id);
+                        }
+                        if shown {
+                            app.jump_to_event = Some(event_id);
+                            app.mode = UiMode::Normal;
+                        } else {
+                            app.warn_toast(t("toast.bookmark_hidden"));
+                        }
+                    }
+                }
+                KeyCode::Char('d') | KeyCode::Delete => {
+                    if app.bookmark_selected < app.bookmarks.len() {
+                        let bookmark = app.bookmarks.remove(app.bookmark_selected);
+                        let _ = client
+                            .lock()
+                            .unwrap()
+                            .call("remove_bookmark", json!({ "id": bookmark.id }));
+                        app.bookmark_selected = app
+                            .bookmark_selected
+                            .min(app.bookmarks.len().saturating_sub(1));
+                        if app.bookmarks.is_empty() {
+                            app.mode = UiMode::Normal;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::TrustPrompt => {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y') => trust_project(app, client),
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    app.mode = after_trust_prompt(app);
+                    app.warn_toast(t("toast.trust_restricted"));
+                }
+                KeyCode::Char('q') => app.should_quit = true,
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::Recovery => {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y') => resume_interrupted(app),
+                KeyCode::Char('n') => dismiss_interrupted(app, client),
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::Telemetry => {
+            match key.code {
+                KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.mode = UiMode::Normal;
+                }
+                KeyCode::Char('q') | KeyCode::Enter => app.mode = UiMode::Normal,
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::Notifications => {
+            let max_offset = app.notifications.len().saturating_sub(NOTIFICATIONS_PAGE);
+            match key.code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.notifications_offset = (app.notifications_offset + 1).min(max_offset);
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.notifications_offset = app.notifications_offset.saturating_sub(1);
+                }
+                KeyCode::PageUp => {
+                    app.notifications_offset =
+                        (app.notifications_offset + NOTIFICATIONS_PAGE).min(max_offset);
+                }
+                KeyCode::PageDown => {
+                    app.notifications_offset =
+                        app.notifications_offset.saturating_sub(NOTIFICATIONS_PAGE);
+                }
+                KeyCode::Char('c') => {
+                    app.notifications.clear();
+                    app.notifications_offset = 0;
+                }
+                KeyCode::Enter | KeyCode::Char('q') => app.mode = UiMode::Normal,
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
         UiMode::SessionHistory => {
             if app.session_rename_active {
                 match key.code {
@@ -639,9 +1416,7 @@ pub fn handle_overlay_keys(
                         app.session_rename_input.clear();
                     }
                     KeyCode::Char(ch) => {
-                        if !key.modifiers.contains(KeyModifiers::CONTROL)
-                            && !key.modifiers.contains(KeyModifiers::ALT)
-                        {
+                        if is_text_input(key.modifiers) {
                             app.session_rename_input.push(ch);
                         }
                     }
@@ -687,11 +1462,14 @@ pub fn handle_overlay_keys(
                     }
                 }
                 KeyCode::Enter => {
-                    if let Some(sess) = app.session_list.get(app.session_selected) {
-                        let _ = client
+                    if let Some(sess) = app.session_list.get(app.session_selected).cloned() {
+                        let loaded = client
                             .lock()
                             .unwrap()
                             .call("load_session", json!({ "sessionId": sess.id }));
+                        if loaded.is_err() {
+                            load_saved_session(app, &sess.id);
+                        }
                     }
                     app.mode = UiMode::Normal;
                 }
@@ -716,7 +1494,6 @@ pub fn handle_overlay_keys(
         }
         UiMode::QuestionPrompt => {
             if let Some(q) = &mut app.question {
-                let total_options = q.options.len() + if q.allow_custom { 1 } else { 0 };
                 match key.code {
                     KeyCode::Esc => {
                         if q.custom_active {
@@ -731,24 +1508,19 @@ pub fn handle_overlay_keys(
                             app.mode = UiMode::Normal;
                         }
                     }
-                    KeyCode::Up => {
-                        if !q.custom_active {
-                            q.focused_index = q.focused_index.saturating_sub(1);
-                        }
+                    KeyCode::Up if !q.custom_active => q.move_focus(-1),
+                    KeyCode::Down if !q.custom_active => q.move_focus(1),
+                    KeyCode::PageUp if !q.custom_active => {
+                        q.move_focus(-(QUESTION_VIEWPORT as isize))
                     }
-                    KeyCode::Down => {
-                        if !q.custom_active && total_options > 0 {
-                            q.focused_index =
-                                (q.focused_index + 1).min(total_options.saturating_sub(1));
-                        }
+                    KeyCode::PageDown if !q.custom_active => {
+                        q.move_focus(QUESTION_VIEWPORT as isize)
                     }
-                    KeyCode::Char(' ') => {
-                        if q.allow_multiple && !q.custom_active {
-                            if q.focused_index < q.options.len() {
-                                select_option(q, q.focused_index);
-                            } else if q.allow_custom {
-                                q.custom_active = true;
-                            }
+                    KeyCode::Char(' ') if q.allow_multiple && !q.custom_active => {
+                        if q.focused_index < q.options.len() {
+                            select_option(q, q.focused_index);
+                        } else if q.allow_custom {
+                            q.custom_active = true;
                         }
                     }
                     KeyCode::Enter => {
@@ -794,16 +1566,19 @@ pub fn handle_overlay_keys(
                     KeyCode::Backspace => {
                         if q.custom_active {
                             q.custom_input.pop();
+                        } else if !q.filter.is_empty() {
+                            let mut filter = q.filter.clone();
+                            filter.pop();
+                            q.set_filter(filter);
                         }
                     }
+                    KeyCode::Char(_)
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            || key.modifiers.contains(KeyModifiers::ALT) => {}
                     KeyCode::Char(ch) => {
                         if q.custom_active {
-                            if !key.modifiers.contains(KeyModifiers::CONTROL)
-                                && !key.modifiers.contains(KeyModifiers::ALT)
-                            {
-                                q.custom_input.push(ch);
-                            }
-                        } else if let Some(d) = ch.to_digit(10) {
+                            q.custom_input.push(ch);
+                        } else if let Some(d) = ch.to_digit(10).filter(|_| q.filter.is_empty()) {
                             let idx = d.saturating_sub(1) as usize;
                             if idx < q.options.len() {
                                 let answer = q.options[idx].label.clone();
@@ -814,6 +1589,9 @@ pub fn handle_overlay_keys(
                                 app.question = None;
                                 app.mode = UiMode::Normal;
                             }
+                        } else {
+                            let filter = format!("{}{}", q.filter, ch);
+                            q.set_filter(filter);
                         }
                     }
                     _ => {}
@@ -823,13 +1601,85 @@ pub fn handle_overlay_keys(
             return true;
         }
         UiMode::PlanActions => {
+            let count = PLAN_ACTION_OPTIONS.len();
+            let plan_lines = app.plan_content.as_deref().map_or(0, |p| p.lines().count());
             match key.code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.plan_scroll = app.plan_scroll.saturating_sub(1);
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.plan_scroll = (app.plan_scroll + 1).min(plan_lines);
+                }
+                KeyCode::PageUp => app.plan_scroll = app.plan_scroll.saturating_sub(10),
+                KeyCode::PageDown => app.plan_scroll = (app.plan_scroll + 10).min(plan_lines),
+                KeyCode::Left | KeyCode::BackTab => {
+                    app.plan_action_selected = (app.plan_action_selected + count - 1) % count;
+                }
+                KeyCode::Right | KeyCode::Tab => {
+                    app.plan_action_selected = (app.plan_action_selected + 1) % count;
+                }
+                KeyCode::Enter => resolve_plan_action(app, client, app.plan_action_selected),
+                KeyCode::Char('a') => resolve_plan_action(app, client, 0),
+                KeyCode::Char('e') => resolve_plan_action(app, client, 1),
+                KeyCode::Esc => resolve_plan_action(app, client, 2),
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::PasteConfirm => {
+            let count = PASTE_CONFIRM_OPTIONS.len();
+            match key.code {
+                KeyCode::Up | KeyCode::BackTab => {
+                    app.paste_confirm_selected = (app.paste_confirm_selected + count - 1) % count;
+                }
+                KeyCode::Down | KeyCode::Tab => {
+                    app.paste_confirm_selected = (app.paste_confirm_selected + 1) % count;
+                }
+                KeyCode::Enter => resolve_pending_paste(app, app.paste_confirm_selected),
+                KeyCode::Char('a') => resolve_pending_paste(app, 0),
+                KeyCode::Char('i') => resolve_pending_paste(app, 1),
+                KeyCode::Char('c') => resolve_pending_paste(app, 2),
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::AgentPicker => {
+            let names = app.agent_names();
+            match key.code {
+                KeyCode::Esc => app.mode = UiMode::Normal,
+                KeyCode::Up => app.agent_selected = app.agent_selected.saturating_sub(1),
+                KeyCode::Down => {
+                    if app.agent_selected + 1 < names.len() {
+                        app.agent_selected += 1;
+                    }
+                }
                 KeyCode::Enter => {
-                    let _ = client.lock().unwrap().call("send_message", json!({ "content": "The plan is approved. Read the plan file and start implementing.", "agentOverride": "build", "options": { "buildSwitch": true } }));
+                    if let Some(name) = names.get(app.agent_selected) {
+                        switch_agent(app, client, name);
+                    }
                     app.mode = UiMode::Normal;
                 }
-                KeyCode::Esc => {
-                    let _ = client.lock().unwrap().call("reset_plan_exit", json!({}));
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::ReasoningPicker => {
+            let levels = app.reasoning_levels();
+            match key.code {
+                KeyCode::Esc => app.mode = UiMode::Normal,
+                KeyCode::Up => app.reasoning_selected = app.reasoning_selected.saturating_sub(1),
+                KeyCode::Down => {
+                    if app.reasoning_selected + 1 < levels.len() {
+                        app.reasoning_selected += 1;
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some(effort) = levels.get(app.reasoning_selected) {
+                        set_reasoning_effort(app, client, effort);
+                    }
                     app.mode = UiMode::Normal;
                 }
                 _ => {}
@@ -837,6 +1687,32 @@ pub fn handle_overlay_keys(
             app.mark_dirty();
             return true;
         }
+        UiMode::TodoFocus => {
+            handle_todo_keys(app, key, client);
+            app.mark_dirty();
+            return true;
+        }
+        UiMode::FileSidebar => {
+            let count = app.touched_files().len();
+            match key.code {
+                KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    toggle_sidebar(app);
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.sidebar_selected = app.sidebar_selected.saturating_sub(1);
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    if app.sidebar_selected + 1 < count {
+                        app.sidebar_selected += 1;
+                    }
+                }
+                KeyCode::Enter => open_file_preview(app),
+                KeyCode::Esc | KeyCode::Tab => app.mode = UiMode::Normal,
+                _ => {}
+            }
+            app.mark_dirty();
+            return true;
+        }
         UiMode::HelpAbout => {
             if matches!(key.code, KeyCode::Esc | KeyCode::Enter) {
                 app.mode = UiMode::Normal;
@@ -852,7 +1728,7 @@ pub fn handle_overlay_keys(
 const MAX_CLIPBOARD_IMAGE_BYTES: usize = 50 * 1024 * 1024; // 50MB
 
 enum ClipboardImageResult {
-    Image(String),
+    Image { data: String, size: usize },
     TooLarge,
     NotAvailable,
     ConversionError,
@@ -882,17 +1758,27 @@ fn read_clipboard_image() -> ClipboardImageResult {
     };
 
     let dynamic = image::DynamicImage::ImageRgba8(rgba_img);
-    let mut buf = std::io::Cursor::new(Vec::new());
-    if dynamic.write_to(&mut buf, image::ImageFormat::Png).is_err() {
+    let Some(png) = encode_png(&dynamic) else {
         return ClipboardImageResult::ConversionError;
+    };
+    ClipboardImageResult::Image {
+        size: png.len(),
+        data: base64::engine::general_purpose::STANDARD.encode(png),
     }
-
-    ClipboardImageResult::Image(base64::engine::general_purpose::STANDARD.encode(buf.into_inner()))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{handle_key, handle_paste};
+    use crate::app::{
+        bookmark_focused, open_bookmarks, open_diff_pager, App, ModelEntry, ModelPrefs, UiMode,
+    };
+    use crate::backend::{BackendClient, ChatState, TimelineEvent};
     use crate::constants::IMAGE_MARKER;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use serde_json::json;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
 
     /// Simulate character insertion (mirrors fixed handle_key Char logic)
     fn insert_char(input: &mut String, cursor: &mut usize, ch: char) {
@@ -1168,4 +2054,246 @@ mod tests {
         move_right(&input, &mut cursor);
         assert_eq!(cursor, 3); // stays at end
     }
+
+    /// An app rooted in a fresh project directory under the system temp dir.
+    fn test_app(name: &str) -> (App, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("stratuscode-input-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state: ChatState = serde_json::from_value(json!({
+            "messages": [],
+            "isLoading": false,
+            "timelineEvents": [],
+            "contextUsage": { "used": 0, "limit": 0, "percent": 0 },
+            "tokens": { "input": 0, "output": 0 },
+            "planExitProposed": false,
+            "agent": "build",
+        }))
+        .unwrap();
+        let app = App::new(state, dir.display().to_string(), "test-model".to_string());
+        (app, dir)
+    }
+
+    #[test]
+    fn paste_confirms_at_threshold() {
+        let (mut app, dir) = test_app("threshold");
+        app.paste_confirm_lines = 3;
+
+        handle_paste(&mut app, "one\ntwo".to_string());
+        assert!(matches!(app.mode, UiMode::Normal));
+        assert!(app.pending_paste.is_none());
+        assert!(app.input.contains("one\ntwo"));
+
+        app.input.clear();
+        app.cursor = 0;
+        handle_paste(&mut app, "one\ntwo\nthree".to_string());
+        assert!(matches!(app.mode, UiMode::PasteConfirm));
+        assert_eq!(app.pending_paste.as_deref(), Some("one\ntwo\nthree"));
+        assert!(app.input.is_empty());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn pasted_file_paths_attach_before_threshold() {
+        let (mut app, dir) = test_app("file-paths");
+        app.paste_confirm_lines = 1;
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "hello").unwrap();
+
+        handle_paste(&mut app, file.display().to_string());
+        assert!(matches!(app.mode, UiMode::Normal));
+        assert!(app.pending_paste.is_none());
+        assert_eq!(app.attachments.len(), 1);
+        assert_eq!(app.input, IMAGE_MARKER.to_string());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn shrinking_model_list_keeps_selection_in_range() {
+        let (mut app, dir) = test_app("models");
+        app.model_prefs = ModelPrefs::default();
+        let models = |ids: &[&str]| -> Vec<ModelEntry> {
+            ids.iter()
+                .map(|id| ModelEntry {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    free: None,
+                    provider_key: None,
+                    group: "openai".to_string(),
+                    reasoning: None,
+                })
+                .collect()
+        };
+        app.apply_model_list(models(&["a", "b", "c"]));
+        app.model_selected = 2;
+        app.apply_model_list(models(&["x", "y"]));
+        assert_eq!(app.model_selected, 1);
+        app.apply_model_list(Vec::new());
+        assert_eq!(app.model_selected, 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// A stand-in backend that logs each request to `log` and answers
+    /// `list_bookmarks` with one bookmark and everything else with `{}`.
+    fn fake_backend(log: &Path) -> Arc<Mutex<BackendClient>> {
+        let script = r#"while IFS= read -r line; do
+  printf '%s\n' "$line" >> "$1"
+  id=$(printf '%s' "$line" | grep -o '"id":[0-9][0-9]*' | head -n 1 | cut -d: -f2)
+  case "$line" in
+    *'"list_bookmarks"'*) result='[{"id":"b1","eventId":"a1","label":"Found the bug","createdAt":0}]' ;;
+    *) result='{}' ;;
+  esac
+  printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$result"
+done"#;
+        let args = [
+            "-c".to_string(),
+            script.to_string(),
+            "fake-backend".to_string(),
+            log.display().to_string(),
+        ];
+        let (client, _notifications) = BackendClient::spawn("sh", &args, &[]).unwrap();
+        Arc::new(Mutex::new(client))
+    }
+
+    fn requests(log: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(log)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bookmarks_add_list_and_remove() {
+        let (mut app, dir) = test_app("bookmarks");
+        let log = dir.join("requests.jsonl");
+        let client = fake_backend(&log);
+
+        bookmark_focused(&mut app, &client);
+        assert!(requests(&log).is_empty());
+
+        let event: TimelineEvent = serde_json::from_value(json!({
+            "id": "a1",
+            "sessionId": "s1",
+            "createdAt": 0,
+            "kind": "assistant",
+            "content": "\n  Found the bug\nin the parser",
+        }))
+        .unwrap();
+        app.state.session_id = Some("s1".to_string());
+        app.state.timeline_events.push(event);
+        app.focused_event = Some("a1".to_string());
+        handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL),
+            &client,
+        );
+        let sent = requests(&log);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["method"], "add_bookmark");
+        assert_eq!(
+            sent[0]["params"],
+            json!({ "sessionId": "s1", "eventId": "a1", "label": "Found the bug" })
+        );
+
+        open_bookmarks(&mut app, &client);
+        assert_eq!(app.mode, UiMode::Bookmarks);
+        assert_eq!(app.bookmarks.len(), 1);
+        assert_eq!(app.bookmarks[0].event_id, "a1");
+
+        handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE),
+            &client,
+        );
+        assert!(app.bookmarks.is_empty());
+        assert_eq!(app.mode, UiMode::Normal);
+        let sent = requests(&log);
+        assert_eq!(sent.last().unwrap()["method"], "remove_bookmark");
+        assert_eq!(sent.last().unwrap()["params"], json!({ "id": "b1" }));
+
+        client.lock().unwrap().shutdown();
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn diff_pager_opens_the_focused_messages_diff() {
+        let (mut app, dir) = test_app("focused-diff");
+        app.diff_max_lines = 1;
+        for (n, file) in ["a.rs", "b.rs"].iter().enumerate() {
+            let diff = format!("--- a/{file}\n+++ b/{file}\n@@ -1 +1,2 @@\n-x\n+y\n+z\n");
+            for event in [
+                json!({ "id": format!("u{n}"), "kind": "user", "content": "change it" }),
+                json!({
+                    "id": format!("c{n}"), "kind": "tool_call", "toolCallId": format!("t{n}"),
+                    "toolName": "edit", "content": json!({ "file_path": file }).to_string(),
+                }),
+                json!({
+                    "id": format!("r{n}"), "kind": "tool_result", "toolCallId": format!("t{n}"),
+                    "toolName": "edit", "content": json!({ "diff": diff }).to_string(),
+                }),
+            ] {
+                let mut event = event;
+                event["sessionId"] = json!("s1");
+                event["createdAt"] = json!(0);
+                let event: TimelineEvent = serde_json::from_value(event).unwrap();
+                app.state.timeline_events.push(event);
+            }
+        }
+
+        app.focused_event = Some("u0".to_string());
+        open_diff_pager(&mut app);
+        assert!(app.pager.take().unwrap().title.contains("a.rs"));
+
+        app.focused_event = None;
+        open_diff_pager(&mut app);
+        assert!(app.pager.take().unwrap().title.contains("b.rs"));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn touched_files_cover_events_outside_the_window() {
+        let (mut app, dir) = test_app("touched-earlier");
+        let edit = |n: usize, file: &str| -> Vec<TimelineEvent> {
+            let diff = format!("--- a/{file}\n+++ b/{file}\n@@ -1 +1 @@\n-x\n+y\n");
+            [
+                json!({
+                    "id": format!("c{n}"), "kind": "tool_call", "toolCallId": format!("t{n}"),
+                    "toolName": "edit", "status": "completed",
+                    "content": json!({ "file_path": file }).to_string(),
+                }),
+                json!({
+                    "id": format!("r{n}"), "kind": "tool_result", "toolCallId": format!("t{n}"),
+                    "toolName": "edit", "content": json!({ "diff": diff }).to_string(),
+                }),
+            ]
+            .into_iter()
+            .map(|mut event| {
+                event["sessionId"] = json!("s1");
+                event["createdAt"] = json!(n);
+                serde_json::from_value(event).unwrap()
+            })
+            .collect()
+        };
+        let paths = |app: &mut App| -> Vec<String> {
+            app.touched_files().iter().map(|f| f.path.clone()).collect()
+        };
+
+        app.state.timeline_events = [edit(1, "a.rs"), edit(2, "b.rs")].concat();
+        app.state.earlier_events = 2;
+        assert_eq!(paths(&mut app), ["a.rs", "b.rs"]);
+        assert_eq!(app.earlier_tools_requested.take().as_deref(), Some("c1"));
+
+        app.set_earlier_tools("c1".to_string(), edit(0, "old.rs"));
+        assert_eq!(paths(&mut app), ["a.rs", "b.rs", "old.rs"]);
+
+        // a.rs's edit leaves the window; it's kept without another fetch.
+        let mut next = app.state.clone();
+        next.timeline_events = [edit(2, "b.rs"), edit(3, "c.rs")].concat();
+        next.earlier_events = 4;
+        app.update_state(next);
+        assert_eq!(paths(&mut app), ["a.rs", "b.rs", "c.rs", "old.rs"]);
+        assert_eq!(app.earlier_tools_requested, None);
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
@@ -0,0 +1,169 @@
+use crate::config::user_config_dir;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What the register typed after Alt+q or Alt+@ is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroAction {
+    Record,
+    Replay,
+}
+
+/// Recorded key sequences by register, kept in
+/// `$XDG_CONFIG_HOME/stratuscode/macros.json` so they follow the user across
+/// projects. Keys are stored as names like `ctrl+r` or `enter` so the file
+/// can be edited by hand.
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    pub registers: BTreeMap<char, Vec<KeyEvent>>,
+}
+
+fn macros_path() -> Option<PathBuf> {
+    Some(user_config_dir()?.join("macros.json"))
+}
+
+impl Macros {
+    /// Missing or unreadable files are empty; unknown key names are dropped.
+    pub fn load() -> Self {
+        let raw: BTreeMap<char, Vec<String>> = macros_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        let registers = raw
+            .into_iter()
+            .map(|(reg, keys)| (reg, keys.iter().filter_map(|k| parse_key(k)).collect()))
+            .collect();
+        Self { registers }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = macros_path().ok_or_else(|| std::io::Error::other("HOME is not set"))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let raw: BTreeMap<char, Vec<String>> = self
+            .registers
+            .iter()
+            .map(|(reg, keys)| (*reg, keys.iter().filter_map(key_name).collect()))
+            .collect();
+        let json = serde_json::to_string_pretty(&raw).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// Registers are single letters or digits, as in vim.
+pub fn is_register(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+}
+
+/// `ctrl+shift+p`, `enter`, `a`. `None` for keys a macro can't replay.
+pub fn key_name(key: &KeyEvent) -> Option<String> {
+    let code = match key.code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char('+') => "plus".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        _ => return None,
+    };
+    let mut name = String::new();
+    for (modifier, label) in [
+        (KeyModifiers::CONTROL, "ctrl+"),
+        (KeyModifiers::ALT, "alt+"),
+        (KeyModifiers::SHIFT, "shift+"),
+    ] {
+        if key.modifiers.contains(modifier) {
+            name.push_str(label);
+        }
+    }
+    name.push_str(&code);
+    Some(name)
+}
+
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let (modifier, len) = if lower.starts_with("ctrl+") && rest.len() > 5 {
+            (KeyModifiers::CONTROL, 5)
+        } else if lower.starts_with("alt+") && rest.len() > 4 {
+            (KeyModifiers::ALT, 4)
+        } else if lower.starts_with("shift+") && rest.len() > 6 {
+            (KeyModifiers::SHIFT, 6)
+        } else {
+            break;
+        };
+        modifiers |= modifier;
+        rest = &rest[len..];
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "plus" => KeyCode::Char('+'),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_round_trip() {
+        for key in [
+            KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('+'), KeyModifiers::NONE),
+            KeyEvent::new(
+                KeyCode::Char('p'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            ),
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::F(5), KeyModifiers::ALT),
+        ] {
+            let name = key_name(&key).unwrap();
+            assert_eq!(parse_key(&name), Some(key), "{}", name);
+        }
+        assert_eq!(
+            key_name(&KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)).as_deref(),
+            Some("ctrl+r")
+        );
+        assert_eq!(parse_key("bogus"), None);
+        assert_eq!(parse_key("fx"), None);
+    }
+}
@@ -1,41 +1,152 @@
+#![allow(clippy::collapsible_match)]
+
+use agents::load_agents;
+use answers::{parse_answer, AutoAnswer, PresetAnswers, Resolution};
 use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand};
-use crossterm::event::{self, DisableBracketedPaste, EnableBracketedPaste, Event};
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind,
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 use crossterm::execute;
+use crossterm::style::Print;
 use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen, SetTitle,
 };
+use ignore::gitignore::Gitignore;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
 use ratatui::backend::CrosstermBackend;
-use ratatui::Terminal;
+use ratatui::{Terminal, TerminalOptions, Viewport};
 use serde_json::json;
-use std::io;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
+mod acp;
+mod agents;
+mod answers;
 mod app;
-mod backend;
+mod attachments;
+mod changes;
+mod clipboard;
 mod commands;
-mod constants;
+mod eval;
+mod event_socket;
+mod export;
+mod import;
 mod input;
+mod macros;
+mod perf;
+mod redact;
+mod sandbox;
+mod symbols;
+mod templates;
+mod trust;
 mod ui;
+mod usage;
 
-use app::{App, PendingQuestion, QuestionState, SessionInfo, TodoCounts, TodoItem, UiMode};
-use backend::{BackendClient, ChatState, TimelineEvent};
-use constants::SPINNER_FRAMES;
-use input::{handle_key, handle_paste};
-use ui::{extract_diff_summary, format_tool_args, render_ui, tool_icon};
+use stratuscode_core::{backend, config, constants, env, i18n, render, store, text};
+
+use app::{
+    build_file_index, fetch_earlier, index_summary, parse_related_code, refresh_todos,
+    save_model_cache, whole_timeline, App, FileResult, IndexSignal, InterruptedSession, ModelEntry,
+    PendingQuestion, QuestionState, RelatedCode, ToastLevel, TodoCounts, TodoItem, UiMode,
+    EARLIER_PAGE, RELATED_DEBOUNCE, TIMELINE_WINDOW,
+};
+use attachments::{add_attachment, attachment_payload, format_size, url_attachment, webfetch_text};
+use backend::{
+    BackendClient, BackendNotification, ChatState, EventPage, PendingCall, SessionInfo,
+    TimelineEvent,
+};
+use commands::{commands_list, execute_command, parse_command};
+use config::{IndexConfig, ModelSource, ProjectConfig};
+use env::{resolve_env, EnvVar};
+use eval::{CaseResult, Suite};
+use event_socket::EventSocket;
+use export::{transcript_html, transcript_markdown};
+use import::ImportSource;
+use input::{add_send_context, handle_key, handle_paste, send_next_queued};
+use redact::{project_redactor, Redactor};
+use render::{extract_diff_summary, format_tool_args, tool_icon};
+use sandbox::{parse_sandbox, Sandbox};
+use store::SessionStore;
+use symbols::{build_symbol_index, SymbolEntry};
+use trust::{is_trusted, trust_folder};
+use ui::{flush_inline_scrollback, render_ui, status_segment, STATUS_SEGMENTS};
+use usage::TurnUsage;
 
 enum UiUpdate {
     Todos {
         list: Vec<TodoItem>,
         counts: TodoCounts,
     },
-    Question(QuestionState),
-    QuestionNone,
+    /// The first pending question, or `None` once nothing is pending.
+    Question(Option<QuestionState>),
+    QuestionPollFailed,
+    FileIndex {
+        files: Vec<FileResult>,
+        elapsed: Duration,
+        announce: bool,
+    },
+    SymbolIndex(Vec<SymbolEntry>),
+    Models(Result<Vec<ModelEntry>, String>),
+    ModelTest {
+        entry: ModelEntry,
+        result: Result<u64, String>,
+    },
+    Usage(Vec<TurnUsage>),
+    Resumed {
+        title: String,
+        result: Result<u64, String>,
+    },
+    UrlFetched {
+        url: String,
+        result: Result<String, String>,
+    },
+    RelatedCode {
+        query: String,
+        result: Result<Vec<RelatedCode>, String>,
+    },
+    EarlierEvents {
+        before: String,
+        result: Result<EventPage, String>,
+    },
+    EarlierTools {
+        before: String,
+        result: Result<EventPage, String>,
+    },
 }
 
+/// Everything the interactive loop reacts to, funnelled through one channel so
+/// the loop can block instead of polling.
+enum LoopEvent {
+    Terminal(Event),
+    Backend(BackendNotification),
+    Ui(UiUpdate),
+    Suspend,
+}
+
+const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+const QUESTION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long the indexer waits for a burst of filesystem events to settle.
+const INDEX_DEBOUNCE: Duration = Duration::from_millis(300);
+/// Gap between replayed macro keys, long enough for an overlay to open.
+const MACRO_KEY_INTERVAL: Duration = Duration::from_millis(30);
+/// Rows reserved for the live area in `--inline` mode.
+const INLINE_VIEWPORT_HEIGHT: u16 = 18;
+/// Upper bound on how long the loop sleeps when no timer is pending.
+const IDLE_WAKEUP: Duration = Duration::from_secs(1);
+
+/// Printed when the backend can't start; saved sessions are still readable.
+const OFFLINE_HINT: &str =
+    "Saved sessions can still be read with `stratuscode replay --list` and `stratuscode replay <id>`.";
+
 #[derive(Parser, Debug)]
 #[command(name = "stratuscode", version = env!("CARGO_PKG_VERSION"))]
 struct Cli {
@@ -45,17 +156,93 @@ struct Cli {
     #[arg(short, long, default_value = ".")]
     dir: String,
 
-    #[arg(short, long, default_value = "build")]
-    agent: String,
+    /// Agent to start with (default: build, or `agent` in .stratuscode/config.toml)
+    #[arg(short, long)]
+    agent: Option<String>,
 
+    /// Send one prompt and print the answer; a prompt starting with `/` runs
+    /// that slash command instead
     #[arg(long)]
     prompt: Option<String>,
 
+    /// Run a slash command headlessly and exit, e.g. `--command "export html
+    /// out.html"` (the leading `/` is optional)
+    #[arg(long = "command", value_name = "COMMAND", conflicts_with = "prompt")]
+    slash_command: Option<String>,
+
+    /// Answer a question asked during `--prompt` or `--command` without
+    /// waiting: the key matches its header, id or part of its text, the
+    /// value an option label or number (repeatable)
+    #[arg(long = "answer", value_name = "KEY=VALUE", value_parser = parse_answer)]
+    answers: Vec<(String, String)>,
+
+    /// Settle questions no `--answer` covers straight away, with their first
+    /// option or by skipping them
+    #[arg(long, value_enum)]
+    auto_answer: Option<AutoAnswer>,
+
     #[arg(long)]
     model: Option<String>,
 
     #[arg(long)]
     provider: Option<String>,
+
+    /// Limit how deep @ file completion indexes the project (overrides
+    /// `index.max_depth`; unlimited by default)
+    #[arg(long)]
+    index_depth: Option<usize>,
+
+    /// Render in the normal screen instead of the alternate screen, keeping
+    /// finished answers in terminal scrollback
+    #[arg(long)]
+    inline: bool,
+
+    /// Print the conversation to the terminal when quitting
+    #[arg(long)]
+    print_on_exit: bool,
+
+    /// A plain line-by-line prompt instead of the full-screen UI, for shell
+    /// buffers, logs and screen readers (used automatically when stdout
+    /// isn't a terminal)
+    #[arg(long)]
+    no_tui: bool,
+
+    /// Ask before accepting pastes with at least this many lines
+    #[arg(long, default_value_t = app::DEFAULT_PASTE_CONFIRM_LINES)]
+    paste_confirm_lines: usize,
+
+    /// Screen reader friendly output: no spinners or box drawing, state
+    /// changes announced as text (on by default when
+    /// `accessibility.screen_reader` is `on`, or `auto` and VoiceOver runs)
+    #[arg(long)]
+    accessible: bool,
+
+    /// Disable tools that change files or run commands (write, edit,
+    /// apply_patch, bash); toggle later with /readonly
+    #[arg(long)]
+    read_only: bool,
+
+    /// Treat the project folder as trusted for this --prompt/--command run,
+    /// e.g. in CI: its [env], hooks and verify apply. Without it an untrusted
+    /// folder runs read-only with project commands off
+    #[arg(long)]
+    trust: bool,
+
+    /// Run bash commands in a container with the project mounted:
+    /// `docker` or `docker:<image>` (default image ubuntu:24.04)
+    #[arg(long, value_name = "docker[:IMAGE]")]
+    sandbox: Option<String>,
+
+    /// Show a HUD with frame times, timeline size, line-cache hits and
+    /// backend latency (toggle with /perf; Ctrl+Shift+P only reaches the
+    /// app in terminals with the kitty keyboard protocol)
+    #[arg(long)]
+    perf: bool,
+
+    /// Mirror timeline events and state changes onto a Unix socket at this
+    /// path, one JSON object per line, for editor extensions
+    #[arg(long, value_name = "PATH")]
+    event_socket: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -67,6 +254,52 @@ enum Commands {
         #[arg(long)]
         provider: Option<String>,
     },
+    /// Print a saved session's transcript, read straight from the session
+    /// store without starting the backend
+    Replay {
+        /// Session id, slug or id prefix (default: the most recent session)
+        session: Option<String>,
+        /// List the project's saved sessions instead
+        #[arg(long)]
+        list: bool,
+    },
+    /// Convert a session from another agent CLI into a StratusCode session
+    Import {
+        #[arg(long, value_enum)]
+        from: ImportSource,
+        /// The session file to convert
+        path: PathBuf,
+    },
+    /// Run a suite of prompts against copies of fixture repos and check the
+    /// results, for regression-testing prompt and agent changes
+    Eval {
+        /// The suite file (TOML)
+        suite: PathBuf,
+        /// Write a JUnit XML report here
+        #[arg(long)]
+        junit: Option<PathBuf>,
+        /// Write a JSON report here
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Keep each case's working copy instead of deleting it
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Serve the Agent Client Protocol on stdio, so Zed and other ACP
+    /// editors can use StratusCode as their agent
+    Acp,
+    /// Write a saved session's transcript as markdown, without starting the
+    /// backend
+    Export {
+        /// Session id, slug or id prefix (default: the most recent session)
+        session: Option<String>,
+        /// File to write (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Write a standalone HTML page instead of markdown
+        #[arg(long)]
+        html: bool,
+    },
 }
 
 fn resolve_root() -> Result<PathBuf> {
@@ -102,18 +335,50 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
     let root = resolve_root()?;
 
-    if let Some(Commands::Auth {
-        key,
-        show,
-        provider,
-    }) = cli.command
-    {
-        return run_auth(&root, key, show, provider);
+    match &cli.command {
+        Some(Commands::Auth {
+            key,
+            show,
+            provider,
+        }) => return run_auth(&root, key.clone(), *show, provider.clone()),
+        Some(Commands::Import { from, path }) => return run_import(&root, &cli, *from, path),
+        Some(Commands::Replay { session, list }) => {
+            return run_replay(&cli, session.as_deref(), *list)
+        }
+        Some(Commands::Export {
+            session,
+            output,
+            html,
+        }) => return run_export(&cli, session.as_deref(), output.clone(), *html),
+        Some(Commands::Eval {
+            suite,
+            junit,
+            json,
+            keep,
+        }) => return run_eval(&root, &cli, suite, junit.as_deref(), json.as_deref(), *keep),
+        Some(Commands::Acp) => return acp::serve(|dir| start_acp_backend(&root, &cli, dir)),
+        None => {}
     }
 
+    if let Some(command) = cli.slash_command.clone() {
+        let line = format!("/{}", command.trim().trim_start_matches('/'));
+        return run_non_interactive(&root, &cli, &line);
+    }
     if let Some(prompt) = cli.prompt.clone() {
         return run_non_interactive(&root, &cli, &prompt);
     }
+    // `echo "…" | stratuscode` runs the piped text as the prompt.
+    if !io::stdin().is_terminal() {
+        let mut prompt = String::new();
+        io::stdin().read_to_string(&mut prompt)?;
+        if !prompt.trim().is_empty() {
+            return run_non_interactive(&root, &cli, prompt.trim());
+        }
+    }
+    // The full-screen UI can't draw into a pipe or a dumb terminal.
+    if cli.no_tui || !io::stdout().is_terminal() || app::dumb_terminal() {
+        return run_repl(&root, &cli);
+    }
 
     run_interactive(&root, &cli)
 }
@@ -148,37 +413,143 @@ fn run_auth(root: &Path, key: Option<String>, show: bool, provider: Option<Strin
     Ok(())
 }
 
-fn run_interactive(root: &Path, cli: &Cli) -> Result<()> {
+fn project_dir(cli: &Cli) -> PathBuf {
+    std::fs::canonicalize(&cli.dir)
+        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&cli.dir)))
+        .unwrap_or_else(|_| PathBuf::from(&cli.dir))
+}
+
+fn run_replay(cli: &Cli, session: Option<&str>, list: bool) -> Result<()> {
+    let store = SessionStore::open()?;
+    let project_dir = project_dir(cli).to_string_lossy().to_string();
+    if list {
+        for sess in store.list_sessions(&project_dir, 50)? {
+            println!(
+                "{}  {}  ({} messages){}",
+                sess.id,
+                sess.title,
+                sess.message_count.unwrap_or(0),
+                sess.first_message
+                    .map(|m| format!("  {}", m.replace('\n', " ")))
+                    .unwrap_or_default()
+            );
+        }
+        return Ok(());
+    }
+    let sess = store.resolve_session(&project_dir, session)?;
+    let mut events = store.timeline_events(&sess.id)?;
+    if let Some(redactor) = project_redactor(Path::new(&project_dir)) {
+        redactor.redact_events(&mut events);
+    }
+    println!("# {}\n", sess.title);
+    print!("{}", transcript_markdown(&events));
+    Ok(())
+}
+
+fn run_export(cli: &Cli, session: Option<&str>, output: Option<PathBuf>, html: bool) -> Result<()> {
+    let store = SessionStore::open()?;
+    let project_dir = project_dir(cli).to_string_lossy().to_string();
+    let sess = store.resolve_session(&project_dir, session)?;
+    let mut events = store.timeline_events(&sess.id)?;
+    if let Some(redactor) = project_redactor(Path::new(&project_dir)) {
+        redactor.redact_events(&mut events);
+    }
+    let transcript = if html {
+        transcript_html(&sess.title, &events)
+    } else {
+        format!("# {}\n\n{}", sess.title, transcript_markdown(&events))
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, transcript)
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+            eprintln!("Exported '{}' to {}", sess.title, path.display());
+        }
+        None => print!("{}", transcript),
+    }
+    Ok(())
+}
+
+fn backend_path(root: &Path) -> Result<PathBuf> {
     let primary_backend = root.join("packages/tui/dist/backend/server.js");
     let fallback_backend = root.join("packages/tui/dist/backend.js");
-    let backend_path = if primary_backend.exists() {
-        primary_backend
+    if primary_backend.exists() {
+        Ok(primary_backend)
     } else if fallback_backend.exists() {
-        fallback_backend
+        Ok(fallback_backend)
     } else {
-        return Err(anyhow!(
+        Err(anyhow!(
             "Backend build not found: {}",
             primary_backend.display()
-        ));
-    };
+        ))
+    }
+}
 
-    let args = vec![backend_path.to_string_lossy().to_string()];
-    let (client, notify_rx) = BackendClient::spawn("bun", &args)?;
-    let client = Arc::new(Mutex::new(client));
+fn run_import(root: &Path, cli: &Cli, source: ImportSource, path: &Path) -> Result<()> {
+    let session = import::load_session(source, path)?;
+    let count = session.messages.len();
+    let args = vec![backend_path(root)?.to_string_lossy().to_string()];
+    let (mut client, _notify_rx) = BackendClient::spawn("bun", &args, &[])?;
+    let resp = client.call(
+        "import_session",
+        json!({ "projectDir": project_dir(cli), "session": session }),
+    )?;
+    println!(
+        "Imported {} messages from {} as '{}' ({})",
+        count,
+        source.label(),
+        resp.get("title").and_then(|v| v.as_str()).unwrap_or(""),
+        resp.get("sessionId").and_then(|v| v.as_str()).unwrap_or("")
+    );
+    Ok(())
+}
 
-    let project_dir = std::fs::canonicalize(&cli.dir)
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&cli.dir)))
-        .unwrap_or_else(|_| PathBuf::from(&cli.dir));
+fn run_interactive(root: &Path, cli: &Cli) -> Result<()> {
+    let backend_path = backend_path(root)?;
+    let args = vec![backend_path.to_string_lossy().to_string()];
+    let project_dir = project_dir(cli);
     let project_dir_str = project_dir.to_string_lossy().to_string();
-
-    let init_payload = json!({
-        "projectDir": project_dir_str,
-        "agent": cli.agent,
-        "model": cli.model,
-        "provider": cli.provider,
+    let (config, config_error) = match ProjectConfig::load(&project_dir) {
+        Ok(config) => (config, None),
+        Err(e) => (ProjectConfig::default(), Some(e)),
+    };
+    // An untrusted project's config doesn't get to run commands or read the
+    // keychain until the trust prompt is answered.
+    let trusted = is_trusted(&project_dir);
+    let (env_vars, env_errors) = if trusted {
+        resolve_env(&config.env)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let (client, notify_rx) = BackendClient::spawn("bun", &args, &env_vars)
+        .map_err(|e| anyhow!("Failed to start the backend with bun: {e}\n{OFFLINE_HINT}"))?;
+    let (_event_socket, notify_rx) = open_event_socket(cli, notify_rx)?;
+    let client = Arc::new(Mutex::new(client));
+    let sandbox = start_sandbox(cli, &project_dir)?;
+    let (mut init_payload, model_source) = init_payload(&project_dir_str, cli, &config);
+    let withheld = (!trusted).then(|| {
+        let withheld = json!({
+            "hooks": init_payload["hooks"].take(),
+            "verify": init_payload["verify"].take(),
+            "readOnly": init_payload["readOnly"].clone(),
+            "env": !config.env.is_empty(),
+        });
+        init_payload["readOnly"] = json!(true);
+        withheld
     });
+    if let Some(sandbox) = &sandbox {
+        init_payload["sandbox"] = sandbox_payload(sandbox, &env_vars);
+    }
+    // Inline mode moves lines into scrollback by index, so it keeps them all.
+    if !cli.inline {
+        init_payload["timelineWindow"] = json!(TIMELINE_WINDOW);
+    }
 
-    let init_result = client.lock().unwrap().call("initialize", init_payload)?;
+    let init_result = client
+        .lock()
+        .unwrap()
+        .call("initialize", init_payload)
+        .map_err(|e| anyhow!("{e}\n{OFFLINE_HINT}"))?;
     let state: ChatState =
         serde_json::from_value(init_result.get("state").cloned().unwrap_or_default())
             .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
@@ -188,106 +559,429 @@ fn run_interactive(root: &Path, cli: &Cli) -> Result<()> {
         .unwrap_or("default")
         .to_string();
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let inline = cli.inline;
+    enter_tui(inline)?;
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = if inline {
+        let rows = crossterm::terminal::size().map(|(_, h)| h).unwrap_or(24);
+        // Keep at least one row above the viewport for scrollback inserts.
+        let height = INLINE_VIEWPORT_HEIGHT.min(rows.saturating_sub(1)).max(8);
+        Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::Inline(height),
+            },
+        )?
+    } else {
+        Terminal::new(backend)?
+    };
 
+    let (loop_tx, loop_rx) = mpsc::channel::<LoopEvent>();
     let mut app = App::new(state, project_dir_str, base_model);
-    let mut last_tick = Instant::now();
-    let (ui_tx, ui_rx) = std::sync::mpsc::channel::<UiUpdate>();
+    app.push_updates = init_result
+        .get("capabilities")
+        .and_then(|v| v.as_array())
+        .is_some_and(|caps| caps.iter().any(|c| c == "push_updates"));
+    app.paste_confirm_lines = cli.paste_confirm_lines;
+    app.mutating_tools = init_result
+        .get("mutatingTools")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    app.set_read_only(cli.read_only || withheld.is_some());
+    app.accessible =
+        cli.accessible || app::screen_reader_detected(config.accessibility.screen_reader);
+    app.no_color = app::no_color_requested();
+    app.inline_mode = inline;
+    app.perf_hud = cli.perf;
+    app.perf.rpc = client.lock().unwrap().timings();
+    app.print_on_exit = cli.print_on_exit || config.print_on_exit;
+    app.model_source = model_source;
+    app.index_config = config.index.clone();
+    app.spinner_config = config.spinner.clone();
+    app.diff_max_lines = config.diff.max_lines.unwrap_or(app::DIFF_MAX_LINES);
+    app.with_diff = config.with_diff.enabled;
+    app.with_diff_status = config.with_diff.status;
+    app.with_diff_max_bytes = config
+        .with_diff
+        .max_bytes
+        .unwrap_or(app::WITH_DIFF_MAX_BYTES);
+    app.bell = config.bell;
+    app.clipboard = config.clipboard;
+    app.bell_after = Duration::from_secs(
+        config
+            .bell_after_secs
+            .unwrap_or(app::DEFAULT_BELL_AFTER_SECS),
+    );
+    app.turn_warn_after = Duration::from_secs(
+        config
+            .status
+            .turn_warn_secs
+            .unwrap_or(app::DEFAULT_TURN_WARN_SECS),
+    );
+    if cli.index_depth.is_some() {
+        app.index_config.max_depth = cli.index_depth;
+    }
+    if let Err(e) = i18n::init(config.lang.as_deref()) {
+        app.warn_toast(e);
+    }
+    if let Some(lines) = &config.status.lines {
+        for name in lines.iter().flatten() {
+            if status_segment(name).is_none() {
+                app.warn_toast(format!(
+                    "Unknown status segment '{}' (known: {})",
+                    name,
+                    STATUS_SEGMENTS.join(", ")
+                ));
+            }
+        }
+        app.status_layout = lines.clone();
+    }
+    let (custom_agents, agent_errors) = load_agents(&project_dir);
+    app.custom_agents = custom_agents;
+    let mut redact_errors = Vec::new();
+    if config.redact.enabled() {
+        let secrets: Vec<&str> = env_vars
+            .iter()
+            .filter(|var| var.secret)
+            .map(|var| var.value.as_str())
+            .collect();
+        let (redactor, errors) = Redactor::new(&config.redact, &secrets);
+        app.redactor = Some(Arc::new(redactor));
+        redact_errors = errors;
+    }
+    app.env_vars = env_vars;
+    app.sandbox = sandbox.as_ref().map(Sandbox::label);
+    for e in config_error
+        .into_iter()
+        .chain(agent_errors)
+        .chain(env_errors)
+        .chain(redact_errors)
+    {
+        app.error_toast(e);
+    }
+    // A turn still marked in flight means the last run died mid-turn.
+    if let Ok(resp) = client.lock().unwrap().call(
+        "find_interrupted_session",
+        json!({ "projectDir": app.project_dir }),
+    ) {
+        if let Ok(Some(session)) = serde_json::from_value::<Option<InterruptedSession>>(resp) {
+            app.interrupted = Some(session);
+            app.mode = UiMode::Recovery;
+        }
+    }
+    if withheld.is_some() {
+        app.untrusted = withheld;
+        app.mode = UiMode::TrustPrompt;
+    }
+    if let Some(agent) = app.custom_agents.iter().find(|a| a.name == app.state.agent) {
+        let _ = client.lock().unwrap().call(
+            "set_agent",
+            json!({ "agent": agent.name, "definition": agent.payload() }),
+        );
+    }
+    // A restored reasoning effort only lives in the UI until the backend hears it.
+    if app.state.reasoning_effort_override.is_none() && app.reasoning_effort != "off" {
+        let _ = client.lock().unwrap().call(
+            "set_reasoning_effort",
+            json!({ "reasoningEffort": app.reasoning_effort }),
+        );
+    }
+    let mut saved_prefs = app.ui_prefs();
+    let mut push_session = None;
+    let mut clock_minute = String::new();
+    app.file_index_loading = true;
+    app.file_index_refresh = Some(spawn_file_indexer(
+        PathBuf::from(&app.project_dir),
+        app.index_config.clone(),
+        loop_tx.clone(),
+    ));
+    let mut last_spinner_tick = Instant::now();
+    let input_gate = Arc::new(InputGate::default());
+    spawn_terminal_reader(loop_tx.clone(), input_gate.clone());
+    spawn_notification_forwarder(notify_rx, loop_tx.clone());
+    spawn_signal_forwarder(loop_tx.clone());
 
     loop {
-        let tick_rate = if app.state.is_loading {
-            Duration::from_millis(80)
-        } else {
-            Duration::from_millis(220)
-        };
-        if app.dirty || last_tick.elapsed() >= tick_rate {
-            if app.state.is_loading {
-                app.spinner_index = (app.spinner_index + 1) % SPINNER_FRAMES.len();
-                app.mark_dirty();
+        if app.state.is_loading
+            && !app.accessible
+            && last_spinner_tick.elapsed() >= SPINNER_INTERVAL
+        {
+            app.spinner_index = app.spinner_index.wrapping_add(1);
+            app.mark_dirty();
+            last_spinner_tick = Instant::now();
+        }
+        if app.dirty {
+            if app.inline_mode {
+                flush_inline_scrollback(&mut terminal, &mut app)?;
             }
             if app.needs_clear {
                 let _ = terminal.clear();
                 app.needs_clear = false;
             }
+            let frame_started = Instant::now();
             render_ui(&mut terminal, &mut app)?;
+            app.perf.record_frame(frame_started.elapsed());
             app.dirty = false;
-            last_tick = Instant::now();
         }
-
-        while let Ok(notif) = notify_rx.try_recv() {
-            app.handle_notification(notif);
+        let title = app.title_text();
+        if app.window_title.as_deref() != Some(title.as_str()) {
+            let _ = execute!(io::stdout(), SetTitle(&title));
+            app.window_title = Some(title);
+        }
+        if app.ring_bell {
+            let mut out = io::stdout();
+            let _ = out.write_all(b"\x07").and_then(|_| out.flush());
+            app.ring_bell = false;
         }
 
-        while let Ok(update) = ui_rx.try_recv() {
-            match update {
-                UiUpdate::Todos { list, counts } => {
-                    app.todos = list;
-                    app.todo_counts = counts;
-                    app.todos_request_inflight = false;
-                    app.mark_dirty();
-                }
-                UiUpdate::Question(question) => {
-                    let replace = match &app.question {
-                        None => true,
-                        Some(existing) => existing.id != question.id,
-                    };
-                    if replace {
-                        app.question = Some(question);
-                        app.mode = UiMode::QuestionPrompt;
-                        app.mark_dirty();
-                    }
-                    app.question_request_inflight = false;
-                }
-                UiUpdate::QuestionNone => {
-                    app.question_request_inflight = false;
+        // Sleep until something happens or the next timer is due. Everything
+        // already queued is drained before the next render so a burst of
+        // streaming deltas costs one frame.
+        match loop_rx.recv_timeout(next_wakeup(&app, last_spinner_tick)) {
+            Ok(ev) => {
+                handle_loop_event(&mut app, ev, &client);
+                while let Ok(ev) = loop_rx.try_recv() {
+                    handle_loop_event(&mut app, ev, &client);
                 }
             }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // Replayed keys go one per tick so replies and redraws land between
+        // them, and wait out a turn the macro started.
+        if !app.state.is_loading {
+            if let Some(key) = app.macro_queue.pop_front() {
+                handle_key(&mut app, key, &client);
+            }
         }
 
         if app.history_needs_refresh && matches!(app.mode, UiMode::SessionHistory) {
-            if let Ok(resp) = client.lock().unwrap().call("list_sessions", json!({ "projectDir": app.project_dir, "limit": 20, "currentSessionId": app.state.session_id })) {
-                if let Ok(list) = serde_json::from_value::<Vec<SessionInfo>>(resp) {
-                    app.session_list = list;
-                    if app.session_selected >= app.session_list.len() && !app.session_list.is_empty() {
-                        app.session_selected = app.session_list.len() - 1;
-                    }
+            let listed = client.lock().unwrap().call("list_sessions", json!({ "projectDir": app.project_dir, "limit": 20, "currentSessionId": app.state.session_id }));
+            // With the backend gone, read the list straight from the store.
+            let list = match listed {
+                Ok(resp) => serde_json::from_value::<Vec<SessionInfo>>(resp).ok(),
+                Err(_) => SessionStore::open()
+                    .and_then(|store| store.list_sessions(&app.project_dir, 20))
+                    .ok(),
+            };
+            if let Some(list) = list {
+                app.session_list = list;
+                if app.session_selected >= app.session_list.len() && !app.session_list.is_empty() {
+                    app.session_selected = app.session_list.len() - 1;
                 }
             }
             app.history_needs_refresh = false;
             app.mark_dirty();
         }
 
-        let timeout = Duration::from_millis(10);
-        if event::poll(timeout)? {
-            match event::read()? {
-                Event::Key(key) => handle_key(&mut app, key, &client),
-                Event::Paste(text) => handle_paste(&mut app, text),
-                _ => {}
-            }
+        let prefs = app.ui_prefs();
+        if prefs != saved_prefs {
+            let _ = prefs.save(&app.project_dir);
+            saved_prefs = prefs;
         }
 
         if app.should_quit {
             break;
         }
+        if app.suspend_requested {
+            app.suspend_requested = false;
+            suspend(&mut terminal, inline)?;
+            app.window_title = None;
+            app.mark_dirty();
+        }
+        if app.edit_plan_requested {
+            app.edit_plan_requested = false;
+            if let Some(path) = app.plan_file_path() {
+                if let Err(e) = edit_in_external_editor(&mut terminal, inline, &input_gate, &path) {
+                    app.error_toast(e.to_string());
+                }
+                app.window_title = None;
+                app.load_plan();
+            }
+        }
+
+        // Pushes only cover changes, so a newly loaded session needs one fetch.
+        if app.push_updates && app.state.session_id != push_session {
+            push_session = app.state.session_id.clone();
+            refresh_todos(&mut app, &client);
+            if app.status_shows("cost") {
+                app.usage_refresh_requested = true;
+            }
+        }
+        if app.status_shows("clock") {
+            let minute = chrono::Local::now().format("%H:%M").to_string();
+            if minute != clock_minute {
+                clock_minute = minute;
+                app.mark_dirty();
+            }
+        }
+
+        if !app.queued_sends.is_empty() {
+            send_next_queued(&mut app, &client);
+        }
+
+        if app.models_refresh_requested {
+            app.models_refresh_requested = false;
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                let result = match client.lock().unwrap().call("list_models", json!({})) {
+                    Ok(resp) => match resp.get("entries") {
+                        Some(entries) => serde_json::from_value::<Vec<ModelEntry>>(entries.clone())
+                            .map_err(|_| "Failed to parse model list".to_string()),
+                        None => Err("Model list unavailable".to_string()),
+                    },
+                    Err(_) => Err("Failed to load models".to_string()),
+                };
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::Models(result)));
+            });
+        }
+
+        if let Some(entry) = app.model_test_requested.take() {
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                // Waits outside the lock; a ping can take up to 20s.
+                let resp = client
+                    .lock()
+                    .unwrap()
+                    .request(
+                        "test_model",
+                        json!({ "model": entry.id, "provider": entry.provider_key }),
+                    )
+                    .and_then(PendingCall::wait);
+                let result = match resp {
+                    Ok(resp) => match resp.get("latencyMs").and_then(|v| v.as_u64()) {
+                        Some(ms) => Ok(ms),
+                        None => Err(resp
+                            .get("error")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("No response")
+                            .to_string()),
+                    },
+                    Err(e) => Err(e.to_string()),
+                };
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::ModelTest { entry, result }));
+            });
+        }
+
+        if let Some(session) = app.resume_requested.take() {
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                // Replaying loads the whole session; wait outside the lock.
+                let result = client
+                    .lock()
+                    .unwrap()
+                    .request("resume_session", json!({ "sessionId": session.id }))
+                    .and_then(PendingCall::wait)
+                    .map(|resp| resp.get("replayed").and_then(|v| v.as_u64()).unwrap_or(0))
+                    .map_err(|e| e.to_string());
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::Resumed {
+                    title: session.title,
+                    result,
+                }));
+            });
+        }
+
+        if let Some(url) = app.url_fetch_requested.take() {
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                // Waits outside the lock so a slow page doesn't hold up Esc.
+                let resp = client
+                    .lock()
+                    .unwrap()
+                    .request(
+                        "execute_tool",
+                        json!({ "name": "webfetch", "args": { "url": url } }),
+                    )
+                    .and_then(PendingCall::wait);
+                let result = resp
+                    .map_err(|e| e.to_string())
+                    .and_then(|resp| webfetch_text(&resp["result"]));
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::UrlFetched { url, result }));
+            });
+        }
+
+        if let Some(before) = app.earlier_requested.take() {
+            app.earlier_loading = true;
+            app.timeline_revision = app.timeline_revision.saturating_add(1);
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                let result = fetch_earlier(&client, &before, Some(EARLIER_PAGE));
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::EarlierEvents { before, result }));
+            });
+        }
+
+        if let Some(before) = app.earlier_tools_requested.take() {
+            app.earlier_tools_loading = true;
+            let client = client.clone();
+            let tx = loop_tx.clone();
+            std::thread::spawn(move || {
+                let result = fetch_earlier(&client, &before, None);
+                let _ = tx.send(LoopEvent::Ui(UiUpdate::EarlierTools { before, result }));
+            });
+        }
+
+        if let Some(query) = app.related_query_due() {
+            if app.mention_typed_at.elapsed() >= RELATED_DEBOUNCE {
+                app.related_inflight = true;
+                app.related_query = Some(query.clone());
+                let client = client.clone();
+                let tx = loop_tx.clone();
+                let project_dir = app.project_dir.clone();
+                std::thread::spawn(move || {
+                    // The first search builds the index; wait outside the lock.
+                    let resp = client
+                        .lock()
+                        .unwrap()
+                        .request(
+                            "execute_tool",
+                            json!({ "name": "codesearch", "args": { "query": query, "maxResults": 10 } }),
+                        )
+                        .and_then(PendingCall::wait);
+                    let result = resp
+                        .map_err(|e| e.to_string())
+                        .and_then(|resp| parse_related_code(&resp["result"], &project_dir));
+                    let _ = tx.send(LoopEvent::Ui(UiUpdate::RelatedCode { query, result }));
+                });
+            }
+        }
+
+        if app.usage_refresh_requested {
+            app.usage_refresh_requested = false;
+            if let Some(session_id) = app.state.session_id.clone() {
+                let client = client.clone();
+                let tx = loop_tx.clone();
+                std::thread::spawn(move || {
+                    let turns = client
+                        .lock()
+                        .unwrap()
+                        .call("get_usage", json!({ "sessionId": session_id }))
+                        .ok()
+                        .and_then(|resp| resp.get("turns").cloned())
+                        .and_then(|turns| serde_json::from_value::<Vec<TurnUsage>>(turns).ok())
+                        .unwrap_or_default();
+                    let _ = tx.send(LoopEvent::Ui(UiUpdate::Usage(turns)));
+                });
+            } else {
+                app.usage_turns.clear();
+            }
+        }
 
-        let todo_refresh = if app.state.is_loading {
-            Duration::from_millis(750)
-        } else {
-            Duration::from_secs(3)
-        };
         if (app.todos_expanded || !app.todos.is_empty())
-            && app.last_todos_refresh.elapsed() > todo_refresh
+            && !app.push_updates
+            && app.last_todos_refresh.elapsed() > todo_refresh_interval(&app)
             && !app.todos_request_inflight
         {
             if let Some(session_id) = app.state.session_id.clone() {
                 app.todos_request_inflight = true;
                 app.last_todos_refresh = Instant::now();
                 let client = client.clone();
-                let tx = ui_tx.clone();
+                let tx = loop_tx.clone();
                 std::thread::spawn(move || {
                     let mut list = Vec::new();
                     let mut counts = TodoCounts {
@@ -316,19 +1010,20 @@ fn run_interactive(root: &Path, cli: &Cli) -> Result<()> {
                             }
                         }
                     }
-                    let _ = tx.send(UiUpdate::Todos { list, counts });
+                    let _ = tx.send(LoopEvent::Ui(UiUpdate::Todos { list, counts }));
                 });
             }
         }
 
-        if app.last_question_poll.elapsed() > Duration::from_millis(500)
+        if !app.push_updates
+            && app.last_question_poll.elapsed() > QUESTION_POLL_INTERVAL
             && !app.question_request_inflight
         {
             if let Some(session_id) = app.state.session_id.clone() {
                 app.question_request_inflight = true;
                 app.last_question_poll = Instant::now();
                 let client = client.clone();
-                let tx = ui_tx.clone();
+                let tx = loop_tx.clone();
                 std::thread::spawn(move || {
                     if let Ok(resp) = client
                         .lock()
@@ -336,131 +1031,796 @@ fn run_interactive(root: &Path, cli: &Cli) -> Result<()> {
                         .call("get_pending_question", json!({ "sessionId": session_id }))
                     {
                         if let Ok(list) = serde_json::from_value::<Vec<PendingQuestion>>(resp) {
-                            if let Some(pending) = list.first() {
-                                if let Some(item) = pending.questions.first() {
-                                    let options = item.options.clone();
-                                    let mut selected = vec![false; options.len()];
-                                    if !selected.is_empty() {
-                                        selected[0] = true;
-                                    }
-                                    let q = QuestionState {
-                                        id: pending.id.clone(),
-                                        question: item.question.clone(),
-                                        header: item.header.clone(),
-                                        options,
-                                        allow_multiple: item.allow_multiple.unwrap_or(false),
-                                        allow_custom: item.allow_custom.unwrap_or(false),
-                                        selected,
-                                        focused_index: 0,
-                                        custom_input: String::new(),
-                                        custom_active: false,
-                                    };
-                                    let _ = tx.send(UiUpdate::Question(q));
-                                    return;
-                                }
-                            }
+                            let q = list.first().and_then(|p| p.to_state());
+                            let _ = tx.send(LoopEvent::Ui(UiUpdate::Question(q)));
+                            return;
                         }
                     }
-                    let _ = tx.send(UiUpdate::QuestionNone);
+                    let _ = tx.send(LoopEvent::Ui(UiUpdate::QuestionPollFailed));
                 });
             }
         }
 
-        if let Some((_, at)) = app.toast {
-            if at.elapsed() > Duration::from_secs(5) {
-                app.toast = None;
-                app.mark_dirty();
-            }
+        app.expire_toasts();
+        app.expire_todos_done();
+    }
+
+    let transcript = (app.print_on_exit && !inline).then(|| {
+        let mut events = whole_timeline(&app, &client);
+        if let Some(redactor) = &app.redactor {
+            redactor.redact_events(&mut events);
         }
+        events
+    });
+    if inline {
+        // Leave everything, including an interrupted turn, in scrollback.
+        app.state.is_loading = false;
+        flush_inline_scrollback(&mut terminal, &mut app)?;
+        terminal.clear()?;
+    }
+    leave_tui(&mut terminal, inline)?;
+    client.lock().unwrap().shutdown();
+    if let Some(sandbox) = &sandbox {
+        sandbox.stop();
+    }
+    // Inline mode already left the conversation in scrollback.
+    if let Some(events) = transcript {
+        print!("{}", transcript_markdown(&events));
+    }
+
+    Ok(())
+}
+
+/// XTWINOPS: save the window title on the terminal's stack, and restore it.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Whether `enter_tui` pushed kitty keyboard flags, so `leave_tui` pops them.
+/// Without them most terminals send Ctrl+Shift+P and Shift+Esc as plain
+/// Ctrl+P and Esc.
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+fn enter_tui(inline: bool) -> Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), Print(PUSH_TITLE))?;
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(
+            io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+        KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+    }
+    if inline {
+        execute!(io::stdout(), EnableBracketedPaste)?;
+    } else {
+        execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste)?;
     }
+    Ok(())
+}
 
+fn leave_tui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, inline: bool) -> Result<()> {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        DisableBracketedPaste,
-        LeaveAlternateScreen
-    )?;
+    if inline {
+        execute!(terminal.backend_mut(), DisableBracketedPaste)?;
+    } else {
+        execute!(
+            terminal.backend_mut(),
+            DisableBracketedPaste,
+            LeaveAlternateScreen
+        )?;
+    }
+    execute!(terminal.backend_mut(), Print(POP_TITLE))?;
     terminal.show_cursor()?;
-    client.lock().unwrap().shutdown();
+    Ok(())
+}
 
+/// Hands the terminal back to the shell and stops the process like a plain
+/// SIGTSTP would; once SIGCONT arrives the TUI is restored and fully redrawn.
+#[cfg(unix)]
+fn suspend(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, inline: bool) -> Result<()> {
+    leave_tui(terminal, inline)?;
+    signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+    enter_tui(inline)?;
+    terminal.clear()?;
     Ok(())
 }
 
-fn run_non_interactive(root: &Path, cli: &Cli, prompt: &str) -> Result<()> {
-    let primary_backend = root.join("packages/tui/dist/backend/server.js");
-    let fallback_backend = root.join("packages/tui/dist/backend.js");
-    let backend_path = if primary_backend.exists() {
-        primary_backend
-    } else if fallback_backend.exists() {
-        fallback_backend
-    } else {
-        return Err(anyhow!(
-            "Backend build not found: {}",
-            primary_backend.display()
-        ));
-    };
-    let args = vec![backend_path.to_string_lossy().to_string()];
-    let (client, notify_rx) = BackendClient::spawn("bun", &args)?;
-    let mut client = client;
+#[cfg(not(unix))]
+fn suspend(_terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, _inline: bool) -> Result<()> {
+    Ok(())
+}
 
-    let notify_handle = thread::spawn(move || {
-        for notif in notify_rx.iter() {
-            if notif.method == "timeline_event" {
-                if let Ok(event) = serde_json::from_value::<TimelineEvent>(notif.params) {
-                    if event.kind == "tool_call" {
-                        let icon = tool_icon(event.tool_name.as_deref().unwrap_or(""));
-                        println!(
-                            "\n{} {}",
-                            icon,
-                            event.tool_name.unwrap_or_else(|| "tool".to_string())
-                        );
-                        if !event.content.is_empty() {
-                            println!("   {}", format_tool_args(&event.content));
-                        }
-                    }
-                    if event.kind == "tool_result" {
-                        if let Some((_summary, diff_lines)) =
-                            extract_diff_summary(&event.content, 120)
-                        {
-                            for line in diff_lines.into_iter().take(120) {
-                                let mut out = String::new();
-                                for span in line.spans {
-                                    out.push_str(span.content.as_ref());
-                                }
-                                if !out.is_empty() {
-                                    println!("   {}", out);
-                                }
-                            }
-                        } else {
-                            // no output for non-diff tool results
-                        }
-                    }
-                }
+/// Raw mode swallows Ctrl+Z, but an external `kill -TSTP` still needs the
+/// terminal restored before stopping.
+#[cfg(unix)]
+fn spawn_signal_forwarder(tx: Sender<LoopEvent>) {
+    let Ok(mut signals) = signal_hook::iterator::Signals::new([signal_hook::consts::SIGTSTP])
+    else {
+        return;
+    };
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if tx.send(LoopEvent::Suspend).is_err() {
+                break;
             }
         }
     });
+}
 
-    println!("\n> Running with agent: {}", cli.agent);
-    println!("> Project: {}", cli.dir);
-    println!("\n> You: {}\n", prompt);
+#[cfg(not(unix))]
+fn spawn_signal_forwarder(_tx: Sender<LoopEvent>) {}
 
-    let project_dir = std::fs::canonicalize(&cli.dir)
-        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&cli.dir)))
-        .unwrap_or_else(|_| PathBuf::from(&cli.dir));
-    let project_dir_str = project_dir.to_string_lossy().to_string();
+/// Lets the main loop take stdin away from the reader thread while a child
+/// process such as `$EDITOR` owns the terminal.
+#[derive(Default)]
+struct InputGate {
+    paused: AtomicBool,
+    reading: Mutex<()>,
+}
 
-    let init_payload = json!({
-        "projectDir": project_dir_str,
-        "agent": cli.agent,
-        "model": cli.model,
-        "provider": cli.provider,
+impl InputGate {
+    /// Blocks until the reader is idle; input stays paused until `resume`.
+    fn pause(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.paused.store(true, Ordering::SeqCst);
+        self.reading.lock().unwrap()
+    }
+
+    fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+fn spawn_terminal_reader(tx: Sender<LoopEvent>, gate: Arc<InputGate>) {
+    thread::spawn(move || loop {
+        if gate.paused.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(20));
+            continue;
+        }
+        let guard = gate.reading.lock().unwrap();
+        // Polling with a timeout keeps the lock short so `pause` never waits
+        // on a keypress.
+        let ev = match event::poll(Duration::from_millis(50)) {
+            Ok(true) => event::read(),
+            Ok(false) => continue,
+            Err(e) => Err(e),
+        };
+        drop(guard);
+        match ev {
+            Ok(ev) => {
+                if tx.send(LoopEvent::Terminal(ev)).is_err() {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    });
+}
+
+/// Runs `$VISUAL`/`$EDITOR` (falling back to `vi`) on `path` with the
+/// terminal handed over, then restores the TUI.
+fn edit_in_external_editor(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    inline: bool,
+    gate: &InputGate,
+    path: &Path,
+) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let args: Vec<&str> = parts.collect();
+
+    let paused = gate.pause();
+    leave_tui(terminal, inline)?;
+    let status = std::process::Command::new(program)
+        .args(&args)
+        .arg(path)
+        .status();
+    enter_tui(inline)?;
+    terminal.clear()?;
+    drop(paused);
+    gate.resume();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(anyhow!("{} exited with {}", program, status)),
+        Err(e) => Err(anyhow!("Failed to run {}: {}", program, e)),
+    }
+}
+
+fn spawn_notification_forwarder(rx: Receiver<BackendNotification>, tx: Sender<LoopEvent>) {
+    thread::spawn(move || {
+        for notif in rx.iter() {
+            if tx.send(LoopEvent::Backend(notif)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn handle_loop_event(app: &mut App, ev: LoopEvent, client: &Arc<Mutex<BackendClient>>) {
+    match ev {
+        // Windows (and terminals with key release reporting) send a release
+        // for every press, which would type IME commits twice.
+        LoopEvent::Terminal(Event::Key(key)) if key.kind == KeyEventKind::Release => {}
+        LoopEvent::Terminal(Event::Key(key))
+            if key.code == KeyCode::Esc && !app.macro_queue.is_empty() =>
+        {
+            app.macro_queue.clear();
+            app.warn_toast(i18n::t("toast.macro_stopped"));
+        }
+        LoopEvent::Terminal(Event::Key(key)) => handle_key(app, key, client),
+        LoopEvent::Terminal(Event::Paste(text)) => handle_paste(app, text),
+        LoopEvent::Terminal(Event::Resize(_, _)) => app.mark_dirty(),
+        LoopEvent::Terminal(_) => {}
+        LoopEvent::Backend(notif) => {
+            app.handle_notification(notif);
+            // The backend leaves these tools out in read-only mode; this
+            // catches any that get through anyway.
+            if let Some(tool) = app.read_only_violation.take() {
+                let client = client.clone();
+                thread::spawn(move || {
+                    let _ = client.lock().unwrap().call("abort", json!({}));
+                });
+                app.error_toast(format!(
+                    "Stopped the turn: {} is blocked in read-only mode",
+                    tool
+                ));
+            }
+        }
+        LoopEvent::Ui(update) => apply_ui_update(app, update),
+        LoopEvent::Suspend => app.suspend_requested = true,
+    }
+}
+
+fn apply_ui_update(app: &mut App, update: UiUpdate) {
+    match update {
+        UiUpdate::Todos { list, counts } => {
+            app.todos = list;
+            app.set_todo_counts(counts);
+            app.todos_request_inflight = false;
+            app.mark_dirty();
+        }
+        UiUpdate::Question(question) => {
+            // A different id or none at all means the shown one was
+            // resolved elsewhere or its turn was aborted.
+            match question {
+                Some(question) => app.show_question(question),
+                None => {
+                    app.dismiss_question(None);
+                }
+            }
+            app.question_request_inflight = false;
+        }
+        UiUpdate::QuestionPollFailed => {
+            app.question_request_inflight = false;
+        }
+        UiUpdate::FileIndex {
+            files,
+            elapsed,
+            announce,
+        } => {
+            if announce {
+                app.set_toast(index_summary(files.len(), elapsed, &app.index_config));
+            }
+            app.file_index = files;
+            app.file_index_loading = false;
+            app.mark_dirty();
+        }
+        UiUpdate::SymbolIndex(symbols) => {
+            app.symbol_index = symbols;
+        }
+        UiUpdate::Resumed { title, result } => match result {
+            Ok(replayed) => app.set_toast(format!(
+                "Resumed '{}' · {} tool result{} replayed",
+                title,
+                replayed,
+                if replayed == 1 { "" } else { "s" }
+            )),
+            Err(e) => app.error_toast(i18n::tf("toast.session_resume_failed", &[("error", &e)])),
+        },
+        UiUpdate::UrlFetched { url, result } => {
+            app.url_fetching = None;
+            match result {
+                Ok(text) => {
+                    let size = format_size(text.len());
+                    add_attachment(app, url_attachment(url, text));
+                    app.set_toast(i18n::tf("toast.url_attached", &[("size", &size)]));
+                }
+                Err(e) => app.error_toast(i18n::tf(
+                    "toast.url_failed",
+                    &[("url", &url), ("error", &e)],
+                )),
+            }
+        }
+        UiUpdate::RelatedCode { query, result } => {
+            app.related_inflight = false;
+            match result {
+                Ok(hits) if app.related_query.as_deref() == Some(query.as_str()) => {
+                    app.related_code = hits;
+                }
+                Ok(_) => {}
+                // Usually no Ollama/Qdrant; stop searching for this session.
+                Err(e) => {
+                    app.related_code.clear();
+                    app.related_error = Some(e);
+                }
+            }
+        }
+        UiUpdate::ModelTest { entry, result } => {
+            match &result {
+                Ok(ms) => app.set_toast(i18n::tf(
+                    "toast.model_test_ok",
+                    &[("model", &entry.name), ("ms", ms)],
+                )),
+                Err(error) => app.error_toast(i18n::tf(
+                    "toast.model_test_failed",
+                    &[("model", &entry.name), ("error", error)],
+                )),
+            }
+            // A newer test may have started meanwhile.
+            if let Some((id, pending)) = app.model_test.as_mut() {
+                if *id == entry.id {
+                    *pending = Some(result);
+                }
+            }
+        }
+        UiUpdate::Models(result) => {
+            app.models_loading = false;
+            match result {
+                Ok(entries) => {
+                    let _ = save_model_cache(&entries);
+                    app.apply_model_list(entries);
+                }
+                // A stale cached list is still better than nothing.
+                Err(e) if app.model_entries.is_empty() => app.error_toast(e),
+                Err(_) => app.mark_dirty(),
+            }
+        }
+        UiUpdate::Usage(turns) => {
+            app.usage_turns = turns;
+            app.mark_dirty();
+        }
+        UiUpdate::EarlierEvents { before, result } => {
+            app.earlier_loading = false;
+            app.timeline_revision = app.timeline_revision.saturating_add(1);
+            match result {
+                Ok(page) => app.prepend_earlier(&before, page),
+                // Stop asking each frame; the exports still fetch them.
+                Err(e) => {
+                    app.state.earlier_events = 0;
+                    app.error_toast(i18n::tf("toast.earlier_failed", &[("error", &e)]));
+                }
+            }
+        }
+        UiUpdate::EarlierTools { before, result } => {
+            app.earlier_tools_loading = false;
+            // On failure the sidebar keeps to the window rather than asking
+            // again each frame.
+            let events = result.map(|page| page.events).unwrap_or_default();
+            app.set_earlier_tools(before, events);
+        }
+    }
+}
+
+/// Opens `--event-socket` if it was given; the returned receiver is the one to
+/// read notifications from, so every one is copied to the socket first.
+fn open_event_socket(
+    cli: &Cli,
+    notify_rx: Receiver<BackendNotification>,
+) -> Result<(Option<EventSocket>, Receiver<BackendNotification>)> {
+    match &cli.event_socket {
+        Some(path) => {
+            let socket = EventSocket::bind(path)?;
+            let notify_rx = socket.mirror(notify_rx);
+            Ok((Some(socket), notify_rx))
+        }
+        None => Ok((None, notify_rx)),
+    }
+}
+
+/// Starts the `--sandbox` container, if one was asked for.
+fn start_sandbox(cli: &Cli, project_dir: &Path) -> Result<Option<Sandbox>> {
+    let Some(spec) = &cli.sandbox else {
+        return Ok(None);
+    };
+    let image = parse_sandbox(spec)?;
+    Sandbox::start(project_dir, &image).map(Some)
+}
+
+/// The `sandbox` initialize option: the container, and which variables to
+/// carry into it since `docker exec` doesn't inherit the environment.
+fn sandbox_payload(sandbox: &Sandbox, env_vars: &[EnvVar]) -> serde_json::Value {
+    json!({
+        "container": sandbox.container,
+        "env": env_vars.iter().map(|var| &var.name).collect::<Vec<_>>(),
+    })
+}
+
+/// Builds the `initialize` params, with command-line flags taking precedence
+/// over the project config.
+fn init_payload(
+    project_dir: &str,
+    cli: &Cli,
+    config: &ProjectConfig,
+) -> (serde_json::Value, ModelSource) {
+    let (model, source) = match (&cli.model, &config.model) {
+        (Some(model), _) => (Some(model), ModelSource::Flag),
+        (None, Some(model)) => (Some(model), ModelSource::ProjectConfig),
+        (None, None) => (None, ModelSource::Default),
+    };
+    let payload = json!({
+        "projectDir": project_dir,
+        "agent": cli.agent.as_deref().or(config.agent.as_deref()).unwrap_or("build"),
+        "model": model,
+        "provider": cli.provider.as_ref().or(config.provider.as_ref()),
+        "readOnly": cli.read_only,
+        "verify": config.verify,
+        "hooks": {
+            "preSend": config.hooks.pre_send,
+            "postTool": config.hooks.post_tool,
+            "postTurn": config.hooks.post_turn,
+            "timeoutSecs": config.hooks.timeout_secs,
+        },
     });
+    (payload, source)
+}
+
+/// Builds the @ completion index off the main thread and rebuilds it whenever
+/// the watcher reports structural changes or `/reindex` asks for it.
+fn spawn_file_indexer(
+    root: PathBuf,
+    config: IndexConfig,
+    tx: Sender<LoopEvent>,
+) -> Sender<IndexSignal> {
+    let (signal_tx, signal_rx) = mpsc::channel::<IndexSignal>();
+    let watch_tx = signal_tx.clone();
+    thread::spawn(move || {
+        let (gitignore, _) = Gitignore::new(root.join(".gitignore"));
+        let watch_root = root.clone();
+        let watch_exclude = config.exclude.clone();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                if affects_file_index(&watch_root, &gitignore, &watch_exclude, &event) {
+                    let _ = watch_tx.send(IndexSignal::Changed);
+                }
+            }
+        });
+        // Without a watcher the index still works, it just needs /reindex.
+        let _watcher = watcher.ok().and_then(|mut w| {
+            w.watch(&root, RecursiveMode::Recursive).ok()?;
+            Some(w)
+        });
+
+        let mut announce = false;
+        loop {
+            let started = Instant::now();
+            let files = build_file_index(&root, &config);
+            // Files go out first; the symbol scan reads every source file.
+            let symbols_from = files.clone();
+            let update = UiUpdate::FileIndex {
+                files,
+                elapsed: started.elapsed(),
+                announce,
+            };
+            if tx.send(LoopEvent::Ui(update)).is_err() {
+                break;
+            }
+            let symbols = build_symbol_index(&root, &symbols_from);
+            if tx
+                .send(LoopEvent::Ui(UiUpdate::SymbolIndex(symbols)))
+                .is_err()
+            {
+                break;
+            }
+            let Ok(signal) = signal_rx.recv() else {
+                break;
+            };
+            announce = matches!(signal, IndexSignal::Reindex);
+            thread::sleep(INDEX_DEBOUNCE);
+            while let Ok(signal) = signal_rx.try_recv() {
+                announce |= matches!(signal, IndexSignal::Reindex);
+            }
+        }
+    });
+    signal_tx
+}
+
+fn affects_file_index(
+    root: &Path,
+    gitignore: &Gitignore,
+    exclude: &[String],
+    event: &notify::Event,
+) -> bool {
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    ) {
+        return false;
+    }
+    event.paths.iter().any(|path| {
+        let Ok(rel) = path.strip_prefix(root) else {
+            return false;
+        };
+        let hidden = rel
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        !hidden
+            && !exclude.iter().any(|dir| rel.starts_with(dir))
+            && !gitignore
+                .matched_path_or_any_parents(rel, path.is_dir())
+                .is_ignore()
+    })
+}
+
+fn todo_refresh_interval(app: &App) -> Duration {
+    if app.state.is_loading {
+        Duration::from_millis(750)
+    } else {
+        Duration::from_secs(3)
+    }
+}
+
+/// How long the loop may sleep before a timer (spinner, polls, toast expiry)
+/// needs servicing.
+fn next_wakeup(app: &App, last_spinner_tick: Instant) -> Duration {
+    let mut wait = IDLE_WAKEUP;
+    if app.state.is_loading && !app.accessible {
+        wait = wait.min(SPINNER_INTERVAL.saturating_sub(last_spinner_tick.elapsed()));
+    }
+    if app.state.session_id.is_some() && !app.push_updates {
+        if !app.question_request_inflight {
+            wait =
+                wait.min(QUESTION_POLL_INTERVAL.saturating_sub(app.last_question_poll.elapsed()));
+        }
+        if (app.todos_expanded || !app.todos.is_empty()) && !app.todos_request_inflight {
+            wait = wait
+                .min(todo_refresh_interval(app).saturating_sub(app.last_todos_refresh.elapsed()));
+        }
+    }
+    if let Some(expiry) = app.next_toast_expiry() {
+        wait = wait.min(expiry);
+    }
+    if let Some(left) = app.todos_done_flash() {
+        wait = wait.min(left);
+    }
+    if !app.macro_queue.is_empty() && !app.state.is_loading {
+        wait = wait.min(MACRO_KEY_INTERVAL);
+    }
+    if app.related_query_due().is_some() {
+        wait = wait.min(RELATED_DEBOUNCE.saturating_sub(app.mention_typed_at.elapsed()));
+    }
+    // Timers are checked with a strict `>`, so never spin on a zero timeout.
+    wait.max(Duration::from_millis(1))
+}
+
+enum RunEvent {
+    Loading(bool),
+    Question(Option<PendingQuestion>),
+}
+
+/// Answers or skips a question on the user's behalf, printing what was
+/// chosen and why.
+fn settle_question(
+    client: &mut BackendClient,
+    id: &str,
+    resolution: Resolution,
+    reason: &str,
+) -> Result<()> {
+    match resolution {
+        Resolution::Answer(answers) => {
+            println!("   Answered '{}' ({})", answers.join(", "), reason);
+            client.call("answer_question", json!({ "id": id, "answers": answers }))?;
+        }
+        Resolution::Skip => {
+            println!("   Skipped ({})", reason);
+            client.call("skip_question", json!({ "id": id }))?;
+        }
+    }
+    Ok(())
+}
+
+/// Blocks until the agent finishes the turn. Questions are printed and
+/// answered straight away from `presets` when they cover them; otherwise,
+/// with `auto_answer_after` set, they are answered with their default option
+/// (or skipped) once that many seconds pass, so unattended runs don't hang.
+/// A preset answer that fits no option aborts the turn, as does passing
+/// `deadline`.
+fn wait_for_run(
+    client: &mut BackendClient,
+    run_rx: &Receiver<RunEvent>,
+    presets: &PresetAnswers,
+    auto_answer_after: Option<u64>,
+    deadline: Option<Instant>,
+) -> Result<()> {
+    let auto_answer_after = auto_answer_after.map(Duration::from_secs);
+    let mut started = false;
+    let mut seen: Option<String> = None;
+    let mut pending: Option<(PendingQuestion, Instant)> = None;
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            client.call("abort", json!({}))?;
+            return Err(anyhow!("Timed out"));
+        }
+        match run_rx.recv_timeout(Duration::from_millis(250)) {
+            Ok(RunEvent::Loading(true)) => started = true,
+            Ok(RunEvent::Loading(false)) if started => return Ok(()),
+            Ok(RunEvent::Loading(false)) => {}
+            Ok(RunEvent::Question(question)) => {
+                if question.as_ref().map(|q| &q.id) == seen.as_ref() {
+                    continue;
+                }
+                seen = question.as_ref().map(|q| q.id.clone());
+                pending = None;
+                let Some(question) = question else {
+                    continue;
+                };
+                let Some(item) = question.questions.first() else {
+                    continue;
+                };
+                let default = item.default_index();
+                println!("\n? {}", item.question);
+                for (i, opt) in item.options.iter().enumerate() {
+                    let marker = if default == Some(i) { " (default)" } else { "" };
+                    println!("   {}. {}{}", i + 1, opt.label, marker);
+                }
+                match presets.resolve(item) {
+                    Ok(Some(resolution)) => {
+                        settle_question(client, &question.id, resolution, "from flags")?;
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        client.call("abort", json!({}))?;
+                        return Err(anyhow!(e));
+                    }
+                }
+                if auto_answer_after.is_none() {
+                    println!(
+                        "   Waiting for an answer (pass --answer key=value or --auto-answer first|skip, or set questions.auto_answer_after in .stratuscode/config.toml, to answer unattended)"
+                    );
+                }
+                pending = Some((question, Instant::now()));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        let (Some(after), Some((question, asked_at))) = (auto_answer_after, &pending) else {
+            continue;
+        };
+        if asked_at.elapsed() < after {
+            continue;
+        }
+        let default = question
+            .questions
+            .first()
+            .and_then(|item| Some(item.options.get(item.default_index()?)?.label.clone()));
+        let (resolution, reason) = match default {
+            Some(answer) => (Resolution::Answer(vec![answer]), "default option"),
+            None => (Resolution::Skip, "no default option"),
+        };
+        let reason = format!("{} after {}s", reason, after.as_secs());
+        settle_question(client, &question.id, resolution, &reason)?;
+        pending = None;
+    }
+}
+
+fn run_non_interactive(root: &Path, cli: &Cli, prompt: &str) -> Result<()> {
+    let backend_path = backend_path(root)?;
+    let args = vec![backend_path.to_string_lossy().to_string()];
+    let project_dir = std::fs::canonicalize(&cli.dir)
+        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(&cli.dir)))
+        .unwrap_or_else(|_| PathBuf::from(&cli.dir));
+    let project_dir_str = project_dir.to_string_lossy().to_string();
+    let config = ProjectConfig::load(&project_dir).map_err(|e| anyhow!(e))?;
+    // Nobody is there to answer the trust prompt, so an untrusted folder gets
+    // the read-only session declining it gives.
+    let trusted = cli.trust || is_trusted(&project_dir);
+    let (env_vars, env_errors) = if trusted {
+        resolve_env(&config.env)
+    } else {
+        eprintln!(
+            "{} is not trusted: project commands are off and the agent is read-only. Pass --trust, or run stratuscode there once to trust it.",
+            project_dir.display()
+        );
+        (Vec::new(), Vec::new())
+    };
+    for e in env_errors {
+        eprintln!("Warning: {}", e);
+    }
+    let (client, notify_rx) = BackendClient::spawn("bun", &args, &env_vars)?;
+    let (_event_socket, notify_rx) = open_event_socket(cli, notify_rx)?;
+    let mut client = client;
+
+    let (run_tx, run_rx) = mpsc::channel::<RunEvent>();
+    let notify_handle = spawn_turn_printer(notify_rx, run_tx);
+    let presets = PresetAnswers {
+        answers: cli.answers.clone(),
+        auto: cli.auto_answer,
+    };
+
+    let sandbox = start_sandbox(cli, &project_dir)?;
+    let (mut init_payload, _) = init_payload(&project_dir_str, cli, &config);
+    if !trusted {
+        init_payload["hooks"] = json!(null);
+        init_payload["verify"] = json!(null);
+        init_payload["readOnly"] = json!(true);
+    }
+    if let Some(sandbox) = &sandbox {
+        init_payload["sandbox"] = sandbox_payload(sandbox, &env_vars);
+    }
+
+    println!(
+        "\n> Running with agent: {}",
+        init_payload["agent"].as_str().unwrap_or("build")
+    );
+    println!("> Project: {}", cli.dir);
+    if !prompt.starts_with('/') {
+        println!("\n> You: {}\n", prompt);
+    }
+
     let init_result = client.call("initialize", init_payload)?;
-    let _state: ChatState =
+    let state: ChatState =
         serde_json::from_value(init_result.get("state").cloned().unwrap_or_default())
             .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
+    let (custom_agents, agent_errors) = load_agents(&project_dir);
+    for e in agent_errors {
+        eprintln!("Warning: {}", e);
+    }
+    if let Some(agent) = custom_agents.iter().find(|a| a.name == state.agent) {
+        client.call(
+            "set_agent",
+            json!({ "agent": agent.name, "definition": agent.payload() }),
+        )?;
+    }
+
+    if prompt.starts_with('/') {
+        let base_model = init_result
+            .get("baseModel")
+            .and_then(|v| v.as_str())
+            .unwrap_or("default")
+            .to_string();
+        let mut app = App::new(state, project_dir_str, base_model);
+        app.set_read_only(cli.read_only || !trusted);
+        app.redactor = project_redactor(&project_dir).map(Arc::new);
+        let client = Arc::new(Mutex::new(client));
+        println!();
+        let ok = run_repl_command(&mut app, &client, prompt);
+        // Commands like /retry start a turn; wait for it like a prompt would.
+        let loading = client.lock().unwrap().call("get_state", json!({}))?["isLoading"]
+            .as_bool()
+            .unwrap_or(false);
+        if loading {
+            wait_for_run(
+                &mut client.lock().unwrap(),
+                &run_rx,
+                &presets,
+                config.questions.auto_answer_after,
+                None,
+            )?;
+        }
+        client.lock().unwrap().shutdown();
+        if let Some(sandbox) = &sandbox {
+            sandbox.stop();
+        }
+        let _ = notify_handle.join();
+        if !ok {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     client.call("send_message", json!({ "content": prompt }))?;
+    wait_for_run(
+        &mut client,
+        &run_rx,
+        &presets,
+        config.questions.auto_answer_after,
+        None,
+    )?;
     let state_value = client.call("get_state", json!({}))?;
     let state: ChatState =
         serde_json::from_value(state_value).map_err(|e| anyhow!("Failed to parse state: {e}"))?;
@@ -478,6 +1838,468 @@ fn run_non_interactive(root: &Path, cli: &Cli, prompt: &str) -> Result<()> {
         state.tokens.input, state.tokens.output
     );
     client.shutdown();
+    if let Some(sandbox) = &sandbox {
+        sandbox.stop();
+    }
+    let _ = notify_handle.join();
+    Ok(())
+}
+
+/// `stratuscode eval`: runs every case, writes the reports, and fails when any
+/// case did.
+fn run_eval(
+    root: &Path,
+    cli: &Cli,
+    suite_path: &Path,
+    junit: Option<&Path>,
+    json_path: Option<&Path>,
+    keep: bool,
+) -> Result<()> {
+    let suite = Suite::load(suite_path).map_err(|e| anyhow!(e))?;
+    let mut results = Vec::new();
+    for (i, case) in suite.cases.iter().enumerate() {
+        println!("\n== {} ({}/{})", case.name, i + 1, suite.cases.len());
+        let started = Instant::now();
+        let dir = match eval::copy_fixture(&suite.dir.join(&case.fixture), i) {
+            Ok(dir) => dir,
+            Err(e) => {
+                let error = format!("Failed to copy {}: {}", case.fixture.display(), e);
+                println!("ERROR {}", error);
+                results.push(CaseResult::errored(&case.name, started.elapsed(), error));
+                continue;
+            }
+        };
+        let deadline = started + suite.timeout(case);
+        let result = match run_eval_case(root, cli, &dir, case, deadline) {
+            Ok(output) => CaseResult::checked(case, started.elapsed(), &dir, &output),
+            Err(e) => CaseResult::errored(&case.name, started.elapsed(), e.to_string()),
+        };
+        match &result.error {
+            Some(error) => println!("ERROR {}", error),
+            None => {
+                for assertion in &result.assertions {
+                    let mark = if assertion.passed { "ok  " } else { "FAIL" };
+                    println!("{} {}", mark, assertion.name);
+                    if let Some(message) = &assertion.message {
+                        println!("     {}", message.replace('\n', "\n     "));
+                    }
+                }
+            }
+        }
+        if keep {
+            println!("Kept {}", dir.display());
+        } else {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        results.push(result);
+    }
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    println!(
+        "\n{}: {} passed, {} failed",
+        suite.name(),
+        passed,
+        results.len() - passed
+    );
+    if let Some(path) = junit {
+        std::fs::write(path, eval::junit_report(suite.name(), &results))?;
+    }
+    if let Some(path) = json_path {
+        std::fs::write(path, eval::json_report(suite.name(), &results))?;
+    }
+    if passed < results.len() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs one eval prompt in `dir` and returns the agent's answers from the
+/// turn. Questions are answered with their defaults straight away.
+fn run_eval_case(
+    root: &Path,
+    cli: &Cli,
+    dir: &Path,
+    case: &eval::Case,
+    deadline: Instant,
+) -> Result<String> {
+    let args = vec![backend_path(root)?.to_string_lossy().to_string()];
+    let config = ProjectConfig::load(dir).map_err(|e| anyhow!(e))?;
+    let (env_vars, env_errors) = resolve_env(&config.env);
+    for e in env_errors {
+        eprintln!("Warning: {}", e);
+    }
+    let (mut client, notify_rx) = BackendClient::spawn("bun", &args, &env_vars)?;
+    let (run_tx, run_rx) = mpsc::channel::<RunEvent>();
+    let notify_handle = spawn_turn_printer(notify_rx, run_tx);
+
+    let (mut init_payload, _) = init_payload(&dir.to_string_lossy(), cli, &config);
+    if let Some(agent) = &case.agent {
+        init_payload["agent"] = json!(agent);
+    }
+    let run = (|| -> Result<ChatState> {
+        let init_result = client.call("initialize", init_payload)?;
+        let state: ChatState =
+            serde_json::from_value(init_result.get("state").cloned().unwrap_or_default())
+                .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
+        let (custom_agents, agent_errors) = load_agents(dir);
+        for e in agent_errors {
+            eprintln!("Warning: {}", e);
+        }
+        if let Some(agent) = custom_agents.iter().find(|a| a.name == state.agent) {
+            client.call(
+                "set_agent",
+                json!({ "agent": agent.name, "definition": agent.payload() }),
+            )?;
+        }
+        client.call("send_message", json!({ "content": case.prompt }))?;
+        wait_for_run(
+            &mut client,
+            &run_rx,
+            &PresetAnswers::default(),
+            Some(0),
+            Some(deadline),
+        )?;
+        let state_value = client.call("get_state", json!({}))?;
+        serde_json::from_value(state_value).map_err(|e| anyhow!("Failed to parse state: {e}"))
+    })();
+    client.shutdown();
+    let _ = notify_handle.join();
+
+    let state = run?;
+    if let Some(error) = &state.error {
+        return Err(anyhow!("{}", error));
+    }
+    let output: Vec<&str> = state
+        .timeline_events
+        .iter()
+        .filter(|e| e.kind == "assistant" && e.parent_tool_call_id.is_none())
+        .map(|e| e.content.trim())
+        .collect();
+    Ok(output.join("\n\n"))
+}
+
+/// Prints tool calls (and diffs from their results) as they happen, and
+/// forwards loading and question changes to `wait_for_run`.
+fn spawn_turn_printer(
+    notify_rx: Receiver<BackendNotification>,
+    run_tx: Sender<RunEvent>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for notif in notify_rx.iter() {
+            match notif.method.as_str() {
+                "state" => {
+                    if let Ok(state) = serde_json::from_value::<ChatState>(notif.params) {
+                        let _ = run_tx.send(RunEvent::Loading(state.is_loading));
+                    }
+                    continue;
+                }
+                "question_pending" => {
+                    let question = notif
+                        .params
+                        .get("question")
+                        .and_then(|q| serde_json::from_value::<PendingQuestion>(q.clone()).ok());
+                    let _ = run_tx.send(RunEvent::Question(question));
+                    continue;
+                }
+                _ => {}
+            }
+            if notif.method == "timeline_event" {
+                if let Ok(event) = serde_json::from_value::<TimelineEvent>(notif.params) {
+                    if event.kind == "tool_call" {
+                        let icon = tool_icon(event.tool_name.as_deref().unwrap_or(""));
+                        println!(
+                            "\n{} {}",
+                            icon,
+                            event.tool_name.unwrap_or_else(|| "tool".to_string())
+                        );
+                        if !event.content.is_empty() {
+                            println!("   {}", format_tool_args(&event.content));
+                        }
+                    }
+                    if event.kind == "tool_result" {
+                        if let Some((_summary, diff_lines)) =
+                            extract_diff_summary(&event.content, 120)
+                        {
+                            for line in diff_lines.into_iter().take(120) {
+                                let mut out = String::new();
+                                for span in line.spans {
+                                    out.push_str(span.content.as_ref());
+                                }
+                                if !out.is_empty() {
+                                    println!("   {}", out);
+                                }
+                            }
+                        } else {
+                            // no output for non-diff tool results
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// `--no-tui`: a line-based loop for Emacs shell buffers, CI logs and screen
+/// readers. Each turn's tool calls and its answer are printed as plain text;
+/// slash commands go through the same parser as the TUI.
+fn run_repl(root: &Path, cli: &Cli) -> Result<()> {
+    let backend_path = backend_path(root)?;
+    let args = vec![backend_path.to_string_lossy().to_string()];
+    let project_dir = project_dir(cli);
+    let project_dir_str = project_dir.to_string_lossy().to_string();
+    let config = ProjectConfig::load(&project_dir).map_err(|e| anyhow!(e))?;
+    let mut trusted = is_trusted(&project_dir);
+    if !trusted {
+        print!(
+            "Trust {}? Its config can run commands and the agent can change files. [y/N] ",
+            project_dir.display()
+        );
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            trust_folder(&project_dir)?;
+            trusted = true;
+        } else {
+            println!("Not trusted: project commands are off and the agent is read-only.");
+        }
+    }
+    let (env_vars, env_errors) = if trusted {
+        resolve_env(&config.env)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    for e in env_errors {
+        eprintln!("Warning: {}", e);
+    }
+    let (client, notify_rx) = BackendClient::spawn("bun", &args, &env_vars)
+        .map_err(|e| anyhow!("Failed to start the backend with bun: {e}\n{OFFLINE_HINT}"))?;
+    let (_event_socket, notify_rx) = open_event_socket(cli, notify_rx)?;
+    let client = Arc::new(Mutex::new(client));
+    let (run_tx, run_rx) = mpsc::channel::<RunEvent>();
+    let notify_handle = spawn_turn_printer(notify_rx, run_tx);
+
+    let sandbox = start_sandbox(cli, &project_dir)?;
+    let (mut init_payload, _) = init_payload(&project_dir_str, cli, &config);
+    if !trusted {
+        init_payload["hooks"] = json!(null);
+        init_payload["verify"] = json!(null);
+        init_payload["readOnly"] = json!(true);
+    }
+    if let Some(sandbox) = &sandbox {
+        init_payload["sandbox"] = sandbox_payload(sandbox, &env_vars);
+    }
+    let init_result = client
+        .lock()
+        .unwrap()
+        .call("initialize", init_payload)
+        .map_err(|e| anyhow!("{e}\n{OFFLINE_HINT}"))?;
+    let state: ChatState =
+        serde_json::from_value(init_result.get("state").cloned().unwrap_or_default())
+            .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
+    let base_model = init_result
+        .get("baseModel")
+        .and_then(|v| v.as_str())
+        .unwrap_or("default")
+        .to_string();
+    let (custom_agents, agent_errors) = load_agents(&project_dir);
+    for e in agent_errors {
+        eprintln!("Warning: {}", e);
+    }
+    if let Some(agent) = custom_agents.iter().find(|a| a.name == state.agent) {
+        client.lock().unwrap().call(
+            "set_agent",
+            json!({ "agent": agent.name, "definition": agent.payload() }),
+        )?;
+    }
+    let mut app = App::new(state, project_dir_str, base_model);
+    app.set_read_only(cli.read_only || !trusted);
+    app.redactor = project_redactor(&project_dir).map(Arc::new);
+
+    println!(
+        "StratusCode {} · {} · {}",
+        env!("CARGO_PKG_VERSION"),
+        app.state.agent,
+        project_dir.display()
+    );
+    println!("Type a message, /help for commands, /quit or Ctrl+D to leave.");
+    let stdin = io::stdin();
+    loop {
+        print!("\n> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            break;
+        }
+        let line = line.trim();
+        match line {
+            "" => continue,
+            "/quit" | "/exit" => break,
+            "/help" => {
+                for cmd in commands_list() {
+                    println!("  /{:<14} {}", cmd.name, cmd.description);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if line.starts_with('/') {
+            run_repl_command(&mut app, &client, line);
+            continue;
+        }
+
+        let mut payload = json!({
+            "content": line,
+            "attachments": app.attachments.iter().map(attachment_payload).collect::<Vec<_>>(),
+        });
+        app.attachments.clear();
+        add_send_context(&app, &mut payload);
+        let sent = client.lock().unwrap().call("send_message", payload);
+        if let Err(e) = sent {
+            eprintln!("! {}", e);
+            continue;
+        }
+        wait_for_run(
+            &mut client.lock().unwrap(),
+            &run_rx,
+            &PresetAnswers::default(),
+            config.questions.auto_answer_after,
+            None,
+        )?;
+        let state_value = client.lock().unwrap().call("get_state", json!({}))?;
+        let mut state: ChatState = serde_json::from_value(state_value)
+            .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
+        if let Some(redactor) = &app.redactor {
+            redactor.redact_events(&mut state.timeline_events);
+        }
+        let turn_start = state
+            .timeline_events
+            .iter()
+            .rposition(|e| e.kind == "user")
+            .unwrap_or(0);
+        for event in &state.timeline_events[turn_start..] {
+            if event.kind == "assistant" && event.parent_tool_call_id.is_none() {
+                println!("\n{}", event.content.trim_end());
+            }
+        }
+        if let Some(error) = &state.error {
+            eprintln!("! {}", error);
+        }
+        app.state = state;
+    }
+
+    client.lock().unwrap().shutdown();
+    if let Some(sandbox) = &sandbox {
+        sandbox.stop();
+    }
     let _ = notify_handle.join();
     Ok(())
 }
+
+/// Starts the backend for an ACP session in `project_dir`. Stdout carries the
+/// protocol, so warnings go to stderr; the editor can't show the trust prompt,
+/// so an untrusted folder gets the read-only session you get by declining it.
+fn start_acp_backend(root: &Path, cli: &Cli, project_dir: &Path) -> Result<acp::Backend> {
+    let config = ProjectConfig::load(project_dir).map_err(|e| anyhow!(e))?;
+    let trusted = is_trusted(project_dir);
+    let (env_vars, env_errors) = if trusted {
+        resolve_env(&config.env)
+    } else {
+        eprintln!(
+            "{} is not trusted: project commands are off and the agent is read-only. Run stratuscode there once to trust it.",
+            project_dir.display()
+        );
+        (Vec::new(), Vec::new())
+    };
+    for e in env_errors {
+        eprintln!("Warning: {}", e);
+    }
+    let args = vec![backend_path(root)?.to_string_lossy().to_string()];
+    let (mut client, notifications) = BackendClient::spawn("bun", &args, &env_vars)
+        .map_err(|e| anyhow!("Failed to start the backend with bun: {e}\n{OFFLINE_HINT}"))?;
+    let sandbox = start_sandbox(cli, project_dir)?;
+    let (mut init_payload, _) = init_payload(&project_dir.to_string_lossy(), cli, &config);
+    if !trusted {
+        init_payload["hooks"] = json!(null);
+        init_payload["verify"] = json!(null);
+        init_payload["readOnly"] = json!(true);
+    }
+    if let Some(sandbox) = &sandbox {
+        init_payload["sandbox"] = sandbox_payload(sandbox, &env_vars);
+    }
+    let init_result = match client.call("initialize", init_payload) {
+        Ok(result) => result,
+        Err(e) => {
+            client.shutdown();
+            if let Some(sandbox) = &sandbox {
+                sandbox.stop();
+            }
+            return Err(e);
+        }
+    };
+    let state: ChatState =
+        serde_json::from_value(init_result.get("state").cloned().unwrap_or_default())
+            .map_err(|e| anyhow!("Failed to parse state: {e}"))?;
+    let (agents, agent_errors) = load_agents(project_dir);
+    for e in agent_errors {
+        eprintln!("Warning: {}", e);
+    }
+    if let Some(agent) = agents.iter().find(|a| a.name == state.agent) {
+        client.call(
+            "set_agent",
+            json!({ "agent": agent.name, "definition": agent.payload() }),
+        )?;
+    }
+    Ok(acp::Backend {
+        client,
+        notifications,
+        state,
+        agents,
+        sandbox,
+        redactor: project_redactor(project_dir),
+    })
+}
+
+/// Runs a slash command in the REPL (or from `--command`) and prints its
+/// toasts. Commands that open a picker or pager need the full-screen UI.
+/// Returns false when the command failed or couldn't run.
+fn run_repl_command(app: &mut App, client: &Arc<Mutex<BackendClient>>, line: &str) -> bool {
+    let Some((cmd, arg)) = parse_command(line) else {
+        println!("! {}", i18n::t("toast.unknown_command"));
+        return false;
+    };
+    if cmd.action == "session:history" {
+        match SessionStore::open().and_then(|store| store.list_sessions(&app.project_dir, 20)) {
+            Ok(list) => {
+                for sess in list {
+                    println!("  {}  {}", sess.id, sess.title);
+                }
+                return true;
+            }
+            Err(e) => {
+                println!("! {}", e);
+                return false;
+            }
+        }
+    }
+    execute_command(app, client, &cmd, arg);
+    let mut ok = true;
+    if app.mode != UiMode::Normal {
+        app.mode = UiMode::Normal;
+        app.pager = None;
+        println!("! /{} needs the full-screen UI", cmd.name);
+        ok = false;
+    }
+    for toast in app.toasts.drain(..) {
+        match toast.level {
+            ToastLevel::Info => println!("{}", toast.message),
+            ToastLevel::Warn => println!("! {}", toast.message),
+            ToastLevel::Error => {
+                println!("! {}", toast.message);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
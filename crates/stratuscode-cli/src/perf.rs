@@ -0,0 +1,101 @@
+//! Frame times, timeline size, line-cache hits and backend latency, for the
+//! `--perf` HUD (Ctrl+Shift+P) and the profile `/perf dump` writes.
+
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use crate::backend::CallTimings;
+
+/// Recent frames kept for the percentiles.
+const FRAMES_KEPT: usize = 240;
+
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    frames: VecDeque<Duration>,
+    /// Lines in the laid-out timeline at the last frame.
+    pub timeline_lines: usize,
+    pub rpc: CallTimings,
+}
+
+impl PerfStats {
+    pub fn record_frame(&mut self, elapsed: Duration) {
+        if self.frames.len() == FRAMES_KEPT {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(elapsed);
+    }
+
+    pub fn frames(&self) -> Vec<Duration> {
+        self.frames.iter().copied().collect()
+    }
+
+    pub fn rpc_times(&self) -> Vec<Duration> {
+        self.rpc.snapshot().into_iter().map(|(_, d)| d).collect()
+    }
+}
+
+/// The nearest-rank percentile (0–100) of `samples`.
+pub fn percentile(samples: &[Duration], p: f64) -> Option<Duration> {
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+pub fn format_ms(d: Option<Duration>) -> String {
+    match d {
+        Some(d) => format!("{:.1}ms", d.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+fn ms(d: Option<Duration>) -> Value {
+    d.map_or(Value::Null, |d| json!(d.as_secs_f64() * 1000.0))
+}
+
+fn summary(samples: &[Duration]) -> Value {
+    json!({
+        "count": samples.len(),
+        "p50Ms": ms(percentile(samples, 50.0)),
+        "p95Ms": ms(percentile(samples, 95.0)),
+        "p99Ms": ms(percentile(samples, 99.0)),
+        "maxMs": ms(samples.iter().max().copied()),
+    })
+}
+
+/// The profile `/perf dump` writes: summaries plus the raw samples.
+pub fn profile_json(perf: &PerfStats, events: usize, cache: (u64, u64)) -> Value {
+    let frames = perf.frames();
+    let calls = perf.rpc.snapshot();
+    let mut by_method: BTreeMap<&str, Vec<Duration>> = BTreeMap::new();
+    for (method, elapsed) in &calls {
+        by_method.entry(method).or_default().push(*elapsed);
+    }
+    let (hits, misses) = cache;
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "capturedAt": chrono::Local::now().to_rfc3339(),
+        "timeline": { "events": events, "lines": perf.timeline_lines },
+        "lineCache": {
+            "hits": hits,
+            "misses": misses,
+            "hitRate": (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+        },
+        "frames": {
+            "summary": summary(&frames),
+            "samplesMs": frames.iter().map(|d| d.as_secs_f64() * 1000.0).collect::<Vec<_>>(),
+        },
+        "rpc": {
+            "summary": summary(&perf.rpc_times()),
+            "byMethod": by_method
+                .iter()
+                .map(|(method, samples)| (method.to_string(), summary(samples)))
+                .collect::<serde_json::Map<_, _>>(),
+            "calls": calls
+                .iter()
+                .map(|(method, d)| json!({ "method": method, "ms": d.as_secs_f64() * 1000.0 }))
+                .collect::<Vec<_>>(),
+        },
+    })
+}
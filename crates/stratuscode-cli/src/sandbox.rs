@@ -0,0 +1,134 @@
+//! `--sandbox docker[:image]`: a long-lived container per project, with the
+//! project mounted at the same path, that the backend runs bash commands in.
+//! Sessions sharing the container each hold a lease file in it; the last one
+//! out stops it.
+
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const DEFAULT_IMAGE: &str = "ubuntu:24.04";
+/// Inside the container: one empty file per session using it.
+const LEASE_DIR: &str = "/tmp/.stratuscode-sessions";
+
+#[derive(Debug)]
+pub struct Sandbox {
+    pub container: String,
+    pub image: String,
+    /// This session's file in `LEASE_DIR`.
+    lease: String,
+}
+
+/// The image from `docker` or `docker:<image>`.
+pub fn parse_sandbox(spec: &str) -> Result<String> {
+    match spec.split_once(':') {
+        None if spec == "docker" => Ok(DEFAULT_IMAGE.to_string()),
+        Some(("docker", image)) if !image.is_empty() => Ok(image.to_string()),
+        _ => bail!(
+            "Unknown sandbox '{}' (expected docker or docker:<image>)",
+            spec
+        ),
+    }
+}
+
+impl Sandbox {
+    /// Reuses the project's container when it exists, starting it if it was
+    /// stopped, and creates it otherwise. The container is kept between runs
+    /// so anything installed in it stays. Leases left by sessions that died
+    /// are cleared when the container wasn't running.
+    pub fn start(project_dir: &Path, image: &str) -> Result<Self> {
+        let container = container_name(project_dir, image);
+        let running = docker(&["inspect", "-f", "{{.State.Running}}", &container]);
+        let started = match running.as_deref().map(str::trim) {
+            Ok("true") => false,
+            Ok(_) => {
+                docker(&["start", &container])?;
+                true
+            }
+            Err(_) => {
+                let dir = project_dir.to_string_lossy();
+                let mount = format!("{}:{}", dir, dir);
+                docker(&[
+                    "run", "-d", "--name", &container, "-v", &mount, "-w", &dir, image, "sleep",
+                    "infinity",
+                ])?;
+                true
+            }
+        };
+        let lease = std::process::id().to_string();
+        let clear = if started {
+            format!("rm -rf {}; ", LEASE_DIR)
+        } else {
+            String::new()
+        };
+        let script = format!(
+            "{}mkdir -p {dir} && touch {dir}/{}",
+            clear,
+            lease,
+            dir = LEASE_DIR
+        );
+        docker(&["exec", &container, "sh", "-c", &script])?;
+        Ok(Self {
+            container,
+            image: image.to_string(),
+            lease,
+        })
+    }
+
+    /// Gives up this session's lease and stops the container once no other
+    /// session holds one.
+    pub fn stop(&self) {
+        let script = format!(
+            "rm -f {dir}/{}; ls {dir} | wc -l",
+            self.lease,
+            dir = LEASE_DIR
+        );
+        let others = docker(&["exec", &self.container, "sh", "-c", &script])
+            .ok()
+            .and_then(|out| out.trim().parse::<usize>().ok());
+        if others == Some(0) {
+            let _ = Command::new("docker")
+                .args(["stop", "-t", "1", &self.container])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("docker:{}", self.image)
+    }
+}
+
+/// One container per project and image, e.g. `stratuscode-myapp-3f2a9c1b`.
+fn container_name(project_dir: &Path, image: &str) -> String {
+    // FNV-1a, so the name stays the same across builds.
+    let hash = format!("{}\0{}", project_dir.display(), image)
+        .bytes()
+        .fold(0x811c_9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        });
+    let base: String = project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("stratuscode-{}-{:08x}", base, hash)
+}
+
+fn docker(args: &[&str]) -> Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("Failed to run docker: {}", e))?;
+    if !output.status.success() {
+        bail!(
+            "docker {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,133 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::app::FileResult;
+
+/// Files larger than this are skipped; they are almost always generated.
+const MAX_SYMBOL_FILE_BYTES: u64 = 256 * 1024;
+const MAX_SYMBOLS: usize = 50_000;
+
+/// A top-level definition found in a project file, used for `@symbol`
+/// completion.
+#[derive(Debug, Clone)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub kind: &'static str,
+    pub path: String,
+    /// 1-based line number.
+    pub line: usize,
+}
+
+impl SymbolEntry {
+    /// The `path:line` reference inserted into the prompt. The backend
+    /// includes the file like any other mention, excerpted around the line
+    /// when it's too large to send whole.
+    pub fn reference(&self) -> String {
+        format!("{}:{}", self.path, self.line)
+    }
+}
+
+struct LanguagePatterns {
+    extensions: &'static [&'static str],
+    patterns: Vec<Regex>,
+}
+
+/// Per-language definition patterns. Each has a `kind` and a `name` capture.
+/// Line-based matching is deliberately shallow: it finds definitions, not
+/// references, and that is all completion needs.
+fn languages() -> &'static [LanguagePatterns] {
+    static LANGUAGES: OnceLock<Vec<LanguagePatterns>> = OnceLock::new();
+    LANGUAGES.get_or_init(|| {
+        let compile = |patterns: &[&str]| -> Vec<Regex> {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).expect("valid symbol pattern"))
+                .collect()
+        };
+        vec![
+            LanguagePatterns {
+                extensions: &["rs"],
+                patterns: compile(&[
+                    r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+\S+\s+)?(?P<kind>fn|struct|enum|trait|type|mod|const|static)\s+(?P<name>[A-Za-z_]\w*)",
+                    r"^\s*(?P<kind>macro_rules)!\s*(?P<name>[A-Za-z_]\w*)",
+                ]),
+            },
+            LanguagePatterns {
+                extensions: &["ts", "tsx", "js", "jsx", "mjs", "cjs"],
+                patterns: compile(&[
+                    r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?P<kind>function|class|interface|type|enum)\*?\s+(?P<name>[A-Za-z_$][\w$]*)",
+                    r"^(?:export\s+)?(?P<kind>const)\s+(?P<name>[A-Za-z_$][\w$]*)\s*=\s*(?:async\s+)?(?:\([^)]*\)|[A-Za-z_$][\w$]*)\s*=>",
+                ]),
+            },
+            LanguagePatterns {
+                extensions: &["py"],
+                patterns: compile(&[r"^\s*(?:async\s+)?(?P<kind>def|class)\s+(?P<name>[A-Za-z_]\w*)"]),
+            },
+            LanguagePatterns {
+                extensions: &["go"],
+                patterns: compile(&[
+                    r"^(?P<kind>func)\s+(?:\([^)]*\)\s*)?(?P<name>[A-Za-z_]\w*)",
+                    r"^(?P<kind>type)\s+(?P<name>[A-Za-z_]\w*)",
+                ]),
+            },
+        ]
+    })
+}
+
+fn normalize_kind(kind: &str) -> &'static str {
+    match kind {
+        "fn" | "function" | "def" | "func" => "fn",
+        "struct" => "struct",
+        "enum" => "enum",
+        "trait" => "trait",
+        "interface" => "interface",
+        "class" => "class",
+        "mod" => "mod",
+        "macro_rules" => "macro",
+        "type" => "type",
+        _ => "const",
+    }
+}
+
+/// Scans the indexed files for definitions in the supported languages.
+pub fn build_symbol_index(project_dir: &Path, files: &[FileResult]) -> Vec<SymbolEntry> {
+    let mut symbols = Vec::new();
+    for file in files.iter().filter(|f| !f.is_dir) {
+        let ext = match Path::new(&file.relative_path).extension() {
+            Some(ext) => ext.to_string_lossy(),
+            None => continue,
+        };
+        let Some(language) = languages()
+            .iter()
+            .find(|l| l.extensions.contains(&ext.as_ref()))
+        else {
+            continue;
+        };
+        let full_path = project_dir.join(&file.relative_path);
+        match std::fs::metadata(&full_path) {
+            Ok(meta) if meta.len() <= MAX_SYMBOL_FILE_BYTES => {}
+            _ => continue,
+        }
+        let Ok(content) = std::fs::read_to_string(&full_path) else {
+            continue;
+        };
+        for (idx, line) in content.lines().enumerate() {
+            for pattern in &language.patterns {
+                if let Some(caps) = pattern.captures(line) {
+                    symbols.push(SymbolEntry {
+                        name: caps["name"].to_string(),
+                        kind: normalize_kind(&caps["kind"]),
+                        path: file.relative_path.clone(),
+                        line: idx + 1,
+                    });
+                    break;
+                }
+            }
+            if symbols.len() >= MAX_SYMBOLS {
+                return symbols;
+            }
+        }
+    }
+    symbols
+}
@@ -0,0 +1,126 @@
+use crate::config::user_config_dir;
+use std::path::PathBuf;
+
+/// A prompt snippet from the templates directory, named after its file.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub body: String,
+}
+
+impl PromptTemplate {
+    /// First non-empty line, shown in the picker.
+    pub fn summary(&self) -> &str {
+        self.body
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("")
+    }
+}
+
+/// Values substituted for `{{selection}}`, `{{file}}` and `{{arg}}`.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    /// Text on the clipboard.
+    pub selection: String,
+    /// The most recently @-mentioned file.
+    pub file: String,
+    /// Whatever followed the template name in `/tpl <name> ...`.
+    pub arg: String,
+}
+
+/// `$XDG_CONFIG_HOME/stratuscode/templates`, else
+/// `~/.config/stratuscode/templates`.
+pub fn templates_dir() -> Option<PathBuf> {
+    Some(user_config_dir()?.join("templates"))
+}
+
+/// Every readable file in the templates directory, sorted by name. Hidden
+/// files are skipped; the extension isn't part of the name.
+pub fn load_templates() -> Vec<PromptTemplate> {
+    let Some(entries) = templates_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<PromptTemplate> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let body = std::fs::read_to_string(&path).ok()?;
+            Some(PromptTemplate { name, body })
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates.dedup_by(|a, b| a.name == b.name);
+    templates
+}
+
+/// Fills in the known placeholders in one pass, so placeholders inside the
+/// substituted text stay as typed; anything else in braces is left alone.
+pub fn expand_template(body: &str, context: &TemplateContext) -> String {
+    let mut out = String::new();
+    let mut rest = body.trim_end();
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = [
+            ("{{selection}}", &context.selection),
+            ("{{file}}", &context.file),
+            ("{{arg}}", &context.arg),
+        ]
+        .into_iter()
+        .find(|(placeholder, _)| tail.starts_with(placeholder));
+        match value {
+            Some((placeholder, value)) => {
+                out.push_str(value);
+                rest = &tail[placeholder.len()..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_placeholders_once() {
+        let context = TemplateContext {
+            selection: "uses {{file}}".to_string(),
+            file: "src/main.rs".to_string(),
+            arg: "{{arg}} twice".to_string(),
+        };
+        assert_eq!(
+            expand_template("Review {{file}}: {{selection}} ({{arg}})\n\n", &context),
+            "Review src/main.rs: uses {{file}} ({{arg}} twice)"
+        );
+        assert_eq!(
+            expand_template("{{ name }} {{{arg}}", &context),
+            "{{ name }} {{{arg}} twice"
+        );
+    }
+
+    #[test]
+    fn summary_is_first_non_empty_line() {
+        let template = |body: &str| PromptTemplate {
+            name: "review".to_string(),
+            body: body.to_string(),
+        };
+        assert_eq!(
+            template("\n  \n  Review this\nmore").summary(),
+            "Review this"
+        );
+        assert_eq!(template("   \n").summary(), "");
+    }
+}
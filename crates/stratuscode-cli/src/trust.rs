@@ -0,0 +1,46 @@
+//! Folders the user has trusted, in `~/.stratuscode/trusted-folders.json`.
+//! Until a project is trusted its config can't run commands and the agent
+//! can't change files or use the shell.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TrustList {
+    folders: Vec<PathBuf>,
+}
+
+fn trust_list_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        Path::new(&home)
+            .join(".stratuscode")
+            .join("trusted-folders.json"),
+    )
+}
+
+fn load() -> TrustList {
+    trust_list_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Trusting a folder trusts everything under it.
+pub fn is_trusted(dir: &Path) -> bool {
+    load().folders.iter().any(|folder| dir.starts_with(folder))
+}
+
+pub fn trust_folder(dir: &Path) -> std::io::Result<()> {
+    let path = trust_list_path()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "HOME is not set"))?;
+    let mut list = load();
+    if !list.folders.iter().any(|folder| folder == dir) {
+        list.folders.push(dir.to_path_buf());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&list)?)
+}
@@ -1,18 +1,39 @@
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, BorderType, Borders, Paragraph, Wrap};
+use ratatui::widgets::block::Title;
+use ratatui::widgets::{
+    Block, BorderType, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+    Widget, Wrap,
+};
 use ratatui::{Frame, Terminal};
 
-use pulldown_cmark::{Event as MdEvent, Options as MdOptions, Parser as MdParser, Tag as MdTag};
-use textwrap::wrap;
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::app::{file_query_from_input, filter_files};
-use crate::app::{App, UiMode};
-use crate::commands::{commands_list, filter_commands, filter_models, sort_models_by_provider};
+use textwrap::wrap;
+use unicode_width::UnicodeWidthStr;
+
+use crate::agents::BUILT_IN_AGENTS;
+use crate::app::{file_query_from_input, FilePreview, MentionTarget};
+use crate::app::{
+    App, FollowMode, ToastLevel, UiMode, NOTIFICATIONS_PAGE, PAGER_PAGE, QUESTION_VIEWPORT,
+};
+use crate::attachments::{attachment_glyph, format_size, is_large_paste, unfetched_url};
+use crate::changes::{FileChange, TouchedFile};
+use crate::commands::{commands_list, filter_commands};
 use crate::constants::*;
+use crate::i18n::{t, tf};
+use crate::perf::{format_ms, percentile};
+use crate::render::{
+    build_timeline_lines, format_clock, render_markdown, wrap_plain_lines, Redact, TimelineFilter,
+    TimelineOptions,
+};
+use crate::text::{format_number, truncate_start, truncate_text};
+use crate::usage::{context_composition, estimate_cost, sparkline, usage_by_model};
 
 pub fn render_ui(
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
@@ -39,11 +60,34 @@ pub fn render_ui(
 
         let status_lines = format_status_lines(app, inner_width);
         let (display_input, cursor_display_idx) =
-            compute_display_input_with_cursor(&app.input, app.cursor);
-        let input_placeholder = if app.input.trim().is_empty() {
-            Some("Type / for commands")
-        } else {
+            compute_display_input_with_cursor(&app.input, app.cursor, &attachment_chips(app));
+        let input_placeholder = if !app.input.trim().is_empty() {
             None
+        } else if app.retry_payload.is_some() && !app.state.is_loading {
+            Some(t("ui.placeholder_retry").to_string())
+        } else if let Some(call) = app
+            .retryable_tool()
+            .filter(|_| !app.state.is_loading && !app.tool_retrying)
+        {
+            Some(tf(
+                "ui.placeholder_tool_retry",
+                &[("tool", &call.tool_name.as_deref().unwrap_or("tool"))],
+            ))
+        } else if let Some(title) = app.session_title().filter(|_| app.title_offer) {
+            Some(tf("ui.placeholder_title_offer", &[("title", &title)]))
+        } else if let Some(marker) = app.focused_compaction() {
+            Some(
+                if app.expanded_summaries.contains(&marker.id) {
+                    t("ui.placeholder_summary_hide")
+                } else {
+                    t("ui.placeholder_summary_show")
+                }
+                .to_string(),
+            )
+        } else if !app.state.is_loading && app.failed_verify().is_some() {
+            Some(t("ui.placeholder_verify").to_string())
+        } else {
+            Some(t("ui.placeholder").to_string())
         };
 
         let max_input_lines = 3usize;
@@ -66,6 +110,8 @@ pub fn render_ui(
         let input_count = (visible_input_lines.len() as u16).max(1);
         let mut unified_height = overlay_lines_count
             + (todo_lines.len() as u16)
+            + (build_attachment_strip(app).len() as u16)
+            + (build_context_strip(app).len() as u16)
             + input_count
             + (status_lines.len() as u16)
             + 2;
@@ -80,33 +126,89 @@ pub fn render_ui(
             ])
             .split(size);
 
-        let timeline_area = chunks[0];
+        let mut timeline_area = chunks[0];
         let input_area = chunks[1];
-
-        let timeline_lines = build_timeline_lines_cached(app, timeline_area.width as usize);
+        if app.sidebar_open && !app.inline_mode && size.width >= SIDEBAR_MIN_WIDTH {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(0)])
+                .split(timeline_area);
+            render_file_sidebar(frame, columns[0], app);
+            timeline_area = columns[1];
+        }
+
+        let all_timeline_lines = build_timeline_lines_cached(app, timeline_area.width as usize);
+        app.perf.timeline_lines = all_timeline_lines.len();
+        // Inline mode only shows what hasn't been moved into scrollback yet.
+        let visible_from = if app.inline_mode {
+            app.inline_flushed.min(all_timeline_lines.len())
+        } else {
+            0
+        };
+        let timeline_lines = &all_timeline_lines[visible_from..];
         let view_height = timeline_area.height as usize;
         let total_lines = timeline_lines.len();
+        // Go with new content when following; otherwise hold the view on the
+        // lines it was showing.
+        if let Some(follows) = app.follow_growth.take() {
+            if app.auto_scroll && follows {
+                app.scroll_from_bottom = 0;
+            } else {
+                let grown = total_lines.saturating_sub(app.timeline_lines_seen);
+                app.scroll_from_bottom = app.scroll_from_bottom.saturating_add(grown);
+            }
+        }
+        app.timeline_lines_seen = total_lines;
         let max_scroll = total_lines.saturating_sub(view_height);
         if app.scroll_from_bottom > max_scroll {
             app.scroll_from_bottom = max_scroll;
         }
+        if let Some(id) = app.jump_to_event.take() {
+            if let Some((line, _)) = app.timeline_anchors.iter().find(|(_, e)| *e == id) {
+                let line = line.saturating_sub(visible_from);
+                app.scroll_from_bottom = total_lines
+                    .saturating_sub(view_height + line)
+                    .min(max_scroll);
+                app.auto_scroll = app.scroll_from_bottom == 0;
+            }
+        }
         let scroll_from_bottom = app.scroll_from_bottom;
         let start = total_lines.saturating_sub(view_height + scroll_from_bottom);
+        // Scrolled back to the top of the resident window: page in more.
+        if start == 0
+            && scroll_from_bottom > 0
+            && app.state.earlier_events > 0
+            && !app.earlier_loading
+        {
+            app.earlier_requested = app.state.timeline_events.first().map(|e| e.id.clone());
+        }
+        // The message at the top of the view, or the latest one while following.
+        app.focused_event = if scroll_from_bottom == 0 {
+            app.timeline_anchors.last()
+        } else {
+            app.timeline_anchors
+                .iter()
+                .rev()
+                .find(|(line, _)| *line <= start + visible_from)
+                .or(app.timeline_anchors.first())
+        }
+        .map(|(_, id)| id.clone());
         let slice = if total_lines <= view_height {
-            &timeline_lines[..]
+            timeline_lines
         } else {
             &timeline_lines[start..start + view_height]
         };
         let timeline_text = Text::from(slice.to_vec());
 
         if app.show_splash
+            && !app.inline_mode
             && app.state.timeline_events.is_empty()
             && matches!(app.mode, UiMode::Normal)
             && !app.state.is_loading
         {
             render_splash(frame, timeline_area, app);
         } else {
-            let title = Line::from(vec![
+            let mut title_spans = vec![
                 Span::styled(
                     "Stratus",
                     Style::default()
@@ -117,25 +219,52 @@ pub fn render_ui(
                     "Code",
                     Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
                 ),
-            ]);
+            ];
+            if let Some(text) = &app.title_rename {
+                title_spans.push(Span::styled(
+                    format!(" · {} {}▏", t("ui.title_rename"), text),
+                    Style::default().fg(COLOR_WARNING),
+                ));
+            } else if let Some(title) = app.session_title() {
+                let room = (timeline_area.width as usize).saturating_sub(30).max(10);
+                title_spans.push(Span::styled(
+                    format!(" · {}", truncate_text(&title, room)),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
+            }
+            if app.timeline_filter != TimelineFilter::All {
+                title_spans.push(Span::styled(
+                    format!(" · filter: {}", app.timeline_filter.label()),
+                    Style::default().fg(COLOR_WARNING),
+                ));
+            }
+            let title = Line::from(title_spans);
+            let mut block = panel_block(app.accessible)
+                .title(title)
+                .style(Style::default().bg(COLOR_BG_ALT));
+            let mut right = vec![follow_indicator(app)];
+            if total_lines > view_height {
+                let position = format_scroll_position(start, view_height, total_lines);
+                right.push(Span::styled(
+                    format!(" {}", position),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
+            }
+            block = block.title(Title::from(Line::from(right)).alignment(Alignment::Right));
             let timeline = Paragraph::new(timeline_text)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded)
-                        .border_style(Style::default().fg(COLOR_BORDER))
-                        .title(title)
-                        .style(Style::default().bg(COLOR_BG_ALT)),
-                )
+                .block(block)
                 .wrap(Wrap { trim: false });
             frame.render_widget(timeline, timeline_area);
+            if total_lines > view_height && !app.accessible {
+                render_timeline_scrollbar(frame, timeline_area, timeline_lines, start, max_scroll);
+            }
         }
 
         render_unified_input_box(
             frame,
             input_area,
             app,
-            input_placeholder,
+            input_placeholder.as_deref(),
             visible_input_lines,
             input_start,
             &display_input,
@@ -148,237 +277,306 @@ pub fn render_ui(
         );
 
         render_overlay(frame, size, app);
+        if app.perf_hud {
+            render_perf_hud(frame, size, app);
+        }
+        if app.no_color {
+            strip_colors(frame.buffer_mut());
+        }
     })?;
     Ok(())
 }
 
-pub fn build_timeline_lines_cached(app: &mut App, width: usize) -> Vec<Line<'static>> {
-    if app.state.is_loading {
-        return build_timeline_lines(&app.state, app.compact_view, width, app.spinner_index);
-    }
-    if app.timeline_cache_rev == app.timeline_revision
-        && app.timeline_cache_width == width
-        && app.timeline_cache_compact == app.compact_view
-    {
-        return app.timeline_cache.clone();
-    }
-    let lines = build_timeline_lines(&app.state, app.compact_view, width, app.spinner_index);
-    app.timeline_cache = lines.clone();
-    app.timeline_cache_rev = app.timeline_revision;
-    app.timeline_cache_width = width;
-    app.timeline_cache_compact = app.compact_view;
-    lines
+/// The `--perf` HUD in the top-right corner. Figures are from before this
+/// frame, since its own render time isn't known yet.
+fn render_perf_hud(frame: &mut Frame, size: Rect, app: &App) {
+    let frames = app.perf.frames();
+    let rpc = app.perf.rpc_times();
+    let (hits, misses) = app.event_line_cache.hits_and_misses();
+    let hit_rate = if hits + misses > 0 {
+        format!("{:.0}%", hits as f64 * 100.0 / (hits + misses) as f64)
+    } else {
+        "-".to_string()
+    };
+    let rows = [
+        tf(
+            "ui.perf_frame",
+            &[
+                ("p50", &format_ms(percentile(&frames, 50.0))),
+                ("p95", &format_ms(percentile(&frames, 95.0))),
+                ("max", &format_ms(frames.iter().max().copied())),
+            ],
+        ),
+        tf(
+            "ui.perf_timeline",
+            &[
+                ("lines", &format_number(app.perf.timeline_lines as u64)),
+                (
+                    "events",
+                    &format_number(app.state.timeline_events.len() as u64),
+                ),
+            ],
+        ),
+        tf("ui.perf_cache", &[("rate", &hit_rate)]),
+        tf(
+            "ui.perf_rpc",
+            &[
+                ("p50", &format_ms(percentile(&rpc, 50.0))),
+                ("p95", &format_ms(percentile(&rpc, 95.0))),
+                ("p99", &format_ms(percentile(&rpc, 99.0))),
+            ],
+        ),
+    ];
+    let width = rows
+        .iter()
+        .map(|row| UnicodeWidthStr::width(row.as_str()))
+        .max()
+        .unwrap_or(0) as u16
+        + 4;
+    let area = Rect {
+        x: size.width.saturating_sub(width + 1),
+        y: 1,
+        width: width.min(size.width),
+        height: (rows.len() as u16 + 2).min(size.height),
+    };
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|row| Line::from(Span::styled(row, Style::default().fg(COLOR_TEXT_DIM))))
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(COLOR_BORDER))
+                .title(Span::styled(" perf ", Style::default().fg(COLOR_TEXT_DIM)))
+                .style(Style::default().bg(COLOR_BG)),
+        ),
+        area,
+    );
 }
 
-pub fn build_timeline_lines(
-    state: &crate::backend::ChatState,
-    compact: bool,
-    width: usize,
-    spinner_index: usize,
-) -> Vec<Line<'static>> {
-    let mut lines: Vec<Line> = Vec::new();
-    let content_width = width.saturating_sub(2).max(10);
-
-    let is_blank = |line: &Line<'static>| line.spans.iter().all(|s| s.content.is_empty());
-    let push_gap = |lines: &mut Vec<Line<'static>>, count: usize| {
-        for _ in 0..count {
-            if let Some(last) = lines.last() {
-                if !is_blank(last) {
-                    lines.push(Line::from(""));
-                }
-            }
+/// `NO_COLOR`: drops colors but keeps bold and italics. Cells with their own
+/// background (the selected row in a list) are reversed instead, so the
+/// selection stays visible.
+fn strip_colors(buf: &mut Buffer) {
+    for cell in buf.content.iter_mut() {
+        if cell.bg != Color::Reset && cell.bg != COLOR_BG {
+            cell.modifier |= Modifier::REVERSED;
         }
-    };
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
 
-    let mut in_assistant_block = false;
-    for event in &state.timeline_events {
-        if event.kind == "user" {
-            in_assistant_block = false;
-            push_gap(&mut lines, 3);
-            lines.push(Line::from(vec![
-                Span::styled(
-                    "> ",
-                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
-                ),
-                Span::styled(
-                    "You",
-                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
-                ),
-            ]));
-            let mut body: Vec<Line> = wrap_plain_lines(&event.content, content_width)
-                .into_iter()
-                .map(Line::from)
-                .collect();
-            if let Some(atts) = &event.attachments {
-                if !atts.is_empty() {
-                    body.push(Line::from(format!(
-                        "[{} attachment{}]",
-                        atts.len(),
-                        if atts.len() == 1 { "" } else { "s" }
-                    )));
-                }
+/// Largest batch handed to a single `insert_before` call.
+const INLINE_FLUSH_CHUNK: usize = 500;
+
+/// In `--inline` mode, moves finished timeline lines above the viewport into
+/// the terminal's scrollback. While a turn is running, everything from its
+/// user message on stays in the viewport.
+pub fn flush_inline_scrollback(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    app: &mut App,
+) -> anyhow::Result<()> {
+    let width = terminal.size()?.width as usize;
+    let lines = build_timeline_lines_cached(app, width);
+    if lines.len() < app.inline_flushed {
+        // The timeline was cleared or replaced by another session.
+        app.inline_flushed = 0;
+    }
+    let stable = if app.state.is_loading || app.has_pending_send() {
+        lines.iter().rposition(is_user_header).unwrap_or(0)
+    } else {
+        lines.len()
+    };
+    if stable <= app.inline_flushed {
+        return Ok(());
+    }
+    for chunk in lines[app.inline_flushed..stable].chunks(INLINE_FLUSH_CHUNK) {
+        let text = Text::from(chunk.to_vec());
+        terminal.insert_before(chunk.len() as u16, |buf| {
+            Paragraph::new(text).render(buf.area, buf);
+            if app.no_color {
+                strip_colors(buf);
             }
-            lines.extend(indent_lines(body, 2));
-            continue;
-        }
+        })?;
+    }
+    app.inline_flushed = stable;
+    Ok(())
+}
 
-        if !in_assistant_block {
-            push_gap(&mut lines, 3);
-            lines.push(Line::from(vec![
-                Span::styled(
-                    "> ",
-                    Style::default()
-                        .fg(Color::White)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Span::styled(
-                    "Stratus",
-                    Style::default()
-                        .fg(Color::White)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Span::styled(
-                    "Code",
-                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
-                ),
-            ]));
-            in_assistant_block = true;
-        }
+/// The follow mode, dimmed while scrolled away from the latest.
+fn follow_indicator(app: &App) -> Span<'static> {
+    let (icon, color) = match app.follow_mode {
+        FollowMode::Manual => ("⏸", COLOR_TEXT_DIM),
+        _ if app.auto_scroll && app.scroll_from_bottom == 0 => ("⇣", COLOR_CYAN),
+        _ => ("⇣", COLOR_TEXT_DIM),
+    };
+    Span::styled(
+        format!("{} {}", icon, app.follow_mode.label()),
+        Style::default().fg(color),
+    )
+}
 
-        match event.kind.as_str() {
-            "assistant" => {
-                let markdown_lines = if event.streaming.unwrap_or(false) {
-                    wrap_plain_lines(&event.content, content_width)
-                        .into_iter()
-                        .map(Line::from)
-                        .collect()
-                } else {
-                    render_markdown(&event.content, content_width)
-                };
-                lines.extend(indent_lines(markdown_lines, 2));
-            }
-            "reasoning" => {
-                if compact {
-                    continue;
-                }
-                lines.push(Line::from(vec![Span::styled(
-                    "~ Reasoning",
-                    Style::default()
-                        .fg(COLOR_TEXT_DIM)
-                        .add_modifier(Modifier::ITALIC),
-                )]));
-                let body: Vec<Line> = wrap_plain_lines(&event.content, content_width)
-                    .into_iter()
-                    .map(|l| {
-                        Line::from(vec![Span::styled(
-                            l,
-                            Style::default()
-                                .fg(COLOR_TEXT_DIM)
-                                .add_modifier(Modifier::ITALIC),
-                        )])
-                    })
-                    .collect();
-                lines.extend(indent_lines(body, 2));
-            }
-            "tool_call" => {
-                let label = event
-                    .tool_name
-                    .clone()
-                    .unwrap_or_else(|| "tool".to_string());
-                let info = tool_display(&label);
-                let status_icon = match event.status.as_deref().unwrap_or("pending") {
-                    "running" => "[.]",
-                    "failed" => "[x]",
-                    "completed" => "[ok]",
-                    _ => "[ ]",
-                };
-                let args = format_tool_args(&event.content);
-                let mut spans = vec![
-                    Span::styled(status_icon, Style::default().fg(info.color)),
-                    Span::raw(" "),
-                    Span::styled(
-                        info.label,
-                        Style::default().fg(info.color).add_modifier(Modifier::BOLD),
-                    ),
-                ];
-                if !args.is_empty() {
-                    spans.push(Span::raw(" "));
-                    spans.push(Span::styled(args, Style::default().fg(COLOR_TEXT_DIM)));
-                }
-                lines.push(Line::from(spans));
-            }
-            "tool_result" => {
-                if !in_assistant_block {
-                    lines.push(Line::from(vec![
-                        Span::styled(
-                            "> ",
-                            Style::default()
-                                .fg(Color::White)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::styled(
-                            "Stratus",
-                            Style::default()
-                                .fg(Color::White)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                    ]));
-                    in_assistant_block = true;
-                }
-                if let Some((summary, diff_lines)) =
-                    extract_diff_summary(&event.content, content_width)
-                {
-                    lines.push(Line::from(vec![
-                        Span::styled("[ok]", Style::default().fg(COLOR_SUCCESS)),
-                        Span::raw(" "),
-                        Span::styled(
-                            "Result",
-                            Style::default()
-                                .fg(COLOR_SUCCESS)
-                                .add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw(" "),
-                        Span::styled(summary, Style::default().fg(COLOR_TEXT_DIM)),
-                    ]));
-                    lines.extend(indent_lines(diff_lines.into_iter().take(120).collect(), 2));
-                }
-            }
-            "status" => {
-                let is_error = event.content.to_lowercase().contains("error");
-                let color = if is_error { COLOR_ERROR } else { COLOR_WARNING };
-                lines.push(Line::from(vec![Span::styled(
-                    format!("! {}", event.content),
-                    Style::default().fg(color),
-                )]));
-            }
-            _ => {
-                lines.push(Line::from(event.content.clone()));
-            }
-        }
+/// "123/4,096 lines · 37%", where 123 is the last visible line.
+fn format_scroll_position(start: usize, view_height: usize, total_lines: usize) -> String {
+    let bottom = (start + view_height).min(total_lines);
+    let max_start = total_lines.saturating_sub(view_height);
+    let pct = (start * 100).checked_div(max_start).unwrap_or(100);
+    format!(
+        " {}/{} lines · {}% ",
+        format_number(bottom as u64),
+        format_number(total_lines as u64),
+        pct
+    )
+}
+
+fn render_timeline_scrollbar(
+    frame: &mut Frame,
+    area: Rect,
+    lines: &[Line<'static>],
+    start: usize,
+    max_scroll: usize,
+) {
+    let track = Rect {
+        x: area.x,
+        y: area.y.saturating_add(1),
+        width: area.width,
+        height: area.height.saturating_sub(2),
+    };
+    if track.height == 0 {
+        return;
+    }
+    let mut state = ScrollbarState::new(max_scroll).position(start);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_symbol(Some("│"))
+        .thumb_symbol("┃")
+        .track_style(Style::default().fg(COLOR_BORDER))
+        .thumb_style(Style::default().fg(COLOR_TEXT_DIM));
+    frame.render_stateful_widget(scrollbar, track, &mut state);
+
+    // Mark where the latest user message sits within the whole timeline.
+    if let Some(user_line) = lines.iter().rposition(is_user_header) {
+        let row = (user_line * track.height as usize) / lines.len().max(1);
+        let marker_area = Rect {
+            x: track.right().saturating_sub(1),
+            y: track.y + (row as u16).min(track.height - 1),
+            width: 1,
+            height: 1,
+        };
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                "◆",
+                Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
+            )),
+            marker_area,
+        );
     }
+}
 
-    if !lines.is_empty() {
-        lines.push(Line::from(""));
-        lines.push(Line::from(""));
+fn is_user_header(line: &Line<'static>) -> bool {
+    line.spans
+        .windows(2)
+        .any(|pair| pair[0].content == "> " && pair[1].content == "You")
+}
+
+fn timeline_options<'a>(
+    app: &App,
+    width: usize,
+    busy_label: Option<&'a str>,
+    expanded_summaries: &'a [String],
+    collapsed_subagents: &'a [String],
+    redact: Option<&'a dyn Redact>,
+) -> TimelineOptions<'a> {
+    TimelineOptions {
+        compact: app.compact_view,
+        timestamps: app.show_timestamps,
+        collapsed_subagents,
+        filter: app.timeline_filter,
+        width,
+        diff_max_lines: app.diff_max_lines,
+        spinner: app.spinner(),
+        now_ms: chrono::Utc::now().timestamp_millis(),
+        busy_label,
+        earlier_events: app.state.earlier_events,
+        loading_earlier: app.earlier_loading,
+        expanded_summaries,
+        redact,
     }
+}
 
-    if state.is_loading {
-        push_gap(&mut lines, 1);
-        let spinner = SPINNER_FRAMES[spinner_index % SPINNER_FRAMES.len()];
-        lines.push(Line::from(vec![
-            Span::styled(spinner, Style::default().fg(COLOR_CODE)),
-            Span::raw(" "),
-            Span::styled(
-                "Thinking...",
-                Style::default()
-                    .fg(COLOR_TEXT_DIM)
-                    .add_modifier(Modifier::ITALIC),
-            ),
-        ]));
+/// Returns the laid-out timeline. The result is shared with `app.timeline_cache`,
+/// so callers should only materialize the visible slice.
+pub fn build_timeline_lines_cached(app: &mut App, width: usize) -> Arc<Vec<Line<'static>>> {
+    if app.state.is_loading {
+        let busy_label = app
+            .retry_status
+            .as_ref()
+            .map(|r| r.label())
+            .unwrap_or_else(|| app.loading_verb());
+        let expanded = app.expanded_summaries.clone();
+        let collapsed = app.collapsed_subagents.clone();
+        let redactor = app.redactor.clone();
+        let options = timeline_options(
+            app,
+            width,
+            Some(&busy_label),
+            &expanded,
+            &collapsed,
+            redactor.as_deref().map(|r| r as &dyn Redact),
+        );
+        let (lines, anchors) = build_timeline_lines(
+            &app.state,
+            &app.tool_outputs,
+            &options,
+            Some(&mut app.event_line_cache),
+        );
+        app.timeline_anchors = anchors;
+        return Arc::new(lines);
+    }
+    if app.timeline_cache_rev == app.timeline_revision
+        && app.timeline_cache_width == width
+        && app.timeline_cache_compact == app.compact_view
+        && app.timeline_cache_timestamps == app.show_timestamps
+        && app.timeline_cache_filter == app.timeline_filter
+    {
+        return Arc::clone(&app.timeline_cache);
     }
+    let expanded = app.expanded_summaries.clone();
+    let collapsed = app.collapsed_subagents.clone();
+    let redactor = app.redactor.clone();
+    let options = timeline_options(
+        app,
+        width,
+        None,
+        &expanded,
+        &collapsed,
+        redactor.as_deref().map(|r| r as &dyn Redact),
+    );
+    let (lines, anchors) = build_timeline_lines(
+        &app.state,
+        &app.tool_outputs,
+        &options,
+        Some(&mut app.event_line_cache),
+    );
+    let lines = Arc::new(lines);
+    app.timeline_anchors = anchors;
+    app.timeline_cache = Arc::clone(&lines);
+    app.timeline_cache_rev = app.timeline_revision;
+    app.timeline_cache_width = width;
+    app.timeline_cache_compact = app.compact_view;
+    app.timeline_cache_timestamps = app.show_timestamps;
+    app.timeline_cache_filter = app.timeline_filter;
     lines
 }
 
+const MENTION_PREVIEW_LINES: usize = 15;
+/// Below this input box width the preview pane is dropped.
+const MENTION_PREVIEW_MIN_WIDTH: u16 = 70;
+
 #[allow(clippy::too_many_arguments)]
 pub fn render_unified_input_box(
     frame: &mut Frame,
@@ -399,10 +597,7 @@ pub fn render_unified_input_box(
         "Input",
         Style::default().fg(COLOR_TEXT_DIM),
     )]);
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(Style::default().fg(COLOR_BORDER))
+    let block = panel_block(app.accessible)
         .title(title)
         .style(Style::default().bg(COLOR_BG_ALT));
     frame.render_widget(block.clone(), rect);
@@ -413,6 +608,10 @@ pub fn render_unified_input_box(
     let mut sections: Vec<(Vec<Line>, u16)> = Vec::new();
     let mut overlay_block: Option<Vec<Line>> = None;
     let mut overlay_index: Option<usize> = None;
+    let overlay_preview = overlay
+        .as_ref()
+        .and_then(|o| o.preview.clone())
+        .filter(|_| inner.width >= MENTION_PREVIEW_MIN_WIDTH);
     if !todo_lines.is_empty() {
         sections.push((todo_lines.to_owned(), todo_lines.len() as u16));
     }
@@ -425,8 +624,13 @@ pub fn render_unified_input_box(
                 .add_modifier(Modifier::BOLD),
         )]));
         lines.append(&mut overlay_lines);
-        if lines.len() < overlay_min_lines as usize {
-            let pad = overlay_min_lines as usize - lines.len();
+        let min_lines = overlay_preview
+            .as_ref()
+            .map(|p| p.len())
+            .unwrap_or(0)
+            .max(overlay_min_lines as usize);
+        if lines.len() < min_lines {
+            let pad = min_lines - lines.len();
             for _ in 0..pad {
                 lines.push(Line::from(""));
             }
@@ -462,6 +666,14 @@ pub fn render_unified_input_box(
             }
         }
     }
+    let context_strip = build_context_strip(app);
+    if !context_strip.is_empty() {
+        sections.push((context_strip.clone(), context_strip.len() as u16));
+    }
+    let attachment_strip = build_attachment_strip(app);
+    if !attachment_strip.is_empty() {
+        sections.push((attachment_strip.clone(), attachment_strip.len() as u16));
+    }
     sections.push((input_spans.clone(), input_spans.len() as u16));
     sections.push((status_lines.clone(), status_lines.len() as u16));
 
@@ -527,10 +739,29 @@ pub fn render_unified_input_box(
         .split(inner);
 
     for (idx, (lines, _)) in sections.iter().enumerate() {
+        let mut area = chunks[idx];
+        if let (Some(preview), true) = (&overlay_preview, Some(idx) == overlay_index) {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(area);
+            area = halves[0];
+            let preview_block = Block::default()
+                .borders(Borders::LEFT)
+                .border_set(if app.accessible {
+                    BLANK_BORDER
+                } else {
+                    border::PLAIN
+                })
+                .border_style(Style::default().fg(COLOR_BORDER))
+                .style(Style::default().bg(COLOR_BG_ALT));
+            let para = Paragraph::new(Text::from(preview.clone())).block(preview_block);
+            frame.render_widget(para, halves[1]);
+        }
         let para = Paragraph::new(Text::from(lines.clone()))
             .wrap(Wrap { trim: false })
             .style(Style::default().bg(COLOR_BG_ALT));
-        frame.render_widget(para, chunks[idx]);
+        frame.render_widget(para, area);
     }
 
     if matches!(app.mode, UiMode::Normal | UiMode::FileMention) {
@@ -558,12 +789,160 @@ pub fn render_overlay(frame: &mut Frame, rect: Rect, app: &App) {
                 Line::from("StratusCode"),
                 Line::from("Terminal-first AI coding agent."),
             ];
-            render_modal(frame, rect, "About", lines);
+            render_modal(frame, rect, app, "About", lines);
+        }
+        UiMode::PasteConfirm => {
+            let Some(text) = &app.pending_paste else {
+                return;
+            };
+            let mut lines = vec![
+                Line::from(tf(
+                    "ui.paste_summary",
+                    &[
+                        ("lines", &format_number(text.lines().count() as u64)),
+                        ("chars", &format_number(text.chars().count() as u64)),
+                        ("size", &format_size(text.len())),
+                    ],
+                )),
+                Line::from(Span::styled(
+                    t("ui.paste_inline_warning"),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )),
+                Line::from(""),
+            ];
+            for (i, option) in crate::input::PASTE_CONFIRM_OPTIONS.iter().enumerate() {
+                let selected = i == app.paste_confirm_selected;
+                let style = if selected {
+                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(COLOR_TEXT)
+                };
+                lines.push(Line::from(Span::styled(
+                    format!("{}{}", if selected { "› " } else { "  " }, t(option)),
+                    style,
+                )));
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                t("ui.paste_hint"),
+                Style::default().fg(COLOR_TEXT_DIM),
+            )));
+            render_modal(frame, rect, app, t("ui.paste_title"), lines);
+        }
+        UiMode::PlanActions => render_plan_viewer(frame, rect, app),
+        UiMode::Telemetry => render_modal(frame, rect, app, "Token usage", telemetry_lines(app)),
+        UiMode::Pager => {
+            let Some(pager) = &app.pager else {
+                return;
+            };
+            let end = (pager.scroll + PAGER_PAGE).min(pager.lines.len());
+            let mut lines: Vec<Line> = pager.lines[pager.scroll..end].to_vec();
+            if let Some((line, _)) = pager.hunk.and_then(|i| pager.hunks.get(i)) {
+                if let Some(selected) = line
+                    .checked_sub(pager.scroll)
+                    .and_then(|i| lines.get_mut(i))
+                {
+                    *selected = selected.clone().patch_style(
+                        Style::default()
+                            .fg(Color::Black)
+                            .bg(COLOR_CODE)
+                            .add_modifier(Modifier::BOLD),
+                    );
+                }
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "lines {}–{} of {} · ↑↓/PgUp/PgDn scroll · g/G top/bottom · q close",
+                    (pager.scroll + 1).min(end),
+                    end,
+                    pager.lines.len()
+                ),
+                Style::default().fg(COLOR_TEXT_DIM),
+            )));
+            if !pager.hunks.is_empty() {
+                let hint = match pager.hunk {
+                    Some(i) => tf(
+                        "ui.pager_hunk_selected",
+                        &[("index", &(i + 1)), ("count", &pager.hunks.len())],
+                    ),
+                    None => tf("ui.pager_hunks", &[("count", &pager.hunks.len())]),
+                };
+                lines.push(Line::from(Span::styled(
+                    hint,
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )));
+            }
+            render_modal(frame, rect, app, &pager.title, lines);
+        }
+        UiMode::TrustPrompt => {
+            let lines = vec![
+                Line::from(format!(
+                    "StratusCode hasn't been trusted in {} yet.",
+                    app.project_dir
+                )),
+                Line::from(""),
+                Line::from(
+                    "Trusted, the agent can edit files and run shell commands here, and \
+                     .stratuscode/config.toml can run hooks and a verify command and read \
+                     [env] from the keychain.",
+                ),
+                Line::from(
+                    "Restricted, the agent can only read and search; the config's commands \
+                     and [env] are ignored.",
+                ),
+                Line::from(""),
+                Line::from(Span::styled(
+                    "Enter/y trust this folder · n stay restricted · q quit",
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )),
+            ];
+            render_modal(frame, rect, app, "Trust this folder?", lines);
+        }
+        UiMode::Recovery => {
+            let Some(session) = &app.interrupted else {
+                return;
+            };
+            let mut lines = vec![Line::from(format!(
+                "'{}' was interrupted mid-turn at {}.",
+                session.title,
+                format_clock(session.updated_at)
+            ))];
+            if let Some(message) = &session.last_message {
+                lines.push(Line::from(Span::styled(
+                    format!("Last message: {}", message),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )));
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!(
+                "{} finished tool result{} will be replayed; {} unfinished call{} will be marked failed.",
+                session.completed_tools,
+                if session.completed_tools == 1 { "" } else { "s" },
+                session.pending_tools,
+                if session.pending_tools == 1 { "" } else { "s" }
+            )));
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                "Enter/y resume · n dismiss · Esc ask next time",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )));
+            render_modal(frame, rect, app, "Interrupted session", lines);
         }
         UiMode::Normal => {
-            if let Some((msg, _)) = &app.toast {
-                let lines = vec![Line::from(msg.clone())];
-                render_modal(frame, rect, "Info", lines);
+            // Stacked oldest first; the title names the most severe one.
+            if let Some(level) = app.toasts.iter().map(|t| t.level).max() {
+                let lines = app
+                    .toasts
+                    .iter()
+                    .map(|t| {
+                        Line::from(Span::styled(
+                            t.message.clone(),
+                            Style::default().fg(toast_color(t.level)),
+                        ))
+                    })
+                    .collect();
+                render_modal(frame, rect, app, level.label(), lines);
             }
         }
         _ => {}
@@ -571,10 +950,7 @@ pub fn render_overlay(frame: &mut Frame, rect: Rect, app: &App) {
 }
 
 pub fn render_splash(frame: &mut Frame, rect: Rect, app: &App) {
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(Style::default().fg(COLOR_BORDER))
+    let block = panel_block(app.accessible)
         .title(Line::from(vec![
             Span::styled(
                 "Stratus",
@@ -593,7 +969,13 @@ pub fn render_splash(frame: &mut Frame, rect: Rect, app: &App) {
     let is_compact = inner.width < 100;
 
     let mut lines: Vec<Line> = Vec::new();
-    if is_compact {
+    if app.accessible {
+        // The block-letter logo is noise to a screen reader.
+        lines.push(Line::from(Span::styled(
+            "StratusCode",
+            Style::default().fg(COLOR_TEXT).add_modifier(Modifier::BOLD),
+        )));
+    } else if is_compact {
         for i in 0..S_LOGO.len() {
             let line = Line::from(vec![
                 Span::styled(
@@ -637,14 +1019,12 @@ pub fn render_splash(frame: &mut Frame, rect: Rect, app: &App) {
         .model_override
         .clone()
         .unwrap_or_else(|| app.base_model.clone());
-    let mut display_path = app.project_dir.clone();
     let max_path = inner.width.saturating_sub(30) as usize;
-    if display_path.len() > max_path && max_path > 6 {
-        display_path = format!(
-            "...{}",
-            &display_path[display_path.len() - (max_path - 3)..]
-        );
-    }
+    let display_path = if max_path > 6 {
+        truncate_start(&app.project_dir, max_path)
+    } else {
+        app.project_dir.clone()
+    };
 
     if is_compact {
         lines.push(Line::from(vec![Span::styled(
@@ -678,34 +1058,400 @@ pub fn render_splash(frame: &mut Frame, rect: Rect, app: &App) {
     frame.render_widget(para, area);
 }
 
-fn render_modal(frame: &mut Frame, rect: Rect, title: &str, lines: Vec<Line>) {
+/// The proposed plan rendered as markdown above the accept/edit/keep actions.
+fn render_plan_viewer(frame: &mut Frame, rect: Rect, app: &App) {
     let width = rect.width.saturating_sub(6);
-    let height = (lines.len() as u16 + 4).min(rect.height.saturating_sub(4));
+    let height = rect.height.saturating_sub(4);
     let area = centered_rect(width, height, rect);
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(Style::default().fg(COLOR_BORDER))
-        .title(Span::styled(title, Style::default().fg(COLOR_TEXT_DIM)))
+    let block = panel_block(app.accessible)
+        .title(Span::styled("Plan", Style::default().fg(COLOR_TEXT_DIM)))
         .style(Style::default().bg(COLOR_BG_ALT));
-    let para = Paragraph::new(lines)
-        .block(block)
-        .wrap(Wrap { trim: false })
-        .style(Style::default().fg(COLOR_TEXT).bg(COLOR_BG_ALT));
-    frame.render_widget(para, area);
-}
-
-fn centered_rect(width: u16, height: u16, rect: Rect) -> Rect {
-    let x = rect.x + (rect.width.saturating_sub(width)) / 2;
-    let y = rect.y + (rect.height.saturating_sub(height)) / 2;
-    Rect {
-        x,
-        y,
-        width,
-        height,
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    if inner.height < 3 {
+        return;
     }
-}
-
+    let body_height = inner.height.saturating_sub(2);
+    let body = Rect {
+        height: body_height,
+        ..inner
+    };
+    let footer = Rect {
+        y: inner.y + body_height,
+        height: 2,
+        ..inner
+    };
+
+    let lines = match &app.plan_content {
+        Some(plan) if !plan.trim().is_empty() => render_markdown(plan, inner.width as usize),
+        _ => vec![Line::from(Span::styled(
+            "The plan file is empty.",
+            Style::default().fg(COLOR_TEXT_DIM),
+        ))],
+    };
+    let max_scroll = lines.len().saturating_sub(body_height as usize);
+    let scroll = app.plan_scroll.min(max_scroll);
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(scroll)
+        .take(body_height as usize)
+        .collect();
+    frame.render_widget(
+        Paragraph::new(visible).style(Style::default().fg(COLOR_TEXT).bg(COLOR_BG_ALT)),
+        body,
+    );
+
+    let mut actions = Vec::new();
+    for (i, option) in crate::input::PLAN_ACTION_OPTIONS.iter().enumerate() {
+        let selected = i == app.plan_action_selected;
+        let style = if selected {
+            Style::default()
+                .fg(Color::Black)
+                .bg(COLOR_CODE)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(COLOR_TEXT)
+        };
+        actions.push(Span::styled(format!(" {} ", option), style));
+        actions.push(Span::raw("  "));
+    }
+    let hint = format!(
+        "↑↓ scroll{} · ←→ choose · Enter · a accept · e edit · Esc keep planning",
+        if max_scroll > 0 {
+            format!(" ({}/{})", scroll, max_scroll)
+        } else {
+            String::new()
+        }
+    );
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(actions),
+            Line::from(Span::styled(hint, Style::default().fg(COLOR_TEXT_DIM))),
+        ])
+        .style(Style::default().bg(COLOR_BG_ALT)),
+        footer,
+    );
+}
+
+/// Rows of the Ctrl+I panel: session totals, a per-model breakdown, a
+/// sparkline of output per turn and the estimated context composition.
+fn telemetry_lines(app: &App) -> Vec<Line<'static>> {
+    let dim = Style::default().fg(COLOR_TEXT_DIM);
+    let text = Style::default().fg(COLOR_TEXT);
+    let mut lines = Vec::new();
+    let models = usage_by_model(&app.usage_turns);
+    let costs: Vec<Option<f64>> = models.iter().map(estimate_cost).collect();
+    let cost_label = |cost: Option<f64>| match cost {
+        Some(cost) => format!("~${:.2}", cost),
+        None => "cost n/a".to_string(),
+    };
+
+    let total_cached: u64 = models.iter().map(|m| m.cached).sum();
+    let total_cost = if costs.iter().any(Option::is_some) {
+        Some(costs.iter().flatten().sum())
+    } else {
+        None
+    };
+    lines.push(Line::from(vec![
+        Span::styled("Session  ", dim),
+        Span::styled(
+            format!(
+                "{} in · {} out · {} cached · {} turns · {}",
+                format_number(app.state.tokens.input),
+                format_number(app.state.tokens.output),
+                format_number(total_cached),
+                app.usage_turns.len(),
+                cost_label(total_cost)
+            ),
+            text,
+        ),
+    ]));
+    lines.push(Line::from(""));
+
+    lines.push(Line::from(Span::styled("By model", dim)));
+    if models.is_empty() {
+        lines.push(Line::from(Span::styled("  No completed turns yet.", dim)));
+    }
+    for (usage, cost) in models.iter().zip(&costs) {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {}", usage.model),
+                Style::default().fg(COLOR_TEXT_MUTED),
+            ),
+            Span::styled(
+                format!(
+                    "  {} turn{} · {} in · {} out · {} cached · {}",
+                    usage.turns,
+                    if usage.turns == 1 { "" } else { "s" },
+                    format_number(usage.input),
+                    format_number(usage.output),
+                    format_number(usage.cached),
+                    cost_label(*cost)
+                ),
+                text,
+            ),
+        ]));
+    }
+    lines.push(Line::from(""));
+
+    if !app.usage_turns.is_empty() {
+        let outputs: Vec<u64> = app.usage_turns.iter().map(|t| t.output).collect();
+        lines.push(Line::from(vec![
+            Span::styled("Output per turn  ", dim),
+            Span::styled(sparkline(&outputs, 40), Style::default().fg(COLOR_PURPLE)),
+        ]));
+    }
+    let usage = &app.state.context_usage;
+    let mut context = vec![
+        Span::styled("Context  ", dim),
+        Span::styled(
+            format!(
+                "{} of {} ({}%)",
+                format_number(usage.used),
+                format_number(usage.limit),
+                usage.percent
+            ),
+            text,
+        ),
+    ];
+    let composition = context_composition(&app.state.messages);
+    if let Some(b) = &usage.breakdown {
+        context.push(Span::styled(
+            format!(
+                "  system {} · memory {} · conversation {}",
+                format_number(b.system),
+                format_number(b.memory),
+                format_number(b.conversation)
+            ),
+            dim,
+        ));
+    } else if !composition.is_empty() {
+        let parts: Vec<String> = composition
+            .iter()
+            .map(|(label, share)| format!("{} {:.0}%", label, share * 100.0))
+            .collect();
+        context.push(Span::styled(format!("  ~ {}", parts.join(" · ")), dim));
+    }
+    lines.push(Line::from(context));
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Costs are estimates from list prices · Ctrl+I/Esc close",
+        dim,
+    )));
+    lines
+}
+
+fn toast_color(level: ToastLevel) -> Color {
+    match level {
+        ToastLevel::Info => COLOR_TEXT,
+        ToastLevel::Warn => COLOR_WARNING,
+        ToastLevel::Error => COLOR_ERROR,
+    }
+}
+
+fn render_modal(frame: &mut Frame, rect: Rect, app: &App, title: &str, lines: Vec<Line>) {
+    let width = rect.width.saturating_sub(6);
+    let height = (lines.len() as u16 + 4).min(rect.height.saturating_sub(4));
+    let area = centered_rect(width, height, rect);
+    let block = panel_block(app.accessible)
+        .title(Span::styled(title, Style::default().fg(COLOR_TEXT_DIM)))
+        .style(Style::default().bg(COLOR_BG_ALT));
+    let para = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(COLOR_TEXT).bg(COLOR_BG_ALT));
+    frame.render_widget(para, area);
+}
+
+/// Spaces in place of box-drawing characters, keeping the layout intact.
+const BLANK_BORDER: border::Set = border::Set {
+    top_left: " ",
+    top_right: " ",
+    bottom_left: " ",
+    bottom_right: " ",
+    vertical_left: " ",
+    vertical_right: " ",
+    horizontal_top: " ",
+    horizontal_bottom: " ",
+};
+
+/// `/changes`: files grouped under their directories, with per-file and
+/// per-directory line counts.
+pub fn build_changes_lines(files: &[TouchedFile]) -> Vec<Line<'static>> {
+    let mut groups: Vec<(&str, Vec<(&str, &TouchedFile)>)> = Vec::new();
+    for file in files {
+        let (dir, name) = split_dir(&file.path);
+        match groups.last_mut() {
+            Some((last, entries)) if *last == dir => entries.push((name, file)),
+            _ => groups.push((dir, vec![(name, file)])),
+        }
+    }
+    let name_width = files
+        .iter()
+        .map(|f| UnicodeWidthStr::width(split_dir(&f.path).1))
+        .max()
+        .unwrap_or(0);
+    let counts = |additions: usize, deletions: usize| {
+        vec![
+            Span::styled(
+                format!("+{}", additions),
+                Style::default().fg(COLOR_SUCCESS),
+            ),
+            Span::raw(" "),
+            Span::styled(format!("-{}", deletions), Style::default().fg(COLOR_ERROR)),
+        ]
+    };
+    let mut lines = Vec::new();
+    for (dir, entries) in groups {
+        let (additions, deletions) = entries
+            .iter()
+            .fold((0, 0), |(a, d), (_, f)| (a + f.additions, d + f.deletions));
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        let mut header = vec![Span::styled(
+            format!("{}/  ", dir),
+            Style::default()
+                .fg(COLOR_TEXT_MUTED)
+                .add_modifier(Modifier::BOLD),
+        )];
+        header.extend(counts(additions, deletions));
+        lines.push(Line::from(header));
+        for (name, file) in entries {
+            let mut spans = vec![
+                Span::styled(
+                    format!("  {} ", file.change.marker()),
+                    Style::default().fg(change_color(file.change)),
+                ),
+                Span::styled(
+                    format!("{:<width$}  ", name, width = name_width),
+                    Style::default().fg(COLOR_TEXT),
+                ),
+            ];
+            spans.extend(counts(file.additions, file.deletions));
+            lines.push(Line::from(spans));
+        }
+    }
+    lines
+}
+
+/// "src/app.rs" as ("src", "app.rs"); top-level files go under ".".
+fn split_dir(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or((".", path))
+}
+
+fn change_color(change: FileChange) -> Color {
+    match change {
+        FileChange::Modified => COLOR_WARNING,
+        FileChange::Created => COLOR_SUCCESS,
+        FileChange::Deleted => COLOR_ERROR,
+        FileChange::Read => COLOR_TEXT_DIM,
+    }
+}
+
+const SIDEBAR_WIDTH: u16 = 32;
+/// Narrower terminals leave the whole width to the timeline.
+const SIDEBAR_MIN_WIDTH: u16 = 80;
+
+/// Files touched this session, grouped under their directories.
+fn render_file_sidebar(frame: &mut Frame, area: Rect, app: &mut App) {
+    let files = app.touched_files();
+    if app.sidebar_selected >= files.len() {
+        app.sidebar_selected = files.len().saturating_sub(1);
+    }
+    let focused = app.mode == UiMode::FileSidebar;
+    let inner_width = area.width.saturating_sub(2) as usize;
+    let mut lines = Vec::new();
+    let mut selected_line = 0;
+    let mut current_dir = None;
+    for (i, file) in files.iter().enumerate() {
+        let (dir, name) = split_dir(&file.path);
+        if current_dir != Some(dir) {
+            current_dir = Some(dir);
+            lines.push(Line::from(Span::styled(
+                truncate_start(&format!("{}/", dir), inner_width),
+                Style::default().fg(COLOR_TEXT_DIM),
+            )));
+        }
+        let selected = focused && i == app.sidebar_selected;
+        if selected {
+            selected_line = lines.len();
+        }
+        let counts = if file.additions + file.deletions > 0 {
+            format!(" +{} -{}", file.additions, file.deletions)
+        } else {
+            String::new()
+        };
+        let name_width = inner_width.saturating_sub(4 + UnicodeWidthStr::width(counts.as_str()));
+        let name_style = if selected {
+            Style::default()
+                .fg(Color::Black)
+                .bg(COLOR_CODE)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(COLOR_TEXT)
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                if selected { "› " } else { "  " },
+                Style::default().fg(COLOR_CODE),
+            ),
+            Span::styled(
+                file.change.marker(),
+                Style::default().fg(change_color(file.change)),
+            ),
+            Span::raw(" "),
+            Span::styled(truncate_text(name, name_width), name_style),
+            Span::styled(counts, Style::default().fg(COLOR_TEXT_DIM)),
+        ]));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(
+            t("ui.sidebar_empty"),
+            Style::default().fg(COLOR_TEXT_DIM),
+        )));
+    }
+    let view_height = area.height.saturating_sub(2) as usize;
+    let scroll = (selected_line + 1).saturating_sub(view_height);
+    let border = if focused { COLOR_CODE } else { COLOR_BORDER };
+    let block = panel_block(app.accessible)
+        .border_style(Style::default().fg(border))
+        .title(Span::styled(
+            t("ui.sidebar_title"),
+            Style::default().fg(COLOR_TEXT_MUTED),
+        ))
+        .style(Style::default().bg(COLOR_BG_ALT));
+    let sidebar = Paragraph::new(Text::from(lines))
+        .block(block)
+        .scroll((scroll as u16, 0));
+    frame.render_widget(sidebar, area);
+}
+
+/// A bordered panel; accessible mode draws the border as blank space so
+/// screen readers don't read out box-drawing characters.
+fn panel_block(accessible: bool) -> Block<'static> {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(COLOR_BORDER));
+    if accessible {
+        block.border_set(BLANK_BORDER)
+    } else {
+        block.border_type(BorderType::Rounded)
+    }
+}
+
+fn centered_rect(width: u16, height: u16, rect: Rect) -> Rect {
+    let x = rect.x + (rect.width.saturating_sub(width)) / 2;
+    let y = rect.y + (rect.height.saturating_sub(height)) / 2;
+    Rect {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
 fn line_width(line: &Line) -> usize {
     line.spans
         .iter()
@@ -716,9 +1462,11 @@ fn line_width(line: &Line) -> usize {
 pub struct InlineOverlay {
     pub title: String,
     pub lines: Vec<Line<'static>>,
+    /// Shown beside `lines` when the input box is wide enough.
+    pub preview: Option<Vec<Line<'static>>>,
 }
 
-fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
+fn build_inline_overlay(app: &mut App, width: usize) -> Option<InlineOverlay> {
     match app.mode {
         UiMode::CommandPalette => {
             let commands = filter_commands(&commands_list(), &app.command_query);
@@ -736,6 +1484,7 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                 return Some(InlineOverlay {
                     title: "Commands".to_string(),
                     lines,
+                    preview: None,
                 });
             }
             let selected = app.command_selected.min(commands.len().saturating_sub(1));
@@ -766,27 +1515,48 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
             Some(InlineOverlay {
                 title: "Commands".to_string(),
                 lines,
+                preview: None,
             })
         }
         UiMode::FileMention => {
             let query = file_query_from_input(&app.input, app.cursor);
-            let results = filter_files(&app.file_index, &query, 10);
+            let results = app.mention_matches(&query);
             let mut lines = Vec::new();
             lines.push(Line::from(vec![
                 Span::styled("Search: ", Style::default().fg(COLOR_TEXT_DIM)),
                 Span::styled(query.clone(), Style::default().fg(COLOR_TEXT)),
             ]));
             if results.is_empty() {
+                let hint = if app.related_inflight {
+                    "Searching related code..."
+                } else if app.file_index_loading {
+                    "Indexing files..."
+                } else {
+                    "No files found. Run /reindex."
+                };
                 lines.push(Line::from(vec![Span::styled(
-                    "No files found. Run /reindex.",
+                    hint,
                     Style::default().fg(COLOR_TEXT_DIM),
                 )]));
                 return Some(InlineOverlay {
                     title: "File Mention".to_string(),
                     lines,
+                    preview: None,
                 });
             }
-            for (i, file) in results.iter().enumerate() {
+            for (i, found) in results.iter().enumerate() {
+                if matches!(found.target, MentionTarget::Related(_))
+                    && !results[..i]
+                        .iter()
+                        .any(|r| matches!(r.target, MentionTarget::Related(_)))
+                {
+                    lines.push(Line::from(Span::styled(
+                        "Related code",
+                        Style::default()
+                            .fg(COLOR_TEXT_DIM)
+                            .add_modifier(Modifier::BOLD),
+                    )));
+                }
                 let selected = i == app.file_selected;
                 let style = if selected {
                     Style::default()
@@ -796,32 +1566,87 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                 } else {
                     Style::default().fg(COLOR_TEXT)
                 };
-                lines.push(Line::from(vec![
-                    Span::styled(if selected { "› " } else { "  " }, style),
-                    Span::styled(
-                        format!(
-                            "{}{}",
-                            file.relative_path,
-                            if file.is_dir { "/" } else { "" }
-                        ),
-                        style,
-                    ),
-                ]));
+                let matched_style = if selected {
+                    style.add_modifier(Modifier::UNDERLINED)
+                } else {
+                    style.fg(COLOR_YELLOW).add_modifier(Modifier::BOLD)
+                };
+                let mut spans = vec![Span::styled(if selected { "› " } else { "  " }, style)];
+                spans.extend(highlight_matches(
+                    found.target.label(),
+                    &found.indices,
+                    style,
+                    matched_style,
+                ));
+                match &found.target {
+                    MentionTarget::File(file) if file.is_dir => {
+                        spans.push(Span::styled("/", style));
+                    }
+                    MentionTarget::File(_) => {}
+                    MentionTarget::Symbol(symbol) => {
+                        let detail_style = if selected {
+                            style
+                        } else {
+                            Style::default().fg(COLOR_TEXT_DIM)
+                        };
+                        spans.push(Span::styled(
+                            format!("  {} · {}:{}", symbol.kind, symbol.path, symbol.line),
+                            detail_style,
+                        ));
+                    }
+                    MentionTarget::Related(related) => {
+                        let detail_style = if selected {
+                            style
+                        } else {
+                            Style::default().fg(COLOR_TEXT_DIM)
+                        };
+                        spans.push(Span::styled(
+                            format!("  lines {}-{}", related.start_line, related.end_line),
+                            detail_style,
+                        ));
+                    }
+                }
+                lines.push(Line::from(spans));
+            }
+            if app.related_inflight {
+                lines.push(Line::from(Span::styled(
+                    "Searching related code...",
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )));
             }
+            let preview = results
+                .get(app.file_selected)
+                .map(|found| build_mention_preview(app, &found.target));
             Some(InlineOverlay {
-                title: "File Mention".to_string(),
+                title: "File Mention · Ctrl+P pin".to_string(),
                 lines,
+                preview,
             })
         }
         UiMode::ModelPicker => {
-            let filtered = filter_models(&app.model_entries, &app.model_query);
-            let filtered = sort_models_by_provider(&filtered);
+            let filtered = app.visible_models();
             let mut lines = Vec::new();
-            lines.push(Line::from(vec![
+            let mut search = vec![
                 Span::styled("Search: ", Style::default().fg(COLOR_TEXT_DIM)),
                 Span::styled(app.model_query.clone(), Style::default().fg(COLOR_TEXT)),
-            ]));
-            if filtered.is_empty() {
+            ];
+            search.push(Span::styled(
+                "  (Ctrl+S star · Ctrl+T test)",
+                Style::default().fg(COLOR_TEXT_DIM),
+            ));
+            if app.models_loading && !app.model_entries.is_empty() {
+                search.push(Span::styled(
+                    "  refreshing...",
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
+            }
+            lines.push(Line::from(search));
+            if filtered.is_empty() && app.models_loading {
+                lines.push(Line::from(vec![Span::styled(
+                    "Loading models...",
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )]));
+            } else if filtered.is_empty() {
                 lines.push(Line::from(vec![Span::styled(
                     "No models found.",
                     Style::default().fg(COLOR_TEXT_DIM),
@@ -888,10 +1713,35 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                         } else {
                             Span::raw("")
                         };
+                        let star = if filtered
+                            .get(*idx)
+                            .is_some_and(|e| app.model_prefs.is_favorite(&e.id))
+                        {
+                            Span::styled("* ", Style::default().fg(COLOR_WARNING))
+                        } else {
+                            Span::raw("")
+                        };
+                        let test_badge = match (filtered.get(*idx), &app.model_test) {
+                            (Some(entry), Some((id, result))) if *id == entry.id => match result {
+                                None => {
+                                    Span::styled(" testing…", Style::default().fg(COLOR_TEXT_DIM))
+                                }
+                                Some(Ok(ms)) => Span::styled(
+                                    format!(" ✓ {} ms", ms),
+                                    Style::default().fg(COLOR_SUCCESS),
+                                ),
+                                Some(Err(_)) => {
+                                    Span::styled(" ✗ failed", Style::default().fg(COLOR_ERROR))
+                                }
+                            },
+                            _ => Span::raw(""),
+                        };
                         lines.push(Line::from(vec![
                             Span::styled(if selected { "  › " } else { "    " }, style),
+                            star,
                             Span::styled(text.clone(), style),
                             free_badge,
+                            test_badge,
                         ]));
                     }
                 }
@@ -921,6 +1771,58 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
             Some(InlineOverlay {
                 title: "Model Picker".to_string(),
                 lines,
+                preview: None,
+            })
+        }
+        UiMode::Notifications => {
+            let mut lines = Vec::new();
+            if app.notifications.is_empty() {
+                lines.push(Line::from(vec![Span::styled(
+                    "No notifications yet.",
+                    Style::default().fg(COLOR_TEXT_DIM),
+                )]));
+            } else {
+                // `notifications_offset` counts back from the newest entry,
+                // which sits at the bottom like a log.
+                let end = app
+                    .notifications
+                    .len()
+                    .saturating_sub(app.notifications_offset);
+                let start = end.saturating_sub(NOTIFICATIONS_PAGE);
+                if start > 0 {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("  ↑ {} older", start),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
+                }
+                for toast in app.notifications.range(start..end) {
+                    lines.push(Line::from(vec![
+                        Span::styled(
+                            format!("{} ", format_clock(toast.created_at_ms)),
+                            Style::default().fg(COLOR_TEXT_DIM),
+                        ),
+                        Span::styled(
+                            format!("{:<8}", toast.level.label()),
+                            Style::default().fg(toast_color(toast.level)),
+                        ),
+                        Span::styled(toast.message.clone(), Style::default().fg(COLOR_TEXT)),
+                    ]));
+                }
+                if app.notifications_offset > 0 {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("  ↓ {} newer", app.notifications_offset),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
+                }
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "Up/Down scroll  c clear  Esc close",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )]));
+            Some(InlineOverlay {
+                title: "Notifications".to_string(),
+                lines,
+                preview: None,
             })
         }
         UiMode::SessionHistory => {
@@ -978,6 +1880,7 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
             Some(InlineOverlay {
                 title: "Session History".to_string(),
                 lines,
+                preview: None,
             })
         }
         UiMode::QuestionPrompt => {
@@ -989,15 +1892,51 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                         Style::default().fg(COLOR_TEXT),
                     )]));
                 }
-                lines.push(Line::from(vec![Span::styled(
-                    q.question.clone(),
-                    Style::default().fg(COLOR_TEXT),
-                )]));
-                let mut total = q.options.len();
-                if q.allow_custom {
-                    total += 1;
+                let waited = q.asked_at.elapsed().as_secs();
+                let waited = if waited < 60 {
+                    format!("{}s", waited)
+                } else {
+                    format!("{}m{:02}s", waited / 60, waited % 60)
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(q.question.clone(), Style::default().fg(COLOR_TEXT)),
+                    Span::styled(
+                        format!("  waiting {}", waited),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ),
+                ]));
+                let visible = q.visible_options();
+                if !q.filter.is_empty() {
+                    lines.push(Line::from(vec![
+                        Span::styled("Filter: ", Style::default().fg(COLOR_CODE)),
+                        Span::styled(q.filter.clone(), Style::default().fg(COLOR_TEXT)),
+                        Span::styled(
+                            format!("  ({}/{})", visible.len(), q.options.len()),
+                            Style::default().fg(COLOR_TEXT_DIM),
+                        ),
+                    ]));
+                } else if q.options.len() > QUESTION_VIEWPORT {
+                    lines.push(Line::from(vec![Span::styled(
+                        "Type to filter",
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
+                }
+                if visible.is_empty() && !q.options.is_empty() {
+                    lines.push(Line::from(vec![Span::styled(
+                        "No matching options",
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
+                }
+                let offset = q.offset.min(visible.len().saturating_sub(1));
+                let end = (offset + QUESTION_VIEWPORT).min(visible.len());
+                if offset > 0 {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("  ↑ {} more", offset),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
                 }
-                for (i, opt) in q.options.iter().enumerate() {
+                for &i in &visible[offset..end] {
+                    let opt = &q.options[i];
                     let sel = q.selected.get(i).copied().unwrap_or(false);
                     let focused = q.focused_index == i && !q.custom_active;
                     let prefix = if q.allow_multiple {
@@ -1020,25 +1959,62 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                     } else {
                         Style::default().fg(COLOR_TEXT)
                     };
-                    lines.push(Line::from(vec![
-                        Span::styled(number, Style::default().fg(COLOR_TEXT_DIM)),
-                        Span::raw(" "),
-                        Span::styled(if focused { "> " } else { "  " }, style),
-                        Span::styled(prefix, style),
-                        Span::raw(" "),
-                        Span::styled(opt.label.clone(), style),
-                        if let Some(desc) = &opt.description {
-                            Span::styled(
+                    // Long labels wrap under themselves; a description that
+                    // doesn't fit beside the label moves to its own lines.
+                    let indent = number.width() + 1 + 2 + prefix.width() + 1;
+                    let label_width = width.saturating_sub(indent).max(10);
+                    let label_lines = wrap(&opt.label, label_width);
+                    let is_default =
+                        q.default_index == Some(i) && !opt.label.contains("(Recommended)");
+                    let desc = opt.description.as_deref().unwrap_or("");
+                    let last_width = label_lines.last().map(|l| l.width()).unwrap_or(0)
+                        + if is_default { " (default)".len() } else { 0 };
+                    let desc_inline =
+                        !desc.is_empty() && last_width + 3 + desc.width() <= label_width;
+                    for (row, text) in label_lines.iter().enumerate() {
+                        let mut spans = if row == 0 {
+                            vec![
+                                Span::styled(number.clone(), Style::default().fg(COLOR_TEXT_DIM)),
+                                Span::raw(" "),
+                                Span::styled(if focused { "> " } else { "  " }, style),
+                                Span::styled(prefix, style),
+                                Span::raw(" "),
+                            ]
+                        } else {
+                            vec![Span::raw(" ".repeat(indent))]
+                        };
+                        spans.push(Span::styled(text.to_string(), style));
+                        if is_default && row + 1 == label_lines.len() {
+                            spans.push(Span::styled(
+                                " (default)",
+                                Style::default().fg(COLOR_SUCCESS),
+                            ));
+                        }
+                        if desc_inline && row + 1 == label_lines.len() {
+                            spans.push(Span::styled(
                                 format!(" - {}", desc),
                                 Style::default().fg(COLOR_TEXT_DIM),
-                            )
-                        } else {
-                            Span::raw("")
-                        },
-                    ]));
+                            ));
+                        }
+                        lines.push(Line::from(spans));
+                    }
+                    if !desc.is_empty() && !desc_inline {
+                        for text in wrap(desc, label_width) {
+                            lines.push(Line::from(vec![
+                                Span::raw(" ".repeat(indent)),
+                                Span::styled(text.to_string(), Style::default().fg(COLOR_TEXT_DIM)),
+                            ]));
+                        }
+                    }
+                }
+                if end < visible.len() {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("  ↓ {} more", visible.len() - end),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )]));
                 }
                 if q.allow_custom {
-                    let custom_focused = q.focused_index == total.saturating_sub(1);
+                    let custom_focused = q.focused_index == q.options.len();
                     let border_style = if custom_focused || q.custom_active {
                         Style::default().fg(COLOR_CODE)
                     } else {
@@ -1064,9 +2040,9 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                     lines.push(Line::from(custom_line));
                 }
                 let hint = if q.allow_multiple {
-                    "Up/Down move  Space toggle  Enter submit  Esc skip"
+                    "Up/Down move  Space toggle  Type to filter  Enter submit  Esc skip"
                 } else {
-                    "Up/Down move  Enter select  Esc skip"
+                    "Up/Down move  Type to filter  Enter select  Esc skip"
                 };
                 lines.push(Line::from(vec![Span::styled(
                     hint,
@@ -1075,34 +2051,322 @@ fn build_inline_overlay(app: &App, _width: usize) -> Option<InlineOverlay> {
                 return Some(InlineOverlay {
                     title: q.header.clone().unwrap_or_else(|| "Question".to_string()),
                     lines,
+                    preview: None,
                 });
             }
             None
         }
-        UiMode::PlanActions => {
-            let lines = vec![
-                Line::from("Plan is ready."),
-                Line::from("Enter = Accept and build"),
-                Line::from("Esc = Keep planning"),
-            ];
-            Some(InlineOverlay {
-                title: "Plan Actions".to_string(),
-                lines,
-            })
-        }
-        _ => None,
+        UiMode::TemplatePicker => {
+            let mut lines = Vec::new();
+            for (i, template) in app.templates.iter().enumerate() {
+                let selected = i == app.template_selected;
+                let style = if selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(COLOR_CODE)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(COLOR_TEXT)
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(if selected { "› " } else { "  " }, style),
+                    Span::styled(template.name.clone(), style),
+                    Span::styled(
+                        format!("  {}", template.summary()),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ),
+                ]));
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "Enter insert into input  Esc close",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )]));
+            let preview = app.templates.get(app.template_selected).map(|template| {
+                template
+                    .body
+                    .lines()
+                    .take(12)
+                    .map(|line| {
+                        Line::from(Span::styled(
+                            line.to_string(),
+                            Style::default().fg(COLOR_TEXT_DIM),
+                        ))
+                    })
+                    .collect()
+            });
+            Some(InlineOverlay {
+                title: "Templates".to_string(),
+                lines,
+                preview,
+            })
+        }
+        UiMode::Bookmarks => {
+            let mut lines = Vec::new();
+            for (i, bookmark) in app.bookmarks.iter().enumerate() {
+                let selected = i == app.bookmark_selected;
+                let style = if selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(COLOR_CODE)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(COLOR_TEXT)
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(if selected { "› " } else { "  " }, style),
+                    Span::styled(bookmark.label.clone(), style),
+                    Span::styled(
+                        format!("  {}", format_clock(bookmark.created_at)),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ),
+                ]));
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "Enter jump  d delete  Esc close",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )]));
+            Some(InlineOverlay {
+                title: t("ui.bookmarks_title").to_string(),
+                lines,
+                preview: None,
+            })
+        }
+        UiMode::AgentPicker => {
+            let mut lines = Vec::new();
+            let descriptions = BUILT_IN_AGENTS
+                .iter()
+                .map(|(_, description)| Some(description.to_string()))
+                .chain(app.custom_agents.iter().map(|a| a.description.clone()));
+            for (i, (name, description)) in
+                app.agent_names().into_iter().zip(descriptions).enumerate()
+            {
+                let selected = i == app.agent_selected;
+                let style = if selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(COLOR_CODE)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(agent_color(&name))
+                };
+                let mut spans = vec![
+                    Span::styled(if selected { "› " } else { "  " }, style),
+                    Span::styled(name.clone(), style),
+                ];
+                if name == app.state.agent {
+                    spans.push(Span::styled(
+                        " (active)",
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ));
+                }
+                if let Some(description) = description {
+                    spans.push(Span::styled(
+                        format!("  {}", description),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "Enter switch  Tab cycles agents  Esc close",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )]));
+            Some(InlineOverlay {
+                title: "Agents".to_string(),
+                lines,
+                preview: None,
+            })
+        }
+        UiMode::ReasoningPicker => {
+            let mut lines = Vec::new();
+            for (i, level) in app.reasoning_levels().iter().enumerate() {
+                let selected = i == app.reasoning_selected;
+                let style = if selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(COLOR_CODE)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(COLOR_TEXT)
+                };
+                let mut spans = vec![
+                    Span::styled(if selected { "› " } else { "  " }, style),
+                    Span::styled(level.to_string(), style),
+                ];
+                if *level == app.reasoning_effort {
+                    spans.push(Span::styled(
+                        " (active)",
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "Enter set  Esc close",
+                Style::default().fg(COLOR_TEXT_DIM),
+            )]));
+            Some(InlineOverlay {
+                title: tf("ui.reasoning_title", &[("model", &app.current_model())]),
+                lines,
+                preview: None,
+            })
+        }
+        _ => None,
     }
 }
 
-pub fn build_todo_strip(app: &App, width: usize) -> Vec<Line<'static>> {
-    let summary = format!(
-        "Todos: {} pending  {} in progress  {} done",
-        app.todo_counts.pending, app.todo_counts.in_progress, app.todo_counts.completed
-    );
-    let line1 = Line::from(vec![Span::styled(
-        summary,
+/// Context above the input: the offer to fetch a URL in the message, then
+/// chips for what goes out with every message (files pinned with `/pin` and,
+/// with `/withdiff`, the git diff).
+fn build_context_strip(app: &App) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    if let Some(url) = &app.url_fetching {
+        lines.push(Line::from(Span::styled(
+            format!("⇣ fetching {}…", url),
+            Style::default().fg(COLOR_TEXT_DIM),
+        )));
+    } else if let Some(url) = unfetched_url(app) {
+        lines.push(Line::from(vec![
+            Span::styled("⇣ ", Style::default().fg(COLOR_CYAN)),
+            Span::styled(url, Style::default().fg(COLOR_TEXT)),
+            Span::styled(
+                "  · Ctrl+G attach page text",
+                Style::default().fg(COLOR_TEXT_DIM),
+            ),
+        ]));
+    }
+    if app.pinned.is_empty() && !app.with_diff {
+        return lines;
+    }
+    let mut spans = Vec::new();
+    if app.with_diff {
+        spans.push(Span::styled("± ", Style::default().fg(COLOR_WARNING)));
+        spans.push(Span::styled("git diff", Style::default().fg(COLOR_TEXT)));
+        if !app.pinned.is_empty() {
+            spans.push(Span::raw("  "));
+        }
+    }
+    for (i, path) in app.pinned.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled("⚲ ", Style::default().fg(COLOR_WARNING)));
+        spans.push(Span::styled(path.clone(), Style::default().fg(COLOR_TEXT)));
+    }
+    let hint = match (app.pinned.is_empty(), app.with_diff) {
+        (true, _) => "  · /withdiff to stop",
+        (false, false) => "  · /unpin to remove",
+        (false, true) => "  · /unpin, /withdiff to remove",
+    };
+    spans.push(Span::styled(hint, Style::default().fg(COLOR_TEXT_DIM)));
+    lines.push(Line::from(spans));
+    lines
+}
+
+/// One chip per pending attachment, numbered for the Alt+N removal keys.
+fn build_attachment_strip(app: &App) -> Vec<Line<'static>> {
+    if app.attachments.is_empty() {
+        return Vec::new();
+    }
+    let mut spans = Vec::new();
+    for (i, attachment) in app.attachments.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw("  "));
+        }
+        spans.push(Span::styled(
+            format!("{} ", i + 1),
+            Style::default().fg(COLOR_TEXT_DIM),
+        ));
+        spans.push(Span::styled(
+            format!("{} ", attachment_glyph(attachment.kind)),
+            Style::default().fg(COLOR_CYAN),
+        ));
+        spans.push(Span::styled(
+            attachment.name.clone(),
+            Style::default().fg(COLOR_TEXT),
+        ));
+        spans.push(Span::styled(
+            format!(" {}", format_size(attachment.size)),
+            Style::default().fg(COLOR_TEXT_DIM),
+        ));
+    }
+    let hint = if app.attachments.len() == 1 {
+        "  · Alt+1 to remove".to_string()
+    } else {
+        format!("  · Alt+1..{} to remove", app.attachments.len().min(9))
+    };
+    spans.push(Span::styled(hint, Style::default().fg(COLOR_TEXT_DIM)));
+    vec![Line::from(spans)]
+}
+
+/// The new-todo input and key hints under a focused todo strip.
+fn todo_focus_footer(app: &App) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    if let Some(edit) = app.todo_edit.as_ref().filter(|edit| edit.id.is_none()) {
+        lines.push(Line::from(vec![
+            Span::styled("+ ", Style::default().fg(COLOR_CODE)),
+            Span::styled(format!("{}▏", edit.input), Style::default().fg(COLOR_CODE)),
+        ]));
+    }
+    let hint = if app.todo_edit.is_some() {
+        "Enter save · Esc cancel"
+    } else {
+        "↑↓ select · Space status · e edit · a add · Esc done"
+    };
+    lines.push(Line::from(Span::styled(
+        hint,
         Style::default().fg(COLOR_TEXT_DIM),
-    )]);
+    )));
+    lines
+}
+
+/// `▰▰▰▱▱` with one cell per todo, scaled down to `TODO_BAR_CELLS` for long
+/// lists.
+fn todo_progress_bar(completed: u64, total: u64) -> (String, String) {
+    const TODO_BAR_CELLS: u64 = 10;
+    let cells = total.min(TODO_BAR_CELLS);
+    let filled = (completed.min(total) * cells)
+        .checked_div(total)
+        .unwrap_or(0);
+    (
+        "▰".repeat(filled as usize),
+        "▱".repeat((cells - filled) as usize),
+    )
+}
+
+pub fn build_todo_strip(app: &App, width: usize) -> Vec<Line<'static>> {
+    let counts = &app.todo_counts;
+    let line1 = if app.todos_done_flash().is_some() {
+        Line::from(Span::styled(
+            tf("ui.todos_all_done", &[("total", &counts.total)]),
+            Style::default()
+                .fg(COLOR_SUCCESS)
+                .add_modifier(Modifier::BOLD),
+        ))
+    } else if counts.total > 0 {
+        let (filled, empty) = todo_progress_bar(counts.completed, counts.total);
+        Line::from(vec![
+            Span::styled(t("ui.todos_label"), Style::default().fg(COLOR_TEXT_DIM)),
+            Span::styled(filled, Style::default().fg(COLOR_SUCCESS)),
+            Span::styled(empty, Style::default().fg(COLOR_TEXT_DIM)),
+            Span::styled(
+                tf(
+                    "ui.todos_progress",
+                    &[
+                        ("completed", &counts.completed),
+                        ("total", &counts.total),
+                        ("in_progress", &counts.in_progress),
+                    ],
+                ),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ),
+        ])
+    } else {
+        Line::from(Span::styled(
+            t("ui.todos_none"),
+            Style::default().fg(COLOR_TEXT_DIM),
+        ))
+    };
 
     if app.todos_expanded {
         let mut lines = vec![line1, Line::from("")];
@@ -1111,20 +2375,44 @@ pub fn build_todo_strip(app: &App, width: usize) -> Vec<Line<'static>> {
                 "No todos yet.",
                 Style::default().fg(COLOR_TEXT_DIM),
             )]));
+            if app.mode == UiMode::TodoFocus {
+                lines.extend(todo_focus_footer(app));
+            }
             return lines;
         }
-        for todo in &app.todos {
+        let focused = app.mode == UiMode::TodoFocus;
+        for (i, todo) in app.todos.iter().enumerate() {
             let (label, color) = match todo.status.as_str() {
                 "completed" => ("[x]", COLOR_SUCCESS),
                 "in_progress" => ("[~]", COLOR_WARNING),
                 _ => ("[ ]", COLOR_TEXT_DIM),
             };
+            let selected = focused && i == app.todo_selected;
+            let editing = app
+                .todo_edit
+                .as_ref()
+                .filter(|edit| selected && edit.id.as_deref() == Some(todo.id.as_str()));
+            let (content, content_style) = match editing {
+                Some(edit) => (format!("{}▏", edit.input), Style::default().fg(COLOR_CODE)),
+                None if selected => (
+                    todo.content.clone(),
+                    Style::default().fg(COLOR_TEXT).add_modifier(Modifier::BOLD),
+                ),
+                None => (todo.content.clone(), Style::default().fg(COLOR_TEXT)),
+            };
             lines.push(Line::from(vec![
+                Span::styled(
+                    if selected { "› " } else { "  " },
+                    Style::default().fg(COLOR_CODE),
+                ),
                 Span::styled(label, Style::default().fg(color)),
                 Span::raw(" "),
-                Span::styled(todo.content.clone(), Style::default().fg(COLOR_TEXT)),
+                Span::styled(content, content_style),
             ]));
         }
+        if focused {
+            lines.extend(todo_focus_footer(app));
+        }
         return lines;
     }
 
@@ -1172,15 +2460,129 @@ pub fn build_todo_strip(app: &App, width: usize) -> Vec<Line<'static>> {
     vec![line1, line2]
 }
 
-fn truncate_text(text: &str, max_len: usize) -> String {
-    if text.len() <= max_len {
-        return text.to_string();
+/// First lines of the highlighted file, or the region around a symbol's
+/// definition, so the right file can be confirmed before inserting it.
+fn build_mention_preview(app: &mut App, target: &MentionTarget) -> Vec<Line<'static>> {
+    let (path, focus_line) = match target {
+        MentionTarget::File(file) => (file.relative_path.as_str(), None),
+        MentionTarget::Symbol(symbol) => (symbol.path.as_str(), Some(symbol.line)),
+        MentionTarget::Related(related) => (related.path.as_str(), Some(related.start_line)),
+    };
+    let mut lines = vec![Line::from(Span::styled(
+        path.to_string(),
+        Style::default()
+            .fg(COLOR_TEXT_DIM)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    let dim = |text: &str| {
+        Line::from(Span::styled(
+            text.to_string(),
+            Style::default().fg(COLOR_TEXT_DIM),
+        ))
+    };
+
+    if let MentionTarget::File(file) = target {
+        if file.is_dir {
+            let prefix = format!("{}/", file.relative_path);
+            let children: Vec<&str> = app
+                .file_index
+                .iter()
+                .filter_map(|f| f.relative_path.strip_prefix(&prefix))
+                .filter(|rest| !rest.contains('/'))
+                .take(MENTION_PREVIEW_LINES)
+                .collect();
+            if children.is_empty() {
+                lines.push(dim("(empty directory)"));
+            }
+            lines.extend(children.into_iter().map(dim));
+            return lines;
+        }
+    }
+
+    let preview = app.mention_preview(path);
+    let text = match preview.as_ref() {
+        FilePreview::Text(text) => text,
+        FilePreview::Binary => {
+            lines.push(dim("(binary file)"));
+            return lines;
+        }
+        FilePreview::Unreadable => {
+            lines.push(dim("(unreadable)"));
+            return lines;
+        }
+    };
+    let start = focus_line.map(|line| line.saturating_sub(3)).unwrap_or(0);
+    let gutter = (start + MENTION_PREVIEW_LINES).to_string().len();
+    for (idx, line) in text
+        .lines()
+        .enumerate()
+        .skip(start)
+        .take(MENTION_PREVIEW_LINES)
+    {
+        let number = idx + 1;
+        let focused = focus_line == Some(number);
+        let text_style = if focused {
+            Style::default().fg(COLOR_TEXT).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(COLOR_TEXT_MUTED)
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:>width$} ", number, width = gutter),
+                Style::default().fg(if focused {
+                    COLOR_YELLOW
+                } else {
+                    COLOR_TEXT_DIM
+                }),
+            ),
+            Span::styled(line.replace('\t', "    "), text_style),
+        ]));
+    }
+    lines
+}
+
+/// Splits `text` into runs of matched and unmatched chars; `indices` are char
+/// positions as returned by the fuzzy matcher.
+fn highlight_matches(
+    text: &str,
+    indices: &[usize],
+    style: Style,
+    matched_style: Style,
+) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, ch) in text.chars().enumerate() {
+        let matched = indices.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            let run_style = if run_matched { matched_style } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_matched = matched;
+        run.push(ch);
     }
-    format!("{}…", &text[..max_len.saturating_sub(1)])
+    if !run.is_empty() {
+        spans.push(Span::styled(
+            run,
+            if run_matched { matched_style } else { style },
+        ));
+    }
+    spans
+}
+
+fn attachment_chips(app: &App) -> Vec<String> {
+    app.attachments.iter().map(|a| a.chip_label()).collect()
 }
 
-fn compute_display_input_with_cursor(value: &str, cursor: usize) -> (String, usize) {
+/// `chips` label the attachment markers in order; markers beyond the list fall
+/// back to a generic `[Image]`.
+fn compute_display_input_with_cursor(
+    value: &str,
+    cursor: usize,
+    chips: &[String],
+) -> (String, usize) {
     let cursor = clamp_cursor(value, cursor);
+    let mut chip_index = 0usize;
     let mut display = String::new();
     let mut cursor_display_index = 0usize;
     let mut cursor_set = false;
@@ -1203,8 +2605,7 @@ fn compute_display_input_with_cursor(value: &str, cursor: usize) -> (String, usi
                 let after_end = end_idx + PASTE_END.len_utf8();
                 let paste_text = &value[start_next..end_idx];
                 let line_count = paste_text.lines().count().max(1);
-                let is_large =
-                    line_count >= PASTE_LINE_THRESHOLD || paste_text.len() >= PASTE_CHAR_THRESHOLD;
+                let is_large = is_large_paste(paste_text);
                 let mut summary = if is_large {
                     format!("[Pasted ~{} lines]", line_count)
                 } else {
@@ -1257,7 +2658,8 @@ fn compute_display_input_with_cursor(value: &str, cursor: usize) -> (String, usi
             if display.chars().last().is_some() && !display.ends_with(' ') {
                 image_marker.push(' ');
             }
-            image_marker.push_str("[Image]");
+            image_marker.push_str(chips.get(chip_index).map_or("[Image]", String::as_str));
+            chip_index += 1;
             if next_char.is_some()
                 && next_char != Some(' ')
                 && next_char != Some(PASTE_START)
@@ -1290,816 +2692,522 @@ fn clamp_cursor(value: &str, cursor: usize) -> usize {
     idx
 }
 
+/// Row and display column of the cursor (a char index into `display`) once
+/// the text is laid out by `wrap_plain_lines`. Columns count terminal cells,
+/// so wide characters take two.
 fn compute_cursor_position(display: &str, cursor_index: usize, width: usize) -> (usize, usize) {
+    let cursor = display
+        .char_indices()
+        .nth(cursor_index)
+        .map_or(display.len(), |(byte, _)| byte);
     let mut row = 0usize;
-    let mut col = 0usize;
-    for (idx, ch) in display.chars().enumerate() {
-        if idx >= cursor_index {
-            break;
-        }
-        if ch == '\n' || col >= width {
-            row += 1;
-            col = 0;
-            if ch == '\n' {
-                continue;
-            }
+    let mut line_start = 0usize;
+    for raw in display.split('\n') {
+        let line_end = line_start + raw.len();
+        if cursor > line_end {
+            row += wrap_plain_lines(raw, width).len();
+            line_start = line_end + 1;
+            continue;
         }
-        col += 1;
+        let local = cursor - line_start;
+        if raw.is_empty() {
+            return (row, 0);
+        }
+        // Wrapped lines borrow from `raw`, so their offsets tell where each
+        // one starts; whitespace dropped at a break stays on the line before.
+        let segments = wrap(raw, width);
+        let starts: Vec<usize> = segments
+            .iter()
+            .map(|segment| (segment.as_ptr() as usize).saturating_sub(raw.as_ptr() as usize))
+            .collect();
+        let index = starts
+            .iter()
+            .rposition(|&start| start <= local)
+            .unwrap_or(0);
+        let start = starts.get(index).copied().unwrap_or(0).min(local);
+        let col = UnicodeWidthStr::width(&raw[start..local]).min(width);
+        return (row + index, col);
     }
-    (row, col)
+    (row, 0)
 }
 
-pub fn format_status_lines(app: &App, width: usize) -> Vec<Line<'static>> {
-    let model = app
-        .state
-        .model_override
-        .clone()
-        .unwrap_or_else(|| app.base_model.clone());
-    let agent = app.state.agent.clone();
-    let agent_color = agent_color(&agent);
-    let mode = agent.to_uppercase();
-    let thinking_label = if app.reasoning_effort != "off" {
-        format!("Thinking {}", app.reasoning_effort.to_uppercase())
-    } else {
-        String::new()
-    };
-    let tokens = format!(
-        "{} in/{} out",
-        format_number(app.state.tokens.input),
-        format_number(app.state.tokens.output)
-    );
+/// One piece of the status bar. Segments are picked and ordered per line by
+/// `[status]` in `.stratuscode/config.toml`.
+pub trait StatusSegment {
+    /// The segment's spans, or `None` when it has nothing to show.
+    fn spans(&self, app: &App, width: usize) -> Option<Vec<Span<'static>>>;
+}
 
-    let mut line1: Vec<Span> = Vec::new();
-    line1.push(Span::styled(
-        format!(" {} ", mode),
-        Style::default()
-            .fg(Color::Black)
-            .bg(agent_color)
-            .add_modifier(Modifier::BOLD),
-    ));
-    line1.push(Span::styled("|", Style::default().fg(COLOR_TEXT_DIM)));
-    line1.push(Span::styled(model, Style::default().fg(COLOR_TEXT_MUTED)));
-    if !thinking_label.is_empty() {
-        line1.push(Span::styled("|", Style::default().fg(COLOR_TEXT_DIM)));
-        line1.push(Span::styled(
-            thinking_label,
-            Style::default().fg(COLOR_PURPLE),
-        ));
-    }
-    line1.push(Span::styled("|", Style::default().fg(COLOR_TEXT_DIM)));
-    line1.push(Span::styled(tokens, Style::default().fg(COLOR_TEXT_MUTED)));
-
-    let bar_width = (width / 5).clamp(8, 20);
-    let pct = app.state.context_usage.percent.min(100);
-    let filled = ((pct as usize * bar_width) / 100).min(bar_width);
-    let empty = bar_width.saturating_sub(filled);
-    let bar_color = if pct > 90 {
-        COLOR_ERROR
-    } else if pct > 70 {
-        COLOR_WARNING
-    } else {
-        COLOR_PURPLE
+/// Names accepted in `[status]`, in the order of the default layout.
+pub const STATUS_SEGMENTS: [&str; 10] = [
+    "agent", "model", "thinking", "tokens", "context", "profile", "git", "cost", "clock", "timer",
+];
+
+pub fn status_segment(name: &str) -> Option<Box<dyn StatusSegment>> {
+    let segment: Box<dyn StatusSegment> = match name {
+        "agent" => Box::new(AgentSegment),
+        "model" => Box::new(ModelSegment),
+        "thinking" => Box::new(ThinkingSegment),
+        "tokens" => Box::new(TokensSegment),
+        "context" => Box::new(ContextSegment),
+        "profile" => Box::new(ProfileSegment),
+        "git" => Box::new(GitSegment),
+        "cost" => Box::new(CostSegment),
+        "clock" => Box::new(ClockSegment),
+        "timer" => Box::new(TimerSegment),
+        _ => return None,
     };
-
-    let mut line2: Vec<Span> = Vec::new();
-    line2.push(Span::styled(
-        "Context ",
-        Style::default().fg(COLOR_TEXT_DIM),
-    ));
-    line2.push(Span::styled(
-        "=".repeat(filled),
-        Style::default().fg(bar_color),
-    ));
-    line2.push(Span::styled(
-        ".".repeat(empty),
-        Style::default().fg(Color::Rgb(30, 41, 59)),
-    ));
-    line2.push(Span::styled(
-        format!(" {}%", pct),
-        Style::default().fg(COLOR_TEXT_DIM),
-    ));
-    if let Some(status) = &app.state.context_status {
-        line2.push(Span::styled(
-            format!(" {}", status),
-            Style::default().fg(COLOR_TEXT_DIM),
-        ));
-    }
-
-    vec![Line::from(line1), Line::from(line2)]
+    Some(segment)
 }
 
-fn agent_color(agent: &str) -> Color {
-    match agent {
-        "plan" => COLOR_PURPLE,
-        _ => COLOR_GREEN,
-    }
-}
+struct AgentSegment;
 
-fn format_number(value: u64) -> String {
-    let s = value.to_string();
-    let mut out = String::new();
-    for (i, ch) in s.chars().rev().enumerate() {
-        if i > 0 && i % 3 == 0 {
-            out.push(',');
-        }
-        out.push(ch);
+impl StatusSegment for AgentSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let agent = &app.state.agent;
+        let mut spans = vec![Span::styled(
+            format!(" {} ", agent.to_uppercase()),
+            Style::default()
+                .fg(Color::Black)
+                .bg(agent_color(agent))
+                .add_modifier(Modifier::BOLD),
+        )];
+        if app.read_only {
+            spans.push(Span::styled(
+                " 🔒 read-only",
+                Style::default().fg(COLOR_WARNING),
+            ));
+        }
+        if let Some((register, _)) = &app.macro_recording {
+            spans.push(Span::styled(
+                format!(" {}", tf("ui.macro_recording", &[("register", register)])),
+                Style::default().fg(COLOR_ERROR),
+            ));
+        }
+        if let Some(sandbox) = &app.sandbox {
+            spans.push(Span::styled(
+                format!(" ⧉ {}", sandbox),
+                Style::default().fg(COLOR_CYAN),
+            ));
+        }
+        Some(spans)
     }
-    out.chars().rev().collect()
 }
 
-fn wrap_plain_lines(text: &str, width: usize) -> Vec<String> {
-    let mut lines = Vec::new();
-    for raw in text.split('\n') {
-        if raw.is_empty() {
-            lines.push(String::new());
-            continue;
-        }
-        let wrapped = wrap(raw, width);
-        for line in wrapped {
-            lines.push(line.to_string());
-        }
-    }
-    if lines.is_empty() {
-        lines.push(String::new());
+struct ModelSegment;
+
+impl StatusSegment for ModelSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let model = app
+            .state
+            .model_override
+            .clone()
+            .unwrap_or_else(|| app.base_model.clone());
+        Some(vec![
+            Span::styled(model, Style::default().fg(COLOR_TEXT_MUTED)),
+            Span::styled(
+                format!(" ({})", app.model_source.label()),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ),
+        ])
     }
-    lines
 }
 
-fn wrap_diff_content(text: &str, width: usize) -> Vec<String> {
-    if UnicodeWidthStr::width(text) <= width {
-        return vec![text.to_string()];
-    }
-    let mut lines = Vec::new();
-    let mut current = String::new();
-    let mut current_width = 0usize;
-    for ch in text.chars() {
-        let ch_width = UnicodeWidthChar::width(ch).unwrap_or(1);
-        if current_width + ch_width > width && !current.is_empty() {
-            lines.push(current);
-            current = String::new();
-            current_width = 0;
-        }
-        current.push(ch);
-        current_width += ch_width;
-    }
-    if !current.is_empty() {
-        lines.push(current);
-    }
-    lines
-}
+struct ThinkingSegment;
 
-fn indent_lines(lines: Vec<Line<'static>>, indent: usize) -> Vec<Line<'static>> {
-    let prefix = Span::raw(" ".repeat(indent));
-    lines
-        .into_iter()
-        .map(|line| {
-            let mut spans = Vec::with_capacity(line.spans.len() + 1);
-            spans.push(prefix.clone());
-            spans.extend(line.spans);
-            Line::from(spans)
+impl StatusSegment for ThinkingSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        (app.reasoning_effort != "off").then(|| {
+            vec![Span::styled(
+                format!("Thinking {}", app.reasoning_effort.to_uppercase()),
+                Style::default().fg(COLOR_PURPLE),
+            )]
         })
-        .collect()
+    }
 }
 
-#[derive(Debug, Clone)]
-struct DiffLine {
-    kind: DiffKind,
-    content: String,
-    old_line: Option<usize>,
-    new_line: Option<usize>,
-}
+struct TokensSegment;
 
-#[derive(Debug, Clone, Copy)]
-enum DiffKind {
-    Header,
-    Hunk,
-    Add,
-    Remove,
-    Context,
+impl StatusSegment for TokensSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        Some(vec![Span::styled(
+            format!(
+                "{} in/{} out",
+                format_number(app.state.tokens.input),
+                format_number(app.state.tokens.output)
+            ),
+            Style::default().fg(COLOR_TEXT_MUTED),
+        )])
+    }
 }
 
-fn parse_diff(diff: &str) -> (Vec<DiffLine>, usize, usize) {
-    let mut lines = Vec::new();
-    let mut additions = 0usize;
-    let mut deletions = 0usize;
-    let mut old_line = 0usize;
-    let mut new_line = 0usize;
-
-    for line in diff.lines() {
-        if line.starts_with("--- ") || line.starts_with("+++ ") {
-            lines.push(DiffLine {
-                kind: DiffKind::Header,
-                content: line.to_string(),
-                old_line: None,
-                new_line: None,
-            });
-            continue;
-        }
-        if line.starts_with("@@") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                if let Some(old_part) = parts.get(1) {
-                    if let Some(num) = old_part.trim_start_matches('-').split(',').next() {
-                        old_line = num.parse::<usize>().unwrap_or(0);
-                    }
-                }
-                if let Some(new_part) = parts.get(2) {
-                    if let Some(num) = new_part.trim_start_matches('+').split(',').next() {
-                        new_line = num.parse::<usize>().unwrap_or(0);
-                    }
-                }
-            }
-            lines.push(DiffLine {
-                kind: DiffKind::Hunk,
-                content: line.to_string(),
-                old_line: None,
-                new_line: None,
-            });
-            continue;
-        }
+struct ProfileSegment;
 
-        if let Some(stripped) = line.strip_prefix('+') {
-            additions += 1;
-            lines.push(DiffLine {
-                kind: DiffKind::Add,
-                content: stripped.to_string(),
-                old_line: None,
-                new_line: Some(new_line),
-            });
-            new_line = new_line.saturating_add(1);
-        } else if let Some(stripped) = line.strip_prefix('-') {
-            deletions += 1;
-            lines.push(DiffLine {
-                kind: DiffKind::Remove,
-                content: stripped.to_string(),
-                old_line: Some(old_line),
-                new_line: None,
-            });
-            old_line = old_line.saturating_add(1);
-        } else if let Some(stripped) = line.strip_prefix(' ') {
-            lines.push(DiffLine {
-                kind: DiffKind::Context,
-                content: stripped.to_string(),
-                old_line: Some(old_line),
-                new_line: Some(new_line),
-            });
-            old_line = old_line.saturating_add(1);
-            new_line = new_line.saturating_add(1);
-        } else {
-            lines.push(DiffLine {
-                kind: DiffKind::Context,
-                content: line.to_string(),
-                old_line: None,
-                new_line: None,
-            });
-        }
+impl StatusSegment for ProfileSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let provider = app.state.provider_override.as_deref().unwrap_or("default");
+        Some(vec![Span::styled(
+            provider.to_string(),
+            Style::default().fg(COLOR_TEXT_MUTED),
+        )])
     }
-
-    (lines, additions, deletions)
 }
 
-pub fn extract_diff_summary(result: &str, width: usize) -> Option<(String, Vec<Line<'static>>)> {
-    let parsed: serde_json::Value = serde_json::from_str(result).ok()?;
-    let diff = parsed.get("diff")?.as_str()?.to_string();
-    let (lines, additions, deletions) = parse_diff(&diff);
-    let summary = format!("(+{} / -{})", additions, deletions);
-    let formatted = format_diff_lines(lines, width);
-    Some((summary, formatted))
+struct GitSegment;
+
+impl StatusSegment for GitSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let branch = app.git_branch.as_ref()?;
+        Some(vec![Span::styled(
+            format!("⎇ {}", branch),
+            Style::default().fg(COLOR_GREEN),
+        )])
+    }
 }
 
-fn format_diff_lines(lines: Vec<DiffLine>, width: usize) -> Vec<Line<'static>> {
-    let mut out = Vec::new();
-    let line_num_width = 4usize;
-    let content_width = width.saturating_sub(line_num_width * 2 + 3).max(10);
-
-    for line in lines {
-        let (prefix, style) = match line.kind {
-            DiffKind::Header => ("", Style::default().fg(COLOR_PURPLE)),
-            DiffKind::Hunk => ("", Style::default().fg(COLOR_CYAN)),
-            DiffKind::Add => ("+", Style::default().fg(COLOR_GREEN)),
-            DiffKind::Remove => ("-", Style::default().fg(COLOR_ERROR)),
-            DiffKind::Context => (" ", Style::default().fg(COLOR_TEXT_DIM)),
-        };
+struct CostSegment;
 
-        let num_left = line
-            .old_line
-            .map(|n| format!("{:>width$}", n, width = line_num_width))
-            .unwrap_or_else(|| " ".repeat(line_num_width));
-        let num_right = line
-            .new_line
-            .map(|n| format!("{:>width$}", n, width = line_num_width))
-            .unwrap_or_else(|| " ".repeat(line_num_width));
-        let mut content_lines = wrap_diff_content(&line.content, content_width);
-        if content_lines.is_empty() {
-            content_lines.push(String::new());
-        }
-        for (idx, content) in content_lines.into_iter().enumerate() {
-            let nums = if matches!(
-                line.kind,
-                DiffKind::Add | DiffKind::Remove | DiffKind::Context
-            ) {
-                if idx == 0 {
-                    format!("{} {} ", num_left, num_right)
-                } else {
-                    " ".repeat(line_num_width * 2 + 2)
-                }
-            } else {
-                String::new()
-            };
-            let mut spans = Vec::new();
-            if !nums.is_empty() {
-                spans.push(Span::styled(nums, Style::default().fg(COLOR_TEXT_DIM)));
-            }
-            spans.push(Span::styled(format!("{}{}", prefix, content), style));
-            out.push(Line::from(spans));
+impl StatusSegment for CostSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let costs: Vec<f64> = usage_by_model(&app.usage_turns)
+            .iter()
+            .filter_map(estimate_cost)
+            .collect();
+        if costs.is_empty() {
+            return None;
         }
+        Some(vec![Span::styled(
+            format!("~${:.2}", costs.iter().sum::<f64>()),
+            Style::default().fg(COLOR_TEXT_MUTED),
+        )])
     }
-    out
 }
 
-pub fn render_markdown(content: &str, width: usize) -> Vec<Line<'static>> {
-    if content.trim().is_empty() {
-        return vec![Line::from("")];
+struct ClockSegment;
+
+impl StatusSegment for ClockSegment {
+    fn spans(&self, _app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        Some(vec![Span::styled(
+            chrono::Local::now().format("%H:%M").to_string(),
+            Style::default().fg(COLOR_TEXT_DIM),
+        )])
     }
-    let mut renderer = MarkdownRenderer::new(width);
-    renderer.render(content);
-    renderer.finish()
 }
 
-#[derive(Debug, Clone)]
-struct ListState {
-    ordered: bool,
-    index: usize,
+/// The running turn's elapsed time, red past `status.turn_warn_secs`, and the
+/// session's total time spent in turns.
+struct TimerSegment;
+
+impl StatusSegment for TimerSegment {
+    fn spans(&self, app: &App, _width: usize) -> Option<Vec<Span<'static>>> {
+        let running = app.busy_since.filter(|_| app.state.is_loading);
+        let turn = running.map(|since| since.elapsed());
+        let active = app.session_active + turn.unwrap_or_default();
+        if active.is_zero() {
+            return None;
+        }
+        let mut spans = Vec::new();
+        if let Some(turn) = turn {
+            let color = if turn >= app.turn_warn_after {
+                COLOR_ERROR
+            } else {
+                COLOR_TEXT
+            };
+            spans.push(Span::styled(
+                format!("⏱ {}", format_elapsed(turn)),
+                Style::default().fg(color),
+            ));
+            spans.push(Span::styled(" · ", Style::default().fg(COLOR_TEXT_DIM)));
+        }
+        spans.push(Span::styled(
+            tf("ui.timer_active", &[("time", &format_elapsed(active))]),
+            Style::default().fg(COLOR_TEXT_MUTED),
+        ));
+        Some(spans)
+    }
 }
 
-struct MarkdownRenderer {
-    width: usize,
-    lines: Vec<Line<'static>>,
-    current_spans: Vec<Span<'static>>,
-    current_width: usize,
-    pending_space: bool,
-    line_prefix: Option<(String, Style)>,
-    pending_item_prefix: Option<(String, Style)>,
-    style_stack: Vec<Style>,
-    list_stack: Vec<ListState>,
-    in_code_block: bool,
+/// `45s`, `2m05s`, `1h02m`.
+fn format_elapsed(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
 }
 
-impl MarkdownRenderer {
-    fn new(width: usize) -> Self {
-        Self {
-            width: width.max(10),
-            lines: Vec::new(),
-            current_spans: Vec::new(),
-            current_width: 0,
-            pending_space: false,
-            line_prefix: None,
-            pending_item_prefix: None,
-            style_stack: vec![Style::default().fg(COLOR_TEXT)],
-            list_stack: Vec::new(),
-            in_code_block: false,
-        }
-    }
-
-    fn finish(mut self) -> Vec<Line<'static>> {
-        self.flush_line();
-        if self.lines.is_empty() {
-            self.lines.push(Line::from(""));
-        }
-        self.lines
-    }
-
-    fn render(&mut self, content: &str) {
-        let mut options = MdOptions::empty();
-        options.insert(MdOptions::ENABLE_STRIKETHROUGH);
-        options.insert(MdOptions::ENABLE_TABLES);
-        options.insert(MdOptions::ENABLE_TASKLISTS);
-        let parser = MdParser::new_ext(content, options);
-        for event in parser {
-            match event {
-                MdEvent::Start(tag) => self.on_start(tag),
-                MdEvent::End(tag) => self.on_end(tag),
-                MdEvent::Text(text) => {
-                    if self.in_code_block {
-                        self.push_code_block_text(&text);
-                    } else {
-                        self.push_text(&text, self.current_style());
-                    }
-                }
-                MdEvent::Code(text) => {
-                    self.push_word(&text, Style::default().fg(COLOR_GREEN));
-                }
-                MdEvent::SoftBreak => {
-                    if self.in_code_block {
-                        self.new_line();
-                    } else {
-                        self.push_space();
-                    }
-                }
-                MdEvent::HardBreak => self.new_line(),
-                MdEvent::Rule => {
-                    self.new_line();
-                    let bar = "─".repeat(self.width.min(40));
-                    self.push_span(&bar, Style::default().fg(COLOR_MUTED));
-                    self.new_line();
-                }
-                _ => {}
-            }
-        }
-    }
+struct ContextSegment;
+
+impl StatusSegment for ContextSegment {
+    fn spans(&self, app: &App, width: usize) -> Option<Vec<Span<'static>>> {
+        let usage = &app.state.context_usage;
+        let bar_width = (width / 5).clamp(8, 20);
+        let pct = usage.percent.min(100);
+        let filled = ((pct as usize * bar_width) / 100).min(bar_width);
+        let empty = bar_width.saturating_sub(filled);
+        let bar_color = if pct > 90 {
+            COLOR_ERROR
+        } else if pct > 70 {
+            COLOR_WARNING
+        } else {
+            COLOR_PURPLE
+        };
 
-    fn on_start(&mut self, tag: MdTag) {
-        match tag {
-            MdTag::Heading(_level, ..) => {
-                self.new_line();
-                let style = Style::default()
-                    .fg(COLOR_PURPLE)
-                    .add_modifier(Modifier::BOLD);
-                self.style_stack.push(self.current_style().patch(style));
-            }
-            MdTag::BlockQuote => {
-                self.new_line();
-                self.line_prefix = Some(("> ".to_string(), Style::default().fg(COLOR_YELLOW)));
-                self.style_stack.push(
-                    self.current_style().patch(
-                        Style::default()
-                            .fg(COLOR_YELLOW)
-                            .add_modifier(Modifier::ITALIC),
-                    ),
-                );
-            }
-            MdTag::List(start) => {
-                let ordered = start.is_some();
-                let index = start.unwrap_or(1) as usize;
-                self.list_stack.push(ListState { ordered, index });
-            }
-            MdTag::Item => {
-                self.new_line();
-                if let Some(state) = self.list_stack.last_mut() {
-                    let prefix = if state.ordered {
-                        format!("{}. ", state.index)
-                    } else {
-                        "• ".to_string()
-                    };
-                    self.pending_item_prefix = Some((prefix, Style::default().fg(COLOR_TEXT)));
-                }
-            }
-            MdTag::CodeBlock(_) => {
-                self.new_line();
-                self.in_code_block = true;
-            }
-            MdTag::Emphasis => {
-                self.style_stack.push(
-                    self.current_style().patch(
-                        Style::default()
-                            .fg(COLOR_YELLOW)
-                            .add_modifier(Modifier::ITALIC),
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        spans.push(Span::styled(
+            "Context ",
+            Style::default().fg(COLOR_TEXT_DIM),
+        ));
+        if app.accessible {
+            // The bar's colors carry the breakdown, so spell it out instead.
+            spans.push(Span::styled(
+                format!(
+                    "{}% {}/{}",
+                    pct,
+                    format_number(usage.used),
+                    format_number(usage.limit)
+                ),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ));
+            if let Some(b) = &usage.breakdown {
+                spans.push(Span::styled(
+                    format!(
+                        " (system {}, memory {}, conversation {})",
+                        format_number(b.system),
+                        format_number(b.memory),
+                        format_number(b.conversation)
                     ),
-                );
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
             }
-            MdTag::Strong => {
-                self.style_stack.push(
-                    self.current_style().patch(
-                        Style::default()
-                            .fg(COLOR_ORANGE)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                );
+            if pct > 90 {
+                spans.push(Span::styled(
+                    " nearly full",
+                    Style::default().fg(COLOR_ERROR),
+                ));
             }
-            MdTag::Strikethrough => {
-                self.style_stack.push(
-                    self.current_style().patch(
-                        Style::default()
-                            .fg(COLOR_MUTED)
-                            .add_modifier(Modifier::CROSSED_OUT),
-                    ),
-                );
+        }
+        // With a breakdown the filled part is split into system prompt, memory
+        // (compacted summary) and conversation, each sized by its share.
+        let segments = match &usage.breakdown {
+            Some(b) if usage.used > 0 => {
+                let cells = |tokens: u64| (tokens as usize * filled) / usage.used as usize;
+                let system = cells(b.system);
+                let memory = cells(b.memory).min(filled - system);
+                vec![
+                    (system, COLOR_CYAN),
+                    (memory, COLOR_YELLOW),
+                    (filled - system - memory, bar_color),
+                ]
             }
-            MdTag::Link(_, _, _) => {
-                self.style_stack.push(
-                    self.current_style().patch(
-                        Style::default()
-                            .fg(COLOR_CYAN)
-                            .add_modifier(Modifier::UNDERLINED),
-                    ),
-                );
+            _ => vec![(filled, bar_color)],
+        };
+        if !app.accessible {
+            for (cells, color) in segments {
+                spans.push(Span::styled("=".repeat(cells), Style::default().fg(color)));
             }
-            _ => {}
+            spans.push(Span::styled(
+                ".".repeat(empty),
+                Style::default().fg(Color::Rgb(30, 41, 59)),
+            ));
+            spans.push(Span::styled(
+                format!(
+                    " {}% {}/{}",
+                    pct,
+                    format_number(usage.used),
+                    format_number(usage.limit)
+                ),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ));
+        }
+        if let Some(cached) = usage.cached.filter(|&c| c > 0 && usage.used > 0) {
+            spans.push(Span::styled(
+                format!(" · {}% cached", (cached * 100 / usage.used).min(100)),
+                Style::default().fg(COLOR_SUCCESS),
+            ));
+        }
+        if let Some(turns) = app.turns_remaining() {
+            spans.push(Span::styled(
+                format!(
+                    " · ~{} turn{} left",
+                    turns,
+                    if turns == 1 { "" } else { "s" }
+                ),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ));
+        }
+        if let Some(status) = &app.state.context_status {
+            spans.push(Span::styled(
+                format!(" {}", status),
+                Style::default().fg(COLOR_TEXT_DIM),
+            ));
+        }
+        if let Some(retry) = &app.retry_status {
+            spans.push(Span::styled("  ", Style::default()));
+            spans.push(Span::styled(
+                retry.label(),
+                Style::default().fg(COLOR_WARNING),
+            ));
         }
+        Some(spans)
     }
+}
 
-    fn on_end(&mut self, tag: MdTag) {
-        match tag {
-            MdTag::Heading(..) => {
-                self.style_stack.pop();
-                self.new_line();
-            }
-            MdTag::BlockQuote => {
-                self.style_stack.pop();
-                self.line_prefix = None;
-                self.new_line();
-            }
-            MdTag::List(_) => {
-                self.list_stack.pop();
-                self.new_line();
-            }
-            MdTag::Item => {
-                if let Some(state) = self.list_stack.last_mut() {
-                    if state.ordered {
-                        state.index += 1;
-                    }
+pub fn format_status_lines(app: &App, width: usize) -> Vec<Line<'static>> {
+    app.status_layout
+        .iter()
+        .map(|names| {
+            let mut spans: Vec<Span<'static>> = Vec::new();
+            for segment in names.iter().filter_map(|name| status_segment(name)) {
+                let Some(segment_spans) = segment.spans(app, width) else {
+                    continue;
+                };
+                if !spans.is_empty() {
+                    spans.push(Span::styled("|", Style::default().fg(COLOR_TEXT_DIM)));
                 }
-                self.new_line();
-            }
-            MdTag::CodeBlock(_) => {
-                self.in_code_block = false;
-                self.new_line();
+                spans.extend(segment_spans);
             }
-            MdTag::Emphasis | MdTag::Strong | MdTag::Strikethrough | MdTag::Link(..) => {
-                self.style_stack.pop();
-            }
-            MdTag::Paragraph => {
-                self.new_line_if_content();
-            }
-            _ => {}
-        }
-    }
+            Line::from(spans)
+        })
+        .collect()
+}
 
-    fn current_style(&self) -> Style {
-        self.style_stack
-            .last()
-            .cloned()
-            .unwrap_or_else(|| Style::default().fg(COLOR_TEXT))
+fn agent_color(agent: &str) -> Color {
+    match agent {
+        "plan" => COLOR_PURPLE,
+        "build" => COLOR_GREEN,
+        _ => COLOR_CYAN,
     }
+}
 
-    fn flush_line(&mut self) {
-        if self.current_spans.is_empty() && self.lines.is_empty() {
-            return;
-        }
-        if !self.current_spans.is_empty() || !self.lines.is_empty() {
-            self.lines.push(Line::from(self.current_spans.clone()));
-        }
-        self.current_spans.clear();
-        self.current_width = 0;
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_cursor_position, compute_display_input_with_cursor, format_elapsed, split_dir,
+        todo_progress_bar, wrap_plain_lines,
+    };
+    use crate::constants::{IMAGE_MARKER, PASTE_END, PASTE_START};
+    use std::time::Duration;
+    use unicode_width::UnicodeWidthStr;
+
+    /// Cursor cell for raw input, going through the same display mapping as
+    /// the input box.
+    fn cursor_cell(value: &str, cursor: usize, chips: &[String], width: usize) -> (usize, usize) {
+        let (display, index) = compute_display_input_with_cursor(value, cursor, chips);
+        compute_cursor_position(&display, index, width)
     }
 
-    fn new_line(&mut self) {
-        self.pending_space = false;
-        self.flush_line();
+    #[test]
+    fn elapsed_switches_units() {
+        assert_eq!(format_elapsed(Duration::from_secs(45)), "45s");
+        assert_eq!(format_elapsed(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_elapsed(Duration::from_secs(3720)), "1h02m");
     }
 
-    fn new_line_if_content(&mut self) {
-        if !self.current_spans.is_empty() {
-            self.flush_line();
-        }
+    #[test]
+    fn todo_bar_scales_long_lists() {
+        let bar = |done, total| {
+            let (filled, empty) = todo_progress_bar(done, total);
+            filled + &empty
+        };
+        assert_eq!(bar(3, 5), "▰▰▰▱▱");
+        assert_eq!(bar(0, 2), "▱▱");
+        assert_eq!(bar(10, 40), "▰▰▱▱▱▱▱▱▱▱");
+        assert_eq!(bar(0, 0), "");
     }
 
-    fn ensure_line_prefix(&mut self) {
-        if self.current_spans.is_empty() {
-            if let Some((prefix, style)) = self.line_prefix.clone() {
-                self.push_span(&prefix, style);
-            }
-            if let Some((prefix, style)) = self.pending_item_prefix.take() {
-                self.push_span(&prefix, style);
-            }
-        }
-    }
+    // ── Single line ─────────────────────────────────────────
 
-    fn push_span(&mut self, text: &str, style: Style) {
-        if text.is_empty() {
-            return;
-        }
-        self.ensure_line_prefix();
-        self.current_spans
-            .push(Span::styled(text.to_string(), style));
-        self.current_width += UnicodeWidthStr::width(text);
+    #[test]
+    fn test_cursor_position_ascii() {
+        assert_eq!(compute_cursor_position("hello", 3, 20), (0, 3));
+        assert_eq!(compute_cursor_position("hello", 5, 20), (0, 5));
     }
 
-    fn push_space(&mut self) {
-        self.pending_space = true;
+    #[test]
+    fn test_cursor_position_cjk_counts_columns() {
+        // Each CJK character is two cells wide.
+        assert_eq!(compute_cursor_position("你好world", 2, 20), (0, 4));
+        assert_eq!(compute_cursor_position("你好world", 7, 20), (0, 9));
     }
 
-    fn push_word(&mut self, word: &str, style: Style) {
-        let word_width = UnicodeWidthStr::width(word);
-        if self.current_width > 0
-            && self.pending_space
-            && self.current_width + 1 + word_width > self.width
-        {
-            self.new_line();
-        } else if self.current_width > 0 && self.pending_space {
-            self.push_span(" ", Style::default().fg(COLOR_TEXT));
-        }
-        self.pending_space = false;
+    #[test]
+    fn test_cursor_position_emoji() {
+        assert_eq!(compute_cursor_position("a😀b", 2, 20), (0, 3));
+        assert_eq!(compute_cursor_position("a😀b", 3, 20), (0, 4));
+    }
 
-        if word_width <= self.width {
-            self.push_span(word, style);
-            return;
-        }
+    // ── Wrapping ────────────────────────────────────────────
 
-        let mut remaining = word;
-        while !remaining.is_empty() {
-            let mut take = remaining.len();
-            while take > 0 && UnicodeWidthStr::width(&remaining[..take]) > self.width {
-                take -= 1;
-            }
-            if take == 0 {
-                break;
-            }
-            let chunk = &remaining[..take];
-            self.push_span(chunk, style);
-            remaining = &remaining[take..];
-            if !remaining.is_empty() {
-                self.new_line();
-            }
-        }
+    #[test]
+    fn test_cursor_position_wraps_wide_chars() {
+        // Width 5 fits two CJK characters per row; a third would need six.
+        let text = "你好你好你好";
+        assert_eq!(compute_cursor_position(text, 1, 5), (0, 2));
+        assert_eq!(compute_cursor_position(text, 3, 5), (1, 2));
+        assert_eq!(compute_cursor_position(text, 6, 5), (2, 4));
     }
 
-    fn push_text(&mut self, text: &str, style: Style) {
-        let mut token = String::new();
-        let mut in_space = false;
-        for ch in text.chars() {
-            if ch == '\n' {
-                self.flush_token(&token, in_space, style);
-                token.clear();
-                in_space = false;
-                self.new_line();
-                continue;
-            }
-            let is_space = ch.is_whitespace();
-            if is_space != in_space && !token.is_empty() {
-                self.flush_token(&token, in_space, style);
-                token.clear();
-            }
-            in_space = is_space;
-            if is_space {
-                token.push(' ');
-            } else {
-                token.push(ch);
-            }
-        }
-        self.flush_token(&token, in_space, style);
+    #[test]
+    fn test_cursor_position_follows_word_wrap() {
+        // "hello world" at width 8 wraps before "world", like the input box.
+        assert_eq!(compute_cursor_position("hello world", 6, 8), (1, 0));
+        assert_eq!(compute_cursor_position("hello world", 9, 8), (1, 3));
+        assert_eq!(compute_cursor_position("日本 語です", 4, 5), (1, 2));
     }
 
-    fn flush_token(&mut self, token: &str, is_space: bool, style: Style) {
-        if token.is_empty() {
-            return;
-        }
-        if is_space {
-            self.push_space();
-        } else {
-            self.push_word(token, style);
-        }
+    #[test]
+    fn test_cursor_position_after_newline() {
+        assert_eq!(compute_cursor_position("你\n好", 2, 20), (1, 0));
+        assert_eq!(compute_cursor_position("你\n好", 3, 20), (1, 2));
+        assert_eq!(compute_cursor_position("a\n\nb", 3, 20), (2, 0));
     }
 
-    fn push_code_block_text(&mut self, text: &str) {
-        for (idx, line) in text.lines().enumerate() {
-            if idx > 0 {
-                self.new_line();
-            }
-            self.push_span(line, Style::default().fg(COLOR_GREEN));
-        }
-    }
-}
+    // ── Pastes and attachments ──────────────────────────────
 
-pub fn tool_icon(name: &str) -> &'static str {
-    match name {
-        "read" => "[R]",
-        "write" => "[W]",
-        "edit" => "[E]",
-        "multi_edit" => "[E]",
-        "bash" => "[$]",
-        "grep" => "[?]",
-        "glob" => "[G]",
-        "ls" => "[L]",
-        "task" => "[T]",
-        "websearch" => "[S]",
-        "webfetch" => "[F]",
-        "apply_patch" => "[P]",
-        "question" => "[Q]",
-        "todoread" => "[>]",
-        "todowrite" => "[>]",
-        "codesearch" => "[C]",
-        _ => "[*]",
+    #[test]
+    fn test_cursor_position_after_wide_paste() {
+        let value = format!("{}日本語{}x", PASTE_START, PASTE_END);
+        assert_eq!(cursor_cell(&value, value.len(), &[], 20), (0, 7));
+        let before_x = value.len() - 1;
+        assert_eq!(cursor_cell(&value, before_x, &[], 20), (0, 6));
     }
-}
 
-struct ToolDisplay {
-    label: String,
-    color: Color,
-}
+    #[test]
+    fn test_cursor_position_around_image_marker() {
+        let chips = vec!["[Image #1]".to_string()];
+        let value = format!("图{}!", IMAGE_MARKER);
+        // Displayed as "图 [Image #1] !".
+        assert_eq!(cursor_cell(&value, "图".len(), &chips, 40), (0, 2));
+        assert_eq!(cursor_cell(&value, value.len(), &chips, 40), (0, 15));
+    }
 
-fn tool_display(name: &str) -> ToolDisplay {
-    match name {
-        "read" => ToolDisplay {
-            label: "Read".to_string(),
-            color: COLOR_SUCCESS,
-        },
-        "write" => ToolDisplay {
-            label: "Write".to_string(),
-            color: COLOR_ORANGE,
-        },
-        "edit" => ToolDisplay {
-            label: "Edit".to_string(),
-            color: COLOR_ORANGE,
-        },
-        "multi_edit" => ToolDisplay {
-            label: "Multi Edit".to_string(),
-            color: COLOR_ORANGE,
-        },
-        "apply_patch" => ToolDisplay {
-            label: "Patch".to_string(),
-            color: COLOR_ORANGE,
-        },
-        "bash" => ToolDisplay {
-            label: "Terminal".to_string(),
-            color: COLOR_CYAN,
-        },
-        "grep" => ToolDisplay {
-            label: "Search".to_string(),
-            color: COLOR_PURPLE,
-        },
-        "glob" => ToolDisplay {
-            label: "Glob".to_string(),
-            color: COLOR_PURPLE,
-        },
-        "ls" => ToolDisplay {
-            label: "List".to_string(),
-            color: COLOR_PURPLE,
-        },
-        "task" => ToolDisplay {
-            label: "Task".to_string(),
-            color: COLOR_WARNING,
-        },
-        "websearch" => ToolDisplay {
-            label: "Web Search".to_string(),
-            color: COLOR_CYAN,
-        },
-        "webfetch" => ToolDisplay {
-            label: "Fetch".to_string(),
-            color: COLOR_CYAN,
-        },
-        "question" => ToolDisplay {
-            label: "Question".to_string(),
-            color: COLOR_WARNING,
-        },
-        "todoread" => ToolDisplay {
-            label: "Todos".to_string(),
-            color: COLOR_WARNING,
-        },
-        "todowrite" => ToolDisplay {
-            label: "Todos".to_string(),
-            color: COLOR_WARNING,
-        },
-        "codesearch" => ToolDisplay {
-            label: "Code Search".to_string(),
-            color: COLOR_PURPLE,
-        },
-        "lsp" => ToolDisplay {
-            label: "LSP".to_string(),
-            color: COLOR_PURPLE,
-        },
-        "revert" => ToolDisplay {
-            label: "Revert".to_string(),
-            color: COLOR_ERROR,
-        },
-        _ => ToolDisplay {
-            label: name.to_string(),
-            color: COLOR_TEXT_DIM,
-        },
+    #[test]
+    fn test_cursor_position_mixed_wraps_consistently() {
+        let chips = vec!["[a.png]".to_string()];
+        let value = format!("表{}{}粘贴{}尾", IMAGE_MARKER, PASTE_START, PASTE_END);
+        // Displayed as "表 [a.png]粘贴尾": 2 + 1 + 7 + 6 = 16 cells.
+        assert_eq!(cursor_cell(&value, value.len(), &chips, 40), (0, 16));
+        // At width 12 the cursor sits after the last wrapped line's text.
+        let (display, _) = compute_display_input_with_cursor(&value, 0, &chips);
+        let lines = wrap_plain_lines(&display, 12);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            cursor_cell(&value, value.len(), &chips, 12),
+            (1, UnicodeWidthStr::width(lines[1].as_str()))
+        );
     }
-}
 
-pub fn format_tool_args(args_json: &str) -> String {
-    if let Ok(value) = serde_json::from_str::<serde_json::Value>(args_json) {
-        if let Some(p) = value.get("file_path").and_then(|v| v.as_str()) {
-            return p.to_string();
-        }
-        if let Some(cmd) = value.get("command").and_then(|v| v.as_str()) {
-            return if cmd.len() > 60 {
-                format!("{}...", &cmd[..60])
-            } else {
-                cmd.to_string()
-            };
-        }
-        if let Some(q) = value.get("query").and_then(|v| v.as_str()) {
-            return format!("\"{}\"", q);
-        }
-        if let Some(pat) = value.get("pattern").and_then(|v| v.as_str()) {
-            return pat.to_string();
-        }
-        if let Some(dir) = value.get("directory_path").and_then(|v| v.as_str()) {
-            return dir.to_string();
-        }
-        if let Some(desc) = value.get("description").and_then(|v| v.as_str()) {
-            return if desc.len() > 60 {
-                format!("{}...", &desc[..60])
-            } else {
-                desc.to_string()
-            };
-        }
-        if let Some(url) = value.get("url").and_then(|v| v.as_str()) {
-            return url.to_string();
-        }
+    #[test]
+    fn split_dir_puts_top_level_files_under_dot() {
+        assert_eq!(split_dir("src/app.rs"), ("src", "app.rs"));
+        assert_eq!(
+            split_dir("crates/core/src/lib.rs"),
+            ("crates/core/src", "lib.rs")
+        );
+        assert_eq!(split_dir("Cargo.toml"), (".", "Cargo.toml"));
     }
-    String::new()
 }
@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Token usage of one assistant turn, from `get_usage`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnUsage {
+    pub input: u64,
+    pub output: u64,
+    pub cached: Option<u64>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelUsage {
+    pub model: String,
+    pub turns: usize,
+    pub input: u64,
+    pub output: u64,
+    pub cached: u64,
+}
+
+/// Totals per model, heaviest first.
+pub fn usage_by_model(turns: &[TurnUsage]) -> Vec<ModelUsage> {
+    let mut models: Vec<ModelUsage> = Vec::new();
+    for turn in turns {
+        let name = turn.model.as_deref().unwrap_or("unknown");
+        let index = match models.iter().position(|m| m.model == name) {
+            Some(index) => index,
+            None => {
+                models.push(ModelUsage {
+                    model: name.to_string(),
+                    ..Default::default()
+                });
+                models.len() - 1
+            }
+        };
+        let entry = &mut models[index];
+        entry.turns += 1;
+        entry.input += turn.input;
+        entry.output += turn.output;
+        entry.cached += turn.cached.unwrap_or(0);
+    }
+    models.sort_by_key(|m| std::cmp::Reverse(m.input + m.output));
+    models
+}
+
+/// List prices in USD per million input and output tokens, matched by
+/// substring with more specific names first. Only a rough guide: providers
+/// change prices and discount differently.
+const PRICES: [(&str, f64, f64); 12] = [
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5", 1.25, 10.0),
+    ("o4-mini", 1.1, 4.4),
+    ("o3", 2.0, 8.0),
+    ("claude-opus", 15.0, 75.0),
+    ("claude-sonnet", 3.0, 15.0),
+    ("claude-haiku", 0.8, 4.0),
+    ("gemini-2.5-pro", 1.25, 10.0),
+];
+
+/// Cached input is billed at this fraction of the input price.
+const CACHED_INPUT_RATE: f64 = 0.1;
+
+pub fn model_price(model: &str) -> Option<(f64, f64)> {
+    let model = model.to_lowercase();
+    PRICES
+        .iter()
+        .find(|(name, _, _)| model.contains(name))
+        .map(|&(_, input, output)| (input, output))
+}
+
+/// Estimated cost in USD, or `None` for models without a known price.
+pub fn estimate_cost(usage: &ModelUsage) -> Option<f64> {
+    let (input_price, output_price) = model_price(&usage.model)?;
+    let cached = usage.cached.min(usage.input) as f64;
+    let uncached = usage.input as f64 - cached;
+    Some(
+        (uncached * input_price
+            + cached * input_price * CACHED_INPUT_RATE
+            + usage.output as f64 * output_price)
+            / 1_000_000.0,
+    )
+}
+
+/// One bar per value, scaled to the largest; only the last `width` values fit.
+pub fn sparkline(values: &[u64], width: usize) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let values = &values[values.len().saturating_sub(width)..];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| BARS[((v * (BARS.len() as u64 - 1)) / max) as usize])
+        .collect()
+}
+
+/// Share of the conversation held by each role, estimated from message
+/// length. Returns (label, fraction) pairs that sum to 1.
+pub fn context_composition(messages: &[Value]) -> Vec<(&'static str, f64)> {
+    let mut sizes = [("user", 0usize), ("assistant", 0), ("tool results", 0)];
+    for message in messages {
+        let slot = match message.get("role").and_then(|r| r.as_str()) {
+            Some("user") => 0,
+            Some("assistant") => 1,
+            Some("tool") => 2,
+            _ => continue,
+        };
+        sizes[slot].1 += message_len(message);
+    }
+    let total: usize = sizes.iter().map(|(_, size)| size).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    sizes
+        .iter()
+        .filter(|(_, size)| *size > 0)
+        .map(|&(label, size)| (label, size as f64 / total as f64))
+        .collect()
+}
+
+fn message_len(message: &Value) -> usize {
+    let content = match message.get("content") {
+        Some(Value::String(text)) => text.len(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .map(str::len)
+            .sum(),
+        _ => 0,
+    };
+    let tool_calls = message
+        .get("toolCalls")
+        .map(|calls| calls.to_string().len())
+        .unwrap_or(0);
+    content + tool_calls
+}
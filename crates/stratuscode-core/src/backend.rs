@@ -0,0 +1,395 @@
+//! The client for the StratusCode backend (a bun process speaking JSON-RPC
+//! over stdio) and the types it sends back.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::env::EnvVar;
+
+/// A message the backend sends unprompted: `state`, `timeline_event`,
+/// `tool_output`, `question_pending` and so on.
+#[derive(Debug, Clone)]
+pub struct BackendNotification {
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+    pub context: Option<u64>,
+    pub model: Option<String>,
+}
+
+/// One entry in a session's timeline: a user or assistant message, reasoning,
+/// a tool call or its result, or a status line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEvent {
+    pub id: String,
+    pub session_id: String,
+    pub created_at: i64,
+    pub kind: String,
+    pub content: String,
+    pub tokens: Option<TokenUsage>,
+    pub streaming: Option<bool>,
+    pub tool_call_id: Option<String>,
+    pub tool_name: Option<String>,
+    pub status: Option<String>,
+    pub attachments: Option<Vec<Attachment>>,
+    /// Set on events emitted by a `task` sub-agent; points at the spawning tool call.
+    pub parent_tool_call_id: Option<String>,
+    /// On user events: the id of the pending event this one replaces.
+    pub client_id: Option<String>,
+    /// On `compaction` events, which hold the summary as their content.
+    pub compaction: Option<Compaction>,
+}
+
+/// Puts loaded events in `created_at` order, ties left in the order they
+/// arrived, keeping the last copy of any event that arrived twice. Ids are
+/// random, so they say nothing about which of two same-millisecond events
+/// came first. Notifications can land out of
+/// order, so anything read back as a whole timeline goes through this.
+pub fn order_events(events: &mut Vec<TimelineEvent>) {
+    let mut seen = std::collections::HashSet::new();
+    let mut deduped: Vec<TimelineEvent> = events
+        .drain(..)
+        .rev()
+        .filter(|e| seen.insert(e.id.clone()))
+        .collect();
+    deduped.reverse();
+    deduped.sort_by_key(|e| e.created_at);
+    *events = deduped;
+}
+
+/// What context management did to the history the model sees.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Compaction {
+    pub messages_removed: u64,
+    /// Otherwise the messages were dropped.
+    pub summarized: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub r#type: String,
+    pub mime: Option<String>,
+    pub line_count: Option<u64>,
+    pub text: Option<String>,
+    pub data: Option<String>,
+}
+
+/// The session as the backend sees it, sent whole with each `state`
+/// notification and returned by `get_state`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatState {
+    pub messages: Vec<Value>,
+    pub is_loading: bool,
+    pub error: Option<String>,
+    pub timeline_events: Vec<TimelineEvent>,
+    pub session_tokens: Option<TokenUsage>,
+    pub context_usage: ContextUsage,
+    pub context_status: Option<String>,
+    pub tokens: TokenUsage,
+    pub session_id: Option<String>,
+    pub plan_exit_proposed: bool,
+    pub agent: String,
+    pub model_override: Option<String>,
+    pub provider_override: Option<String>,
+    pub reasoning_effort_override: Option<String>,
+    /// Events before `timeline_events` that the backend left out; fetch them
+    /// with `get_events`.
+    #[serde(default)]
+    pub earlier_events: u64,
+    /// Set from the first message unless the session was renamed.
+    pub title: Option<String>,
+}
+
+/// A page of older events returned by `get_events`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventPage {
+    pub events: Vec<TimelineEvent>,
+    /// How many events are older still.
+    pub earlier: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextUsage {
+    pub used: u64,
+    pub limit: u64,
+    pub percent: u64,
+    pub cached: Option<u64>,
+    pub breakdown: Option<ContextBreakdown>,
+}
+
+/// Estimated split of `ContextUsage::used`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContextBreakdown {
+    pub system: u64,
+    pub memory: u64,
+    pub conversation: u64,
+}
+
+/// A saved session as listed by `list_sessions`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub title: String,
+    pub message_count: Option<u64>,
+    pub first_message: Option<String>,
+}
+
+/// How many recent calls `CallTimings` keeps.
+const CALL_TIMINGS_KEPT: usize = 512;
+
+/// How long recent requests took, by method. Cloning shares the log, so it
+/// can be read while another thread holds the client for a slow call.
+#[derive(Debug, Clone, Default)]
+pub struct CallTimings(Arc<Mutex<VecDeque<(String, Duration)>>>);
+
+impl CallTimings {
+    fn record(&self, method: &str, elapsed: Duration) {
+        let mut calls = self.0.lock().unwrap();
+        if calls.len() == CALL_TIMINGS_KEPT {
+            calls.pop_front();
+        }
+        calls.push_back((method.to_string(), elapsed));
+    }
+
+    /// The kept calls, oldest first.
+    pub fn snapshot(&self) -> Vec<(String, Duration)> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// A request in flight, from [`BackendClient::request`].
+pub struct PendingCall {
+    method: String,
+    started: Instant,
+    rx: Receiver<Value>,
+    timings: CallTimings,
+}
+
+impl PendingCall {
+    /// Blocks until the result, or the backend's error.
+    pub fn wait(self) -> Result<Value> {
+        let resp = self.rx.recv().map_err(|_| anyhow!("Backend closed"))?;
+        self.timings.record(&self.method, self.started.elapsed());
+        if let Some(error) = resp.get("error") {
+            return Err(anyhow!(error.to_string()));
+        }
+        Ok(resp.get("result").cloned().unwrap_or(Value::Null))
+    }
+}
+
+/// A running backend. Requests are answered in order of `id`; notifications
+/// arrive on the receiver returned by [`BackendClient::spawn`].
+pub struct BackendClient {
+    child: Child,
+    stdin: ChildStdin,
+    pending: Arc<Mutex<HashMap<u64, Sender<Value>>>>,
+    next_id: AtomicU64,
+    timings: CallTimings,
+}
+
+impl BackendClient {
+    /// Starts `backend_cmd args…` (usually `bun server.js`) with `envs` added
+    /// to its environment.
+    pub fn spawn(
+        backend_cmd: &str,
+        args: &[String],
+        envs: &[EnvVar],
+    ) -> Result<(Self, Receiver<BackendNotification>)> {
+        let mut cmd = Command::new(backend_cmd);
+        cmd.args(args)
+            .envs(envs.iter().map(|var| (&var.name, &var.value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        let mut child = cmd.spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open stdout"))?;
+
+        let pending: Arc<Mutex<HashMap<u64, Sender<Value>>>> = Arc::new(Mutex::new(HashMap::new()));
+        let (notify_tx, notify_rx) = mpsc::channel();
+
+        Self::start_reader_thread(stdout, pending.clone(), notify_tx);
+
+        Ok((
+            Self {
+                child,
+                stdin,
+                pending,
+                next_id: AtomicU64::new(1),
+                timings: CallTimings::default(),
+            },
+            notify_rx,
+        ))
+    }
+
+    fn start_reader_thread(
+        stdout: ChildStdout,
+        pending: Arc<Mutex<HashMap<u64, Sender<Value>>>>,
+        notify_tx: Sender<BackendNotification>,
+    ) {
+        thread::spawn(move || {
+            let reader = BufReader::new(stdout);
+            for line in reader.lines().map_while(Result::ok) {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let value: Value = match serde_json::from_str(trimmed) {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                if let Some(id) = value.get("id").and_then(|v| v.as_u64()) {
+                    let mut map = pending.lock().unwrap();
+                    if let Some(tx) = map.remove(&id) {
+                        let _ = tx.send(value);
+                    }
+                } else if let Some(method) = value.get("method").and_then(|v| v.as_str()) {
+                    let params = value.get("params").cloned().unwrap_or(Value::Null);
+                    let _ = notify_tx.send(BackendNotification {
+                        method: method.to_string(),
+                        params,
+                    });
+                }
+            }
+        });
+    }
+
+    /// Sends a request and blocks until its result, or the backend's error.
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.request(method, params)?.wait()
+    }
+
+    /// Sends a request without waiting for the answer. Slow calls made
+    /// through a shared client should wait on the returned [`PendingCall`]
+    /// after letting go of the lock, so other requests (an abort, say) can
+    /// go out meanwhile.
+    pub fn request(&mut self, method: &str, params: Value) -> Result<PendingCall> {
+        let started = Instant::now();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+
+        // Registered first so an answer that beats `write_all` isn't dropped.
+        let (tx, rx) = mpsc::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        if let Err(e) = self
+            .stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+        {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e.into());
+        }
+        Ok(PendingCall {
+            method: method.to_string(),
+            started,
+            rx,
+            timings: self.timings.clone(),
+        })
+    }
+
+    pub fn timings(&self) -> CallTimings {
+        self.timings.clone()
+    }
+
+    /// Kills the backend process.
+    pub fn shutdown(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, at: i64, content: &str) -> TimelineEvent {
+        serde_json::from_value(json!({
+            "id": id,
+            "sessionId": "s1",
+            "createdAt": at,
+            "kind": "assistant",
+            "content": content,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn order_events_sorts_and_keeps_latest_copy() {
+        let mut events = vec![
+            event("c", 30, "third"),
+            event("b", 10, "tie, arrived first"),
+            event("a", 10, "tie, arrived second"),
+            event("c", 30, "third, updated"),
+        ];
+        order_events(&mut events);
+        let got: Vec<(&str, &str)> = events
+            .iter()
+            .map(|e| (e.id.as_str(), e.content.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("b", "tie, arrived first"),
+                ("a", "tie, arrived second"),
+                ("c", "third, updated"),
+            ]
+        );
+    }
+
+    #[test]
+    fn requests_can_be_answered_out_of_order() {
+        // Answers the second request before the first, as a slow call and a
+        // quick one would be.
+        let script = r#"read -r first; read -r second
+printf '{"jsonrpc":"2.0","id":2,"result":"quick"}\n{"jsonrpc":"2.0","id":1,"result":"slow"}\n'
+read -r rest"#;
+        let (mut client, _notifications) =
+            BackendClient::spawn("sh", &["-c".to_string(), script.to_string()], &[]).unwrap();
+        let slow = client.request("slow", json!({})).unwrap();
+        let quick = client.request("quick", json!({})).unwrap();
+        assert_eq!(quick.wait().unwrap(), json!("quick"));
+        assert_eq!(slow.wait().unwrap(), json!("slow"));
+        let methods: Vec<String> = client
+            .timings()
+            .snapshot()
+            .into_iter()
+            .map(|(method, _)| method)
+            .collect();
+        assert_eq!(methods, ["quick", "slow"]);
+        client.shutdown();
+    }
+}
@@ -0,0 +1,256 @@
+//! Project settings (`.stratuscode/config.toml`) and where user-level files
+//! live.
+
+use crate::constants::{SPINNER_BRAILLE, SPINNER_CLASSIC, SPINNER_DOTS};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Per-project settings from `.stratuscode/config.toml`. Command-line flags
+/// take precedence over anything set here.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub agent: Option<String>,
+    /// UI language, e.g. `de` or `pt-BR`; defaults to the locale from
+    /// `LC_ALL`/`LC_MESSAGES`/`LANG`.
+    pub lang: Option<String>,
+    pub print_on_exit: bool,
+    /// Shell command run after a turn that changed files, e.g.
+    /// `cargo test -q`; its output streams into the timeline.
+    pub verify: Option<String>,
+    pub bell: BellMode,
+    /// How long a turn must run before `bell = "long-tasks"` rings (default 30).
+    pub bell_after_secs: Option<u64>,
+    /// How `/copy` and `/patch` reach the clipboard: `system`, `osc52`
+    /// (works over SSH and in tmux), or `auto` to pick per session.
+    pub clipboard: ClipboardMode,
+    pub index: IndexConfig,
+    pub questions: QuestionConfig,
+    pub status: StatusConfig,
+    pub hooks: HooksConfig,
+    pub spinner: SpinnerConfig,
+    pub diff: DiffConfig,
+    /// Variables for the backend and the commands it runs, e.g.
+    /// `NPM_TOKEN = { keychain = "npm" }` or `RUST_LOG = "debug"`.
+    pub env: HashMap<String, EnvValue>,
+    pub redact: RedactConfig,
+    pub with_diff: WithDiffConfig,
+    pub accessibility: AccessibilityConfig,
+}
+
+/// A value, or where in the OS keychain to find it: `keychain` is the
+/// service, `account` defaults to the variable name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Plain(String),
+    Keychain {
+        keychain: String,
+        account: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    #[default]
+    Auto,
+    System,
+    Osc52,
+}
+
+/// When the terminal bell rings as a turn finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BellMode {
+    Always,
+    LongTasks,
+    #[default]
+    Never,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// Directories left out of the @ mention index, relative to the project
+    /// root (`vendor`, `fixtures/large`).
+    pub exclude: Vec<String>,
+    /// How many directory levels to walk; unlimited when unset.
+    /// `--index-depth` takes precedence.
+    pub max_depth: Option<usize>,
+    /// Descend into symlinked directories. Off by default; link cycles are
+    /// detected either way.
+    pub follow_symlinks: bool,
+    /// Stop after this many entries, for trees too large to index fully.
+    pub max_files: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuestionConfig {
+    /// Seconds a `--prompt` run waits on a question before answering with its
+    /// default option, or skipping it when there is none. Unset, the run
+    /// waits until the question tool times out. Questions settled by
+    /// `--answer` or `--auto-answer` don't wait at all.
+    pub auto_answer_after: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusConfig {
+    /// Segment names per status line, e.g.
+    /// `lines = [["agent", "model", "git"], ["context", "cost", "clock"]]`.
+    pub lines: Option<Vec<Vec<String>>>,
+    /// How long a turn runs before the `timer` segment turns red (default 120).
+    pub turn_warn_secs: Option<u64>,
+}
+
+/// Shell commands the backend runs around a turn, each with the event as JSON
+/// on stdin. `pre_send` can rewrite the message by printing a replacement or
+/// block it by exiting with status 2; the others only observe. Failures show
+/// up in the timeline.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    pub pre_send: Option<String>,
+    pub post_tool: Option<String>,
+    pub post_turn: Option<String>,
+    /// Seconds before a hook is killed (default 30).
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffConfig {
+    /// Diff lines shown inline in the timeline before the rest is folded
+    /// behind Enter (default 120).
+    pub max_lines: Option<usize>,
+}
+
+/// The working tree's `git diff` attached to every message; `/withdiff`
+/// toggles it for the session.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WithDiffConfig {
+    pub enabled: bool,
+    /// Include `git status --short` too, for untracked files.
+    pub status: bool,
+    /// Cut the attachment at this many bytes (default 20000).
+    pub max_bytes: Option<usize>,
+}
+
+/// Masking of likely secrets in the timeline and exports.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// On unless set to `false`.
+    pub enabled: Option<bool>,
+    /// Extra regexes to mask, e.g. `["internal-[0-9a-f]{32}"]`; a capture
+    /// group masks only the group.
+    pub patterns: Vec<String>,
+}
+
+impl RedactConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessibilityConfig {
+    /// Screen reader friendly output: `on`, `off`, or `auto` to follow
+    /// VoiceOver on macOS. `--accessible` turns it on regardless.
+    pub screen_reader: ScreenReaderMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenReaderMode {
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+/// The busy indicator: frame set and the label next to it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpinnerConfig {
+    pub style: SpinnerStyle,
+    /// Labels rotated every few seconds while the model is thinking, e.g.
+    /// `["Thinking…", "Pondering…"]`.
+    pub verbs: Vec<String>,
+    /// Label while a tool runs, by tool name, e.g.
+    /// `bash = "Running commands…"`; overrides the built-in ones.
+    pub tools: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpinnerStyle {
+    #[default]
+    Braille,
+    Dots,
+    Classic,
+}
+
+impl SpinnerStyle {
+    pub fn frames(self) -> &'static [&'static str] {
+        match self {
+            SpinnerStyle::Braille => &SPINNER_BRAILLE,
+            SpinnerStyle::Dots => &SPINNER_DOTS,
+            SpinnerStyle::Classic => &SPINNER_CLASSIC,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/stratuscode`, else `~/.config/stratuscode`; holds
+/// per-user files like templates and translations.
+pub fn user_config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("stratuscode"))
+}
+
+pub fn config_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".stratuscode").join("config.toml")
+}
+
+impl ProjectConfig {
+    /// A missing file is an empty config; a malformed one is an error so a
+    /// typo doesn't silently fall back to the defaults.
+    pub fn load(project_dir: &Path) -> Result<Self, String> {
+        let path = config_path(project_dir);
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        toml::from_str(&raw).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+}
+
+/// Where the active model came from, shown next to it in the status bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelSource {
+    Default,
+    Flag,
+    ProjectConfig,
+    Picker,
+}
+
+impl ModelSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ModelSource::Default => "default",
+            ModelSource::Flag => "--model",
+            ModelSource::ProjectConfig => "config.toml",
+            ModelSource::Picker => "picked",
+        }
+    }
+}
@@ -1,9 +1,13 @@
+//! The palette, spinner frames and input markers shared by the renderers.
+
 use ratatui::style::Color;
 
 pub const PASTE_START: char = '\u{FFF0}';
 pub const PASTE_END: char = '\u{FFF1}';
 pub const IMAGE_MARKER: char = '\u{FFFC}';
-pub const SPINNER_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+pub const SPINNER_CLASSIC: [&str; 4] = ["|", "/", "-", "\\"];
+pub const SPINNER_BRAILLE: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+pub const SPINNER_DOTS: [&str; 4] = ["   ", ".  ", ".. ", "..."];
 
 pub const PASTE_LINE_THRESHOLD: usize = 3;
 pub const PASTE_CHAR_THRESHOLD: usize = 150;
@@ -0,0 +1,69 @@
+//! Environment variables from `[env]` in the project config, set on the
+//! backend process so its tools and hooks inherit them.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::config::EnvValue;
+
+#[derive(Debug, Clone)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    /// Read from the keychain; never shown.
+    pub secret: bool,
+}
+
+/// Resolves `[env]`, sorted by name. Keychain entries that can't be read are
+/// left out and reported.
+pub fn resolve_env(entries: &HashMap<String, EnvValue>) -> (Vec<EnvVar>, Vec<String>) {
+    let mut vars = Vec::new();
+    let mut errors = Vec::new();
+    for (name, entry) in entries {
+        match entry {
+            EnvValue::Plain(value) => vars.push(EnvVar {
+                name: name.clone(),
+                value: value.clone(),
+                secret: false,
+            }),
+            EnvValue::Keychain { keychain, account } => {
+                let account = account.as_deref().unwrap_or(name);
+                match keychain_lookup(keychain, account) {
+                    Ok(value) => vars.push(EnvVar {
+                        name: name.clone(),
+                        value,
+                        secret: true,
+                    }),
+                    Err(e) => errors.push(format!("env {}: {}", name, e)),
+                }
+            }
+        }
+    }
+    vars.sort_by(|a, b| a.name.cmp(&b.name));
+    (vars, errors)
+}
+
+/// A generic password from the macOS keychain, or the Secret Service
+/// (`secret-tool`) elsewhere.
+fn keychain_lookup(service: &str, account: &str) -> Result<String, String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", service, "-a", account, "-w"])
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", service, "account", account])
+            .output()
+    }
+    .map_err(|e| format!("keychain unavailable ({})", e))?;
+    let value = String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches('\n')
+        .to_string();
+    if !output.status.success() || value.is_empty() {
+        return Err(format!(
+            "no keychain entry for service '{}', account '{}'",
+            service, account
+        ));
+    }
+    Ok(value)
+}
@@ -0,0 +1,137 @@
+//! UI strings by key (`t`, `tf`), from the bundled catalogs or a user's
+//! translation file.
+
+use crate::config::user_config_dir;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Catalogs compiled into the binary, by language tag.
+const BUNDLED: &[(&str, &str)] = &[("en", include_str!("../locales/en.toml"))];
+
+/// UI strings keyed `section.name`, with English behind any gaps.
+struct Catalog {
+    messages: HashMap<String, String>,
+    english: HashMap<String, String>,
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog {
+        messages: HashMap::new(),
+        english: english(),
+    })
+}
+
+fn english() -> HashMap<String, String> {
+    parse_catalog(BUNDLED[0].1).expect("bundled English catalog is valid TOML")
+}
+
+/// `$XDG_CONFIG_HOME/stratuscode/locales`; a `<lang>.toml` there overrides
+/// the bundled catalog, so translations can be tried without a rebuild.
+pub fn locales_dir() -> Option<PathBuf> {
+    Some(user_config_dir()?.join("locales"))
+}
+
+/// Picks the catalog for `lang`, or for the system locale when unset. Must run
+/// before any UI text is built; later calls are ignored. A language without a
+/// catalog falls back to English with an error describing why.
+pub fn init(lang: Option<&str>) -> Result<(), String> {
+    let requested = lang.map(str::to_string).or_else(system_language);
+    let (messages, result) = match requested.as_deref().map(normalize_tag) {
+        None => (HashMap::new(), Ok(())),
+        Some(tag) if tag == "en" || tag.starts_with("en-") => (HashMap::new(), Ok(())),
+        Some(tag) => match load_catalog(&tag) {
+            Ok(messages) => (messages, Ok(())),
+            // Only complain about a language someone asked for by name.
+            Err(_) if lang.is_none() => (HashMap::new(), Ok(())),
+            Err(e) => (HashMap::new(), Err(e)),
+        },
+    };
+    let _ = CATALOG.set(Catalog {
+        messages,
+        english: english(),
+    });
+    result
+}
+
+/// The translated string for `key`, falling back to English and then to the
+/// key itself.
+pub fn t(key: &'static str) -> &'static str {
+    let catalog = catalog();
+    catalog
+        .messages
+        .get(key)
+        .or_else(|| catalog.english.get(key))
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// `t` with `{name}` placeholders filled in.
+pub fn tf(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(t(key).to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+/// The language part of `LC_ALL`, `LC_MESSAGES` or `LANG` (`de_DE.UTF-8`).
+fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| value != "C" && value != "POSIX")
+}
+
+/// `pt_BR.UTF-8` -> `pt-BR`.
+fn normalize_tag(raw: &str) -> String {
+    let base = raw.split(['.', '@']).next().unwrap_or(raw);
+    base.replace('_', "-")
+}
+
+/// `pt-BR` tries `pt-BR.toml` then `pt.toml`, each from the user's locales
+/// directory before the bundled ones.
+fn load_catalog(tag: &str) -> Result<HashMap<String, String>, String> {
+    let mut candidates = vec![tag.to_string()];
+    if let Some((language, _)) = tag.split_once('-') {
+        candidates.push(language.to_string());
+    }
+    for candidate in &candidates {
+        if let Some(path) = locales_dir().map(|dir| dir.join(format!("{}.toml", candidate))) {
+            if let Ok(raw) = std::fs::read_to_string(&path) {
+                return parse_catalog(&raw).map_err(|e| format!("{}: {}", path.display(), e));
+            }
+        }
+        if let Some((_, raw)) = BUNDLED.iter().find(|(name, _)| name == candidate) {
+            return parse_catalog(raw);
+        }
+    }
+    Err(format!("No translation for '{}', using English", tag))
+}
+
+/// Flattens `[section] key = "text"` into `section.key`.
+fn parse_catalog(raw: &str) -> Result<HashMap<String, String>, String> {
+    let table: toml::Table = raw.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let mut messages = HashMap::new();
+    flatten(&table, "", &mut messages);
+    Ok(messages)
+}
+
+fn flatten(table: &toml::Table, prefix: &str, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::String(text) => {
+                out.insert(name, text.clone());
+            }
+            toml::Value::Table(inner) => flatten(inner, &name, out),
+            _ => {}
+        }
+    }
+}
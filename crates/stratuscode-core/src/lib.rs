@@ -0,0 +1,35 @@
+//! The parts of StratusCode that don't need a terminal: the backend client
+//! and the protocol types it returns, project config, the session store and
+//! the timeline renderers. The `stratuscode` binary is one front end on top
+//! of this crate; editor plugins and other tools can drive the same backend.
+//!
+//! ```no_run
+//! use serde_json::json;
+//! use stratuscode_core::backend::{BackendClient, ChatState};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let (mut client, notifications) =
+//!     BackendClient::spawn("bun", &["server.js".to_string()], &[])?;
+//! let result = client.call("initialize", json!({ "projectDir": "." }))?;
+//! let state: ChatState = serde_json::from_value(result["state"].clone())?;
+//! println!("agent: {}", state.agent);
+//!
+//! client.call("send_message", json!({ "content": "Explain src/main.rs" }))?;
+//! for notification in notifications {
+//!     if notification.method == "state" {
+//!         break;
+//!     }
+//! }
+//! client.shutdown();
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod backend;
+pub mod config;
+pub mod constants;
+pub mod env;
+pub mod i18n;
+pub mod render;
+pub mod store;
+pub mod text;
@@ -0,0 +1,2033 @@
+//! Turns session state into styled lines: the timeline, diffs, markdown and
+//! tool summaries. Everything here is a pure function of its inputs, so the
+//! layout can be snapshot-tested without a terminal.
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+use pulldown_cmark::{Event as MdEvent, Options as MdOptions, Parser as MdParser, Tag as MdTag};
+use textwrap::wrap;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use std::borrow::Cow;
+
+use crate::backend::TimelineEvent;
+use crate::constants::*;
+use crate::i18n::{t, tf};
+use crate::text::{format_number, take_width, truncate_text};
+
+/// Which timeline events are shown; applied while building timeline lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineFilter {
+    All,
+    Assistant,
+    Tools,
+    Errors,
+}
+
+impl TimelineFilter {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "assistant" | "prose" => Some(Self::Assistant),
+            "tools" | "tool" => Some(Self::Tools),
+            "errors" | "error" => Some(Self::Errors),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::All => Self::Assistant,
+            Self::Assistant => Self::Tools,
+            Self::Tools => Self::Errors,
+            Self::Errors => Self::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Assistant => "assistant",
+            Self::Tools => "tools",
+            Self::Errors => "errors",
+        }
+    }
+
+    /// User messages stay visible in every mode so filtered output keeps its context.
+    pub fn allows(self, event: &TimelineEvent) -> bool {
+        match self {
+            Self::All => true,
+            Self::Assistant => matches!(event.kind.as_str(), "user" | "assistant"),
+            Self::Tools => matches!(event.kind.as_str(), "user" | "tool_call" | "tool_result"),
+            Self::Errors => match event.kind.as_str() {
+                "user" => true,
+                "tool_call" => event.status.as_deref() == Some("failed"),
+                "status" => event.content.to_lowercase().contains("error"),
+                "error" => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Masks secrets in text as it's laid out; the timeline itself keeps the raw
+/// text so patches and reverts still apply.
+pub trait Redact: std::fmt::Debug {
+    fn redact<'t>(&self, text: &'t str) -> Cow<'t, str>;
+}
+
+/// `text` masked by `redact`, borrowed when there's nothing to mask.
+fn redact_text<'t>(text: &'t str, redact: Option<&dyn Redact>) -> Cow<'t, str> {
+    match redact {
+        Some(redact) => redact.redact(text),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// The event with its content masked by `redact`.
+fn redacted_event<'e>(
+    event: &'e TimelineEvent,
+    redact: Option<&dyn Redact>,
+) -> Cow<'e, TimelineEvent> {
+    match redact_text(&event.content, redact) {
+        Cow::Borrowed(_) => Cow::Borrowed(event),
+        Cow::Owned(content) => Cow::Owned(TimelineEvent {
+            content,
+            ..event.clone()
+        }),
+    }
+}
+
+/// How the timeline is laid out: the view toggles, the width, and the
+/// moment it's drawn (for spinners and elapsed times).
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineOptions<'a> {
+    pub compact: bool,
+    pub timestamps: bool,
+    /// `task` calls, by tool call id, whose sub-agent timeline is folded.
+    pub collapsed_subagents: &'a [String],
+    pub filter: TimelineFilter,
+    pub width: usize,
+    pub diff_max_lines: usize,
+    pub spinner: Option<&'static str>,
+    pub now_ms: i64,
+    /// The label beside the spinner while a turn runs.
+    pub busy_label: Option<&'a str>,
+    /// Events before the first resident one, announced in a row at the top.
+    pub earlier_events: u64,
+    pub loading_earlier: bool,
+    /// Compaction markers whose summary is shown.
+    pub expanded_summaries: &'a [String],
+    pub redact: Option<&'a dyn Redact>,
+}
+
+/// Lays out the timeline. Also returns where each message starts: the line of
+/// its header and the id of its first event.
+pub fn build_timeline_lines(
+    state: &crate::backend::ChatState,
+    tool_outputs: &std::collections::HashMap<String, String>,
+    options: &TimelineOptions,
+    mut cache: Option<&mut EventLineCache>,
+) -> (Vec<Line<'static>>, Vec<(usize, String)>) {
+    let TimelineOptions {
+        compact,
+        timestamps,
+        collapsed_subagents,
+        filter,
+        width,
+        diff_max_lines,
+        spinner,
+        now_ms,
+        busy_label,
+        earlier_events,
+        loading_earlier,
+        expanded_summaries,
+        redact,
+    } = *options;
+    let mut lines: Vec<Line> = Vec::new();
+    if loading_earlier || earlier_events > 0 {
+        let label = if loading_earlier {
+            t("ui.earlier_loading").to_string()
+        } else {
+            tf("ui.earlier_events", &[("count", &earlier_events)])
+        };
+        lines.push(Line::from(Span::styled(
+            format!("  {}", label),
+            Style::default()
+                .fg(COLOR_MUTED)
+                .add_modifier(Modifier::ITALIC),
+        )));
+    }
+    let mut anchors: Vec<(usize, String)> = Vec::new();
+    let gutter_width = if timestamps {
+        TIMESTAMP_GUTTER_WIDTH
+    } else {
+        0
+    };
+    let content_width = width.saturating_sub(2 + gutter_width).max(10);
+    // (first line index, clock label) for each message header, used to fill the gutter.
+    let mut stamps: Vec<(usize, String)> = Vec::new();
+    let tool_durations = if timestamps || compact {
+        tool_call_durations(&state.timeline_events)
+    } else {
+        std::collections::HashMap::new()
+    };
+    let result_tokens = if compact {
+        tool_result_tokens(&state.timeline_events)
+    } else {
+        std::collections::HashMap::new()
+    };
+    let turn_durations = if timestamps {
+        assistant_turn_durations(&state.timeline_events)
+    } else {
+        Vec::new()
+    };
+
+    let is_blank = |line: &Line<'static>| line.spans.iter().all(|s| s.content.is_empty());
+    let push_gap = |lines: &mut Vec<Line<'static>>, count: usize| {
+        for _ in 0..count {
+            if let Some(last) = lines.last() {
+                if !is_blank(last) {
+                    lines.push(Line::from(""));
+                }
+            }
+        }
+    };
+
+    let mut in_assistant_block = false;
+    for (event_idx, event) in state.timeline_events.iter().enumerate() {
+        if event.parent_tool_call_id.is_some() {
+            // Sub-agent events are rendered beneath their parent `task` call.
+            continue;
+        }
+        if !filter.allows(event) {
+            continue;
+        }
+        if event.kind == "user" {
+            in_assistant_block = false;
+            push_gap(&mut lines, 3);
+            anchors.push((lines.len(), event.id.clone()));
+            if timestamps {
+                stamps.push((lines.len(), format_clock(event.created_at)));
+            }
+            lines.push(Line::from(vec![
+                Span::styled(
+                    "> ",
+                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    "You",
+                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    match event.status.as_deref() {
+                        Some("sending") => " sending…",
+                        Some("queued") => " queued",
+                        _ => "",
+                    },
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ),
+            ]));
+            let mut body: Vec<Line> =
+                wrap_plain_lines(&redact_text(&event.content, redact), content_width)
+                    .into_iter()
+                    .map(Line::from)
+                    .collect();
+            if let Some(atts) = &event.attachments {
+                if !atts.is_empty() {
+                    body.push(Line::from(format!(
+                        "[{} attachment{}]",
+                        atts.len(),
+                        if atts.len() == 1 { "" } else { "s" }
+                    )));
+                }
+            }
+            lines.extend(indent_lines(body, 2));
+            continue;
+        }
+
+        if event.kind == "compaction" {
+            // A marker between turns; what follows gets its own header.
+            in_assistant_block = false;
+            push_gap(&mut lines, 2);
+            anchors.push((lines.len(), event.id.clone()));
+            let expanded = expanded_summaries.contains(&event.id);
+            lines.extend(build_compaction_lines(
+                &redacted_event(event, redact),
+                expanded,
+                content_width,
+            ));
+            continue;
+        }
+
+        if !in_assistant_block {
+            push_gap(&mut lines, 3);
+            anchors.push((lines.len(), event.id.clone()));
+            if timestamps {
+                stamps.push((lines.len(), format_clock(event.created_at)));
+            }
+            let mut header = vec![
+                Span::styled(
+                    "> ",
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    "Stratus",
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    "Code",
+                    Style::default().fg(COLOR_CODE).add_modifier(Modifier::BOLD),
+                ),
+            ];
+            if let Some((_, ms)) = turn_durations.iter().find(|(idx, _)| *idx == event_idx) {
+                header.push(Span::styled(
+                    format!("  {}", format_duration(*ms)),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
+            }
+            lines.push(Line::from(header));
+            in_assistant_block = true;
+        }
+
+        // Failed calls can be focused to read their error.
+        if event.kind == "tool_call" && event.status.as_deref() == Some("failed") {
+            anchors.push((lines.len(), event.id.clone()));
+        }
+        let ctx = EventRenderContext {
+            events: &state.timeline_events,
+            tool_outputs,
+            compact,
+            collapsed_subagents,
+            content_width,
+            diff_max_lines,
+            spinner,
+            now_ms,
+            redact,
+            duration: event
+                .tool_call_id
+                .as_deref()
+                .and_then(|id| tool_durations.get(id))
+                .copied(),
+            result_tokens: event
+                .tool_call_id
+                .as_deref()
+                .and_then(|id| result_tokens.get(id))
+                .copied(),
+        };
+        match cache.as_deref_mut() {
+            Some(cache) if is_event_cacheable(event, &state.timeline_events) => {
+                lines.extend(cache.lines_for(event, &ctx).iter().cloned());
+            }
+            _ => lines.extend(build_event_lines(event, &ctx)),
+        }
+    }
+
+    if !lines.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(""));
+    }
+
+    if state.is_loading {
+        push_gap(&mut lines, 1);
+        let mut spans = Vec::new();
+        if let Some(frame) = spinner {
+            spans.push(Span::styled(frame, Style::default().fg(COLOR_CODE)));
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(
+            busy_label.unwrap_or(t("ui.thinking")).to_string(),
+            Style::default()
+                .fg(COLOR_TEXT_DIM)
+                .add_modifier(Modifier::ITALIC),
+        ));
+        lines.push(Line::from(spans));
+    }
+
+    if timestamps {
+        let mut next_stamp = stamps.into_iter().peekable();
+        lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                let label = match next_stamp.peek() {
+                    Some((at, _)) if *at == idx => next_stamp.next().map(|(_, l)| l),
+                    _ => None,
+                };
+                let gutter = match label {
+                    Some(label) => Span::styled(
+                        format!("{:<width$}", label, width = gutter_width),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    ),
+                    None => Span::raw(" ".repeat(gutter_width)),
+                };
+                let mut spans = Vec::with_capacity(line.spans.len() + 1);
+                spans.push(gutter);
+                spans.extend(line.spans);
+                Line::from(spans)
+            })
+            .collect();
+    }
+    (lines, anchors)
+}
+
+/// Inputs needed to lay out a single timeline event, independent of its neighbours.
+struct EventRenderContext<'a> {
+    events: &'a [TimelineEvent],
+    tool_outputs: &'a std::collections::HashMap<String, String>,
+    compact: bool,
+    collapsed_subagents: &'a [String],
+    content_width: usize,
+    diff_max_lines: usize,
+    spinner: Option<&'static str>,
+    now_ms: i64,
+    redact: Option<&'a dyn Redact>,
+    /// Elapsed time of a completed tool call, shown when timestamps are on
+    /// and in compact view.
+    duration: Option<i64>,
+    /// Tokens the call's result added to the context, shown in compact view.
+    result_tokens: Option<u64>,
+}
+
+/// Duration and result tokens: the inputs besides the event itself that a cached
+/// rendering depends on.
+type EventStats = (Option<i64>, Option<u64>);
+
+/// Rendered lines per timeline event, so only changed events are laid out again.
+/// Entries are dropped by `invalidate` when an event is updated and wholesale when
+/// the layout inputs (width, compact view) change. Calls with a sub-agent
+/// timeline are never cached, so folding one needs no invalidation.
+#[derive(Default)]
+pub struct EventLineCache {
+    layout: (usize, bool),
+    entries: std::collections::HashMap<String, (EventStats, Vec<Line<'static>>)>,
+    hits: u64,
+    misses: u64,
+}
+
+impl EventLineCache {
+    /// Lookups answered from the cache and ones that had to lay the event out.
+    pub fn hits_and_misses(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    pub fn invalidate(&mut self, id: &str) {
+        self.entries.remove(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn lines_for(&mut self, event: &TimelineEvent, ctx: &EventRenderContext) -> &[Line<'static>] {
+        let layout = (ctx.content_width, ctx.compact);
+        if self.layout != layout {
+            self.entries.clear();
+            self.layout = layout;
+        }
+        let stale = self
+            .entries
+            .get(&event.id)
+            .map(|(stats, _)| *stats != (ctx.duration, ctx.result_tokens))
+            .unwrap_or(true);
+        if stale {
+            self.misses += 1;
+            self.entries.insert(
+                event.id.clone(),
+                (
+                    (ctx.duration, ctx.result_tokens),
+                    build_event_lines(event, ctx),
+                ),
+            );
+        } else {
+            self.hits += 1;
+        }
+        &self.entries[&event.id].1
+    }
+}
+
+/// Events whose rendering changes without an update (spinners, elapsed time,
+/// streamed text, nested sub-agent activity) are always rendered fresh.
+fn is_event_cacheable(event: &TimelineEvent, events: &[TimelineEvent]) -> bool {
+    if event.streaming.unwrap_or(false) || event.status.as_deref() == Some("running") {
+        return false;
+    }
+    if event.kind == "tool_call" {
+        if let Some(id) = event.tool_call_id.as_deref() {
+            return !events
+                .iter()
+                .any(|e| e.parent_tool_call_id.as_deref() == Some(id));
+        }
+    }
+    true
+}
+
+fn build_event_lines(event: &TimelineEvent, ctx: &EventRenderContext) -> Vec<Line<'static>> {
+    let event = &*redacted_event(event, ctx.redact);
+    let mut lines: Vec<Line<'static>> = Vec::new();
+    match event.kind.as_str() {
+        "assistant" => {
+            let markdown_lines = if event.streaming.unwrap_or(false) {
+                render_streaming_markdown(&event.content, ctx.content_width)
+            } else {
+                render_markdown(&event.content, ctx.content_width)
+            };
+            lines.extend(indent_lines(markdown_lines, 2));
+        }
+        "reasoning" => {
+            if ctx.compact {
+                return lines;
+            }
+            lines.push(Line::from(vec![Span::styled(
+                "~ Reasoning",
+                Style::default()
+                    .fg(COLOR_TEXT_DIM)
+                    .add_modifier(Modifier::ITALIC),
+            )]));
+            let body: Vec<Line> = wrap_plain_lines(&event.content, ctx.content_width)
+                .into_iter()
+                .map(|l| {
+                    Line::from(vec![Span::styled(
+                        l,
+                        Style::default()
+                            .fg(COLOR_TEXT_DIM)
+                            .add_modifier(Modifier::ITALIC),
+                    )])
+                })
+                .collect();
+            lines.extend(indent_lines(body, 2));
+        }
+        "tool_call" => {
+            let label = event
+                .tool_name
+                .clone()
+                .unwrap_or_else(|| "tool".to_string());
+            let info = tool_display(&label);
+            let running = event.status.as_deref() == Some("running");
+            let status_icon = match event.status.as_deref().unwrap_or("pending") {
+                "running" => running_icon(ctx.spinner),
+                "failed" => "[x]".to_string(),
+                "completed" => "[ok]".to_string(),
+                _ => "[ ]".to_string(),
+            };
+            let args = format_tool_args(&event.content);
+            let mut spans = vec![
+                Span::styled(status_icon, Style::default().fg(info.color)),
+                Span::raw(" "),
+                Span::styled(
+                    info.label,
+                    Style::default().fg(info.color).add_modifier(Modifier::BOLD),
+                ),
+            ];
+            if !args.is_empty() {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(args, Style::default().fg(COLOR_TEXT_DIM)));
+            }
+            let mut stats = Vec::new();
+            if let Some(ms) = ctx.duration {
+                stats.push(format_duration(ms));
+            }
+            if let Some(tokens) = ctx.result_tokens {
+                stats.push(format!("~{} tok", format_token_count(tokens)));
+            }
+            if !stats.is_empty() {
+                spans.push(Span::styled(
+                    format!(" ({})", stats.join(" · ")),
+                    Style::default().fg(COLOR_TEXT_DIM),
+                ));
+            }
+            if running {
+                let elapsed_secs = (ctx.now_ms - event.created_at).max(0) / 1000;
+                spans.push(Span::styled(
+                    format!(" {}s", elapsed_secs),
+                    Style::default().fg(COLOR_WARNING),
+                ));
+            }
+            lines.push(Line::from(spans));
+            if running {
+                if let Some(output) = event
+                    .tool_call_id
+                    .as_deref()
+                    .and_then(|id| ctx.tool_outputs.get(id))
+                {
+                    // Only the shown tail is masked, not the whole buffer.
+                    let tail: Vec<&str> = output.lines().collect();
+                    let skip = tail.len().saturating_sub(LIVE_OUTPUT_TAIL_LINES);
+                    let tail = tail[skip..].join("\n");
+                    let body: Vec<Line> = redact_text(&tail, ctx.redact)
+                        .lines()
+                        .map(|l| {
+                            Line::from(vec![Span::styled(
+                                l.to_string(),
+                                Style::default().fg(COLOR_TEXT_DIM),
+                            )])
+                        })
+                        .collect();
+                    lines.extend(indent_lines(body, 4));
+                }
+            }
+            if let Some(id) = event.tool_call_id.as_deref() {
+                let children: Vec<Cow<TimelineEvent>> = ctx
+                    .events
+                    .iter()
+                    .filter(|e| e.parent_tool_call_id.as_deref() == Some(id))
+                    .map(|e| redacted_event(e, ctx.redact))
+                    .collect();
+                let children: Vec<&TimelineEvent> = children.iter().map(AsRef::as_ref).collect();
+                if !children.is_empty() {
+                    lines.extend(indent_lines(
+                        build_subagent_lines(
+                            &children,
+                            ctx.collapsed_subagents.iter().any(|c| c == id),
+                            ctx.content_width.saturating_sub(4),
+                            ctx.spinner,
+                        ),
+                        4,
+                    ));
+                }
+            }
+        }
+        "tool_result" => {
+            if let Some((summary, diff_lines)) =
+                extract_diff_summary(&event.content, ctx.content_width)
+            {
+                lines.push(Line::from(vec![
+                    Span::styled("[ok]", Style::default().fg(COLOR_SUCCESS)),
+                    Span::raw(" "),
+                    Span::styled(
+                        "Result",
+                        Style::default()
+                            .fg(COLOR_SUCCESS)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(" "),
+                    Span::styled(summary, Style::default().fg(COLOR_TEXT_DIM)),
+                ]));
+                let hidden = diff_lines.len().saturating_sub(ctx.diff_max_lines);
+                let mut shown: Vec<Line<'static>> =
+                    diff_lines.into_iter().take(ctx.diff_max_lines).collect();
+                if hidden > 0 {
+                    shown.push(Line::from(Span::styled(
+                        tf("ui.diff_more", &[("count", &format_number(hidden as u64))]),
+                        Style::default().fg(COLOR_TEXT_DIM),
+                    )));
+                }
+                lines.extend(indent_lines(shown, 2));
+            } else if event.tool_name.as_deref() == Some("verify")
+                && event.status.as_deref() == Some("failed")
+            {
+                // The end of a failed verify run, where test runners summarize.
+                let output: Vec<&str> = event.content.lines().collect();
+                let skip = output.len().saturating_sub(LIVE_OUTPUT_TAIL_LINES);
+                let body: Vec<Line> = output[skip..]
+                    .iter()
+                    .map(|l| {
+                        Line::from(Span::styled(
+                            l.to_string(),
+                            Style::default().fg(COLOR_ERROR),
+                        ))
+                    })
+                    .collect();
+                lines.extend(indent_lines(body, 4));
+            }
+        }
+        "error" => {
+            for (i, line) in event.content.lines().enumerate() {
+                let text = if i == 0 {
+                    format!("✗ {}", line)
+                } else {
+                    format!("  {}", line)
+                };
+                lines.push(Line::from(vec![Span::styled(
+                    text,
+                    Style::default().fg(COLOR_ERROR),
+                )]));
+            }
+        }
+        "status" => {
+            let is_error = event.content.to_lowercase().contains("error");
+            let color = if is_error { COLOR_ERROR } else { COLOR_WARNING };
+            lines.push(Line::from(vec![Span::styled(
+                format!("! {}", event.content),
+                Style::default().fg(color),
+            )]));
+        }
+        _ => {
+            lines.push(Line::from(event.content.clone()));
+        }
+    }
+    lines
+}
+
+/// "─── 34 earlier messages summarized ───", and the summary beneath it when
+/// expanded.
+fn build_compaction_lines(
+    event: &TimelineEvent,
+    expanded: bool,
+    width: usize,
+) -> Vec<Line<'static>> {
+    let (count, summarized) = event
+        .compaction
+        .as_ref()
+        .map_or((0, false), |c| (c.messages_removed, c.summarized));
+    let mut label = if summarized {
+        tf("ui.compaction_summarized", &[("count", &count)])
+    } else {
+        tf("ui.compaction_truncated", &[("count", &count)])
+    };
+    let summary = event.content.trim();
+    if !summary.is_empty() {
+        label = format!("{} {}", if expanded { "▾" } else { "▸" }, label);
+    }
+    let mut lines = vec![Line::from(Span::styled(
+        format!("─── {} ───", label),
+        Style::default().fg(COLOR_TEXT_DIM),
+    ))];
+    if expanded {
+        lines.extend(
+            wrap_plain_lines(summary, width.saturating_sub(2).max(10))
+                .into_iter()
+                .map(|line| {
+                    Line::from(vec![
+                        Span::styled("│ ", Style::default().fg(COLOR_BORDER)),
+                        Span::styled(line, Style::default().fg(COLOR_TEXT_DIM)),
+                    ])
+                }),
+        );
+    }
+    lines
+}
+
+/// `[/]`, or a static `[running]` when animation is off.
+fn running_icon(spinner: Option<&str>) -> String {
+    match spinner {
+        Some(frame) => format!("[{}]", frame),
+        None => "[running]".to_string(),
+    }
+}
+
+/// Renders the events of a `task` sub-agent as a compact nested timeline.
+/// Collapsed, only the summary row (event count and tokens) is shown.
+fn build_subagent_lines(
+    children: &[&TimelineEvent],
+    collapsed: bool,
+    width: usize,
+    spinner: Option<&'static str>,
+) -> Vec<Line<'static>> {
+    let tokens: u64 = children
+        .iter()
+        .filter_map(|e| e.tokens.as_ref())
+        .map(|t| t.input + t.output)
+        .sum();
+    let mut summary = format!(
+        "{} Sub-agent · {} event{}",
+        if collapsed { "▸" } else { "▾" },
+        children.len(),
+        if children.len() == 1 { "" } else { "s" }
+    );
+    if tokens > 0 {
+        summary.push_str(&format!(" · {} tokens", format_number(tokens)));
+    }
+    let mut lines = vec![Line::from(vec![Span::styled(
+        summary,
+        Style::default().fg(COLOR_WARNING),
+    )])];
+    if collapsed {
+        return lines;
+    }
+
+    let mut body: Vec<Line<'static>> = Vec::new();
+    for event in children {
+        match event.kind.as_str() {
+            "assistant" => {
+                body.extend(
+                    wrap_plain_lines(&event.content, width.max(10))
+                        .into_iter()
+                        .map(|l| {
+                            Line::from(vec![Span::styled(l, Style::default().fg(COLOR_TEXT))])
+                        }),
+                );
+            }
+            "tool_call" => {
+                let name = event
+                    .tool_name
+                    .clone()
+                    .unwrap_or_else(|| "tool".to_string());
+                let info = tool_display(&name);
+                let icon = match event.status.as_deref().unwrap_or("pending") {
+                    "running" => running_icon(spinner),
+                    "failed" => "[x]".to_string(),
+                    "completed" => "[ok]".to_string(),
+                    _ => "[ ]".to_string(),
+                };
+                let mut spans = vec![
+                    Span::styled(icon, Style::default().fg(info.color)),
+                    Span::raw(" "),
+                    Span::styled(info.label, Style::default().fg(info.color)),
+                ];
+                let args = format_tool_args(&event.content);
+                if !args.is_empty() {
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(args, Style::default().fg(COLOR_TEXT_DIM)));
+                }
+                body.push(Line::from(spans));
+            }
+            "status" => {
+                body.push(Line::from(vec![Span::styled(
+                    format!("! {}", event.content),
+                    Style::default().fg(COLOR_WARNING),
+                )]));
+            }
+            _ => {}
+        }
+    }
+    lines.extend(body.into_iter().map(|line| {
+        let mut spans = vec![Span::styled("│ ", Style::default().fg(COLOR_BORDER))];
+        spans.extend(line.spans);
+        Line::from(spans)
+    }));
+    lines
+}
+
+const TIMESTAMP_GUTTER_WIDTH: usize = 9;
+const LIVE_OUTPUT_TAIL_LINES: usize = 5;
+
+/// Elapsed time per tool call, keyed by `tool_call_id`, from the call event to its result.
+fn tool_call_durations(events: &[TimelineEvent]) -> std::collections::HashMap<String, i64> {
+    let mut started: std::collections::HashMap<&str, i64> = std::collections::HashMap::new();
+    let mut durations = std::collections::HashMap::new();
+    for event in events {
+        let Some(id) = event.tool_call_id.as_deref() else {
+            continue;
+        };
+        match event.kind.as_str() {
+            "tool_call" => {
+                started.insert(id, event.created_at);
+            }
+            "tool_result" => {
+                if let Some(start) = started.get(id) {
+                    durations.insert(id.to_string(), (event.created_at - start).max(0));
+                }
+            }
+            _ => {}
+        }
+    }
+    durations
+}
+
+/// Tokens reported on each tool result, keyed by tool call id.
+fn tool_result_tokens(events: &[TimelineEvent]) -> std::collections::HashMap<String, u64> {
+    events
+        .iter()
+        .filter(|e| e.kind == "tool_result")
+        .filter_map(|e| {
+            let tokens = e.tokens.as_ref()?;
+            Some((e.tool_call_id.clone()?, tokens.input + tokens.output))
+        })
+        .filter(|(_, tokens)| *tokens > 0)
+        .collect()
+}
+
+/// "850", "1.2k", "3.4M".
+fn format_token_count(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+/// Duration of each assistant turn, keyed by the index of the turn's first event.
+/// A turn runs from the preceding user message to the last event before the next one.
+fn assistant_turn_durations(events: &[TimelineEvent]) -> Vec<(usize, i64)> {
+    let mut durations = Vec::new();
+    let mut turn_start: Option<i64> = None;
+    let mut block: Option<(usize, i64)> = None;
+    for (idx, event) in events.iter().enumerate() {
+        if event.kind == "user" {
+            if let (Some(start), Some((first, last))) = (turn_start, block.take()) {
+                durations.push((first, (last - start).max(0)));
+            }
+            turn_start = Some(event.created_at);
+            continue;
+        }
+        match &mut block {
+            Some((_, last)) => *last = event.created_at,
+            None => block = Some((idx, event.created_at)),
+        }
+    }
+    if let (Some(start), Some((first, last))) = (turn_start, block) {
+        durations.push((first, (last - start).max(0)));
+    }
+    durations
+}
+
+/// UTC ISO 8601 to the second, for exports: `2024-03-01T14:05:09Z`.
+pub fn format_iso(created_at_ms: i64) -> String {
+    match chrono::DateTime::from_timestamp_millis(created_at_ms) {
+        Some(t) => t.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        None => String::new(),
+    }
+}
+
+pub fn format_clock(created_at_ms: i64) -> String {
+    use chrono::TimeZone;
+    match chrono::Local.timestamp_millis_opt(created_at_ms) {
+        chrono::LocalResult::Single(t) | chrono::LocalResult::Ambiguous(t, _) => {
+            t.format("%H:%M:%S").to_string()
+        }
+        chrono::LocalResult::None => "--:--:--".to_string(),
+    }
+}
+
+fn format_duration(ms: i64) -> String {
+    let ms = ms.max(0);
+    if ms < 1_000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        let secs = ms / 1000;
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+pub fn wrap_plain_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for raw in text.split('\n') {
+        if raw.is_empty() {
+            lines.push(String::new());
+            continue;
+        }
+        let wrapped = wrap(raw, width);
+        for line in wrapped {
+            lines.push(line.to_string());
+        }
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn wrap_diff_content(text: &str, width: usize) -> Vec<String> {
+    if UnicodeWidthStr::width(text) <= width {
+        return vec![text.to_string()];
+    }
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0usize;
+    for ch in text.chars() {
+        let ch_width = UnicodeWidthChar::width(ch).unwrap_or(1);
+        if current_width + ch_width > width && !current.is_empty() {
+            lines.push(current);
+            current = String::new();
+            current_width = 0;
+        }
+        current.push(ch);
+        current_width += ch_width;
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn indent_lines(lines: Vec<Line<'static>>, indent: usize) -> Vec<Line<'static>> {
+    let prefix = Span::raw(" ".repeat(indent));
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut spans = Vec::with_capacity(line.spans.len() + 1);
+            spans.push(prefix.clone());
+            spans.extend(line.spans);
+            Line::from(spans)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct DiffLine {
+    kind: DiffKind,
+    content: String,
+    old_line: Option<usize>,
+    new_line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DiffKind {
+    Header,
+    Hunk,
+    Add,
+    Remove,
+    Context,
+}
+
+fn parse_diff(diff: &str) -> (Vec<DiffLine>, usize, usize) {
+    let mut lines = Vec::new();
+    let mut additions = 0usize;
+    let mut deletions = 0usize;
+    let mut old_line = 0usize;
+    let mut new_line = 0usize;
+
+    for line in diff.lines() {
+        if line.starts_with("--- ") || line.starts_with("+++ ") {
+            lines.push(DiffLine {
+                kind: DiffKind::Header,
+                content: line.to_string(),
+                old_line: None,
+                new_line: None,
+            });
+            continue;
+        }
+        if line.starts_with("@@") {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 3 {
+                if let Some(old_part) = parts.get(1) {
+                    if let Some(num) = old_part.trim_start_matches('-').split(',').next() {
+                        old_line = num.parse::<usize>().unwrap_or(0);
+                    }
+                }
+                if let Some(new_part) = parts.get(2) {
+                    if let Some(num) = new_part.trim_start_matches('+').split(',').next() {
+                        new_line = num.parse::<usize>().unwrap_or(0);
+                    }
+                }
+            }
+            lines.push(DiffLine {
+                kind: DiffKind::Hunk,
+                content: line.to_string(),
+                old_line: None,
+                new_line: None,
+            });
+            continue;
+        }
+
+        if let Some(stripped) = line.strip_prefix('+') {
+            additions += 1;
+            lines.push(DiffLine {
+                kind: DiffKind::Add,
+                content: stripped.to_string(),
+                old_line: None,
+                new_line: Some(new_line),
+            });
+            new_line = new_line.saturating_add(1);
+        } else if let Some(stripped) = line.strip_prefix('-') {
+            deletions += 1;
+            lines.push(DiffLine {
+                kind: DiffKind::Remove,
+                content: stripped.to_string(),
+                old_line: Some(old_line),
+                new_line: None,
+            });
+            old_line = old_line.saturating_add(1);
+        } else if let Some(stripped) = line.strip_prefix(' ') {
+            lines.push(DiffLine {
+                kind: DiffKind::Context,
+                content: stripped.to_string(),
+                old_line: Some(old_line),
+                new_line: Some(new_line),
+            });
+            old_line = old_line.saturating_add(1);
+            new_line = new_line.saturating_add(1);
+        } else if line.starts_with('\\') {
+            // "\ No newline at end of file"
+            continue;
+        } else {
+            lines.push(DiffLine {
+                kind: DiffKind::Context,
+                content: line.to_string(),
+                old_line: None,
+                new_line: None,
+            });
+        }
+    }
+
+    (lines, additions, deletions)
+}
+
+pub fn extract_diff_summary(result: &str, width: usize) -> Option<(String, Vec<Line<'static>>)> {
+    let parsed: serde_json::Value = serde_json::from_str(result).ok()?;
+    let diff = parsed.get("diff")?.as_str()?;
+    Some(render_diff(diff, width))
+}
+
+/// A unified diff as "(+3 / -1)" and colored, line-numbered lines.
+pub fn render_diff(diff: &str, width: usize) -> (String, Vec<Line<'static>>) {
+    let (lines, additions, deletions) = parse_diff(diff);
+    let summary = format!("(+{} / -{})", additions, deletions);
+    (summary, format_diff_lines(lines, width))
+}
+
+fn format_diff_lines(lines: Vec<DiffLine>, width: usize) -> Vec<Line<'static>> {
+    let mut out = Vec::new();
+    let line_num_width = 4usize;
+    let content_width = width.saturating_sub(line_num_width * 2 + 3).max(10);
+
+    for line in lines {
+        let (prefix, style) = match line.kind {
+            DiffKind::Header => ("", Style::default().fg(COLOR_PURPLE)),
+            DiffKind::Hunk => ("", Style::default().fg(COLOR_CYAN)),
+            DiffKind::Add => ("+", Style::default().fg(COLOR_GREEN)),
+            DiffKind::Remove => ("-", Style::default().fg(COLOR_ERROR)),
+            DiffKind::Context => (" ", Style::default().fg(COLOR_TEXT_DIM)),
+        };
+
+        let num_left = line
+            .old_line
+            .map(|n| format!("{:>width$}", n, width = line_num_width))
+            .unwrap_or_else(|| " ".repeat(line_num_width));
+        let num_right = line
+            .new_line
+            .map(|n| format!("{:>width$}", n, width = line_num_width))
+            .unwrap_or_else(|| " ".repeat(line_num_width));
+        let mut content_lines = wrap_diff_content(&line.content, content_width);
+        if content_lines.is_empty() {
+            content_lines.push(String::new());
+        }
+        for (idx, content) in content_lines.into_iter().enumerate() {
+            let nums = if matches!(
+                line.kind,
+                DiffKind::Add | DiffKind::Remove | DiffKind::Context
+            ) {
+                if idx == 0 {
+                    format!("{} {} ", num_left, num_right)
+                } else {
+                    " ".repeat(line_num_width * 2 + 2)
+                }
+            } else {
+                String::new()
+            };
+            let mut spans = Vec::new();
+            if !nums.is_empty() {
+                spans.push(Span::styled(nums, Style::default().fg(COLOR_TEXT_DIM)));
+            }
+            spans.push(Span::styled(format!("{}{}", prefix, content), style));
+            out.push(Line::from(spans));
+        }
+    }
+    out
+}
+
+pub fn render_markdown(content: &str, width: usize) -> Vec<Line<'static>> {
+    if content.trim().is_empty() {
+        return vec![Line::from("")];
+    }
+    let mut renderer = MarkdownRenderer::new(width);
+    renderer.render(content);
+    renderer.finish()
+}
+
+/// Renders a message that is still streaming: every block that is already
+/// complete is formatted as markdown, only the trailing partial block is shown
+/// as plain wrapped text. Avoids the jump from plain text to markdown when the
+/// stream ends.
+fn render_streaming_markdown(content: &str, width: usize) -> Vec<Line<'static>> {
+    let (complete, partial) = content.split_at(streaming_block_boundary(content));
+    let mut lines = if complete.trim().is_empty() {
+        Vec::new()
+    } else {
+        render_markdown(complete, width)
+    };
+    if !partial.trim().is_empty() {
+        lines.extend(
+            wrap_plain_lines(partial.trim_start_matches('\n'), width)
+                .into_iter()
+                .map(|l| Line::from(Span::styled(l, Style::default().fg(COLOR_TEXT)))),
+        );
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(""));
+    }
+    lines
+}
+
+/// Byte offset just past the last blank line that is not inside a code fence,
+/// i.e. where the trailing (possibly incomplete) block starts.
+fn streaming_block_boundary(content: &str) -> usize {
+    let mut boundary = 0;
+    let mut offset = 0;
+    let mut fence: Option<&str> = None;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        let trimmed = line.trim();
+        let marker = if trimmed.starts_with("```") {
+            Some("```")
+        } else if trimmed.starts_with("~~~") {
+            Some("~~~")
+        } else {
+            None
+        };
+        match (fence, marker) {
+            (None, Some(m)) => fence = Some(m),
+            (Some(open), Some(m)) if open == m && trimmed.len() == m.len() => {
+                fence = None;
+                // A closed fence is a complete block even without a blank line.
+                if line.ends_with('\n') {
+                    boundary = offset;
+                }
+            }
+            (None, None) if trimmed.is_empty() && line.ends_with('\n') => boundary = offset,
+            _ => {}
+        }
+    }
+    boundary
+}
+
+#[derive(Debug, Clone)]
+struct ListState {
+    ordered: bool,
+    index: usize,
+}
+
+struct MarkdownRenderer {
+    width: usize,
+    lines: Vec<Line<'static>>,
+    current_spans: Vec<Span<'static>>,
+    current_width: usize,
+    pending_space: bool,
+    line_prefix: Option<(String, Style)>,
+    pending_item_prefix: Option<(String, Style)>,
+    style_stack: Vec<Style>,
+    list_stack: Vec<ListState>,
+    in_code_block: bool,
+}
+
+impl MarkdownRenderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width: width.max(10),
+            lines: Vec::new(),
+            current_spans: Vec::new(),
+            current_width: 0,
+            pending_space: false,
+            line_prefix: None,
+            pending_item_prefix: None,
+            style_stack: vec![Style::default().fg(COLOR_TEXT)],
+            list_stack: Vec::new(),
+            in_code_block: false,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Line<'static>> {
+        self.flush_line();
+        if self.lines.is_empty() {
+            self.lines.push(Line::from(""));
+        }
+        self.lines
+    }
+
+    fn render(&mut self, content: &str) {
+        let mut options = MdOptions::empty();
+        options.insert(MdOptions::ENABLE_STRIKETHROUGH);
+        options.insert(MdOptions::ENABLE_TABLES);
+        options.insert(MdOptions::ENABLE_TASKLISTS);
+        let parser = MdParser::new_ext(content, options);
+        for event in parser {
+            match event {
+                MdEvent::Start(tag) => self.on_start(tag),
+                MdEvent::End(tag) => self.on_end(tag),
+                MdEvent::Text(text) => {
+                    if self.in_code_block {
+                        self.push_code_block_text(&text);
+                    } else {
+                        self.push_text(&text, self.current_style());
+                    }
+                }
+                MdEvent::Code(text) => {
+                    self.push_word(&text, Style::default().fg(COLOR_GREEN));
+                }
+                MdEvent::SoftBreak => {
+                    if self.in_code_block {
+                        self.new_line();
+                    } else {
+                        self.push_space();
+                    }
+                }
+                MdEvent::HardBreak => self.new_line(),
+                MdEvent::Rule => {
+                    self.new_line();
+                    let bar = "─".repeat(self.width.min(40));
+                    self.push_span(&bar, Style::default().fg(COLOR_MUTED));
+                    self.new_line();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_start(&mut self, tag: MdTag) {
+        match tag {
+            MdTag::Heading(_level, ..) => {
+                self.new_line();
+                let style = Style::default()
+                    .fg(COLOR_PURPLE)
+                    .add_modifier(Modifier::BOLD);
+                self.style_stack.push(self.current_style().patch(style));
+            }
+            MdTag::BlockQuote => {
+                self.new_line();
+                self.line_prefix = Some(("> ".to_string(), Style::default().fg(COLOR_YELLOW)));
+                self.style_stack.push(
+                    self.current_style().patch(
+                        Style::default()
+                            .fg(COLOR_YELLOW)
+                            .add_modifier(Modifier::ITALIC),
+                    ),
+                );
+            }
+            MdTag::List(start) => {
+                let ordered = start.is_some();
+                let index = start.unwrap_or(1) as usize;
+                self.list_stack.push(ListState { ordered, index });
+            }
+            MdTag::Item => {
+                self.new_line();
+                if let Some(state) = self.list_stack.last_mut() {
+                    let prefix = if state.ordered {
+                        format!("{}. ", state.index)
+                    } else {
+                        "• ".to_string()
+                    };
+                    self.pending_item_prefix = Some((prefix, Style::default().fg(COLOR_TEXT)));
+                }
+            }
+            MdTag::CodeBlock(_) => {
+                self.new_line();
+                self.in_code_block = true;
+            }
+            MdTag::Emphasis => {
+                self.style_stack.push(
+                    self.current_style().patch(
+                        Style::default()
+                            .fg(COLOR_YELLOW)
+                            .add_modifier(Modifier::ITALIC),
+                    ),
+                );
+            }
+            MdTag::Strong => {
+                self.style_stack.push(
+                    self.current_style().patch(
+                        Style::default()
+                            .fg(COLOR_ORANGE)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                );
+            }
+            MdTag::Strikethrough => {
+                self.style_stack.push(
+                    self.current_style().patch(
+                        Style::default()
+                            .fg(COLOR_MUTED)
+                            .add_modifier(Modifier::CROSSED_OUT),
+                    ),
+                );
+            }
+            MdTag::Link(_, _, _) => {
+                self.style_stack.push(
+                    self.current_style().patch(
+                        Style::default()
+                            .fg(COLOR_CYAN)
+                            .add_modifier(Modifier::UNDERLINED),
+                    ),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn on_end(&mut self, tag: MdTag) {
+        match tag {
+            MdTag::Heading(..) => {
+                self.style_stack.pop();
+                self.new_line();
+            }
+            MdTag::BlockQuote => {
+                self.style_stack.pop();
+                self.line_prefix = None;
+                self.new_line();
+            }
+            MdTag::List(_) => {
+                self.list_stack.pop();
+                self.new_line();
+            }
+            MdTag::Item => {
+                if let Some(state) = self.list_stack.last_mut() {
+                    if state.ordered {
+                        state.index += 1;
+                    }
+                }
+                self.new_line();
+            }
+            MdTag::CodeBlock(_) => {
+                self.in_code_block = false;
+                self.new_line();
+            }
+            MdTag::Emphasis | MdTag::Strong | MdTag::Strikethrough | MdTag::Link(..) => {
+                self.style_stack.pop();
+            }
+            MdTag::Paragraph => {
+                self.new_line_if_content();
+            }
+            _ => {}
+        }
+    }
+
+    fn current_style(&self) -> Style {
+        self.style_stack
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Style::default().fg(COLOR_TEXT))
+    }
+
+    fn flush_line(&mut self) {
+        if self.current_spans.is_empty() && self.lines.is_empty() {
+            return;
+        }
+        if !self.current_spans.is_empty() || !self.lines.is_empty() {
+            self.lines.push(Line::from(self.current_spans.clone()));
+        }
+        self.current_spans.clear();
+        self.current_width = 0;
+    }
+
+    fn new_line(&mut self) {
+        self.pending_space = false;
+        self.flush_line();
+    }
+
+    fn new_line_if_content(&mut self) {
+        if !self.current_spans.is_empty() {
+            self.flush_line();
+        }
+    }
+
+    fn ensure_line_prefix(&mut self) {
+        if self.current_spans.is_empty() {
+            // Pushed directly: going through `push_span` would come back here
+            // while the line is still empty.
+            let prefixes = [self.line_prefix.clone(), self.pending_item_prefix.take()];
+            for (prefix, style) in prefixes.into_iter().flatten() {
+                self.current_width += UnicodeWidthStr::width(prefix.as_str());
+                self.current_spans.push(Span::styled(prefix, style));
+            }
+        }
+    }
+
+    fn push_span(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        self.ensure_line_prefix();
+        self.current_spans
+            .push(Span::styled(text.to_string(), style));
+        self.current_width += UnicodeWidthStr::width(text);
+    }
+
+    fn push_space(&mut self) {
+        self.pending_space = true;
+    }
+
+    fn push_word(&mut self, word: &str, style: Style) {
+        let word_width = UnicodeWidthStr::width(word);
+        if self.current_width > 0
+            && self.pending_space
+            && self.current_width + 1 + word_width > self.width
+        {
+            self.new_line();
+        } else if self.current_width > 0 && self.pending_space {
+            self.push_span(" ", Style::default().fg(COLOR_TEXT));
+        }
+        self.pending_space = false;
+
+        if word_width <= self.width {
+            self.push_span(word, style);
+            return;
+        }
+
+        let mut remaining = word;
+        while !remaining.is_empty() {
+            let mut chunk = take_width(remaining, self.width);
+            if chunk.is_empty() {
+                // A single grapheme wider than the line still gets one.
+                chunk = remaining.graphemes(true).next().unwrap_or(remaining);
+            }
+            self.push_span(chunk, style);
+            remaining = &remaining[chunk.len()..];
+            if !remaining.is_empty() {
+                self.new_line();
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str, style: Style) {
+        let mut token = String::new();
+        let mut in_space = false;
+        for ch in text.chars() {
+            if ch == '\n' {
+                self.flush_token(&token, in_space, style);
+                token.clear();
+                in_space = false;
+                self.new_line();
+                continue;
+            }
+            let is_space = ch.is_whitespace();
+            if is_space != in_space && !token.is_empty() {
+                self.flush_token(&token, in_space, style);
+                token.clear();
+            }
+            in_space = is_space;
+            if is_space {
+                token.push(' ');
+            } else {
+                token.push(ch);
+            }
+        }
+        self.flush_token(&token, in_space, style);
+    }
+
+    fn flush_token(&mut self, token: &str, is_space: bool, style: Style) {
+        if token.is_empty() {
+            return;
+        }
+        if is_space {
+            self.push_space();
+        } else {
+            self.push_word(token, style);
+        }
+    }
+
+    fn push_code_block_text(&mut self, text: &str) {
+        for (idx, line) in text.lines().enumerate() {
+            if idx > 0 {
+                self.new_line();
+            }
+            self.push_span(line, Style::default().fg(COLOR_GREEN));
+        }
+    }
+}
+
+pub fn tool_icon(name: &str) -> &'static str {
+    match name {
+        "read" => "[R]",
+        "write" => "[W]",
+        "edit" => "[E]",
+        "multi_edit" => "[E]",
+        "bash" => "[$]",
+        "grep" => "[?]",
+        "glob" => "[G]",
+        "ls" => "[L]",
+        "task" => "[T]",
+        "websearch" => "[S]",
+        "webfetch" => "[F]",
+        "apply_patch" => "[P]",
+        "question" => "[Q]",
+        "todoread" => "[>]",
+        "todowrite" => "[>]",
+        "codesearch" => "[C]",
+        _ => "[*]",
+    }
+}
+
+struct ToolDisplay {
+    label: String,
+    color: Color,
+}
+
+fn tool_display(name: &str) -> ToolDisplay {
+    match name {
+        "read" => ToolDisplay {
+            label: "Read".to_string(),
+            color: COLOR_SUCCESS,
+        },
+        "write" => ToolDisplay {
+            label: "Write".to_string(),
+            color: COLOR_ORANGE,
+        },
+        "edit" => ToolDisplay {
+            label: "Edit".to_string(),
+            color: COLOR_ORANGE,
+        },
+        "multi_edit" => ToolDisplay {
+            label: "Multi Edit".to_string(),
+            color: COLOR_ORANGE,
+        },
+        "apply_patch" => ToolDisplay {
+            label: "Patch".to_string(),
+            color: COLOR_ORANGE,
+        },
+        "bash" => ToolDisplay {
+            label: "Terminal".to_string(),
+            color: COLOR_CYAN,
+        },
+        "grep" => ToolDisplay {
+            label: "Search".to_string(),
+            color: COLOR_PURPLE,
+        },
+        "glob" => ToolDisplay {
+            label: "Glob".to_string(),
+            color: COLOR_PURPLE,
+        },
+        "ls" => ToolDisplay {
+            label: "List".to_string(),
+            color: COLOR_PURPLE,
+        },
+        "task" => ToolDisplay {
+            label: "Task".to_string(),
+            color: COLOR_WARNING,
+        },
+        "websearch" => ToolDisplay {
+            label: "Web Search".to_string(),
+            color: COLOR_CYAN,
+        },
+        "webfetch" => ToolDisplay {
+            label: "Fetch".to_string(),
+            color: COLOR_CYAN,
+        },
+        "question" => ToolDisplay {
+            label: "Question".to_string(),
+            color: COLOR_WARNING,
+        },
+        "todoread" => ToolDisplay {
+            label: "Todos".to_string(),
+            color: COLOR_WARNING,
+        },
+        "todowrite" => ToolDisplay {
+            label: "Todos".to_string(),
+            color: COLOR_WARNING,
+        },
+        "codesearch" => ToolDisplay {
+            label: "Code Search".to_string(),
+            color: COLOR_PURPLE,
+        },
+        "lsp" => ToolDisplay {
+            label: "LSP".to_string(),
+            color: COLOR_PURPLE,
+        },
+        "revert" => ToolDisplay {
+            label: "Revert".to_string(),
+            color: COLOR_ERROR,
+        },
+        _ => ToolDisplay {
+            label: name.to_string(),
+            color: COLOR_TEXT_DIM,
+        },
+    }
+}
+
+/// The name a tool is shown under, e.g. "Terminal" for `bash`.
+pub fn tool_label(name: &str) -> String {
+    tool_display(name).label
+}
+
+pub fn format_tool_args(args_json: &str) -> String {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(args_json) {
+        if let Some(p) = value.get("file_path").and_then(|v| v.as_str()) {
+            return p.to_string();
+        }
+        if let Some(cmd) = value.get("command").and_then(|v| v.as_str()) {
+            return truncate_text(cmd, 60);
+        }
+        if let Some(q) = value.get("query").and_then(|v| v.as_str()) {
+            return format!("\"{}\"", q);
+        }
+        if let Some(pat) = value.get("pattern").and_then(|v| v.as_str()) {
+            return pat.to_string();
+        }
+        if let Some(dir) = value.get("directory_path").and_then(|v| v.as_str()) {
+            return dir.to_string();
+        }
+        if let Some(desc) = value.get("description").and_then(|v| v.as_str()) {
+            return truncate_text(desc, 60);
+        }
+        if let Some(url) = value.get("url").and_then(|v| v.as_str()) {
+            return url.to_string();
+        }
+    }
+    String::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ChatState;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    // ── Fixtures ────────────────────────────────────────────
+
+    fn event(id: &str, at: i64, kind: &str, content: &str, extra: Value) -> Value {
+        let mut event = json!({
+            "id": id,
+            "sessionId": "s1",
+            "createdAt": 1_700_000_000_000i64 + at,
+            "kind": kind,
+            "content": content,
+        });
+        event
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().cloned().unwrap_or_default());
+        event
+    }
+
+    fn tool(id: &str, at: i64, kind: &str, name: &str, status: &str, content: &str) -> Value {
+        event(
+            &format!("{}-{}", id, kind),
+            at,
+            kind,
+            content,
+            json!({ "toolCallId": id, "toolName": name, "status": status }),
+        )
+    }
+
+    fn session(events: Vec<Value>) -> ChatState {
+        serde_json::from_value(json!({
+            "messages": [],
+            "isLoading": false,
+            "timelineEvents": events,
+            "contextUsage": { "used": 0, "limit": 0, "percent": 0 },
+            "tokens": { "input": 0, "output": 0 },
+            "planExitProposed": false,
+            "agent": "build",
+        }))
+        .unwrap()
+    }
+
+    fn conversation() -> ChatState {
+        session(vec![
+            event("u1", 0, "user", "How do I read a config file?", json!({})),
+            event(
+                "a1",
+                900,
+                "assistant",
+                "## Reading config\n\nUse `toml::from_str` on the file's contents:\n\n```rust\nlet config: Config = toml::from_str(&raw)?;\n```\n\n- Errors carry the line\n- Missing fields use `#[serde(default)]`\n\n1. Read the file\n2. Parse it",
+                json!({}),
+            ),
+        ])
+    }
+
+    fn tool_session() -> ChatState {
+        let diff = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,4 +1,4 @@\n fn main() {\n-    println!(\"hello\");\n+    println!(\"hello, world\");\n }\n";
+        session(vec![
+            event(
+                "u1",
+                0,
+                "user",
+                "Fix the greeting and run the tests",
+                json!({}),
+            ),
+            event("a1", 200, "assistant", "Reading the file first.", json!({})),
+            tool(
+                "t1",
+                300,
+                "tool_call",
+                "read",
+                "completed",
+                r#"{"file_path":"src/lib.rs"}"#,
+            ),
+            tool(
+                "t1",
+                350,
+                "tool_result",
+                "read",
+                "completed",
+                "fn main() {}",
+            ),
+            tool(
+                "t2",
+                400,
+                "tool_call",
+                "edit",
+                "completed",
+                r#"{"file_path":"src/lib.rs"}"#,
+            ),
+            tool(
+                "t2",
+                450,
+                "tool_result",
+                "edit",
+                "completed",
+                &json!({ "success": true, "diff": diff }).to_string(),
+            ),
+            tool(
+                "t3",
+                500,
+                "tool_call",
+                "bash",
+                "failed",
+                r#"{"command":"cargo test"}"#,
+            ),
+            tool(
+                "t3",
+                2500,
+                "tool_result",
+                "bash",
+                "failed",
+                r#"{"error":true,"message":"exit code 101"}"#,
+            ),
+            event("a2", 2600, "assistant", "The tests still fail.", json!({})),
+        ])
+    }
+
+    fn options(width: usize) -> TimelineOptions<'static> {
+        TimelineOptions {
+            compact: false,
+            timestamps: false,
+            collapsed_subagents: &[],
+            filter: TimelineFilter::All,
+            width,
+            diff_max_lines: 20,
+            spinner: None,
+            now_ms: 1_700_000_010_000,
+            busy_label: None,
+            earlier_events: 0,
+            loading_earlier: false,
+            expanded_summaries: &[],
+            redact: None,
+        }
+    }
+
+    fn timeline(state: &ChatState, options: &TimelineOptions) -> String {
+        plain(&build_timeline_lines(state, &HashMap::new(), options, None).0)
+    }
+
+    /// The text of each line, trailing spaces dropped.
+    fn plain(lines: &[Line]) -> String {
+        lines
+            .iter()
+            .map(|line| {
+                let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+                text.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Like `plain`, with each colored span prefixed by its color.
+    fn styled(lines: &[Line]) -> String {
+        lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|s| match s.style.fg {
+                        Some(color) => format!("<{:?}>{}", color, s.content),
+                        None => s.content.to_string(),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // ── Timeline ────────────────────────────────────────────
+
+    #[test]
+    fn iso_timestamps_are_utc() {
+        assert_eq!(format_iso(1_700_000_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn conversation_wide() {
+        insta::assert_snapshot!(timeline(&conversation(), &options(80)));
+    }
+
+    #[test]
+    fn conversation_narrow() {
+        insta::assert_snapshot!(timeline(&conversation(), &options(32)));
+    }
+
+    #[test]
+    fn tool_calls() {
+        insta::assert_snapshot!(timeline(&tool_session(), &options(80)));
+    }
+
+    #[test]
+    fn tool_calls_compact() {
+        let options = TimelineOptions {
+            compact: true,
+            ..options(80)
+        };
+        insta::assert_snapshot!(timeline(&tool_session(), &options));
+    }
+
+    #[test]
+    fn tool_calls_errors_only() {
+        let options = TimelineOptions {
+            filter: TimelineFilter::Errors,
+            ..options(80)
+        };
+        insta::assert_snapshot!(timeline(&tool_session(), &options));
+    }
+
+    #[test]
+    fn compaction_marker_expands_to_its_summary() {
+        let state = session(vec![
+            event("u1", 0, "user", "Keep going", json!({})),
+            event(
+                "c1",
+                100,
+                "compaction",
+                "Refactored the parser.",
+                json!({ "compaction": { "messagesRemoved": 34, "summarized": true } }),
+            ),
+        ]);
+        let collapsed = timeline(&state, &options(80));
+        assert!(collapsed.contains("─── ▸ 34 earlier messages summarized ───"));
+        assert!(!collapsed.contains("Refactored"));
+        let expanded = ["c1".to_string()];
+        let options = TimelineOptions {
+            expanded_summaries: &expanded,
+            ..options(80)
+        };
+        assert!(timeline(&state, &options).contains("│ Refactored the parser."));
+    }
+
+    #[test]
+    fn redaction_masks_what_is_shown_but_not_the_events() {
+        #[derive(Debug)]
+        struct MaskHunter2;
+        impl Redact for MaskHunter2 {
+            fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+                if text.contains("hunter2") {
+                    Cow::Owned(text.replace("hunter2", "[REDACTED]"))
+                } else {
+                    Cow::Borrowed(text)
+                }
+            }
+        }
+        let state = session(vec![
+            event("u1", 0, "user", "My password is hunter2", json!({})),
+            event("a1", 100, "assistant", "Don't share hunter2.", json!({})),
+        ]);
+        let options = TimelineOptions {
+            redact: Some(&MaskHunter2),
+            ..options(80)
+        };
+        let text = timeline(&state, &options);
+        assert!(!text.contains("hunter2"), "{}", text);
+        assert!(text.contains("My password is [REDACTED]"));
+        assert!(text.contains("Don't share [REDACTED]."));
+        assert!(state.timeline_events[0].content.contains("hunter2"));
+    }
+
+    #[test]
+    fn subagent_events_nest_under_their_task_call() {
+        let child = |parent: &str, id: &str, at: i64, kind: &str, content: &str, extra: Value| {
+            let mut e = event(id, at, kind, content, extra);
+            e["parentToolCallId"] = json!(parent);
+            e
+        };
+        let state = session(vec![
+            event("u1", 0, "user", "Where is the config parsed?", json!({})),
+            tool(
+                "k1",
+                100,
+                "tool_call",
+                "task",
+                "completed",
+                r#"{"description":"find config parsing"}"#,
+            ),
+            child(
+                "k1",
+                "k1-grep",
+                200,
+                "tool_call",
+                r#"{"pattern":"from_str"}"#,
+                json!({ "toolCallId": "g1", "toolName": "grep", "status": "completed" }),
+            ),
+            child(
+                "k1",
+                "k1-text",
+                300,
+                "assistant",
+                "It's in config.rs.",
+                json!({ "tokens": { "input": 1200, "output": 40 } }),
+            ),
+            tool("k1", 400, "tool_result", "task", "completed", "config.rs"),
+            tool(
+                "t2",
+                500,
+                "tool_call",
+                "task",
+                "completed",
+                r#"{"description":"other"}"#,
+            ),
+            child(
+                "t2",
+                "t2-text",
+                600,
+                "assistant",
+                "Nothing else.",
+                json!({}),
+            ),
+        ]);
+        let text = timeline(&state, &options(80));
+        assert!(
+            text.contains("▾ Sub-agent · 2 events · 1,240 tokens"),
+            "{}",
+            text
+        );
+        assert!(text.contains("│ It's in config.rs."));
+        // Sub-agent events aren't repeated at the top level.
+        assert_eq!(text.matches("It's in config.rs.").count(), 1);
+
+        let collapsed = ["k1".to_string()];
+        let options = TimelineOptions {
+            collapsed_subagents: &collapsed,
+            ..options(80)
+        };
+        let text = timeline(&state, &options);
+        assert!(text.contains("▸ Sub-agent · 2 events"));
+        assert!(!text.contains("It's in config.rs."));
+        // Folding is per call: the other task call stays open.
+        assert!(text.contains("│ Nothing else."));
+    }
+
+    #[test]
+    fn earlier_events_row_leads_the_timeline() {
+        let options = TimelineOptions {
+            earlier_events: 120,
+            ..options(80)
+        };
+        let text = timeline(&conversation(), &options);
+        assert!(text.starts_with("  ↑ 120 earlier events"));
+        let loading = TimelineOptions {
+            loading_earlier: true,
+            ..options
+        };
+        assert!(timeline(&conversation(), &loading).starts_with("  Loading earlier messages…"));
+    }
+
+    #[test]
+    fn anchors_mark_messages_and_failed_calls() {
+        let (lines, anchors) =
+            build_timeline_lines(&tool_session(), &HashMap::new(), &options(80), None);
+        let ids: Vec<&str> = anchors.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(ids, ["u1", "a1", "t3-tool_call"]);
+        assert!(anchors.iter().all(|(line, _)| *line < lines.len()));
+    }
+
+    // ── Diffs and markdown ──────────────────────────────────
+
+    #[test]
+    fn diff_colors() {
+        let diff = "@@ -10,3 +10,3 @@\n context\n-old line\n+new line\n";
+        let (summary, lines) = render_diff(diff, 60);
+        assert_eq!(summary, "(+1 / -1)");
+        insta::assert_snapshot!(styled(&lines));
+    }
+
+    #[test]
+    fn markdown_blocks() {
+        let content = "# Title\n\nSome **bold** and `code`.\n\n> quoted\n\n```\nplain block\n```";
+        insta::assert_snapshot!(styled(&render_markdown(content, 40)));
+    }
+}
@@ -0,0 +1,136 @@
+//! Read-only access to saved sessions in the backend's SQLite database, for
+//! replaying and exporting without starting the backend.
+
+use crate::backend::{order_events, SessionInfo, TimelineEvent};
+use crate::text::truncate_text;
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// The backend's SQLite database, `~/.stratuscode/stratuscode.db`.
+pub fn database_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(
+        PathBuf::from(home)
+            .join(".stratuscode")
+            .join("stratuscode.db"),
+    )
+}
+
+/// Read-only access to saved sessions, for when the backend isn't running or
+/// can't start.
+pub struct SessionStore {
+    conn: Connection,
+}
+
+impl SessionStore {
+    pub fn open() -> Result<Self> {
+        let path = database_path().ok_or_else(|| anyhow!("No home directory"))?;
+        if !path.exists() {
+            return Err(anyhow!("No saved sessions at {}", path.display()));
+        }
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Self { conn })
+    }
+
+    /// Sessions of a project that have messages, most recently updated first.
+    pub fn list_sessions(&self, project_dir: &str, limit: usize) -> Result<Vec<SessionInfo>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT s.id, s.title, COUNT(m.id),
+                (SELECT content FROM messages
+                 WHERE session_id = s.id AND role = 'user'
+                 ORDER BY created_at ASC LIMIT 1)
+             FROM sessions s JOIN messages m ON m.session_id = s.id
+             WHERE s.project_dir = ?1
+             GROUP BY s.id
+             ORDER BY s.updated_at DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![project_dir, limit as i64], |row| {
+            let first: Option<String> = row.get(3)?;
+            Ok(SessionInfo {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                message_count: Some(row.get::<_, i64>(2)? as u64),
+                first_message: first.map(|m| truncate_text(&m, 50)),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Finds a session by id, slug or unique id prefix; the project's most
+    /// recent session when `query` is `None`.
+    pub fn resolve_session(&self, project_dir: &str, query: Option<&str>) -> Result<SessionInfo> {
+        let Some(query) = query else {
+            return self
+                .list_sessions(project_dir, 1)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("No saved sessions for {}", project_dir));
+        };
+        let exact: Option<(String, String)> = self
+            .conn
+            .query_row(
+                "SELECT id, title FROM sessions WHERE id = ?1 OR slug = ?1",
+                params![query],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let (id, title) = match exact {
+            Some(found) => found,
+            None => {
+                let mut stmt = self
+                    .conn
+                    .prepare("SELECT id, title FROM sessions WHERE id LIKE ?1 || '%' LIMIT 2")?;
+                let matches = stmt
+                    .query_map(params![query], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+                match matches.len() {
+                    0 => return Err(anyhow!("No session matches '{}'", query)),
+                    1 => matches.into_iter().next().unwrap(),
+                    _ => return Err(anyhow!("'{}' matches more than one session", query)),
+                }
+            }
+        };
+        Ok(SessionInfo {
+            id,
+            title,
+            message_count: None,
+            first_message: None,
+        })
+    }
+
+    /// The session's timeline in the shape `load_session` produces.
+    pub fn timeline_events(&self, session_id: &str) -> Result<Vec<TimelineEvent>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, data, created_at FROM message_parts
+             WHERE session_id = ?1 AND type = 'timeline_event'
+             ORDER BY created_at ASC, rowid ASC",
+        )?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, data, created_at) = row?;
+            let Ok(Value::Object(mut event)) = serde_json::from_str::<Value>(&data) else {
+                continue;
+            };
+            event.insert("id".into(), id.into());
+            event.insert("sessionId".into(), session_id.into());
+            event.insert("createdAt".into(), created_at.into());
+            event.insert("streaming".into(), false.into());
+            if let Ok(event) = serde_json::from_value(Value::Object(event)) {
+                events.push(event);
+            }
+        }
+        order_events(&mut events);
+        Ok(events)
+    }
+}
@@ -0,0 +1,94 @@
+//! Width-aware string helpers: terminal columns, not bytes or chars, so wide
+//! characters and emoji line up.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The longest prefix of `text` that fits in `max_width` columns, cut between
+/// grapheme clusters so wide characters, emoji and combining marks stay whole.
+pub fn take_width(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        width += UnicodeWidthStr::width(grapheme);
+        if width > max_width {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+/// Shortens `text` to `max_width` columns, ending in `…` when it was cut.
+pub fn truncate_text(text: &str, max_width: usize) -> String {
+    if UnicodeWidthStr::width(text) <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    format!("{}…", take_width(text, max_width - 1))
+}
+
+/// Like `truncate_text` but keeps the end, for paths: `…/src/app.rs`.
+pub fn truncate_start(text: &str, max_width: usize) -> String {
+    if UnicodeWidthStr::width(text) <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut width = 0;
+    let mut start = text.len();
+    for (idx, grapheme) in text.grapheme_indices(true).rev() {
+        width += UnicodeWidthStr::width(grapheme);
+        if width > max_width - 1 {
+            break;
+        }
+        start = idx;
+    }
+    format!("…{}", &text[start..])
+}
+
+/// `1234567` as "1,234,567".
+pub fn format_number(value: u64) -> String {
+    let s = value.to_string();
+    let mut out = String::new();
+    for (i, ch) in s.chars().rev().enumerate() {
+        if i > 0 && i % 3 == 0 {
+            out.push(',');
+        }
+        out.push(ch);
+    }
+    out.chars().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_width_keeps_graphemes_whole() {
+        assert_eq!(take_width("hello", 3), "hel");
+        assert_eq!(take_width("表格", 3), "表");
+        assert_eq!(take_width("e\u{301}x", 1), "e\u{301}");
+        assert_eq!(take_width("hi", 10), "hi");
+    }
+
+    #[test]
+    fn truncates_from_either_end() {
+        assert_eq!(truncate_text("src/app.rs", 20), "src/app.rs");
+        assert_eq!(truncate_text("src/app.rs", 6), "src/a…");
+        assert_eq!(truncate_text("表格表格", 5), "表格…");
+        assert_eq!(truncate_text("abc", 0), "");
+        assert_eq!(truncate_start("crates/cli/src/app.rs", 11), "…src/app.rs");
+        assert_eq!(truncate_start("表格表格", 5), "…表格");
+        assert_eq!(truncate_start("abc", 0), "");
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(format_number(0), "0");
+        assert_eq!(format_number(999), "999");
+        assert_eq!(format_number(1000), "1,000");
+        assert_eq!(format_number(1234567), "1,234,567");
+    }
+}
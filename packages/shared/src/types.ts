@@ -39,6 +39,8 @@ export interface TokenUsage {
   input: number;
   output: number;
   context?: number;
+  /** Input tokens served from the provider's prompt cache, when reported. */
+  cached?: number;
   model?: string;
 }
 
@@ -48,14 +50,24 @@ export type TimelineEventKind =
   | 'reasoning'
   | 'tool_call'
   | 'tool_result'
-  | 'status';
+  | 'status'
+  | 'error'
+  | 'compaction';
 
 export interface TimelineAttachment {
-  type: 'image' | 'text';
-  mime?: string;       // for images
+  type: 'image' | 'text' | 'file';
+  name?: string;       // original file name, when attached from disk
+  mime?: string;       // for images and files
   lineCount?: number;  // for text pastes
   text?: string;       // full pasted text content
-  data?: string;       // base64 image data
+  data?: string;       // base64 image or file data
+}
+
+/** On `compaction` events: what context management did to the history. */
+export interface TimelineCompaction {
+  messagesRemoved: number;
+  /** Summarized into the event's content; otherwise the messages were dropped. */
+  summarized: boolean;
 }
 
 export interface TimelineEventBase {
@@ -67,6 +79,11 @@ export interface TimelineEventBase {
   tokens?: TokenUsage;
   streaming?: boolean;
   attachments?: TimelineAttachment[];
+  /** On user events: the id the client showed the message under while sending. */
+  clientId?: string;
+  compaction?: TimelineCompaction;
+  /** On events from a `task` sub-agent: the tool call that spawned it. */
+  parentToolCallId?: string;
 }
 
 export interface TimelineToolEvent extends TimelineEventBase {
@@ -77,7 +94,7 @@ export interface TimelineToolEvent extends TimelineEventBase {
 }
 
 export type TimelineEvent =
-  | (TimelineEventBase & { kind: 'user' | 'assistant' | 'reasoning' | 'status'; role?: Message['role'] })
+  | (TimelineEventBase & { kind: 'user' | 'assistant' | 'reasoning' | 'status' | 'error' | 'compaction'; role?: Message['role'] })
   | TimelineToolEvent;
 
 // ============================================
@@ -186,6 +203,14 @@ export interface ToolContext {
   sessionId: string;
   projectDir: string;
   abort?: AbortSignal;
+  sandbox?: SandboxConfig;
+}
+
+/** A running container bash commands go through `docker exec` into. */
+export interface SandboxConfig {
+  container: string;
+  /** Variables passed through from the backend's environment. */
+  env?: string[];
 }
 
 export interface Tool extends ToolDefinition {
@@ -341,6 +341,28 @@ describe('withRetry', () => {
     expect(retries).toEqual([1, 2]);
   });
 
+  test('abort cuts the backoff short', async () => {
+    const controller = new AbortController();
+    let attempts = 0;
+    const started = Date.now();
+    const run = withRetry(
+      async () => {
+        attempts++;
+        throw new Error('rate limited');
+      },
+      {
+        maxAttempts: 3,
+        baseDelay: 10_000,
+        maxDelay: 10_000,
+        signal: controller.signal,
+        onRetry: () => controller.abort(new Error('Aborted by user')),
+      }
+    );
+    await expect(run).rejects.toThrow('Aborted by user');
+    expect(attempts).toBe(1);
+    expect(Date.now() - started).toBeLessThan(1000);
+  });
+
   test('throws when maxAttempts is 0 (post-loop safeguard)', async () => {
     await expect(withRetry(
       async () => 'should never run',
@@ -39,6 +39,29 @@ export function sleep(ms: number): Promise<void> {
   return new Promise(resolve => setTimeout(resolve, ms));
 }
 
+/**
+ * Sleep that rejects with the signal's reason as soon as it aborts
+ */
+export function abortableSleep(ms: number, signal?: AbortSignal): Promise<void> {
+  if (!signal) return sleep(ms);
+  return new Promise((resolve, reject) => {
+    const abortError = () => signal.reason instanceof Error ? signal.reason : new Error('Aborted');
+    if (signal.aborted) {
+      reject(abortError());
+      return;
+    }
+    const onAbort = () => {
+      clearTimeout(timer);
+      reject(abortError());
+    };
+    const timer = setTimeout(() => {
+      signal.removeEventListener('abort', onAbort);
+      resolve();
+    }, ms);
+    signal.addEventListener('abort', onAbort, { once: true });
+  });
+}
+
 /**
  * Execute with timeout
  */
@@ -204,6 +227,8 @@ export interface RetryOptions {
   maxDelay: number;
   shouldRetry?: (error: Error) => boolean;
   onRetry?: (attempt: number, error: Error, delay: number) => void;
+  /** Cuts the backoff short; the pending retry is dropped. */
+  signal?: AbortSignal;
 }
 
 /**
@@ -219,6 +244,7 @@ export async function withRetry<T>(
     maxDelay,
     shouldRetry = () => true,
     onRetry,
+    signal,
   } = options;
 
   let lastError: Error | undefined;
@@ -235,7 +261,7 @@ export async function withRetry<T>(
 
       const delay = Math.min(baseDelay * Math.pow(2, attempt - 1), maxDelay);
       onRetry?.(attempt, lastError, delay);
-      await sleep(delay);
+      await abortableSleep(delay, signal);
     }
   }
 
@@ -0,0 +1,48 @@
+/**
+ * Bookmark Storage Tests
+ *
+ * Covers: addBookmark, listBookmarks, deleteBookmark.
+ */
+
+import { describe, test, expect, beforeAll, afterAll } from 'bun:test';
+import { initDatabase, closeDatabase } from './database';
+import { addBookmark, listBookmarks, deleteBookmark } from './bookmarks';
+
+const testDir = `/tmp/stratuscode-bookmarks-test-${Date.now()}`;
+
+describe('Bookmark Storage', () => {
+  const testSessionId = 'test-session-bookmarks';
+
+  beforeAll(() => {
+    initDatabase({ dataDir: testDir });
+  });
+
+  afterAll(() => {
+    closeDatabase();
+  });
+
+  test('adds and lists bookmarks in order', () => {
+    const first = addBookmark(testSessionId, 'evt-1', 'Found the bug');
+    const second = addBookmark(testSessionId, 'evt-2', 'Fix applied');
+    expect(first.id).toBeTruthy();
+    expect(first.eventId).toBe('evt-1');
+    const list = listBookmarks(testSessionId);
+    expect(list.map(b => b.id)).toEqual([first.id, second.id]);
+  });
+
+  test('bookmarking an event twice keeps one bookmark', () => {
+    const again = addBookmark(testSessionId, 'evt-1', 'Another label');
+    expect(again.label).toBe('Found the bug');
+    expect(listBookmarks(testSessionId).filter(b => b.eventId === 'evt-1')).toHaveLength(1);
+  });
+
+  test('deletes a bookmark', () => {
+    const bookmark = addBookmark(testSessionId, 'evt-3', 'Temporary');
+    deleteBookmark(bookmark.id);
+    expect(listBookmarks(testSessionId).some(b => b.id === bookmark.id)).toBe(false);
+  });
+
+  test('bookmarks are per session', () => {
+    expect(listBookmarks('other-session')).toEqual([]);
+  });
+});
@@ -0,0 +1,90 @@
+/**
+ * Bookmarks Storage
+ *
+ * Timeline events the user marked in a session, to jump back to later.
+ */
+
+import { getDatabase, insert, findAll, deleteById } from './database';
+import { generateId } from '@stratuscode/shared';
+
+// ============================================
+// Types
+// ============================================
+
+export interface BookmarkRow {
+  id: string;
+  session_id: string;
+  event_id: string;
+  label: string;
+  created_at: number;
+}
+
+export interface Bookmark {
+  id: string;
+  sessionId: string;
+  eventId: string;
+  label: string;
+  createdAt: number;
+}
+
+// ============================================
+// Conversions
+// ============================================
+
+function rowToBookmark(row: BookmarkRow): Bookmark {
+  return {
+    id: row.id,
+    sessionId: row.session_id,
+    eventId: row.event_id,
+    label: row.label,
+    createdAt: row.created_at,
+  };
+}
+
+// ============================================
+// Operations
+// ============================================
+
+/**
+ * Get all bookmarks for a session, oldest first
+ */
+export function listBookmarks(sessionId: string): Bookmark[] {
+  const rows = findAll<BookmarkRow>('bookmarks', { session_id: sessionId }, 'created_at ASC');
+  return rows.map(rowToBookmark);
+}
+
+/**
+ * Bookmark an event; bookmarking it again returns the existing bookmark
+ */
+export function addBookmark(sessionId: string, eventId: string, label: string): Bookmark {
+  const db = getDatabase();
+  const existing = db
+    .query('SELECT * FROM bookmarks WHERE session_id = ? AND event_id = ?')
+    .get(sessionId, eventId) as BookmarkRow | undefined;
+  if (existing) return rowToBookmark(existing);
+
+  const bookmark: Bookmark = {
+    id: generateId('bookmark'),
+    sessionId,
+    eventId,
+    label,
+    createdAt: Date.now(),
+  };
+
+  insert('bookmarks', {
+    id: bookmark.id,
+    session_id: bookmark.sessionId,
+    event_id: bookmark.eventId,
+    label: bookmark.label,
+    created_at: bookmark.createdAt,
+  });
+
+  return bookmark;
+}
+
+/**
+ * Delete a bookmark
+ */
+export function deleteBookmark(id: string): void {
+  deleteById('bookmarks', id);
+}
@@ -116,6 +116,7 @@ function createTables(db: Database): void {
       input_tokens INTEGER,
       output_tokens INTEGER,
       context_tokens INTEGER,
+      cached_tokens INTEGER,
       model TEXT,
       created_at INTEGER NOT NULL,
       FOREIGN KEY (session_id) REFERENCES sessions(id),
@@ -202,6 +203,18 @@ function createTables(db: Database): void {
     )
   `);
 
+  // Bookmarks table
+  db.exec(`
+    CREATE TABLE IF NOT EXISTS bookmarks (
+      id TEXT PRIMARY KEY,
+      session_id TEXT NOT NULL,
+      event_id TEXT NOT NULL,
+      label TEXT NOT NULL,
+      created_at INTEGER NOT NULL,
+      FOREIGN KEY (session_id) REFERENCES sessions(id)
+    )
+  `);
+
   // Create indexes
   db.exec(`
     CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project_dir);
@@ -211,6 +224,7 @@ function createTables(db: Database): void {
     CREATE INDEX IF NOT EXISTS idx_tool_calls_message ON tool_calls(message_id);
     CREATE INDEX IF NOT EXISTS idx_todos_session ON todos(session_id);
     CREATE INDEX IF NOT EXISTS idx_pending_questions_session ON pending_questions(session_id);
+    CREATE INDEX IF NOT EXISTS idx_bookmarks_session ON bookmarks(session_id);
     CREATE INDEX IF NOT EXISTS idx_error_memories_project ON error_memories(project_dir);
     CREATE INDEX IF NOT EXISTS idx_error_memories_hash ON error_memories(error_hash);
   `);
@@ -228,8 +242,10 @@ function createTables(db: Database): void {
   addColumn('messages', 'output_tokens', 'INTEGER');
   addColumn('messages', 'context_tokens', 'INTEGER');
   addColumn('messages', 'model', 'TEXT');
+  addColumn('messages', 'cached_tokens', 'INTEGER');
   addColumn('tool_calls', 'started_at', 'INTEGER');
   addColumn('tool_calls', 'completed_at', 'INTEGER');
+  addColumn('sessions', 'inflight_pid', 'INTEGER');
 }
 
 // ============================================
@@ -0,0 +1,122 @@
+/**
+ * Session Import
+ *
+ * Writes a conversation converted from another agent CLI into the store,
+ * keeping its original timestamps.
+ */
+
+import type { Session } from '@stratuscode/shared';
+import { generateId } from '@stratuscode/shared';
+import { getDatabase, insert } from './database';
+import { createSession, updateSession } from './sessions';
+
+// ============================================
+// Types
+// ============================================
+
+export interface ImportedToolCall {
+  id: string;
+  name: string;
+  arguments: string;
+  result?: string;
+  status: 'completed' | 'failed';
+}
+
+export interface ImportedMessage {
+  role: 'user' | 'assistant';
+  content: string;
+  createdAt?: number;
+  toolCalls?: ImportedToolCall[];
+}
+
+export interface ImportedSession {
+  title: string;
+  source: string;
+  messages: ImportedMessage[];
+}
+
+// ============================================
+// Operations
+// ============================================
+
+/**
+ * Create a session holding the imported messages, their timeline events and
+ * tool calls. Everything is written in one transaction.
+ */
+export function importSession(projectDir: string, imported: ImportedSession): Session {
+  const db = getDatabase();
+  const session = createSession(projectDir, imported.title);
+  const sid = session.id;
+  let clock = 0;
+  // Timeline order is by created_at, so keep timestamps strictly increasing.
+  const tick = (at?: number) => {
+    clock = Math.max(at ?? 0, clock + 1);
+    return clock;
+  };
+  const addEvent = (messageId: string, at: number, payload: Record<string, unknown>) => {
+    insert('message_parts', {
+      id: generateId('event'),
+      message_id: messageId,
+      session_id: sid,
+      type: 'timeline_event',
+      data: JSON.stringify(payload),
+      created_at: at,
+    });
+  };
+
+  db.transaction(() => {
+    for (const message of imported.messages) {
+      const messageId = generateId('msg');
+      const at = tick(message.createdAt);
+      insert('messages', {
+        id: messageId,
+        session_id: sid,
+        parent_id: null,
+        role: message.role,
+        content: message.content,
+        created_at: at,
+      });
+      if (message.content.trim()) {
+        addEvent(messageId, at, { kind: message.role, content: message.content, messageId });
+      }
+      for (const call of message.toolCalls ?? []) {
+        // Ids are only unique within the source session.
+        const callId = `${sid}_${call.id}`;
+        const startedAt = tick();
+        insert('tool_calls', {
+          id: callId,
+          message_id: messageId,
+          session_id: sid,
+          name: call.name,
+          arguments: call.arguments,
+          result: call.result ?? null,
+          status: call.status,
+          started_at: startedAt,
+          completed_at: startedAt,
+        });
+        addEvent(messageId, startedAt, {
+          kind: 'tool_call',
+          content: call.arguments,
+          toolCallId: callId,
+          toolName: call.name,
+          status: call.status,
+          messageId,
+        });
+        if (call.result != null) {
+          addEvent(messageId, tick(), {
+            kind: 'tool_result',
+            content: call.result.slice(0, 2000),
+            toolCallId: callId,
+            toolName: call.name,
+            status: call.status,
+            messageId,
+          });
+        }
+      }
+    }
+    addEvent(sid, tick(), { kind: 'status', content: `Imported from ${imported.source}` });
+  })();
+
+  updateSession(sid, { status: 'completed' });
+  return { ...session, status: 'completed' };
+}
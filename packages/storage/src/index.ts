@@ -3,5 +3,7 @@ export * from './sessions';
 export * from './messages';
 export * from './config-loader';
 export * from './todos';
+export * from './bookmarks';
 export * from './questions';
 export * from './error-memories';
+export * from './import';
@@ -219,4 +219,22 @@ describe('timeline events extended', () => {
     expect(event.kind).toBe('tool_result');
     expect(event.content).toBe('file content');
   });
+
+  test('sub-agent events keep their parent tool call', () => {
+    const sid = createSession('/test/timeline-subagent').id;
+    const msgId = createMessage(sid, 'assistant', 'Delegating');
+
+    createTimelineEvent(sid, 'tool_call', '{}', { toolCallId: 'tc-task', toolName: 'task', status: 'running' }, msgId);
+    const child = createTimelineEvent(sid, 'tool_call', '{}', {
+      toolCallId: 'tc-child',
+      toolName: 'grep',
+      status: 'running',
+      parentToolCallId: 'tc-task',
+    }, msgId);
+
+    expect(child.parentToolCallId).toBe('tc-task');
+    const loaded = listTimelineEvents(sid);
+    expect(loaded.find(e => e.id === child.id)?.parentToolCallId).toBe('tc-task');
+    expect(loaded.find(e => e.id !== child.id)?.parentToolCallId).toBeUndefined();
+  });
 });
@@ -4,7 +4,7 @@
  * CRUD operations for messages and message parts.
  */
 
-import type { Message, MessagePart, ToolCall, TimelineEvent, TimelineAttachment, TokenUsage, TimelineEventKind } from '@stratuscode/shared';
+import type { Message, MessagePart, ToolCall, TimelineEvent, TimelineAttachment, TimelineCompaction, TokenUsage, TimelineEventKind } from '@stratuscode/shared';
 import { generateId } from '@stratuscode/shared';
 import { getDatabase, insert, findAll } from './database';
 
@@ -24,6 +24,7 @@ interface MessageRow {
   input_tokens: number | null;
   output_tokens: number | null;
   context_tokens: number | null;
+  cached_tokens: number | null;
   model: string | null;
   created_at: number;
 }
@@ -154,11 +155,12 @@ export function updateMessageReasoning(id: string, reasoning: string): void {
 export function updateMessageTokens(id: string, tokenUsage: TokenUsage): void {
   const db = getDatabase();
   db.prepare(
-    'UPDATE messages SET input_tokens = ?, output_tokens = ?, context_tokens = ?, model = ? WHERE id = ?'
+    'UPDATE messages SET input_tokens = ?, output_tokens = ?, context_tokens = ?, cached_tokens = ?, model = ? WHERE id = ?'
   ).run(
     tokenUsage.input ?? null,
     tokenUsage.output ?? null,
     tokenUsage.context ?? null,
+    tokenUsage.cached ?? null,
     tokenUsage.model ?? null,
     id
   );
@@ -294,7 +296,7 @@ export function createTimelineEvent(
   sessionId: string,
   kind: TimelineEventKind,
   content: string,
-  data: { toolCallId?: string; toolName?: string; status?: ToolCall['status']; tokens?: TokenUsage; streaming?: boolean; attachments?: TimelineAttachment[] } = {},
+  data: { toolCallId?: string; toolName?: string; status?: ToolCall['status']; tokens?: TokenUsage; streaming?: boolean; attachments?: TimelineAttachment[]; compaction?: TimelineCompaction; parentToolCallId?: string } = {},
   messageId?: string
 ): TimelineEvent {
   const id = generateId('event');
@@ -308,6 +310,8 @@ export function createTimelineEvent(
     tokens: data.tokens,
     streaming: data.streaming,
     attachments: data.attachments,
+    compaction: data.compaction,
+    parentToolCallId: data.parentToolCallId,
     messageId,
   };
 
@@ -332,14 +336,26 @@ export function createTimelineEvent(
     ...(data.toolCallId ? { toolCallId: data.toolCallId } : {}),
     ...(data.toolName ? { toolName: data.toolName } : {}),
     ...(data.status ? { status: data.status } : {}),
+    ...(data.compaction ? { compaction: data.compaction } : {}),
+    ...(data.parentToolCallId ? { parentToolCallId: data.parentToolCallId } : {}),
   } as TimelineEvent;
 }
 
+/**
+ * Replace a timeline event's content, e.g. once a compaction summary is known
+ */
+export function updateTimelineEventContent(id: string, content: string): void {
+  const db = getDatabase();
+  db.prepare("UPDATE message_parts SET data = json_set(data, '$.content', ?) WHERE id = ?").run(content, id);
+}
+
 export function listTimelineEvents(sessionId: string): TimelineEvent[] {
   const rows = findAll<MessagePartRow>(
     'message_parts',
     { session_id: sessionId, type: 'timeline_event' },
-    'created_at ASC'
+    // Same-millisecond events would otherwise come back in whatever order
+    // SQLite picks; rowid keeps them in the order they were written.
+    'created_at ASC, rowid ASC'
   );
 
   return rows.map(row => {
@@ -353,6 +369,8 @@ export function listTimelineEvents(sessionId: string): TimelineEvent[] {
       tokens: parsed.tokens,
       streaming: false, // Loaded events are never streaming
       ...(parsed.attachments ? { attachments: parsed.attachments } : {}),
+      ...(parsed.compaction ? { compaction: parsed.compaction } : {}),
+      ...(parsed.parentToolCallId ? { parentToolCallId: parsed.parentToolCallId } : {}),
     };
     if (parsed.toolCallId) {
       return {
@@ -379,6 +397,25 @@ export function getMessageTokens(messageId: string): TokenUsage | undefined {
   };
 }
 
+/**
+ * Token usage of each assistant turn in a session, oldest first
+ */
+export function getSessionTurnUsage(sessionId: string): Array<TokenUsage & { createdAt: number }> {
+  const rows = getDatabase().prepare(
+    `SELECT input_tokens, output_tokens, context_tokens, cached_tokens, model, created_at FROM messages
+     WHERE session_id = ? AND role = 'assistant' AND (input_tokens IS NOT NULL OR output_tokens IS NOT NULL)
+     ORDER BY created_at ASC`
+  ).all(sessionId) as MessageRow[];
+  return rows.map(row => ({
+    input: row.input_tokens ?? 0,
+    output: row.output_tokens ?? 0,
+    context: row.context_tokens ?? undefined,
+    cached: row.cached_tokens ?? undefined,
+    model: row.model ?? undefined,
+    createdAt: row.created_at,
+  }));
+}
+
 export function getSessionTokenTotals(sessionId: string): TokenUsage {
   const db = getDatabase();
   const row = db.prepare('SELECT SUM(input_tokens) as input, SUM(output_tokens) as output, SUM(context_tokens) as context FROM messages WHERE session_id = ?').get(sessionId) as { input: number | null; output: number | null; context: number | null };
@@ -23,6 +23,8 @@ export interface Question {
   options: QuestionOption[];
   allowMultiple?: boolean;
   allowCustom?: boolean;
+  /** Index of the recommended option, used when answering unattended. */
+  defaultOption?: number;
 }
 
 export interface PendingQuestionRow {
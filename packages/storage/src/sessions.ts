@@ -23,6 +23,7 @@ interface SessionRow {
   created_at: number;
   updated_at: number;
   completed_at: number | null;
+  inflight_pid: number | null;
 }
 
 // ============================================
@@ -135,9 +136,50 @@ export function deleteSession(id: string): void {
   db.prepare('DELETE FROM tool_calls WHERE session_id = ?').run(id);
   db.prepare('DELETE FROM message_parts WHERE session_id = ?').run(id);
   db.prepare('DELETE FROM messages WHERE session_id = ?').run(id);
+  db.prepare('DELETE FROM bookmarks WHERE session_id = ?').run(id);
   deleteById('sessions', id);
 }
 
+/**
+ * Mark a session as mid-turn. The marker outlives a crash, which is how the
+ * next launch finds the interrupted turn.
+ */
+export function markTurnInFlight(id: string): void {
+  const db = getDatabase();
+  db.prepare('UPDATE sessions SET inflight_pid = ? WHERE id = ?').run(process.pid, id);
+}
+
+/**
+ * Clear the in-flight marker once a turn ends, however it ended
+ */
+export function clearTurnInFlight(id: string): void {
+  const db = getDatabase();
+  db.prepare('UPDATE sessions SET inflight_pid = NULL WHERE id = ?').run(id);
+}
+
+function processAlive(pid: number): boolean {
+  try {
+    process.kill(pid, 0);
+    return true;
+  } catch (err: any) {
+    return err?.code === 'EPERM';
+  }
+}
+
+/**
+ * Sessions of a project left mid-turn by a process that is no longer running,
+ * most recent first
+ */
+export function listInterruptedSessions(projectDir: string): Session[] {
+  const db = getDatabase();
+  const rows = db.prepare(
+    'SELECT * FROM sessions WHERE project_dir = ? AND inflight_pid IS NOT NULL ORDER BY updated_at DESC'
+  ).all(projectDir) as SessionRow[];
+  return rows
+    .filter(row => row.inflight_pid !== process.pid && !processAlive(row.inflight_pid!))
+    .map(rowToSession);
+}
+
 /**
  * Get session by slug
  */
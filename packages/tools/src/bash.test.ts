@@ -1,5 +1,5 @@
 import { describe, expect, test } from 'bun:test';
-import { bashTool } from './bash';
+import { bashTool, sandboxArgs, sandboxKillArgs } from './bash';
 
 const ctx = { sessionId: 'test', metadata: { projectDir: '/tmp' } };
 
@@ -77,3 +77,22 @@ describe('bash tool', () => {
     expect((result as string).trim()).toMatch(/\/(private\/)?tmp$/);
   });
 });
+
+describe('sandboxArgs', () => {
+  test('runs the command in the container at the same cwd', () => {
+    const args = sandboxArgs({ container: 'box', env: ['NPM_TOKEN'] }, '/work/app', 'ls -a', '/tmp/x.pid');
+    expect(args.slice(0, 10)).toEqual([
+      'exec', '-i', '-w', '/work/app',
+      '-e', 'PAGER=cat', '-e', 'GIT_PAGER=cat', '-e', 'NPM_TOKEN',
+    ]);
+    expect(args.slice(10, 13)).toEqual(['box', 'sh', '-c']);
+    expect(args.slice(-3)).toEqual(['sh', '/tmp/x.pid', 'ls -a']);
+  });
+
+  test('kills the command\'s process group in the container', () => {
+    const args = sandboxKillArgs({ container: 'box' }, '/tmp/x.pid', 'TERM');
+    expect(args.slice(0, 4)).toEqual(['exec', 'box', 'sh', '-c']);
+    expect(args[4]).toContain('kill -s TERM -- -');
+    expect(args.slice(-2)).toEqual(['sh', '/tmp/x.pid']);
+  });
+});
@@ -6,7 +6,10 @@
 
 import { defineTool } from './sage-adapter';
 import { spawn } from 'child_process';
+import { randomUUID } from 'crypto';
 import * as path from 'path';
+import { ToolOutput } from './lib/tool-output';
+import type { SandboxConfig } from '@stratuscode/shared';
 
 export interface BashArgs extends Record<string, unknown> {
   command: string;
@@ -14,6 +17,35 @@ export interface BashArgs extends Record<string, unknown> {
   timeout?: number;
 }
 
+/**
+ * Runs `$2` in its own process group inside the container and writes the
+ * group id to `$1` while it runs.
+ */
+const SANDBOX_WRAPPER = 'setsid sh -c "$2" & echo $! > "$1"; wait $!; status=$?; rm -f "$1"; exit $status';
+
+/**
+ * `docker exec` arguments running the command in the sandbox. The project is
+ * mounted at the same path, so the working directory carries over. Killing
+ * `docker exec` leaves the command running in the container, so it gets a
+ * process group `sandboxKillArgs` can stop, recorded in `pidFile`.
+ */
+export function sandboxArgs(sandbox: SandboxConfig, cwd: string, command: string, pidFile: string): string[] {
+  const env = ['PAGER=cat', 'GIT_PAGER=cat', ...(sandbox.env ?? [])];
+  return [
+    'exec', '-i', '-w', cwd,
+    ...env.flatMap(e => ['-e', e]),
+    sandbox.container, 'sh', '-c', SANDBOX_WRAPPER, 'sh', pidFile, command,
+  ];
+}
+
+/** `docker exec` arguments sending `signal` to a command `sandboxArgs` started. */
+export function sandboxKillArgs(sandbox: SandboxConfig, pidFile: string, signal: 'TERM' | 'KILL'): string[] {
+  return [
+    'exec', sandbox.container,
+    'sh', '-c', `[ -f "$1" ] && kill -s ${signal} -- -"$(cat "$1")"`, 'sh', pidFile,
+  ];
+}
+
 export const bashTool = defineTool<BashArgs>({
   name: 'bash',
   description: `Executes a shell command.
@@ -62,8 +94,19 @@ Tips:
       // Determine shell based on platform
       const shell = process.platform === 'win32' ? 'cmd' : '/bin/bash';
       const shellArgs = process.platform === 'win32' ? ['/c', command] : ['-c', command];
+      const sandbox = context.sandbox;
+      const pidFile = `/tmp/stratuscode-${randomUUID()}.pid`;
+      const [file, fileArgs] = sandbox
+        ? ['docker', sandboxArgs(sandbox, workingDir, command, pidFile)]
+        : [shell, shellArgs];
+      const kill = (signal: 'TERM' | 'KILL') => {
+        if (sandbox) {
+          spawn('docker', sandboxKillArgs(sandbox, pidFile, signal), { stdio: 'ignore' }).on('error', () => {});
+        }
+        proc.kill(`SIG${signal}`);
+      };
 
-      const proc = spawn(shell, shellArgs, {
+      const proc = spawn(file, fileArgs, {
         cwd: workingDir,
         env: {
           ...process.env,
@@ -76,24 +119,35 @@ Tips:
       // Handle timeout
       const timeoutId = setTimeout(() => {
         killed = true;
-        proc.kill('SIGTERM');
+        kill('TERM');
         setTimeout(() => {
           if (!proc.killed) {
-            proc.kill('SIGKILL');
+            kill('KILL');
           }
         }, 5000);
       }, timeout);
 
+      // Stopping the turn, or just this call, ends the command.
+      const onAbort = () => kill('TERM');
+      context.abort?.addEventListener('abort', onAbort, { once: true });
+
+      const stream = (chunk: string) => {
+        ToolOutput.emit({ sessionId: context.sessionId, tool: 'bash', args, chunk });
+      };
+
       proc.stdout?.on('data', (data) => {
         stdout.push(data.toString());
+        stream(data.toString());
       });
 
       proc.stderr?.on('data', (data) => {
         stderr.push(data.toString());
+        stream(data.toString());
       });
 
       proc.on('close', (code) => {
         clearTimeout(timeoutId);
+        context.abort?.removeEventListener('abort', onAbort);
 
         const stdoutStr = stdout.join('');
         const stderrStr = stderr.join('');
@@ -77,28 +77,8 @@ Usage:
     // Read current content
     const content = await fs.readFile(file_path, 'utf-8');
 
-    // Find occurrences
-    const occurrences = countOccurrences(content, old_string);
-
-    if (occurrences === 0) {
-      throw new Error(
-        `old_string not found in file. Make sure it matches exactly including whitespace.\n\nSearched for:\n${old_string.slice(0, 200)}${old_string.length > 200 ? '...' : ''}`
-      );
-    }
-
-    if (occurrences > 1 && !replace_all) {
-      throw new Error(
-        `old_string found ${occurrences} times. Either make it more specific to be unique, or set replace_all: true to replace all occurrences.`
-      );
-    }
-
     // Perform replacement
-    let newContent: string;
-    if (replace_all) {
-      newContent = content.split(old_string).join(new_string);
-    } else {
-      newContent = content.replace(old_string, new_string);
-    }
+    const { newContent, occurrences } = replaceText(content, old_string, new_string, replace_all);
 
     // Generate diff before writing
     const diff = generateUnifiedDiff(content, newContent, file_path);
@@ -122,6 +102,35 @@ Usage:
   },
 });
 
+/**
+ * Apply an edit to file content, with the same uniqueness checks as the tool.
+ */
+function replaceText(
+  content: string,
+  old_string: string,
+  new_string: string,
+  replace_all: boolean
+): { newContent: string; occurrences: number } {
+  const occurrences = countOccurrences(content, old_string);
+
+  if (occurrences === 0) {
+    throw new Error(
+      `old_string not found in file. Make sure it matches exactly including whitespace.\n\nSearched for:\n${old_string.slice(0, 200)}${old_string.length > 200 ? '...' : ''}`
+    );
+  }
+
+  if (occurrences > 1 && !replace_all) {
+    throw new Error(
+      `old_string found ${occurrences} times. Either make it more specific to be unique, or set replace_all: true to replace all occurrences.`
+    );
+  }
+
+  const newContent = replace_all
+    ? content.split(old_string).join(new_string)
+    : content.replace(old_string, new_string);
+  return { newContent, occurrences };
+}
+
 function countOccurrences(str: string, search: string): number {
   let count = 0;
   let pos = 0;
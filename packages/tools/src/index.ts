@@ -31,4 +31,5 @@ export * from './sage-adapter';
 // Export utility modules (moved from @stratuscode/core)
 export { Todo } from './lib/todo';
 export { Question } from './lib/question';
+export { ToolOutput } from './lib/tool-output';
 export { Snapshot } from './lib/snapshot';
@@ -60,6 +60,16 @@ describe('generateUnifiedDiff', () => {
     expect(result).toContain('+new line');
   });
 
+  test('new file diffs against /dev/null', () => {
+    const result = generateUnifiedDiff(null, 'one\ntwo\n', 'new-file.ts');
+    expect(result).toBe('--- /dev/null\n+++ b/new-file.ts\n@@ -0,0 +1,2 @@\n+one\n+two');
+  });
+
+  test('marks a last line without a newline', () => {
+    const result = generateUnifiedDiff('a\nb\n', 'a\nb', 'test.ts');
+    expect(result).toContain('-b\n+b\n\\ No newline at end of file');
+  });
+
   test('handles empty new content', () => {
     const result = generateUnifiedDiff('old line', '', 'deleted-file.ts');
     expect(result).toContain('-old line');
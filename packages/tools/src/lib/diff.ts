@@ -4,19 +4,40 @@
  * Generates unified diff format for file changes.
  */
 
+/** Appended to a last line that has no newline, so it never matches the same text with one. */
+const NO_EOL = '\u0000';
+
+/** Lines as git counts them: a trailing newline ends the last line rather than starting another. */
+function splitLines(content: string): string[] {
+  if (content === '') return [];
+  const lines = content.split('\n');
+  if (lines[lines.length - 1] === '') {
+    lines.pop();
+  } else {
+    lines[lines.length - 1] += NO_EOL;
+  }
+  return lines;
+}
+
+/** `start,count` for a hunk header; an empty range names the line before it, as git does. */
+function hunkRange(start: number, count: number): string {
+  return `${count === 0 ? start - 1 : start},${count}`;
+}
+
 /**
- * Generate a unified diff between old and new content.
+ * Generate a unified diff between old and new content, in a form `git apply`
+ * accepts. `oldContent` is null for a file that did not exist before.
  */
 export function generateUnifiedDiff(
-  oldContent: string,
+  oldContent: string | null,
   newContent: string,
   filePath: string
 ): string {
-  const oldLines = oldContent.split('\n');
-  const newLines = newContent.split('\n');
+  const oldLines = splitLines(oldContent ?? '');
+  const newLines = splitLines(newContent);
 
   const diffLines: string[] = [
-    `--- a/${filePath}`,
+    oldContent === null ? '--- /dev/null' : `--- a/${filePath}`,
     `+++ b/${filePath}`,
   ];
 
@@ -105,11 +126,15 @@ export function generateUnifiedDiff(
       const oldCount = hunkChanges.filter(c => c.type !== 'add').length;
       const newCount = hunkChanges.filter(c => c.type !== 'remove').length;
 
-      diffLines.push(`@@ -${oldLine},${oldCount} +${newLine},${newCount} @@`);
+      diffLines.push(`@@ -${hunkRange(oldLine, oldCount)} +${hunkRange(newLine, newCount)} @@`);
 
       for (const change of hunkChanges) {
         const prefix = change.type === 'add' ? '+' : change.type === 'remove' ? '-' : ' ';
-        diffLines.push(`${prefix}${change.line}`);
+        if (change.line.endsWith(NO_EOL)) {
+          diffLines.push(`${prefix}${change.line.slice(0, -1)}`, '\\ No newline at end of file');
+        } else {
+          diffLines.push(`${prefix}${change.line}`);
+        }
       }
     }
   }
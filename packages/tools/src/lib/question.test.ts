@@ -92,6 +92,23 @@ describe('question: full ask + answer lifecycle', () => {
     await expect(answersPromise).rejects.toBeInstanceOf(QuestionSkippedError);
   });
 
+  test('cancelAll rejects pending questions and reports them resolved', async () => {
+    const sessionId = 'q-lifecycle-cancel-' + Date.now();
+    const resolved: string[] = [];
+    const off = Question.onResolved((r) => {
+      if (r.sessionId === sessionId) resolved.push(r.status);
+    });
+    const answersPromise = Question.ask({
+      sessionId,
+      questions: [{ question: 'Pick?', options: [{ label: 'A' }] }],
+    });
+    expect(Question.cancelAll(sessionId)).toBe(1);
+    await expect(answersPromise).rejects.toBeInstanceOf(QuestionRejectedError);
+    expect(Question.hasPending(sessionId)).toBe(false);
+    expect(resolved).toEqual(['cancelled']);
+    off();
+  });
+
   test('ask + reject rejects with RejectedError', async () => {
     const sessionId = 'q-lifecycle-reject-' + Date.now();
     const answersPromise = Question.ask({
@@ -4,6 +4,7 @@
  * Manages interactive questions that block tool execution until answered.
  */
 
+import { EventEmitter } from 'events';
 import {
   createPendingQuestion,
   getPendingQuestions,
@@ -31,6 +32,7 @@ export interface QuestionInfo {
   options: QuestionOption[];
   allowMultiple?: boolean;
   allowCustom?: boolean;
+  defaultOption?: number;
 }
 
 export interface PendingQuestionInfo {
@@ -41,6 +43,7 @@ export interface PendingQuestionInfo {
   questions: QuestionInfo[];
   answers?: string[][];
   status: 'pending' | 'answered' | 'skipped';
+  createdAt: number;
 }
 
 // ============================================
@@ -72,6 +75,18 @@ type QuestionResolver = {
 
 const pendingResolvers = new Map<string, QuestionResolver>();
 
+/**
+ * Emits 'change' with the session id when a question is asked or resolved,
+ * and 'resolved' with the question once it is answered, skipped or cancelled.
+ */
+const changes = new EventEmitter();
+
+export interface ResolvedQuestion {
+  sessionId: string;
+  id: string;
+  status: 'answered' | 'skipped' | 'cancelled';
+}
+
 // ============================================
 // Question Operations
 // ============================================
@@ -84,6 +99,7 @@ export namespace Question {
     options: [] as QuestionOption[],
     allowMultiple: undefined as boolean | undefined,
     allowCustom: undefined as boolean | undefined,
+    defaultOption: undefined as number | undefined,
   };
 
   export type Answer = string[];
@@ -91,6 +107,24 @@ export namespace Question {
   export const RejectedError = QuestionRejectedError;
   export const SkippedError = QuestionSkippedError;
 
+  /**
+   * Subscribe to questions being asked or resolved; the listener receives the
+   * session id. Returns an unsubscribe function.
+   */
+  export function onChange(listener: (sessionId: string) => void): () => void {
+    changes.on('change', listener);
+    return () => changes.off('change', listener);
+  }
+
+  /**
+   * Subscribe to individual questions leaving the pending state. Returns an
+   * unsubscribe function.
+   */
+  export function onResolved(listener: (resolved: ResolvedQuestion) => void): () => void {
+    changes.on('resolved', listener);
+    return () => changes.off('resolved', listener);
+  }
+
   /**
    * Ask questions and wait for answers (blocks until answered)
    */
@@ -112,9 +146,11 @@ export namespace Question {
     );
 
     // Return a promise that will be resolved when the TUI provides answers
-    return new Promise<string[][]>((resolve, reject) => {
+    const answered = new Promise<string[][]>((resolve, reject) => {
       pendingResolvers.set(pending.id, { resolve, reject });
     });
+    changes.emit('change', sessionId);
+    return answered;
   }
 
   /**
@@ -129,6 +165,7 @@ export namespace Question {
       questions: p.questions,
       answers: p.answers,
       status: p.status,
+      createdAt: p.createdAt,
     }));
   }
 
@@ -146,6 +183,7 @@ export namespace Question {
       questions: p.questions,
       answers: p.answers,
       status: p.status,
+      createdAt: p.createdAt,
     };
   }
 
@@ -155,6 +193,8 @@ export namespace Question {
   export function answer(questionId: string, answers: string[][]): void {
     const result = answerQuestion(questionId, answers);
     if (!result) return;
+    changes.emit('change', result.sessionId);
+    changes.emit('resolved', { sessionId: result.sessionId, id: questionId, status: 'answered' });
 
     // Resolve the pending promise
     const resolver = pendingResolvers.get(questionId);
@@ -170,6 +210,8 @@ export namespace Question {
   export function skip(questionId: string): void {
     const result = skipQuestion(questionId);
     if (!result) return;
+    changes.emit('change', result.sessionId);
+    changes.emit('resolved', { sessionId: result.sessionId, id: questionId, status: 'skipped' });
 
     // Reject the pending promise
     const resolver = pendingResolvers.get(questionId);
@@ -179,6 +221,25 @@ export namespace Question {
     }
   }
 
+  /**
+   * Cancel every pending question in a session, e.g. when its turn is
+   * aborted. Returns how many were cancelled.
+   */
+  export function cancelAll(sessionId: string): number {
+    const pending = getPendingQuestions(sessionId);
+    for (const p of pending) {
+      skipQuestion(p.id);
+      changes.emit('resolved', { sessionId, id: p.id, status: 'cancelled' });
+      const resolver = pendingResolvers.get(p.id);
+      if (resolver) {
+        resolver.reject(new QuestionRejectedError('Turn was aborted'));
+        pendingResolvers.delete(p.id);
+      }
+    }
+    if (pending.length > 0) changes.emit('change', sessionId);
+    return pending.length;
+  }
+
   /**
    * Reject a pending question with custom error
    */
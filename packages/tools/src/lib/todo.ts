@@ -6,8 +6,10 @@
 
 import * as fs from 'fs';
 import * as path from 'path';
+import { EventEmitter } from 'events';
 import {
   listTodos,
+  getTodo,
   createTodo,
   updateTodo,
   deleteTodo,
@@ -33,11 +35,26 @@ export interface TodoUpdateInput {
   priority?: 'low' | 'medium' | 'high';
 }
 
+// ============================================
+// Change Notifications
+// ============================================
+
+const changes = new EventEmitter();
+
 // ============================================
 // Todo Operations
 // ============================================
 
 export namespace Todo {
+  /**
+   * Subscribe to todo changes; the listener receives the session id.
+   * Returns an unsubscribe function.
+   */
+  export function onChange(listener: (sessionId: string) => void): () => void {
+    changes.on('change', listener);
+    return () => changes.off('change', listener);
+  }
+
   /**
    * Get all todos for a session
    */
@@ -59,6 +76,7 @@ export namespace Todo {
     options?: { status?: TodoInfo['status']; priority?: TodoInfo['priority'] }
   ): TodoInfo {
     const todo = createTodo(sessionId, content, options);
+    changes.emit('change', sessionId);
     return {
       id: todo.id,
       content: todo.content,
@@ -76,6 +94,7 @@ export namespace Todo {
   ): TodoInfo | undefined {
     const todo = updateTodo(id, updates);
     if (!todo) return undefined;
+    changes.emit('change', todo.sessionId);
     return {
       id: todo.id,
       content: todo.content,
@@ -88,7 +107,9 @@ export namespace Todo {
    * Delete a todo
    */
   export function remove(id: string): void {
+    const existing = getTodo(id);
     deleteTodo(id);
+    if (existing) changes.emit('change', existing.sessionId);
   }
 
   /**
@@ -96,6 +117,7 @@ export namespace Todo {
    */
   export function replaceAll(sessionId: string, todos: TodoUpdateInput[]): TodoInfo[] {
     const result = replaceTodos(sessionId, todos);
+    changes.emit('change', sessionId);
     return result.map((t: StorageTodo) => ({
       id: t.id,
       content: t.content,
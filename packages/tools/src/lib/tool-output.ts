@@ -0,0 +1,33 @@
+/**
+ * Tool Output
+ *
+ * Incremental output from long-running tools, so the UI can show progress
+ * before the result arrives.
+ */
+
+import { EventEmitter } from 'events';
+
+export interface ToolOutputChunk {
+  sessionId: string;
+  /** Tool that produced the output. */
+  tool: string;
+  /** The call's arguments, for matching the chunk to its tool call. */
+  args: Record<string, unknown>;
+  chunk: string;
+}
+
+const output = new EventEmitter();
+
+export namespace ToolOutput {
+  /**
+   * Subscribe to output chunks. Returns an unsubscribe function.
+   */
+  export function onOutput(listener: (chunk: ToolOutputChunk) => void): () => void {
+    output.on('output', listener);
+    return () => output.off('output', listener);
+  }
+
+  export function emit(chunk: ToolOutputChunk): void {
+    output.emit('output', chunk);
+  }
+}
@@ -19,6 +19,7 @@ export interface QuestionItem {
   options: QuestionOption[];
   allowMultiple?: boolean;
   allowCustom?: boolean;
+  defaultOption?: number;
 }
 
 export interface QuestionArgs extends Record<string, unknown> {
@@ -40,6 +41,7 @@ Each question has:
 - options: Array of {label, description?} choices
 - allowMultiple: If true, user can select multiple options
 - allowCustom: If true, user can provide a custom text answer
+- defaultOption: Optional index of the recommended option; it is preselected and used when the run is unattended
 
 Returns the user's selected options for each question.
 
@@ -89,6 +91,10 @@ IMPORTANT GUIDELINES:
               type: 'boolean',
               description: 'Allow custom text input',
             },
+            defaultOption: {
+              type: 'number',
+              description: 'Index of the recommended option',
+            },
           },
           required: ['question', 'options'],
         },
@@ -117,6 +123,7 @@ IMPORTANT GUIDELINES:
           options: q.options,
           allowMultiple: q.allowMultiple,
           allowCustom: q.allowCustom,
+          defaultOption: q.defaultOption,
         })),
       });
 
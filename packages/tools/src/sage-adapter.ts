@@ -50,6 +50,7 @@ export function defineTool<TArgs extends Record<string, unknown>>(config: {
         sessionId: sageContext.sessionId,
         projectDir: (sageContext.metadata?.projectDir as string) || process.cwd(),
         abort: sageContext.metadata?.abort as AbortSignal | undefined,
+        sandbox: sageContext.metadata?.sandbox as StratusToolContext['sandbox'],
       };
       return config.execute(args as TArgs, stratusContext);
     },
@@ -69,8 +69,8 @@ export const writeTool = defineTool<WriteArgs>({
     const stats = await fs.stat(file_path);
     const lineCount = content.split('\n').length;
 
-    // Generate diff (new file = empty old content)
-    const diff = generateUnifiedDiff('', content, file_path);
+    // Generate diff against a file that did not exist
+    const diff = generateUnifiedDiff(null, content, file_path);
 
     return JSON.stringify({
       success: true,
@@ -2,7 +2,7 @@
  * ChatSession Extended Tests
  *
  * Tests for ChatSession class lifecycle (constructor, getState, clear, abort,
- * setters) and the expandMentions utility.
+ * setters) and the expandMentions/expandPinned utilities.
  */
 
 import { describe, test, expect, beforeAll, afterAll } from 'bun:test';
@@ -10,7 +10,7 @@ import * as fs from 'fs';
 import * as path from 'path';
 import * as os from 'os';
 import { initDatabase, closeDatabase } from '@stratuscode/storage';
-import { ChatSession, expandMentions, toSageConfig } from './chat-session';
+import { ChatSession, expandMentions, expandPinned, toSageConfig } from './chat-session';
 
 const testDir = `/tmp/stratuscode-chat-session-ext-test-${Date.now()}`;
 const projectDir = path.join(testDir, 'project');
@@ -44,6 +44,26 @@ function createBaseConfig() {
 // expandMentions
 // ============================================
 
+describe('expandPinned', () => {
+  test('prepends pinned files and skips ones already mentioned', () => {
+    fs.writeFileSync(path.join(projectDir, 'pinned-a.ts'), 'const a = 1;', 'utf-8');
+    fs.writeFileSync(path.join(projectDir, 'pinned-b.ts'), 'const b = 2;', 'utf-8');
+
+    const result = expandPinned('Compare with @pinned-b.ts', ['pinned-a.ts', 'pinned-b.ts'], projectDir);
+    expect(result).toContain('<file path="pinned-a.ts">\nconst a = 1;');
+    expect(result).not.toContain('const b = 2;');
+    expect(result.endsWith('Compare with @pinned-b.ts')).toBe(true);
+
+    fs.unlinkSync(path.join(projectDir, 'pinned-a.ts'));
+    fs.unlinkSync(path.join(projectDir, 'pinned-b.ts'));
+  });
+
+  test('returns content unchanged with nothing pinned', () => {
+    expect(expandPinned('Hello', undefined, projectDir)).toBe('Hello');
+    expect(expandPinned('Hello', ['missing.ts'], projectDir)).toBe('Hello');
+  });
+});
+
 describe('expandMentions', () => {
   test('returns content unchanged when no mentions', () => {
     const result = expandMentions('Hello world', projectDir);
@@ -96,6 +116,21 @@ describe('expandMentions', () => {
     fs.unlinkSync(filePath);
   });
 
+  test('excerpts large files around a :line suffix', () => {
+    const filePath = path.join(projectDir, 'long-file.ts');
+    const lines = Array.from({ length: 2000 }, (_, i) => `const line${i + 1} = ${i + 1};`);
+    fs.writeFileSync(filePath, lines.join('\n'), 'utf-8');
+
+    const result = expandMentions('Why is @long-file.ts:1500 slow?', projectDir);
+    expect(result).toContain('<file path="long-file.ts" line="1500">');
+    expect(result).toContain('... (lines 1400-1600 of 2000)');
+    expect(result).toContain('const line1500 = 1500;');
+    expect(result).not.toContain('const line1 = 1;');
+    expect(result.endsWith('Why is @long-file.ts:1500 slow?')).toBe(true);
+
+    fs.unlinkSync(filePath);
+  });
+
   test('handles mention with directory path', () => {
     const dir = path.join(projectDir, 'src');
     fs.mkdirSync(dir, { recursive: true });
@@ -75,7 +75,7 @@ mock.module('@stratuscode/tools', () => ({
 
 // Now import after mocks are set up
 import { initDatabase, closeDatabase } from '@stratuscode/storage';
-import { ChatSession, expandMentions, toSageConfig } from './chat-session';
+import { ChatSession, expandMentions, sessionTitleFrom, toSageConfig } from './chat-session';
 
 // ============================================
 // Test setup
@@ -671,6 +671,26 @@ describe('sendMessage streaming callbacks', () => {
     expect(statuses.some(s => typeof s === 'string' && s.includes('Truncated'))).toBe(true);
   });
 
+  test('onContextManaged adds a compaction marker holding the new summary', async () => {
+    mockProcessDirectly.mockImplementation(async (opts: any) => {
+      opts.callbacks.onContextManaged({
+        wasTruncated: false,
+        wasSummarized: true,
+        messagesRemoved: 34,
+        tokensBefore: 120000,
+        tokensAfter: 40000,
+      });
+      return { ...defaultResult(), newSummary: 'Refactored the parser.' };
+    });
+
+    const session = createTestSession();
+    await session.sendMessage('Compact me');
+
+    const marker = session.getState().timelineEvents.find(e => e.kind === 'compaction');
+    expect(marker?.compaction).toEqual({ messagesRemoved: 34, summarized: true });
+    expect(marker?.content).toBe('Refactored the parser.');
+  });
+
   test('onError callback creates status timeline event', async () => {
     mockProcessDirectly.mockImplementation(async (opts: any) => {
       opts.callbacks.onError(new Error('Stream error'));
@@ -1229,6 +1249,129 @@ describe('executeTool', () => {
   });
 });
 
+// ============================================
+// retryToolCall
+// ============================================
+
+describe('retryToolCall', () => {
+  beforeEach(() => {
+    mockProcessDirectly.mockReset();
+  });
+
+  test('runs a failed call again and tells the model the result', async () => {
+    mockProcessDirectly.mockImplementation(async (opts: any) => {
+      const tc = { id: 'tc-retry', type: 'function', function: { name: 'test_tool', arguments: '{"input":"again"}' } };
+      opts.callbacks.onToolCall(tc);
+      opts.callbacks.onToolResult(tc, JSON.stringify({ error: true, message: 'Timed out' }));
+      return defaultResult({ content: 'It failed.' });
+    });
+
+    const session = createTestSession();
+    await session.sendMessage('Run the tool');
+    const result = await session.retryToolCall('tc-retry');
+
+    expect(result.status).toBe('completed');
+    const calls = session.getState().timelineEvents.filter(e => e.kind === 'tool_call');
+    expect(calls.length).toBe(2);
+    expect((calls[1] as any).status).toBe('completed');
+    expect(calls[1]!.content).toBe('{"input":"again"}');
+    const last = session.getState().messages.at(-1)!;
+    expect(String(last.content)).toContain('retried a failed tool call: test_tool');
+  });
+
+  test('throws for an unknown tool call', async () => {
+    const session = createTestSession();
+    await session.executeTool('test_tool', { input: 'x' });
+    await expect(session.retryToolCall('missing')).rejects.toThrow('Tool call not found');
+  });
+});
+
+// ============================================
+// timelineWindow / getEvents
+// ============================================
+
+describe('cancelTool', () => {
+  beforeEach(() => {
+    mockProcessDirectly.mockReset();
+  });
+
+  test('ends the running call with a cancelled result and lets the turn go on', async () => {
+    const session = createTestSession();
+    (session as any).getRegistry().register({
+      name: 'hang',
+      description: 'Never finishes',
+      parameters: { type: 'object', properties: {} },
+      execute: () => new Promise(() => {}),
+    });
+    let result: any;
+    mockProcessDirectly.mockImplementation(async (opts: any) => {
+      const pending = opts.tools.execute('hang', {}, { metadata: {} });
+      setTimeout(() => session.cancelTool(), 0);
+      result = await pending;
+      return defaultResult();
+    });
+
+    await session.sendMessage('Run the hanging tool');
+
+    expect(JSON.parse(result).cancelled).toBe(true);
+    expect(session.getState().isLoading).toBe(false);
+    expect(session.cancelTool()).toBe(0);
+  });
+});
+
+describe('session titles', () => {
+  beforeEach(() => {
+    mockProcessDirectly.mockReset();
+    mockProcessDirectly.mockImplementation(async () => defaultResult());
+  });
+
+  test('sessionTitleFrom takes the first line and cuts at a word', () => {
+    expect(sessionTitleFrom('\n  Fix   the parser\nmore detail')).toBe('Fix the parser');
+    expect(sessionTitleFrom('   ')).toBeUndefined();
+    const long = sessionTitleFrom('word '.repeat(30))!;
+    expect(long.endsWith('word…')).toBe(true);
+    expect(long.length).toBeLessThanOrEqual(61);
+  });
+
+  test('the first message titles a new session and later ones do not', async () => {
+    const session = createTestSession();
+    await session.sendMessage('Add a square function');
+    await session.sendMessage('Now add tests');
+    expect(session.getState().title).toBe('Add a square function');
+
+    session.setTitle('Math helpers');
+    expect(session.getState().title).toBe('Math helpers');
+  });
+});
+
+describe('timelineWindow', () => {
+  beforeEach(() => {
+    mockProcessDirectly.mockReset();
+    mockProcessDirectly.mockImplementation(async () => defaultResult());
+  });
+
+  test('state carries only the newest events and getEvents pages back', async () => {
+    const session = new ChatSession({
+      projectDir,
+      config: createBaseConfig() as any,
+      agent: 'build',
+      timelineWindow: 2,
+    });
+    session.on('error', () => {});
+    await session.sendMessage('First');
+    await session.sendMessage('Second');
+
+    const state = session.getState();
+    expect(state.timelineEvents.length).toBe(2);
+    expect(state.earlierEvents).toBeGreaterThan(0);
+
+    const page = session.getEvents(state.timelineEvents[0]!.id, 1);
+    expect(page.events.length).toBe(1);
+    expect(page.earlier).toBe(state.earlierEvents! - 1);
+    expect(session.getEvents(state.timelineEvents[0]!.id).events.length).toBe(state.earlierEvents!);
+  });
+});
+
 // ============================================
 // Private helper coverage via sendMessage
 // ============================================
@@ -9,9 +9,9 @@
 import { EventEmitter } from 'events';
 import * as fs from 'fs';
 import * as path from 'path';
-import type { StratusCodeConfig, AgentInfo, Message, ToolCall, TimelineEvent, TokenUsage, ContentPart, TimelineAttachment } from '@stratuscode/shared';
-import { buildSystemPrompt, BUILT_IN_AGENTS, modelSupportsReasoning } from '@stratuscode/shared';
-import { registerBuiltInTools, createStratusCodeToolRegistry } from '@stratuscode/tools';
+import type { SandboxConfig, StratusCodeConfig, AgentInfo, Message, ToolCall, TimelineEvent, TokenUsage, ContentPart, TimelineAttachment } from '@stratuscode/shared';
+import { buildSystemPrompt, BUILT_IN_AGENTS, modelSupportsReasoning, withRetry } from '@stratuscode/shared';
+import { registerBuiltInTools, createStratusCodeToolRegistry, ToolOutput } from '@stratuscode/tools';
 import {
   getSession as getStoredSession,
   getMessages as getStoredMessages,
@@ -20,14 +20,44 @@ import {
   createMessage,
   updateMessage,
   createTimelineEvent,
+  updateTimelineEventContent,
   listTimelineEvents,
   createToolCall,
   updateToolCallResult,
+  getToolCallsForSession,
   getSessionTokenTotals,
+  markTurnInFlight,
+  clearTurnInFlight,
 } from '@stratuscode/storage';
 import { processDirectly, type ToolRegistry } from '@willebrew/sage-core';
+import { runHook, hookFailure, HOOK_BLOCK_EXIT_CODE, type HooksConfig } from './hooks';
 import { SQLiteErrorStore } from '@stratuscode/storage';
 
+const PROVIDER_MAX_ATTEMPTS = 5;
+
+/** Why a provider error is worth retrying, or null when it isn't. */
+function retryReason(err: Error): 'rate_limit' | 'server_error' | null {
+  const status = (err as { status?: number }).status ?? (err as { statusCode?: number }).statusCode;
+  if (status === 429 || /\b429\b|rate.?limit|too many requests/i.test(err.message)) {
+    return 'rate_limit';
+  }
+  if ((status !== undefined && status >= 500 && status < 600)
+    || /\b5\d\d\b|overloaded|internal server error|service unavailable|bad gateway/i.test(err.message)) {
+    return 'server_error';
+  }
+  return null;
+}
+
+export interface ContextUsage {
+  used: number;
+  limit: number;
+  percent: number;
+  /** Prompt tokens served from the provider cache on the last call. */
+  cached?: number;
+  /** Estimated split of `used`; system prompt, compacted summary and the rest. */
+  breakdown?: { system: number; memory: number; conversation: number };
+}
+
 export interface ChatSessionOptions {
   projectDir: string;
   config: StratusCodeConfig;
@@ -35,6 +65,64 @@ export interface ChatSessionOptions {
   modelOverride?: string;
   providerOverride?: string;
   reasoningEffortOverride?: 'off' | 'minimal' | 'low' | 'medium' | 'high';
+  hooks?: HooksConfig;
+  readOnly?: boolean;
+  /** Shell command run after a turn that changed files, e.g. `cargo test -q`. */
+  verify?: string;
+  /** Container bash commands run in, from `--sandbox`. */
+  sandbox?: SandboxConfig;
+  /**
+   * Most recent timeline events sent with each state; older ones are fetched
+   * with `getEvents`. All of them when unset.
+   */
+  timelineWindow?: number;
+}
+
+/** Tools left out of the registry in read-only mode; `initialize` hands the list to the client. */
+export const MUTATING_TOOLS = ['write', 'edit', 'multi_edit', 'apply_patch', 'bash', 'revert'];
+
+/** Tools whose success means the turn changed files, which triggers `verify`. */
+const FILE_TOOLS = ['write', 'edit', 'multi_edit', 'apply_patch'];
+
+/** Verify commands run test suites, so they get much longer than a hook. */
+const VERIFY_TIMEOUT_SECS = 600;
+
+/** Tool results are cut to this in the timeline; the full text stays in tool_calls. */
+const TIMELINE_RESULT_CHARS = 2000;
+/** Diffs are kept whole for the UI up to this size. */
+const TIMELINE_DIFF_CHARS = 500_000;
+
+/** Longest title taken from a session's first message. */
+const SESSION_TITLE_CHARS = 60;
+
+/**
+ * A session title from its first message: the first non-empty line with
+ * whitespace collapsed, cut at a word boundary.
+ */
+export function sessionTitleFrom(content: string): string | undefined {
+  const line = content.split('\n').map(l => l.replace(/\s+/g, ' ').trim()).find(l => l.length > 0);
+  if (!line) return undefined;
+  if (line.length <= SESSION_TITLE_CHARS) return line;
+  const cut = line.slice(0, SESSION_TITLE_CHARS);
+  const space = cut.lastIndexOf(' ');
+  return `${(space > SESSION_TITLE_CHARS / 2 ? cut.slice(0, space) : cut).trimEnd()}…`;
+}
+
+/**
+ * The part of a tool result stored on its timeline event. Long results are
+ * cut, except that a diff is kept intact so the UI can still render it.
+ */
+function timelineResultContent(result: string): string {
+  if (result.length <= TIMELINE_RESULT_CHARS) return result;
+  try {
+    const parsed = JSON.parse(result);
+    if (typeof parsed?.diff === 'string' && parsed.diff.length <= TIMELINE_DIFF_CHARS) {
+      return JSON.stringify({ success: parsed.success, diff: parsed.diff });
+    }
+  } catch {
+    // not JSON
+  }
+  return result.slice(0, TIMELINE_RESULT_CHARS);
 }
 
 export interface ChatSessionState {
@@ -43,7 +131,7 @@ export interface ChatSessionState {
   error: string | null;
   timelineEvents: TimelineEvent[];
   sessionTokens: TokenUsage | undefined;
-  contextUsage: { used: number; limit: number; percent: number };
+  contextUsage: ContextUsage;
   contextStatus: string | null;
   tokens: TokenUsage;
   sessionId: string | undefined;
@@ -52,10 +140,29 @@ export interface ChatSessionState {
   modelOverride?: string;
   providerOverride?: string;
   reasoningEffortOverride?: 'off' | 'minimal' | 'low' | 'medium' | 'high';
+  readOnly: boolean;
+  /** Events before `timelineEvents`, left out by `timelineWindow`. */
+  earlierEvents?: number;
+  /** The session's title, set from its first message unless renamed. */
+  title?: string;
+}
+
+/** A user-defined agent sent by the frontend with `set_agent`. */
+export interface CustomAgentDefinition {
+  name: string;
+  description?: string;
+  prompt?: string;
+  /** Tool names the agent may call; all tools when omitted. */
+  tools?: string[];
+  model?: string;
 }
 
 export interface SendMessageOptions {
   buildSwitch?: boolean;
+  /** Echoed on the user event so the client can replace its local copy. */
+  clientId?: string;
+  /** Files pinned by the client, read fresh and included with every message. */
+  pinned?: string[];
 }
 
 const CODEX_ISSUER = 'https://auth.openai.com';
@@ -157,6 +264,68 @@ const MODEL_CONTEXT_WINDOWS: Record<string, number> = {
   'moonshotai/kimi-k2': 128_000,
 };
 
+/** How long a model ping may take before it counts as failed. */
+const PING_TIMEOUT_MS = 20_000;
+
+/**
+ * Sends the smallest possible request to a model through its resolved
+ * provider: the latency on success, else the provider's own error message.
+ */
+export async function pingModel(
+  config: StratusCodeConfig,
+  model: string,
+  providerOverride?: string,
+): Promise<{ ok: true; latencyMs: number } | { ok: false; error: string }> {
+  await ensureCodexToken(config, providerOverride);
+  const { provider } = toSageConfig(config, model, providerOverride);
+  const base = provider.baseUrl.replace(/\/$/, '');
+  const codex = base.includes('chatgpt.com/backend-api/codex');
+  const [url, body] = provider.type === 'responses-api' || codex
+    ? [`${base}/responses`, {
+        model,
+        input: [{ role: 'user', content: 'ping' }],
+        store: false,
+        ...(codex ? { instructions: '', stream: true } : { max_output_tokens: 16 }),
+      }]
+    : [`${base}/chat/completions`, {
+        model,
+        messages: [{ role: 'user', content: 'ping' }],
+        max_tokens: 1,
+      }];
+  const started = Date.now();
+  try {
+    const resp = await fetch(url, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+        ...(provider.apiKey ? { Authorization: `Bearer ${provider.apiKey}` } : {}),
+        ...provider.headers,
+      },
+      body: JSON.stringify(body),
+      signal: AbortSignal.timeout(PING_TIMEOUT_MS),
+    });
+    if (!resp.ok) {
+      const text = await resp.text();
+      let message = text.trim();
+      try {
+        const parsed = JSON.parse(text);
+        message = parsed?.error?.message ?? parsed?.message ?? parsed?.detail ?? message;
+      } catch {
+        // not JSON; keep the raw body
+      }
+      return { ok: false, error: `${resp.status} ${resp.statusText}: ${message}`.trim() };
+    }
+    const latencyMs = Date.now() - started;
+    await resp.body?.cancel();
+    return { ok: true, latencyMs };
+  } catch (err) {
+    if (err instanceof Error && err.name === 'TimeoutError') {
+      return { ok: false, error: `No response within ${PING_TIMEOUT_MS / 1000}s` };
+    }
+    return { ok: false, error: err instanceof Error ? err.message : String(err) };
+  }
+}
+
 export function toSageConfig(
   config: StratusCodeConfig,
   modelOverride?: string,
@@ -337,56 +506,120 @@ function BUILD_SWITCH_REMINDER(planFilePath: string): string {
   return `<system-reminder>\nYour operational mode has changed from plan to build.\nYou are no longer in read-only mode.\nYou are permitted to make file changes, run shell commands, and utilize your full arsenal of tools.\n\nA plan file exists at: ${planFilePath}\nYou should execute on the plan defined within it and in the todo list.\nRead the plan file first, then work through each task, updating status as you go.\n</system-reminder>`;
 }
 
+/**
+ * Attachment sent alongside a message. Images and files (e.g. PDFs) are passed
+ * to the model as content parts; text attachments are inlined like mentions.
+ */
+export interface MessageAttachment {
+  type: 'image' | 'file' | 'text';
+  name?: string;
+  mime?: string;
+  data?: string;
+  text?: string;
+}
+
+export function expandTextAttachments(content: string, attachments?: MessageAttachment[]): string {
+  const context = (attachments ?? [])
+    .filter(a => a.type === 'text')
+    .map(a => `<file path="${a.name ?? 'attachment'}">\n${a.text ?? ''}\n</file>\n\n`)
+    .join('');
+  return context + content;
+}
+
+/**
+ * Prepends each @mentioned file. A `:line` suffix, as symbol mentions carry,
+ * is kept so a large file is excerpted around that line.
+ */
 export function expandMentions(content: string, projectDir: string): string {
-  const mentionRegex = /@([\w./-]+\.\w+)/g;
-  const mentions: string[] = [];
+  const mentionRegex = /@([\w./-]+\.\w+)(?::(\d+))?/g;
+  const mentions: { file: string; line?: number }[] = [];
   let match: RegExpExecArray | null;
 
   while ((match = mentionRegex.exec(content)) !== null) {
-    mentions.push(match[1]!);
+    mentions.push({ file: match[1]!, line: match[2] ? Number(match[2]) : undefined });
   }
 
   if (mentions.length === 0) return content;
 
-  let context = '';
-  for (const mention of mentions) {
-    const fullPath = path.isAbsolute(mention)
-      ? mention
-      : path.join(projectDir, mention);
-
-    try {
-      if (fs.existsSync(fullPath) && fs.statSync(fullPath).isFile()) {
-        const fileContent = fs.readFileSync(fullPath, 'utf-8');
-        const truncated = fileContent.length > 10000
-          ? fileContent.slice(0, 10000) + '\n... (truncated)'
-          : fileContent;
-        context += `<file path="${mention}">\n${truncated}\n</file>\n\n`;
-      }
-    } catch {
-      // ignore
-    }
-  }
-
+  const context = mentions.map(({ file, line }) => fileContext(file, projectDir, line)).join('');
   if (context) {
     return context + content;
   }
   return content;
 }
 
+/**
+ * Prepends pinned files, skipping any the message already @mentions so they
+ * aren't included twice.
+ */
+export function expandPinned(content: string, pinned: string[] | undefined, projectDir: string): string {
+  const context = (pinned ?? [])
+    .filter(file => !content.includes(`@${file}`))
+    .map(file => fileContext(file, projectDir))
+    .join('');
+  return context + content;
+}
+
+const MENTION_MAX_CHARS = 10000;
+/** Lines kept on each side of a mentioned line when excerpting. */
+const MENTION_LINE_CONTEXT = 100;
+
+/**
+ * A file wrapped in `<file>` tags, or '' when it can't be read. Files over
+ * the limit are cut to their head, or to the lines around `line` if given.
+ */
+function fileContext(file: string, projectDir: string, line?: number): string {
+  const fullPath = path.isAbsolute(file) ? file : path.join(projectDir, file);
+  try {
+    if (fs.existsSync(fullPath) && fs.statSync(fullPath).isFile()) {
+      const fileContent = fs.readFileSync(fullPath, 'utf-8');
+      const attrs = line !== undefined ? `path="${file}" line="${line}"` : `path="${file}"`;
+      return `<file ${attrs}>\n${excerpt(fileContent, line)}\n</file>\n\n`;
+    }
+  } catch {
+    // ignore
+  }
+  return '';
+}
+
+function excerpt(text: string, line?: number): string {
+  if (text.length <= MENTION_MAX_CHARS) return text;
+  if (line === undefined) return text.slice(0, MENTION_MAX_CHARS) + '\n... (truncated)';
+  const lines = text.split('\n');
+  const start = Math.max(0, Math.min(line, lines.length) - 1 - MENTION_LINE_CONTEXT);
+  const end = Math.min(lines.length, line + MENTION_LINE_CONTEXT);
+  const body = lines.slice(start, end).join('\n').slice(0, MENTION_MAX_CHARS);
+  return `... (lines ${start + 1}-${end} of ${lines.length})\n${body}`;
+}
+
 export class ChatSession extends EventEmitter {
   private options: ChatSessionOptions;
   private state: ChatSessionState;
 
   private registryRef: ToolRegistry | null = null;
+  private customAgents = new Map<string, CustomAgentDefinition>();
   private abortRef: AbortController | null = null;
   private sessionIdRef: string | undefined;
   private messagesRef: Message[] = [];
   private timelineEventsRef: TimelineEvent[] = [];
   private reasoningEventIdRef: string | null = null;
   private textEventIdRef: string | null = null;
+  private runningToolCallsRef = new Map<string, { name: string; arguments: string }>();
+  /** `task` calls whose sub-agent is running, innermost last. */
+  private subagentParentsRef: string[] = [];
+  /** Each running sub-agent's streamed text event, by its `task` call id. */
+  private subagentTextRef = new Map<string, { eventId: string; content: string }>();
+  private turnChangedFilesRef = false;
+  /** Cancels for the tool calls running now, used by `cancelTool`. */
+  private toolCancelsRef = new Set<() => void>();
+  /** Stops forwarding tool output; called by `dispose`. */
+  private unsubscribeOutputRef: () => void;
+  /** The session was created here and still has its placeholder title. */
+  private untitledRef = false;
   private previousAgentRef: string;
   private existingSummaryRef: any = undefined;
   private lastPromptTokensRef = 0;
+  private systemPromptTokensRef = 0;
 
   private streamingContentRef = '';
   private streamingReasoningRef = '';
@@ -409,20 +642,57 @@ export class ChatSession extends EventEmitter {
       contextStatus: null,
       tokens: { input: 0, output: 0 },
       sessionId: undefined,
+      title: undefined,
       planExitProposed: false,
       agent: options.agent,
       modelOverride: options.modelOverride,
       providerOverride: options.providerOverride,
       reasoningEffortOverride: options.reasoningEffortOverride,
+      readOnly: options.readOnly ?? false,
     };
     this.previousAgentRef = options.agent;
+    this.unsubscribeOutputRef = ToolOutput.onOutput(({ sessionId, tool, args, chunk }) => {
+      if (sessionId !== this.sessionIdRef) return;
+      const toolCallId = this.matchRunningToolCall(tool, args);
+      if (toolCallId) this.emit('tool_output', { toolCallId, chunk });
+    });
+  }
+
+  /**
+   * Detaches the session from process-wide emitters. Call it before dropping
+   * the session, e.g. when `initialize` replaces it.
+   */
+  dispose(): void {
+    this.unsubscribeOutputRef();
+  }
+
+  /**
+   * Tools don't know their call id, so output is matched to the running call
+   * of that tool with the same arguments, or the only one running.
+   */
+  private matchRunningToolCall(tool: string, args: Record<string, unknown>): string | undefined {
+    const candidates = [...this.runningToolCallsRef.entries()].filter(([, call]) => call.name === tool);
+    const exact = candidates.find(([, call]) => {
+      try {
+        const parsed = JSON.parse(call.arguments);
+        return Object.entries(args).every(([key, value]) => parsed[key] === value);
+      } catch {
+        return false;
+      }
+    });
+    if (exact) return exact[0];
+    return candidates.length === 1 ? candidates[0]![0] : undefined;
   }
 
   getState(): ChatSessionState {
+    const windowSize = this.options.timelineWindow;
+    const events = this.state.timelineEvents;
+    const earlierEvents = windowSize !== undefined ? Math.max(0, events.length - windowSize) : 0;
     return {
       ...this.state,
       messages: [...this.state.messages],
-      timelineEvents: [...this.state.timelineEvents],
+      timelineEvents: events.slice(earlierEvents),
+      earlierEvents,
       tokens: { ...this.state.tokens },
       sessionTokens: this.state.sessionTokens ? { ...this.state.sessionTokens } : undefined,
       contextUsage: { ...this.state.contextUsage },
@@ -485,14 +755,80 @@ export class ChatSession extends EventEmitter {
     if (!this.sessionIdRef) {
       const session = persistSession(this.options.projectDir);
       this.sessionIdRef = session.id;
-      this.setState({ sessionId: session.id });
+      this.untitledRef = true;
+      this.setState({ sessionId: session.id, title: session.title });
       this.emit('session_changed', session.id);
     }
     return this.sessionIdRef;
   }
 
   private getAgent(): AgentInfo {
-    return BUILT_IN_AGENTS[this.options.agent] || BUILT_IN_AGENTS.build!;
+    return this.resolveAgent(this.options.agent);
+  }
+
+  private resolveAgent(name: string): AgentInfo {
+    const custom = this.customAgents.get(name);
+    if (custom) {
+      return {
+        name: custom.name,
+        description: custom.description,
+        mode: 'primary',
+        prompt: custom.prompt ?? BUILT_IN_AGENTS.build!.prompt,
+        model: custom.model,
+        permissions: BUILT_IN_AGENTS.build!.permissions,
+      };
+    }
+    return BUILT_IN_AGENTS[name] || BUILT_IN_AGENTS.build!;
+  }
+
+  /** The full registry, or only the tools a custom agent allows. */
+  private getRegistryFor(agentName: string): ToolRegistry {
+    const registry = this.getRegistry();
+    const allowed = this.customAgents.get(agentName)?.tools;
+    if (!allowed && !this.state.readOnly) return registry;
+    const names = allowed ?? registry.list().map(tool => tool.name);
+    const restricted = createStratusCodeToolRegistry();
+    for (const name of names) {
+      if (this.state.readOnly && MUTATING_TOOLS.includes(name)) continue;
+      const tool = registry.get(name);
+      if (tool) restricted.register(tool);
+    }
+    return restricted;
+  }
+
+  /**
+   * The registry with each tool wrapped so `cancelTool` can end a call early:
+   * the call's abort signal fires and the model gets a cancelled result.
+   */
+  private cancellable(registry: ToolRegistry): ToolRegistry {
+    const wrapped = createStratusCodeToolRegistry();
+    for (const tool of registry.list()) {
+      wrapped.register({
+        ...tool,
+        execute: (args: Record<string, unknown>, context: any) => new Promise((resolve, reject) => {
+          const controller = new AbortController();
+          const turnAbort = context?.metadata?.abort as AbortSignal | undefined;
+          const cancel = () => {
+            controller.abort();
+            resolve(JSON.stringify({ error: true, cancelled: true, message: 'Tool cancelled by user' }));
+          };
+          this.toolCancelsRef.add(cancel);
+          const signal = turnAbort ? AbortSignal.any([turnAbort, controller.signal]) : controller.signal;
+          Promise.resolve(tool.execute(args, { ...context, metadata: { ...context?.metadata, abort: signal } }))
+            .then(resolve, reject)
+            .finally(() => this.toolCancelsRef.delete(cancel));
+        }),
+      });
+    }
+    return wrapped;
+  }
+
+  /** Ends the running tool calls; the turn carries on. Returns how many. */
+  cancelTool(): number {
+    const cancels = [...this.toolCancelsRef];
+    this.toolCancelsRef.clear();
+    for (const cancel of cancels) cancel();
+    return cancels.length;
   }
 
   private getContextWindow(): number {
@@ -500,15 +836,24 @@ export class ChatSession extends EventEmitter {
     return MODEL_CONTEXT_WINDOWS[model] ?? 128_000;
   }
 
-  private computeContextUsage(promptTokens: number): void {
+  private computeContextUsage(promptTokens: number, cached?: number): void {
     this.lastPromptTokensRef = promptTokens;
     const limit = this.getContextWindow();
     const percent = Math.min(99, Math.round((promptTokens / limit) * 100));
-    this.setState({ contextUsage: { used: promptTokens, limit, percent } });
+    // Character counts over four approximate tokens well enough for a gauge.
+    const system = Math.min(this.systemPromptTokensRef, promptTokens);
+    const summary = this.existingSummaryRef;
+    const summaryChars = typeof summary === 'string' ? summary.length : summary ? JSON.stringify(summary).length : 0;
+    const memory = Math.min(Math.ceil(summaryChars / 4), promptTokens - system);
+    const breakdown = system > 0 ? { system, memory, conversation: promptTokens - system - memory } : undefined;
+    this.setState({ contextUsage: { used: promptTokens, limit, percent, cached, breakdown } });
     this.emitTokens();
   }
 
-  setAgent(agent: string): void {
+  setAgent(agent: string, definition?: CustomAgentDefinition): void {
+    if (definition) {
+      this.customAgents.set(agent, { ...definition, name: agent });
+    }
     this.options.agent = agent;
     this.setState({ agent });
   }
@@ -523,26 +868,74 @@ export class ChatSession extends EventEmitter {
     this.setState({ providerOverride: provider });
   }
 
+  setReadOnly(readOnly: boolean): void {
+    this.setState({ readOnly });
+  }
+
+  /** Mirrors a `rename_session` of the current session. */
+  setTitle(title: string): void {
+    this.untitledRef = false;
+    this.setState({ title });
+  }
+
+  /** Hooks and `verify` from a project config trusted after startup. */
+  setProjectCommands(hooks?: HooksConfig, verify?: string): void {
+    this.options.hooks = hooks;
+    this.options.verify = verify;
+  }
+
   setReasoningEffortOverride(reasoning?: 'off' | 'minimal' | 'low' | 'medium' | 'high'): void {
     this.options.reasoningEffortOverride = reasoning;
     this.setState({ reasoningEffortOverride: reasoning });
   }
 
-  async sendMessage(content: string, agentOverride?: string, options?: SendMessageOptions, attachments?: { type: 'image'; data: string; mime?: string }[]): Promise<void> {
+  async sendMessage(content: string, agentOverride?: string, options?: SendMessageOptions, attachments?: MessageAttachment[]): Promise<void> {
     if (this.state.isLoading) return;
 
     this.setState({ error: null, isLoading: true });
 
-    const expandedContent = expandMentions(content, this.options.projectDir);
+    // pre_send may rewrite the message (stdout) or block it (exit 2).
+    let preSendFailure: string | null = null;
+    const preSend = this.options.hooks?.preSend;
+    if (preSend) {
+      const result = await runHook(preSend, {
+        event: 'pre_send',
+        sessionId: this.sessionIdRef ?? null,
+        agent: agentOverride ?? this.state.agent,
+        content,
+        projectDir: this.options.projectDir,
+      }, this.options.projectDir, this.options.hooks?.timeoutSecs);
+      if (result.code === HOOK_BLOCK_EXIT_CODE && !result.timedOut) {
+        const reason = result.stderr.trim() || result.stdout.trim() || 'no reason given';
+        const message = `Blocked by pre_send hook: ${reason}`;
+        this.pushEvent(createTimelineEvent(this.getSessionId(), 'error', message));
+        this.setState({ error: message, isLoading: false });
+        this.emitError(message);
+        return;
+      }
+      preSendFailure = hookFailure('pre_send', result);
+      if (!preSendFailure && result.stdout.trim()) {
+        content = result.stdout.trim();
+      }
+    }
+
+    const expandedContent = expandTextAttachments(
+      expandPinned(
+        expandMentions(content, this.options.projectDir),
+        options?.pinned,
+        this.options.projectDir,
+      ),
+      attachments,
+    );
+    const binaryAttachments = (attachments ?? []).filter(a => a.type !== 'text');
 
     let messageContent: string | ContentPart[];
-    if (attachments && attachments.length > 0) {
+    if (binaryAttachments.length > 0) {
       const parts: ContentPart[] = [
         { type: 'text', text: expandedContent },
-        ...attachments.map(a => ({
-          type: 'image' as const,
-          imageUrl: `data:${a.mime};base64,${a.data}`,
-        })),
+        ...binaryAttachments.map(a => a.type === 'file'
+          ? { type: 'file' as const, fileUrl: `data:${a.mime};base64,${a.data}` }
+          : { type: 'image' as const, imageUrl: `data:${a.mime};base64,${a.data}` }),
       ];
       messageContent = parts;
     } else {
@@ -556,10 +949,13 @@ export class ChatSession extends EventEmitter {
 
     const timelineAttachments: TimelineAttachment[] | undefined =
       attachments && attachments.length > 0
-        ? attachments.map(a => ({ type: 'image' as const, mime: a.mime, data: a.data }))
+        ? attachments.map(a => a.type === 'text'
+          ? { type: 'text' as const, name: a.name, lineCount: (a.text ?? '').split('\n').length, text: a.text }
+          : { type: a.type, name: a.name, mime: a.mime, data: a.data })
         : undefined;
 
     const sid = this.getSessionId();
+    this.turnChangedFilesRef = false;
     const userMessageId = createMessage(sid, 'user', content);
     const assistantMessageId = createMessage(sid, 'assistant', '');
     const userEvent = createTimelineEvent(
@@ -569,10 +965,25 @@ export class ChatSession extends EventEmitter {
       { ...(timelineAttachments ? { attachments: timelineAttachments } : {}) },
       userMessageId
     );
+    if (options?.clientId) {
+      userEvent.clientId = options.clientId;
+    }
     this.timelineEventsRef = [...this.timelineEventsRef, userEvent];
     this.setState({ timelineEvents: [...this.timelineEventsRef] });
     this.emitTimelineEvent(userEvent);
+    if (this.untitledRef) {
+      this.untitledRef = false;
+      const title = sessionTitleFrom(content);
+      if (title) {
+        persistSessionUpdate(sid, { title });
+        this.setState({ title });
+      }
+    }
+    if (preSendFailure) {
+      this.pushEvent(createTimelineEvent(sid, 'error', preSendFailure, {}, userMessageId));
+    }
     persistSessionUpdate(sid, { status: 'running' });
+    markTurnInFlight(sid);
     this.reasoningEventIdRef = null;
 
     this.abortRef = new AbortController();
@@ -596,6 +1007,10 @@ export class ChatSession extends EventEmitter {
       this.reasoningEventIdRef = null;
     };
 
+    // The marker for history compacted during this turn; its summary is filled
+    // in once the run returns it.
+    let compactionEventId: string | null = null;
+
     const flushTextEvent = (final = true) => {
       const pending = this.streamingContentRef;
       if (!pending) return;
@@ -618,13 +1033,13 @@ export class ChatSession extends EventEmitter {
     };
 
     try {
-      const registry = this.getRegistry();
       const effectiveAgentName = agentOverride || this.options.agent;
-      const currentAgent = agentOverride
-        ? (BUILT_IN_AGENTS[agentOverride] || BUILT_IN_AGENTS.build!)
-        : this.getAgent();
+      const currentAgent = this.resolveAgent(effectiveAgentName);
+      const registry = this.cancellable(this.getRegistryFor(effectiveAgentName));
 
-      const effectiveModelId = this.options.modelOverride || this.options.config.model;
+      // A model picked in the UI wins over the one an agent pins.
+      const agentModelOverride = this.options.modelOverride || currentAgent.model;
+      const effectiveModelId = agentModelOverride || this.options.config.model;
 
       let systemPrompt = buildSystemPrompt({
         agent: currentAgent,
@@ -690,19 +1105,67 @@ export class ChatSession extends EventEmitter {
 
       await ensureCodexToken(this.options.config, this.options.providerOverride);
 
-      const result: any = await processDirectly({
+      this.systemPromptTokensRef = Math.ceil(systemPrompt.length / 4);
+      // Events produced while a `task` sub-agent runs nest under its call.
+      // The agent loop reports `<agent>::<toolCallId>` and then repeats the
+      // bare agent name, which carries no call id and is ignored.
+      const subagentParent = (ownId?: string) => {
+        const parent = this.subagentParentsRef[this.subagentParentsRef.length - 1];
+        return parent !== ownId ? parent : undefined;
+      };
+      const subagentCallbacks = {
+        onSubagentStart: (agentId: string) => {
+          const parent = agentId.split('::')[1];
+          if (parent) this.subagentParentsRef.push(parent);
+        },
+        onSubagentToken: (_agentId: string, token: string) => {
+          const parent = subagentParent();
+          if (!parent) return;
+          const text = this.subagentTextRef.get(parent);
+          if (!text) {
+            const ev = createTimelineEvent(sid, 'assistant', token, { streaming: true, parentToolCallId: parent }, assistantMessageId);
+            this.subagentTextRef.set(parent, { eventId: ev.id, content: token });
+            this.pushEvent(ev);
+            return;
+          }
+          text.content += token;
+          const idx = this.timelineEventsRef.findIndex(e => e.id === text.eventId);
+          if (idx !== -1) {
+            this.timelineEventsRef[idx] = { ...this.timelineEventsRef[idx]!, content: text.content };
+            this.setState({ timelineEvents: [...this.timelineEventsRef] });
+          }
+        },
+        onSubagentEnd: (agentId: string) => {
+          const parent = agentId.split('::')[1];
+          if (!parent) return;
+          const text = this.subagentTextRef.get(parent);
+          if (text) {
+            try { updateTimelineEventContent(text.eventId, text.content); } catch { /* ignore */ }
+            this.timelineEventsRef = this.timelineEventsRef.map(e =>
+              e.id === text.eventId ? { ...e, content: text.content, streaming: false } as TimelineEvent : e);
+            this.setState({ timelineEvents: [...this.timelineEventsRef] });
+            this.subagentTextRef.delete(parent);
+          }
+          this.subagentParentsRef = this.subagentParentsRef.filter(id => id !== parent);
+        },
+      };
+      const runAgent = () => processDirectly({
         systemPrompt,
         messages: messagesForLLM,
         tools: registry,
-        config: toSageConfig(this.options.config, this.options.modelOverride, this.options.providerOverride, sid, this.options.reasoningEffortOverride),
+        config: toSageConfig(this.options.config, agentModelOverride, this.options.providerOverride, sid, this.options.reasoningEffortOverride),
         abort: this.abortRef.signal,
         sessionId: sid,
         existingSummary: this.existingSummaryRef,
         toolMetadata: {
           projectDir: this.options.projectDir,
           abort: this.abortRef.signal,
+          sandbox: this.options.sandbox,
         },
         callbacks: {
+          // Spread rather than listed: sage-core's callback type doesn't
+          // declare the sub-agent hooks.
+          ...subagentCallbacks,
           onToken: (token: string) => {
             if (this.lastStreamingTypeRef === 'reasoning' && this.streamingReasoningRef) {
               flushReasoningEvent();
@@ -743,6 +1206,7 @@ export class ChatSession extends EventEmitter {
             }
             this.lastStreamingTypeRef = null;
 
+            this.runningToolCallsRef.set(tc.id, { name: tc.function.name, arguments: tc.function.arguments });
             try { createToolCall(assistantMessageId, sid, tc); } catch { /* ignore */ }
             const toolEvent = createTimelineEvent(
               sid,
@@ -752,27 +1216,41 @@ export class ChatSession extends EventEmitter {
                 toolCallId: tc.id,
                 toolName: tc.function.name,
                 status: 'running',
+                parentToolCallId: subagentParent(tc.id),
               },
               assistantMessageId
             );
             this.pushEvent(toolEvent);
           },
           onToolResult: (tc: ToolCall, result: string) => {
+            this.runningToolCallsRef.delete(tc.id);
+            void this.runObserverHook('post_tool', this.options.hooks?.postTool, sid, {
+              toolCallId: tc.id,
+              toolName: tc.function.name,
+              arguments: tc.function.arguments,
+              result,
+            }, assistantMessageId);
             try { updateToolCallResult(tc.id, result, 'completed'); } catch { /* ignore */ }
             const resultEvent = createTimelineEvent(
               sid,
               'tool_result',
-              result.slice(0, 2000),
+              timelineResultContent(result),
               {
                 toolCallId: tc.id,
                 toolName: tc.function.name,
                 status: 'completed',
+                // The full result goes into the context; estimated like the gauge.
+                tokens: { input: Math.ceil(result.length / 4), output: 0 },
+                parentToolCallId: subagentParent(tc.id),
               },
               assistantMessageId
             );
             this.pushEvent(resultEvent);
             try {
               const parsed = JSON.parse(result);
+              if (!parsed?.error && parsed?.success !== false && FILE_TOOLS.includes(tc.function.name)) {
+                this.turnChangedFilesRef = true;
+              }
               if (parsed?.error || parsed?.success === false) {
                 const idx = this.timelineEventsRef.findIndex(e => e.kind === 'tool_call' && (e as any).toolCallId === tc.id);
                 if (idx !== -1) {
@@ -837,6 +1315,20 @@ export class ChatSession extends EventEmitter {
             } else if (event.wasTruncated) {
               this.setState({ contextStatus: `Truncated (${event.messagesRemoved} msgs dropped)` });
             }
+            if (event.wasSummarized || event.wasTruncated) {
+              if (this.lastStreamingTypeRef === 'reasoning' && this.streamingReasoningRef) {
+                flushReasoningEvent();
+              }
+              if (this.lastStreamingTypeRef === 'text' && this.streamingContentRef) {
+                flushTextEvent();
+              }
+              this.lastStreamingTypeRef = null;
+              const marker = createTimelineEvent(sid, 'compaction', '', {
+                compaction: { messagesRemoved: event.messagesRemoved, summarized: event.wasSummarized },
+              }, assistantMessageId);
+              compactionEventId = marker.id;
+              this.pushEvent(marker);
+            }
             this.emitContextStatus();
             setTimeout(() => {
               this.setState({ contextStatus: null });
@@ -849,6 +1341,33 @@ export class ChatSession extends EventEmitter {
         },
       });
 
+      // Rate limits and provider 5xx are retried with backoff, but only while
+      // nothing has streamed yet so a retry never duplicates output.
+      const producedEvents = () => this.timelineEventsRef.filter(e => e.kind !== 'status').length;
+      const eventsBeforeCall = producedEvents();
+      const result: any = await withRetry(runAgent, {
+        maxAttempts: PROVIDER_MAX_ATTEMPTS,
+        baseDelay: 2000,
+        maxDelay: 30000,
+        signal: this.abortRef?.signal,
+        shouldRetry: (err) =>
+          retryReason(err) !== null &&
+          !this.abortRef?.signal.aborted &&
+          !this.streamingContentRef &&
+          !this.streamingReasoningRef &&
+          producedEvents() === eventsBeforeCall,
+        onRetry: (attempt, err, delay) => {
+          this.emit('retry_status', {
+            reason: retryReason(err),
+            message: err.message,
+            attempt: attempt + 1,
+            maxAttempts: PROVIDER_MAX_ATTEMPTS,
+            delayMs: delay,
+          });
+        },
+      });
+      this.emit('retry_status', null);
+
       const trailingText = this.streamingContentRef;
       const trailingReasoning = this.streamingReasoningRef;
       if (trailingReasoning) flushReasoningEvent();
@@ -867,12 +1386,20 @@ export class ChatSession extends EventEmitter {
 
       if (result.newSummary) {
         this.existingSummaryRef = result.newSummary;
+        if (compactionEventId) {
+          const summary = typeof result.newSummary === 'string' ? result.newSummary : JSON.stringify(result.newSummary, null, 2);
+          updateTimelineEventContent(compactionEventId, summary);
+          this.timelineEventsRef = this.timelineEventsRef.map(e =>
+            e.id === compactionEventId ? { ...e, content: summary } as TimelineEvent : e);
+          this.setState({ timelineEvents: [...this.timelineEventsRef] });
+        }
       }
 
       const tokenUsage: TokenUsage = {
         input: result.inputTokens,
         output: result.outputTokens,
         context: (result.inputTokens ?? 0) + (result.outputTokens ?? 0),
+        cached: result.cachedInputTokens,
         model: effectiveModelId,
       };
       updateMessage(assistantMessageId, result.content, tokenUsage);
@@ -891,8 +1418,9 @@ export class ChatSession extends EventEmitter {
       });
       const totals = getSessionTokenTotals(sid);
       this.setState({ sessionTokens: totals });
-      this.computeContextUsage(result.lastInputTokens ?? result.inputTokens);
+      this.computeContextUsage(result.lastInputTokens ?? result.inputTokens, result.cachedInputTokens);
     } catch (err) {
+      this.emit('retry_status', null);
       const errorMessage = err instanceof Error ? err.message : String(err);
       this.setState({ error: errorMessage });
       this.emitError(errorMessage);
@@ -908,7 +1436,7 @@ export class ChatSession extends EventEmitter {
       this.setState({ messages: [...this.messagesRef] });
       updateMessage(assistantMessageId, typeof errorMsg.content === 'string' ? errorMsg.content : '');
 
-      this.pushEvent(createTimelineEvent(sid, 'status', `Error: ${errorMessage}`, {}, assistantMessageId));
+      this.pushEvent(createTimelineEvent(sid, 'error', errorMessage, {}, assistantMessageId));
       persistSessionUpdate(sid, { status: 'failed', error: errorMessage });
     } finally {
       if (this.streamingFlushRef) {
@@ -925,6 +1453,19 @@ export class ChatSession extends EventEmitter {
         // ignore
       }
 
+      try { clearTurnInFlight(sid); } catch { /* ignore */ }
+      this.runningToolCallsRef.clear();
+      this.subagentParentsRef = [];
+      this.subagentTextRef.clear();
+      void this.runObserverHook('post_turn', this.options.hooks?.postTurn, sid, {
+        status: this.state.error ? 'failed' : 'completed',
+        error: this.state.error,
+        tokens: this.state.tokens,
+      }, assistantMessageId);
+      if (this.turnChangedFilesRef && !this.state.error && this.abortRef && !this.abortRef.signal.aborted) {
+        // Part of the turn: Esc stops it and the next message waits for it.
+        await this.runVerify(sid, assistantMessageId, this.abortRef.signal);
+      }
       this.setState({ isLoading: false });
       this.streamingContentRef = '';
       this.streamingReasoningRef = '';
@@ -952,6 +1493,7 @@ export class ChatSession extends EventEmitter {
     this.messagesRef = [];
     this.timelineEventsRef = [];
     this.sessionIdRef = undefined;
+    this.untitledRef = false;
     this.registryRef = null;
     this.existingSummaryRef = undefined;
   }
@@ -983,6 +1525,7 @@ export class ChatSession extends EventEmitter {
         tokens: totals,
         sessionTokens: totals,
         sessionId: id,
+        title: storedSession.title,
       });
       const lastAssistant = [...storedMessages].reverse().find(m => m.role === 'assistant' && m.tokenUsage?.input);
       if (lastAssistant?.tokenUsage?.input) {
@@ -997,7 +1540,215 @@ export class ChatSession extends EventEmitter {
     }
   }
 
+  /**
+   * post_tool and post_turn hooks only observe; a failure is recorded in the
+   * timeline and the turn carries on.
+   */
+  private async runObserverHook(
+    name: string,
+    command: string | undefined,
+    sid: string,
+    payload: Record<string, unknown>,
+    messageId: string,
+  ): Promise<void> {
+    if (!command) return;
+    const result = await runHook(command, {
+      event: name,
+      sessionId: sid,
+      projectDir: this.options.projectDir,
+      ...payload,
+    }, this.options.projectDir, this.options.hooks?.timeoutSecs);
+    const failure = hookFailure(name, result);
+    if (failure && this.sessionIdRef === sid) {
+      this.pushEvent(createTimelineEvent(sid, 'error', failure, {}, messageId));
+    }
+  }
+
+  /**
+   * Runs the `verify` command after a turn that changed files. It shows up as
+   * a `verify` tool call with streamed output, failed when the command exits
+   * non-zero; the model doesn't see it unless asked to fix the failures.
+   */
+  private async runVerify(sid: string, messageId: string, signal?: AbortSignal): Promise<void> {
+    const command = this.options.verify;
+    if (!command) return;
+    const toolCallId = `verify_${Date.now()}`;
+    const callEvent = createTimelineEvent(
+      sid,
+      'tool_call',
+      JSON.stringify({ command }),
+      { toolCallId, toolName: 'verify', status: 'running' },
+      messageId
+    );
+    this.pushEvent(callEvent);
+    const result = await runHook(
+      command,
+      { event: 'verify', sessionId: sid, projectDir: this.options.projectDir },
+      this.options.projectDir,
+      VERIFY_TIMEOUT_SECS,
+      (chunk) => this.emit('tool_output', { toolCallId, chunk }),
+      signal,
+    );
+    if (this.sessionIdRef !== sid) return;
+    const passed = result.code === 0 && !result.timedOut && !result.aborted;
+    const status = result.aborted ? 'cancelled' : passed ? 'completed' : 'failed';
+    const output = `${result.stdout}${result.stderr}`.trim();
+    const summary = result.aborted
+      ? 'Stopped'
+      : result.timedOut
+        ? `Timed out after ${VERIFY_TIMEOUT_SECS}s`
+        : `Exited with ${result.code ?? 'signal'}`;
+    // The end of the output is where test runners put the failures.
+    const tail = output.length > TIMELINE_RESULT_CHARS ? output.slice(-TIMELINE_RESULT_CHARS) : output;
+    this.pushEvent(createTimelineEvent(
+      sid,
+      'tool_result',
+      tail ? `${summary}\n${tail}` : summary,
+      { toolCallId, toolName: 'verify', status },
+      messageId
+    ));
+    const idx = this.timelineEventsRef.findIndex(e => e.id === callEvent.id);
+    if (idx !== -1) {
+      this.timelineEventsRef[idx] = { ...this.timelineEventsRef[idx]!, status } as TimelineEvent;
+      this.setState({ timelineEvents: [...this.timelineEventsRef] });
+    }
+  }
+
+  /**
+   * Load a session whose last turn was cut short by a crash. Tool calls that
+   * finished after the timeline was last written get their results replayed;
+   * ones that never finished are marked failed. The model only sees the
+   * replayed results through a note appended to the conversation.
+   */
+  async recoverSession(id: string): Promise<{ replayed: number; interrupted: number }> {
+    await this.loadSession(id);
+    if (this.sessionIdRef !== id) {
+      return { replayed: 0, interrupted: 0 };
+    }
+
+    let replayed = 0;
+    let interrupted = 0;
+    const notes: string[] = [];
+    for (const call of getToolCallsForSession(id)) {
+      const callIdx = this.timelineEventsRef.findIndex(e => e.kind === 'tool_call' && (e as any).toolCallId === call.id);
+      const hasResult = this.timelineEventsRef.some(e => e.kind === 'tool_result' && (e as any).toolCallId === call.id);
+      if (call.result == null) {
+        try { updateToolCallResult(call.id, JSON.stringify({ error: true, message: 'Interrupted' }), 'failed'); } catch { /* ignore */ }
+        if (callIdx !== -1) {
+          this.timelineEventsRef[callIdx] = { ...this.timelineEventsRef[callIdx]!, status: 'failed' } as TimelineEvent;
+        }
+        interrupted++;
+        continue;
+      }
+      if (callIdx !== -1) {
+        this.timelineEventsRef[callIdx] = { ...this.timelineEventsRef[callIdx]!, status: call.status } as TimelineEvent;
+      }
+      if (hasResult) continue;
+      const resultEvent = createTimelineEvent(
+        id,
+        'tool_result',
+        timelineResultContent(call.result),
+        { toolCallId: call.id, toolName: call.name, status: call.status as ToolCall['status'] },
+        call.message_id
+      );
+      this.timelineEventsRef = [...this.timelineEventsRef, resultEvent];
+      notes.push(`- ${call.name}(${call.arguments.slice(0, 200)}) → ${call.result.slice(0, 500)}`);
+      replayed++;
+    }
+
+    const summary = `Recovered an interrupted turn: ${replayed} tool result${replayed === 1 ? '' : 's'} replayed, ${interrupted} tool call${interrupted === 1 ? '' : 's'} cut short`;
+    this.timelineEventsRef = [...this.timelineEventsRef, createTimelineEvent(id, 'status', summary)];
+    if (notes.length > 0) {
+      this.messagesRef = [
+        ...this.messagesRef,
+        { role: 'assistant', content: `[The previous turn was interrupted. Tool calls that completed:\n${notes.join('\n')}]` },
+      ];
+    }
+    this.setState({ messages: [...this.messagesRef], timelineEvents: [...this.timelineEventsRef] });
+    clearTurnInFlight(id);
+    persistSessionUpdate(id, { status: 'failed', error: 'Interrupted' });
+    return { replayed, interrupted };
+  }
+
+  /**
+   * Up to `limit` events before the one with id `before` (the newest events
+   * when `before` is unset; all of them without a limit), and how many are
+   * older still.
+   */
+  getEvents(before?: string, limit?: number): { events: TimelineEvent[]; earlier: number } {
+    const events = this.state.timelineEvents;
+    const end = before === undefined ? events.length : events.findIndex(e => e.id === before);
+    if (end === -1) return { events: [], earlier: 0 };
+    const start = limit === undefined ? 0 : Math.max(0, end - limit);
+    return { events: events.slice(start, end), earlier: start };
+  }
+
+  /**
+   * Runs a failed tool call again with the same arguments. The new attempt is
+   * added to the timeline as its own call and result, and the model sees it
+   * through a note appended to the conversation, as with recovered calls.
+   */
+  async retryToolCall(toolCallId: string): Promise<{ status: 'completed' | 'failed' }> {
+    const sid = this.sessionIdRef;
+    const call = this.timelineEventsRef.find(e => e.kind === 'tool_call' && (e as any).toolCallId === toolCallId);
+    if (!sid || !call) throw new Error(`Tool call not found: ${toolCallId}`);
+    if (this.state.isLoading) throw new Error('Wait for the current turn to finish');
+    const name = (call as any).toolName as string;
+    const messageId = (call as any).messageId as string | undefined;
+    if (name === 'verify') {
+      await this.runVerify(sid, messageId ?? sid);
+      const last = [...this.timelineEventsRef].reverse().find(e => e.kind === 'tool_result' && (e as any).toolName === 'verify');
+      return { status: (last as any)?.status === 'failed' ? 'failed' : 'completed' };
+    }
+
+    let args: Record<string, unknown> = {};
+    try {
+      args = JSON.parse(call.content);
+    } catch {
+      // arguments that never parsed; the tool reports what's missing
+    }
+    const retryId = `retry_${Date.now()}`;
+    const callEvent = createTimelineEvent(
+      sid,
+      'tool_call',
+      call.content,
+      { toolCallId: retryId, toolName: name, status: 'running' },
+      messageId
+    );
+    this.pushEvent(callEvent);
+    const result = await this.executeTool(name, args);
+    if (this.sessionIdRef !== sid) return { status: 'failed' };
+    let failed = false;
+    try {
+      const parsed = JSON.parse(result);
+      failed = !!parsed?.error || parsed?.success === false;
+    } catch {
+      // plain-text results are successes
+    }
+    const status = failed ? 'failed' : 'completed';
+    this.pushEvent(createTimelineEvent(
+      sid,
+      'tool_result',
+      timelineResultContent(result),
+      { toolCallId: retryId, toolName: name, status, tokens: { input: Math.ceil(result.length / 4), output: 0 } },
+      messageId
+    ));
+    const idx = this.timelineEventsRef.findIndex(e => e.id === callEvent.id);
+    if (idx !== -1) {
+      this.timelineEventsRef[idx] = { ...this.timelineEventsRef[idx]!, status } as TimelineEvent;
+    }
+    this.messagesRef = [
+      ...this.messagesRef,
+      { role: 'assistant', content: `[The user retried a failed tool call: ${name}(${call.content.slice(0, 200)}) → ${result.slice(0, 500)}]` },
+    ];
+    this.setState({ messages: [...this.messagesRef], timelineEvents: [...this.timelineEventsRef] });
+    return { status };
+  }
+
   async executeTool(name: string, args: Record<string, unknown>): Promise<string> {
+    if (this.state.readOnly && MUTATING_TOOLS.includes(name)) {
+      return JSON.stringify({ error: true, message: `${name} is disabled in read-only mode` });
+    }
     const registry = this.getRegistry();
     const tool = registry.get(name);
 
@@ -1010,12 +1761,17 @@ export class ChatSession extends EventEmitter {
         sessionId: this.getSessionId(),
         conversationId: this.getSessionId(),
         userId: 'local',
-        metadata: { projectDir: this.options.projectDir },
+        metadata: { projectDir: this.options.projectDir, sandbox: this.options.sandbox },
       });
       return typeof result === 'string' ? result : JSON.stringify(result);
     } catch (err) {
       return JSON.stringify({ error: true, message: String(err) });
     }
   }
+
+  /** A 1-token request to `model`, to check its key and quota. */
+  testModel(model: string, provider?: string) {
+    return pingModel(this.options.config, model, provider);
+  }
 }
 
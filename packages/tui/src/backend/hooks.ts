@@ -0,0 +1,71 @@
+import { spawn } from 'child_process';
+
+/**
+ * User scripts run around a turn, configured under `[hooks]` in
+ * `.stratuscode/config.toml`. Each gets the event as JSON on stdin.
+ */
+export interface HooksConfig {
+  preSend?: string;
+  postTool?: string;
+  postTurn?: string;
+  timeoutSecs?: number;
+}
+
+export interface HookResult {
+  code: number | null;
+  stdout: string;
+  stderr: string;
+  timedOut: boolean;
+  /** Killed because `signal` fired. */
+  aborted?: boolean;
+}
+
+const DEFAULT_HOOK_TIMEOUT_SECS = 30;
+
+export function runHook(
+  command: string,
+  event: Record<string, unknown>,
+  cwd: string,
+  timeoutSecs?: number,
+  onOutput?: (chunk: string) => void,
+  signal?: AbortSignal,
+): Promise<HookResult> {
+  return new Promise((resolve) => {
+    const child = spawn(command, { cwd, shell: true, stdio: ['pipe', 'pipe', 'pipe'] });
+    let stdout = '';
+    let stderr = '';
+    let timedOut = false;
+    const timer = setTimeout(() => {
+      timedOut = true;
+      child.kill('SIGKILL');
+    }, (timeoutSecs ?? DEFAULT_HOOK_TIMEOUT_SECS) * 1000);
+    const onAbort = () => child.kill('SIGKILL');
+    if (signal?.aborted) onAbort();
+    else signal?.addEventListener('abort', onAbort, { once: true });
+    child.stdout.on('data', (chunk) => { stdout += chunk; onOutput?.(String(chunk)); });
+    child.stderr.on('data', (chunk) => { stderr += chunk; onOutput?.(String(chunk)); });
+    child.on('error', (err) => {
+      clearTimeout(timer);
+      signal?.removeEventListener('abort', onAbort);
+      resolve({ code: null, stdout, stderr: stderr || err.message, timedOut, aborted: signal?.aborted });
+    });
+    child.on('close', (code) => {
+      clearTimeout(timer);
+      signal?.removeEventListener('abort', onAbort);
+      resolve({ code, stdout, stderr, timedOut, aborted: signal?.aborted });
+    });
+    child.stdin.on('error', () => { /* the hook may not read stdin */ });
+    child.stdin.end(JSON.stringify(event));
+  });
+}
+
+/** One line describing why a hook run failed, or null if it succeeded. */
+export function hookFailure(name: string, result: HookResult): string | null {
+  if (result.timedOut) return `${name} hook timed out`;
+  if (result.code === 0) return null;
+  const detail = result.stderr.trim().split('\n').slice(-3).join(' ').slice(0, 300);
+  return `${name} hook failed (exit ${result.code ?? 'signal'})${detail ? `: ${detail}` : ''}`;
+}
+
+/** Exit code a pre_send hook uses to stop the message from being sent. */
+export const HOOK_BLOCK_EXIT_CODE = 2;
@@ -7,9 +7,11 @@
 
 import * as readline from 'readline';
 import * as path from 'path';
-import { ChatSession } from './chat-session';
+import { ChatSession, MUTATING_TOOLS } from './chat-session';
 import { loadConfig, hasApiKey, initDatabase } from '@stratuscode/storage';
-import { listSessions, deleteSession, getMessages, updateSession } from '@stratuscode/storage';
+import { listSessions, deleteSession, getMessages, updateSession, getSessionTurnUsage, listTimelineEvents } from '@stratuscode/storage';
+import { listInterruptedSessions, clearTurnInFlight, getToolCallsForSession, importSession } from '@stratuscode/storage';
+import { listBookmarks, addBookmark, deleteBookmark } from '@stratuscode/storage';
 import { Question, Todo } from '@stratuscode/tools';
 import { discoverOllamaModels } from '@stratuscode/shared';
 import { buildModelEntries } from './model_entries';
@@ -46,10 +48,22 @@ function notify(method: string, params?: any): void {
   send({ jsonrpc: '2.0', method, params });
 }
 
+// Todos and questions are keyed by session, so these are registered once and
+// pushed for whichever session changed.
+Todo.onChange((sessionId) => {
+  notify('todos_changed', { sessionId, list: Todo.list(sessionId), counts: Todo.counts(sessionId) });
+});
+Question.onChange((sessionId) => {
+  notify('question_pending', { sessionId, question: Question.getFirst(sessionId) ?? null });
+});
+Question.onResolved((resolved) => notify('question_resolved', resolved));
+
 function attachSessionEvents(s: ChatSession): void {
   s.on('timeline_event', (event) => notify('timeline_event', event));
   s.on('tokens_update', (payload) => notify('tokens_update', payload));
   s.on('context_status', (status) => notify('context_status', status));
+  s.on('retry_status', (status) => notify('retry_status', status));
+  s.on('tool_output', (payload) => notify('tool_output', payload));
   s.on('plan_exit_proposed', (flag) => notify('plan_exit_proposed', flag));
   s.on('session_changed', (id) => notify('session_changed', id));
   s.on('state', (state) => notify('state', state));
@@ -85,6 +99,7 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         }
 
         currentConfig = config;
+        session?.dispose();
         session = new ChatSession({
           projectDir,
           config,
@@ -92,9 +107,20 @@ async function handleRequest(req: RpcRequest): Promise<void> {
           modelOverride: params.modelOverride,
           providerOverride: params.providerOverride,
           reasoningEffortOverride: params.reasoningEffortOverride,
+          hooks: params.hooks,
+          readOnly: params.readOnly,
+          verify: params.verify,
+          sandbox: params.sandbox,
+          timelineWindow: params.timelineWindow,
         });
         attachSessionEvents(session);
-        respond(id, { ok: true, state: session.getState(), baseModel: config.model });
+        respond(id, {
+          ok: true,
+          state: session.getState(),
+          baseModel: config.model,
+          capabilities: ['push_updates', 'test_model'],
+          mutatingTools: MUTATING_TOOLS,
+        });
         return;
       }
       case 'get_state': {
@@ -111,7 +137,12 @@ async function handleRequest(req: RpcRequest): Promise<void> {
           return;
         }
         session
-          .sendMessage(params.content || '', params.agentOverride, params.options, params.attachments)
+          .sendMessage(
+            params.content || '',
+            params.agentOverride,
+            { ...params.options, clientId: params.clientId, pinned: params.pinned },
+            params.attachments,
+          )
           .catch((err: Error) => {
             notify('error', err.message || 'Failed to send message');
           });
@@ -127,6 +158,37 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         respond(id, { result });
         return;
       }
+      case 'retry_tool_call': {
+        if (!session) {
+          respond(id, undefined, { code: 400, message: 'Not initialized' });
+          return;
+        }
+        const toolCallId = params.toolCallId;
+        session
+          .retryToolCall(toolCallId)
+          .then(({ status }) => notify('tool_retried', { toolCallId, status }))
+          .catch((err: Error) => {
+            notify('tool_retried', { toolCallId, error: err.message || 'Retry failed' });
+          });
+        respond(id, { ok: true });
+        return;
+      }
+      case 'test_model': {
+        if (!session) {
+          respond(id, undefined, { code: 400, message: 'Not initialized' });
+          return;
+        }
+        respond(id, await session.testModel(params.model, params.provider ?? undefined));
+        return;
+      }
+      case 'get_events': {
+        if (!session) {
+          respond(id, undefined, { code: 400, message: 'Not initialized' });
+          return;
+        }
+        respond(id, session.getEvents(params.before ?? undefined, params.limit ?? undefined));
+        return;
+      }
       case 'load_session': {
         if (!session) {
           respond(id, undefined, { code: 400, message: 'Not initialized' });
@@ -137,10 +199,17 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         return;
       }
       case 'abort': {
+        const abortedId = session?.getState().sessionId;
         session?.abort();
+        // Nothing is waiting on these answers any more.
+        if (abortedId) Question.cancelAll(abortedId);
         respond(id, { ok: true });
         return;
       }
+      case 'cancel_tool': {
+        respond(id, { cancelled: session?.cancelTool() ?? 0 });
+        return;
+      }
       case 'clear': {
         session?.clear();
         respond(id, { ok: true });
@@ -152,7 +221,7 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         return;
       }
       case 'set_agent': {
-        session?.setAgent(params.agent);
+        session?.setAgent(params.agent, params.definition ?? undefined);
         respond(id, { ok: true });
         return;
       }
@@ -166,6 +235,16 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         respond(id, { ok: true });
         return;
       }
+      case 'set_read_only': {
+        session?.setReadOnly(!!params.readOnly);
+        respond(id, { ok: true });
+        return;
+      }
+      case 'set_project_commands': {
+        session?.setProjectCommands(params.hooks ?? undefined, params.verify ?? undefined);
+        respond(id, { ok: true });
+        return;
+      }
       case 'set_reasoning_effort': {
         session?.setReasoningEffortOverride(params.reasoningEffort);
         respond(id, { ok: true });
@@ -239,9 +318,81 @@ async function handleRequest(req: RpcRequest): Promise<void> {
           return;
         }
         updateSession(params.sessionId, { title: params.title });
+        if (session && session.getState().sessionId === params.sessionId) {
+          session.setTitle(params.title);
+        }
+        respond(id, { ok: true });
+        return;
+      }
+      case 'import_session': {
+        // Works before `initialize`: importing needs no provider or API key.
+        if (!params.session || !Array.isArray(params.session.messages)) {
+          respond(id, undefined, { code: 400, message: 'Missing session' });
+          return;
+        }
+        initDatabase();
+        const projectDir = path.resolve(params.projectDir || process.cwd());
+        const imported = importSession(projectDir, params.session);
+        respond(id, { sessionId: imported.id, title: imported.title });
+        return;
+      }
+      case 'find_interrupted_session': {
+        const projectDir = path.resolve(params.projectDir || process.cwd());
+        const interrupted = listInterruptedSessions(projectDir)[0];
+        if (!interrupted) {
+          respond(id, null);
+          return;
+        }
+        const messages = getMessages(interrupted.id);
+        const lastUser = [...messages].reverse().find(m => m.role === 'user');
+        const calls = getToolCallsForSession(interrupted.id);
+        // Only finished calls whose result never reached the timeline get
+        // replayed by resume_session.
+        const shown = new Set(
+          listTimelineEvents(interrupted.id)
+            .filter(e => e.kind === 'tool_result')
+            .map(e => (e as any).toolCallId)
+        );
+        respond(id, {
+          id: interrupted.id,
+          title: interrupted.title,
+          updatedAt: interrupted.updatedAt,
+          lastMessage: typeof lastUser?.content === 'string' ? lastUser.content.slice(0, 80) : undefined,
+          completedTools: calls.filter(c => c.result != null && !shown.has(c.id)).length,
+          pendingTools: calls.filter(c => c.result == null).length,
+        });
+        return;
+      }
+      case 'resume_session': {
+        if (!session) {
+          respond(id, undefined, { code: 400, message: 'Not initialized' });
+          return;
+        }
+        if (!params.sessionId) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId' });
+          return;
+        }
+        respond(id, await session.recoverSession(params.sessionId));
+        return;
+      }
+      case 'dismiss_interrupted': {
+        if (!params.sessionId) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId' });
+          return;
+        }
+        clearTurnInFlight(params.sessionId);
+        updateSession(params.sessionId, { status: 'failed', error: 'Interrupted' });
         respond(id, { ok: true });
         return;
       }
+      case 'get_usage': {
+        if (!params.sessionId) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId' });
+          return;
+        }
+        respond(id, { turns: getSessionTurnUsage(params.sessionId) });
+        return;
+      }
       case 'get_pending_question': {
         if (!params.sessionId) {
           respond(id, undefined, { code: 400, message: 'Missing sessionId' });
@@ -279,6 +430,55 @@ async function handleRequest(req: RpcRequest): Promise<void> {
         respond(id, { list, counts });
         return;
       }
+      case 'update_todo': {
+        if (!params.id) {
+          respond(id, undefined, { code: 400, message: 'Missing id' });
+          return;
+        }
+        const updates: { content?: string; status?: 'pending' | 'in_progress' | 'completed' } = {};
+        if (typeof params.content === 'string') updates.content = params.content;
+        if (params.status) updates.status = params.status;
+        const todo = Todo.update(params.id, updates);
+        if (!todo) {
+          respond(id, undefined, { code: 404, message: 'Todo not found' });
+          return;
+        }
+        respond(id, todo);
+        return;
+      }
+      case 'add_todo': {
+        if (!params.sessionId || !params.content) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId or content' });
+          return;
+        }
+        respond(id, Todo.create(params.sessionId, params.content));
+        return;
+      }
+      case 'list_bookmarks': {
+        if (!params.sessionId) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId' });
+          return;
+        }
+        respond(id, listBookmarks(params.sessionId));
+        return;
+      }
+      case 'add_bookmark': {
+        if (!params.sessionId || !params.eventId) {
+          respond(id, undefined, { code: 400, message: 'Missing sessionId or eventId' });
+          return;
+        }
+        respond(id, addBookmark(params.sessionId, params.eventId, params.label || ''));
+        return;
+      }
+      case 'remove_bookmark': {
+        if (!params.id) {
+          respond(id, undefined, { code: 400, message: 'Missing id' });
+          return;
+        }
+        deleteBookmark(params.id);
+        respond(id, { ok: true });
+        return;
+      }
       case 'list_models': {
         if (!session) {
           respond(id, undefined, { code: 400, message: 'Not initialized' });